@@ -0,0 +1,13 @@
+batch-finished = Stapel abgeschlossen
+batch-finished-body = { $count ->
+    [one] 1 Datei komprimiert, { $saved } gespart
+   *[other] { $count } Dateien komprimiert, { $saved } gespart
+}
+batch-failed = Stapel mit Fehlern abgeschlossen
+batch-failed-body = { $failed } von { $count } Dateien fehlgeschlagen
+video-converted = Video konvertiert
+video-failed = Videokonvertierung fehlgeschlagen
+pdf-compressed = PDF komprimiert
+pdf-failed = PDF-Komprimierung fehlgeschlagen
+clipboard-image = Bild kopiert
+clipboard-image-body = { $width }×{ $height }-Bild in der Zwischenablage. Öffne Squish, um es zu komprimieren.
@@ -0,0 +1,16 @@
+# Strings for native surfaces the backend renders itself (notifications,
+# menus, dialogs). The frontend has its own catalogs.
+
+batch-finished = Batch finished
+batch-finished-body = { $count ->
+    [one] 1 file compressed, { $saved } saved
+   *[other] { $count } files compressed, { $saved } saved
+}
+batch-failed = Batch finished with errors
+batch-failed-body = { $failed } of { $count } files failed
+video-converted = Video converted
+video-failed = Video conversion failed
+pdf-compressed = PDF compressed
+pdf-failed = PDF compression failed
+clipboard-image = Image copied
+clipboard-image-body = { $width }×{ $height } image on the clipboard. Open Squish to compress it.
@@ -0,0 +1,13 @@
+batch-finished = Lote terminado
+batch-finished-body = { $count ->
+    [one] 1 archivo comprimido, { $saved } ahorrados
+   *[other] { $count } archivos comprimidos, { $saved } ahorrados
+}
+batch-failed = Lote terminado con errores
+batch-failed-body = Fallaron { $failed } de { $count } archivos
+video-converted = Vídeo convertido
+video-failed = Error al convertir el vídeo
+pdf-compressed = PDF comprimido
+pdf-failed = Error al comprimir el PDF
+clipboard-image = Imagen copiada
+clipboard-image-body = Imagen de { $width }×{ $height } en el portapapeles. Abre Squish para comprimirla.
@@ -0,0 +1,13 @@
+batch-finished = Lot terminé
+batch-finished-body = { $count ->
+    [one] 1 fichier compressé, { $saved } économisés
+   *[other] { $count } fichiers compressés, { $saved } économisés
+}
+batch-failed = Lot terminé avec des erreurs
+batch-failed-body = { $failed } fichiers sur { $count } ont échoué
+video-converted = Vidéo convertie
+video-failed = Échec de la conversion vidéo
+pdf-compressed = PDF compressé
+pdf-failed = Échec de la compression du PDF
+clipboard-image = Image copiée
+clipboard-image-body = Image de { $width }×{ $height } dans le presse-papiers. Ouvrez Squish pour la compresser.
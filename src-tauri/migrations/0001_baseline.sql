@@ -0,0 +1,101 @@
+-- Baseline schema the frontend has been creating ad hoc in src/lib/db.ts.
+-- IF NOT EXISTS keeps this a no-op on existing libraries.
+
+CREATE TABLE IF NOT EXISTS projects (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    current_action_index INTEGER DEFAULT -1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    local_hosted INTEGER NOT NULL CHECK (local_hosted IN (0, 1)),
+    runpod_api_key TEXT,
+    runpod_instance_id TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    CHECK (
+        (local_hosted = 1 AND runpod_api_key IS NULL AND runpod_instance_id IS NULL) OR
+        (local_hosted = 0 AND runpod_api_key IS NOT NULL AND runpod_instance_id IS NOT NULL)
+    )
+);
+
+CREATE TABLE IF NOT EXISTS fonts (
+    name TEXT PRIMARY KEY,
+    is_enabled INTEGER NOT NULL DEFAULT 1,
+    is_system INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS image_assets (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    data BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sticker_assets (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    source_image_id TEXT NOT NULL,
+    data BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (source_image_id) REFERENCES image_assets(id)
+);
+
+CREATE TABLE IF NOT EXISTS layers (
+    id TEXT PRIMARY KEY,
+    project_id TEXT NOT NULL,
+    type TEXT NOT NULL CHECK (type IN ('image', 'sticker', 'text')),
+    image_asset_id TEXT,
+    sticker_asset_id TEXT,
+    content TEXT,
+    transform TEXT NOT NULL, -- JSON object
+    style TEXT, -- JSON object for text layers
+    crop TEXT, -- JSON object for image layers
+    vertical_align TEXT CHECK (vertical_align IN ('top', 'center', 'bottom')),
+    word_wrap TEXT CHECK (word_wrap IN ('normal', 'break-word')),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE,
+    FOREIGN KEY (image_asset_id) REFERENCES image_assets(id) ON DELETE SET NULL,
+    FOREIGN KEY (sticker_asset_id) REFERENCES sticker_assets(id) ON DELETE SET NULL,
+    CHECK (
+        (type = 'image' AND image_asset_id IS NOT NULL AND sticker_asset_id IS NULL AND content IS NULL AND style IS NULL AND vertical_align IS NULL AND word_wrap IS NULL) OR
+        (type = 'sticker' AND sticker_asset_id IS NOT NULL AND image_asset_id IS NULL AND content IS NULL AND style IS NULL AND vertical_align IS NULL AND word_wrap IS NULL) OR
+        (type = 'text' AND image_asset_id IS NULL AND sticker_asset_id IS NULL AND content IS NOT NULL AND style IS NOT NULL)
+    )
+);
+
+CREATE TABLE IF NOT EXISTS layer_order (
+    project_id TEXT NOT NULL,
+    layer_id TEXT NOT NULL,
+    index_number INTEGER NOT NULL,
+    PRIMARY KEY (project_id, layer_id),
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE,
+    FOREIGN KEY (layer_id) REFERENCES layers(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS actions (
+    id TEXT PRIMARY KEY,
+    project_id TEXT NOT NULL,
+    type TEXT NOT NULL,
+    layer_id TEXT NOT NULL,
+    before TEXT,
+    after TEXT NOT NULL,
+    timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS canvas_settings (
+    project_id TEXT PRIMARY KEY,
+    width INTEGER NOT NULL DEFAULT 1920,
+    height INTEGER NOT NULL DEFAULT 1080,
+    background_type TEXT CHECK(background_type IN ('color', 'image', 'none')) NOT NULL DEFAULT 'none',
+    background_color TEXT DEFAULT '#FFFFFF',
+    background_image_id TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE,
+    FOREIGN KEY (background_image_id) REFERENCES image_assets(id)
+);
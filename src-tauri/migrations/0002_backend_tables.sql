@@ -0,0 +1,38 @@
+-- Tables owned by the Rust backend.
+
+CREATE TABLE IF NOT EXISTS preferences (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL, -- JSON
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS blurhashes (
+    path TEXT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS watch_folders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE,
+    destination TEXT NOT NULL,
+    options TEXT NOT NULL, -- JSON CompressOptions
+    enabled INTEGER NOT NULL DEFAULT 1 CHECK (enabled IN (0, 1)),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS watch_ledger (
+    folder_id INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    modified INTEGER NOT NULL,
+    output_path TEXT,
+    error TEXT,
+    processed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (folder_id, path),
+    FOREIGN KEY (folder_id) REFERENCES watch_folders(id) ON DELETE CASCADE
+);
+
+-- Watch folders skip files they wrote themselves, looked up by output path
+CREATE INDEX IF NOT EXISTS idx_watch_ledger_output ON watch_ledger(folder_id, output_path);
@@ -0,0 +1,14 @@
+-- Denormalized summary of each project for the library grid, refreshed on save.
+
+CREATE TABLE IF NOT EXISTS project_index (
+    project_id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    page_sizes TEXT NOT NULL, -- JSON array of [width, height]
+    fonts TEXT NOT NULL, -- JSON array of font family names
+    asset_count INTEGER NOT NULL DEFAULT 0,
+    thumbnail_path TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_index_updated_at ON project_index(updated_at DESC);
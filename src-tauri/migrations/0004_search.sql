@@ -0,0 +1,46 @@
+-- Full-text index over projects and assets for search_library.
+-- Asset rows are maintained by triggers; project rows are refreshed when a
+-- project is indexed on save, since their text lives across many layers.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS library_fts USING fts5(
+    kind UNINDEXED, -- 'project' or 'asset'
+    ref_id UNINDEXED,
+    name,
+    tags,
+    content,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO library_fts (kind, ref_id, name, tags, content)
+SELECT 'asset', id, name, '', '' FROM image_assets;
+
+INSERT INTO library_fts (kind, ref_id, name, tags, content)
+SELECT 'asset', id, name, '', '' FROM sticker_assets;
+
+INSERT INTO library_fts (kind, ref_id, name, tags, content)
+SELECT 'project', p.id, p.name, '',
+    COALESCE((SELECT group_concat(l.content, ' ') FROM layers l
+              WHERE l.project_id = p.id AND l.type = 'text'), '')
+FROM projects p;
+
+CREATE TRIGGER IF NOT EXISTS library_fts_image_insert AFTER INSERT ON image_assets BEGIN
+    INSERT INTO library_fts (kind, ref_id, name, tags, content)
+    VALUES ('asset', new.id, new.name, '', '');
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_fts_image_delete AFTER DELETE ON image_assets BEGIN
+    DELETE FROM library_fts WHERE kind = 'asset' AND ref_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_fts_sticker_insert AFTER INSERT ON sticker_assets BEGIN
+    INSERT INTO library_fts (kind, ref_id, name, tags, content)
+    VALUES ('asset', new.id, new.name, '', '');
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_fts_sticker_delete AFTER DELETE ON sticker_assets BEGIN
+    DELETE FROM library_fts WHERE kind = 'asset' AND ref_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS library_fts_project_delete AFTER DELETE ON projects BEGIN
+    DELETE FROM library_fts WHERE kind = 'project' AND ref_id = old.id;
+END;
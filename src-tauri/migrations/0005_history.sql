@@ -0,0 +1,17 @@
+-- Persistent undo/redo log. Ops with undone = 1 form the redo stack and are
+-- discarded as soon as a new op is pushed.
+
+CREATE TABLE IF NOT EXISTS operation_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    label TEXT NOT NULL, -- shown in the history panel
+    layer_id TEXT,
+    before TEXT, -- JSON, null when the op created something
+    after TEXT, -- JSON, null when the op removed something
+    undone INTEGER NOT NULL DEFAULT 0 CHECK (undone IN (0, 1)),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_operation_log_project ON operation_log(project_id, id);
@@ -0,0 +1,20 @@
+-- Saved compression presets, and per-item sync bookkeeping.
+
+CREATE TABLE IF NOT EXISTS presets (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    options TEXT NOT NULL, -- JSON CompressOptions
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Last state known to match the remote, per synced item
+CREATE TABLE IF NOT EXISTS sync_state (
+    kind TEXT NOT NULL CHECK (kind IN ('project', 'preset')),
+    item_id TEXT NOT NULL,
+    version_vector TEXT NOT NULL, -- JSON object of device id to counter
+    content_hash TEXT NOT NULL,
+    conflict INTEGER NOT NULL DEFAULT 0 CHECK (conflict IN (0, 1)),
+    synced_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (kind, item_id)
+);
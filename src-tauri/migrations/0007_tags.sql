@@ -0,0 +1,37 @@
+-- User tags on projects and assets. An asset id refers to image_assets or
+-- sticker_assets, which never share ids.
+
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    color TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS item_tags (
+    tag_id INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('project', 'asset')),
+    item_id TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (tag_id, kind, item_id),
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_tags_item ON item_tags(kind, item_id);
+
+-- Foreign keys are off on our connections, so clean up by trigger
+CREATE TRIGGER IF NOT EXISTS item_tags_tag_delete AFTER DELETE ON tags BEGIN
+    DELETE FROM item_tags WHERE tag_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS item_tags_project_delete AFTER DELETE ON projects BEGIN
+    DELETE FROM item_tags WHERE kind = 'project' AND item_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS item_tags_image_delete AFTER DELETE ON image_assets BEGIN
+    DELETE FROM item_tags WHERE kind = 'asset' AND item_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS item_tags_sticker_delete AFTER DELETE ON sticker_assets BEGIN
+    DELETE FROM item_tags WHERE kind = 'asset' AND item_id = old.id;
+END;
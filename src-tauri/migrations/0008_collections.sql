@@ -0,0 +1,41 @@
+-- User-defined, nestable groups of projects and assets for the sidebar.
+
+CREATE TABLE IF NOT EXISTS collections (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    parent_id TEXT, -- null for top-level collections
+    position INTEGER NOT NULL DEFAULT 0, -- order among siblings
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (parent_id) REFERENCES collections(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS collection_items (
+    collection_id TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('project', 'asset')),
+    item_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (collection_id, kind, item_id),
+    FOREIGN KEY (collection_id) REFERENCES collections(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_collections_parent ON collections(parent_id, position);
+CREATE INDEX IF NOT EXISTS idx_collection_items_order ON collection_items(collection_id, position);
+
+-- Foreign keys are off on our connections, so clean up by trigger. Nested
+-- collections are deleted explicitly, since triggers don't recurse by default.
+CREATE TRIGGER IF NOT EXISTS collections_delete AFTER DELETE ON collections BEGIN
+    DELETE FROM collection_items WHERE collection_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS collection_items_project_delete AFTER DELETE ON projects BEGIN
+    DELETE FROM collection_items WHERE kind = 'project' AND item_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS collection_items_image_delete AFTER DELETE ON image_assets BEGIN
+    DELETE FROM collection_items WHERE kind = 'asset' AND item_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS collection_items_sticker_delete AFTER DELETE ON sticker_assets BEGIN
+    DELETE FROM collection_items WHERE kind = 'asset' AND item_id = old.id;
+END;
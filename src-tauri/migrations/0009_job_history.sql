@@ -0,0 +1,17 @@
+-- One row per finished compression or export, successful or not.
+
+CREATE TABLE IF NOT EXISTS job_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL, -- 'image', 'batch', 'lossless', 'watch', 'pdf', 'video'
+    input_path TEXT NOT NULL,
+    output_path TEXT,
+    input_bytes INTEGER,
+    output_bytes INTEGER,
+    duration_ms INTEGER NOT NULL,
+    settings TEXT NOT NULL, -- JSON options the job ran with
+    error TEXT, -- null on success
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_history_created_at ON job_history(created_at);
+CREATE INDEX IF NOT EXISTS idx_job_history_kind ON job_history(kind, created_at);
@@ -0,0 +1,9 @@
+-- Opt-in feature usage counts. Only feature names and daily counts are kept:
+-- no paths, file contents or identifiers, and nothing leaves this database.
+
+CREATE TABLE IF NOT EXISTS usage_counters (
+    feature TEXT NOT NULL,
+    day TEXT NOT NULL, -- YYYY-MM-DD
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (feature, day)
+);
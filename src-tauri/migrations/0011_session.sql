@@ -0,0 +1,20 @@
+-- What was open when Squish last ran, so it can come back the same way after
+-- a reboot or forced quit.
+
+CREATE TABLE IF NOT EXISTS session_documents (
+    project_id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    active INTEGER NOT NULL DEFAULT 0 CHECK (active IN (0, 1)),
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS session_windows (
+    label TEXT PRIMARY KEY,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    maximized INTEGER NOT NULL DEFAULT 0 CHECK (maximized IN (0, 1)),
+    fullscreen INTEGER NOT NULL DEFAULT 0 CHECK (fullscreen IN (0, 1)),
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Security-scoped bookmarks for folders the user granted access to, so a
+-- sandboxed build can reopen them after a relaunch. Only written on macOS.
+
+CREATE TABLE IF NOT EXISTS bookmarks (
+    path TEXT PRIMARY KEY,
+    data BLOB NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Remembered answers to permission prompts. An empty project_id is a decision
+-- for every project.
+
+CREATE TABLE IF NOT EXISTS permission_decisions (
+    project_id TEXT NOT NULL DEFAULT '',
+    capability TEXT NOT NULL,
+    allowed INTEGER NOT NULL CHECK (allowed IN (0, 1)),
+    decided_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (project_id, capability)
+);
@@ -0,0 +1,9 @@
+-- Jobs waiting in the queue (or interrupted by a quit), restored at launch.
+-- `job` is the JSON of the job's priority and spec; rows are removed once a
+-- job finishes, fails or is cancelled.
+
+CREATE TABLE IF NOT EXISTS job_queue (
+    id TEXT PRIMARY KEY,
+    job TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
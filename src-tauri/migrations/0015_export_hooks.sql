@@ -0,0 +1,15 @@
+-- Commands run after each successful export or compression. `args` and
+-- `kinds` are JSON arrays; an empty `kinds` matches every job kind.
+
+CREATE TABLE IF NOT EXISTS export_hooks (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    program TEXT NOT NULL,
+    args TEXT NOT NULL DEFAULT '[]',
+    kinds TEXT NOT NULL DEFAULT '[]',
+    enabled INTEGER NOT NULL DEFAULT 1 CHECK (enabled IN (0, 1)),
+    timeout_secs INTEGER NOT NULL DEFAULT 60,
+    last_run_at DATETIME,
+    last_error TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,33 @@
+-- Curated heading/body font pairs for suggest_font_pairings. Generated
+-- suggestions come from the installed fonts and aren't stored.
+
+CREATE TABLE IF NOT EXISTS font_pairings (
+    heading TEXT NOT NULL COLLATE NOCASE,
+    body TEXT NOT NULL COLLATE NOCASE,
+    note TEXT,
+    PRIMARY KEY (heading, body)
+);
+
+INSERT OR IGNORE INTO font_pairings (heading, body, note) VALUES
+    ('Playfair Display', 'Source Sans Pro', 'High-contrast serif display over a neutral sans'),
+    ('Montserrat', 'Merriweather', 'Geometric sans headings, sturdy screen serif for reading'),
+    ('Oswald', 'Open Sans', 'Condensed headings that save space over a friendly humanist sans'),
+    ('Raleway', 'Lora', 'Elegant thin sans over a calligraphic serif'),
+    ('Roboto Slab', 'Roboto', 'Same skeleton, slab serifs only where they show'),
+    ('Georgia', 'Verdana', 'Web-safe classic, both drawn for the screen'),
+    ('Helvetica Neue', 'Garamond', 'Swiss sans over an old-style book face'),
+    ('Lato', 'Merriweather', 'Warm sans headings with a large x-height serif'),
+    ('Abril Fatface', 'Lato', 'Heavy didone headlines over a calm sans'),
+    ('Libre Baskerville', 'Source Sans Pro', 'Transitional serif over a clean sans'),
+    ('Poppins', 'Lora', 'Round geometric sans with a brushed serif'),
+    ('Inter', 'Source Serif Pro', 'UI sans headings, serif for long text'),
+    ('Futura', 'Baskerville', 'Geometric modernism over a transitional serif'),
+    ('Didot', 'Helvetica Neue', 'Fashion-magazine didone over a neutral sans'),
+    ('Archivo Black', 'Roboto', 'Heavy grotesque headings over a neutral sans'),
+    ('Bebas Neue', 'Montserrat', 'All-caps display over a geometric sans'),
+    ('Cormorant Garamond', 'Proza Libre', 'Refined display serif with a humanist sans'),
+    ('Space Grotesk', 'IBM Plex Serif', 'Quirky grotesque with a technical serif'),
+    ('Fira Sans', 'Merriweather', 'Humanist sans and serif with matching x-heights'),
+    ('Avenir Next', 'Charter', 'Geometric sans over a robust text serif'),
+    ('Gill Sans', 'Palatino', 'British humanist sans over an old-style serif'),
+    ('Baskerville', 'Avenir', 'Transitional serif headings over a clean geometric sans');
@@ -0,0 +1,18 @@
+-- Fonts extracted from imported PDFs and SVGs. Files live in
+-- <app data>/project-fonts named by sha256; a NULL project_id means the font
+-- was imported outside a project and only lasts the session.
+
+CREATE TABLE IF NOT EXISTS project_fonts (
+    id TEXT PRIMARY KEY,
+    project_id TEXT,
+    family TEXT NOT NULL,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    subset INTEGER NOT NULL DEFAULT 0 CHECK (subset IN (0, 1)),
+    origin_path TEXT NOT NULL,
+    origin_kind TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_fonts_project ON project_fonts(project_id);
@@ -0,0 +1,48 @@
+-- Font faces used by each project's text layers, refreshed along with the
+-- library index on every save. The face is a style name fonts::face_data
+-- understands, e.g. "Bold Italic".
+
+CREATE TABLE IF NOT EXISTS font_usage (
+    project_id TEXT NOT NULL,
+    family TEXT NOT NULL COLLATE NOCASE,
+    face TEXT NOT NULL,
+    layer_count INTEGER NOT NULL,
+    PRIMARY KEY (project_id, family, face),
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_font_usage_family ON font_usage(family);
+
+-- Projects saved before this table existed, named the way font_usage.rs does
+INSERT OR IGNORE INTO font_usage (project_id, family, face, layer_count)
+SELECT
+    project_id,
+    family,
+    CASE
+        WHEN italic AND weight = 'Regular' THEN 'Italic'
+        WHEN italic THEN weight || ' Italic'
+        ELSE weight
+    END,
+    COUNT(*)
+FROM (
+    SELECT
+        project_id,
+        json_extract(style, '$.fontFamily') AS family,
+        CASE MIN(MAX(CAST((COALESCE(json_extract(style, '$.fontWeight'), 400) + 50) / 100 AS INTEGER), 1), 9)
+            WHEN 1 THEN 'Thin'
+            WHEN 2 THEN 'ExtraLight'
+            WHEN 3 THEN 'Light'
+            WHEN 4 THEN 'Regular'
+            WHEN 5 THEN 'Medium'
+            WHEN 6 THEN 'SemiBold'
+            WHEN 7 THEN 'Bold'
+            WHEN 8 THEN 'ExtraBold'
+            ELSE 'Black'
+        END AS weight,
+        COALESCE(json_extract(style, '$.italic'), 0) AS italic
+    FROM layers
+    WHERE type = 'text' AND json_valid(style)
+)
+-- Blank and non-string families are skipped, as in font_usage.rs
+WHERE typeof(family) = 'text' AND trim(family, ' ' || char(9, 10, 13)) != ''
+GROUP BY project_id, family COLLATE NOCASE, weight, italic;
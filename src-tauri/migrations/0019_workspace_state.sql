@@ -0,0 +1,13 @@
+-- How each document's workspace was left: panels, zoom, scroll position and
+-- tool, restored when the document is opened again.
+
+CREATE TABLE IF NOT EXISTS workspace_state (
+    project_id TEXT PRIMARY KEY,
+    panels TEXT NOT NULL, -- JSON object of panel id to visibility
+    zoom REAL NOT NULL,
+    scroll_x REAL NOT NULL,
+    scroll_y REAL NOT NULL,
+    tool TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+);
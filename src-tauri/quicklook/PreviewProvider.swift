@@ -0,0 +1,14 @@
+import Foundation
+import QuickLookUI
+
+// Hands over the page picture Squish saved into the document; the project
+// itself is never read here
+class PreviewProvider: QLPreviewProvider {
+    func providePreview(for request: QLFilePreviewRequest) async throws -> QLPreviewReply {
+        let image = request.fileURL.appendingPathComponent("QuickLook/Preview.png")
+        guard FileManager.default.fileExists(atPath: image.path) else {
+            throw CocoaError(.fileReadNoSuchFile)
+        }
+        return QLPreviewReply(fileURL: image)
+    }
+}
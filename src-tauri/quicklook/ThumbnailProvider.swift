@@ -0,0 +1,17 @@
+import Foundation
+import QuickLookThumbnailing
+
+// Finder's icon for a document, from the thumbnail Squish saved into it
+class ThumbnailProvider: QLThumbnailProvider {
+    override func provideThumbnail(
+        for request: QLFileThumbnailRequest,
+        _ handler: @escaping (QLThumbnailReply?, Error?) -> Void
+    ) {
+        let image = request.fileURL.appendingPathComponent("QuickLook/Thumbnail.png")
+        guard FileManager.default.fileExists(atPath: image.path) else {
+            handler(nil, CocoaError(.fileReadNoSuchFile))
+            return
+        }
+        handler(QLThumbnailReply(imageFileURL: image), nil)
+    }
+}
@@ -0,0 +1,28 @@
+#!/bin/sh
+# Builds the Quick Look extensions into build/ for release bundles to pick up.
+# Signs with $APPLE_SIGNING_IDENTITY, the identity Tauri signs the app with,
+# or ad hoc without one.
+set -e
+cd "$(dirname "$0")"
+identity="${APPLE_SIGNING_IDENTITY:--}"
+target="${QUICKLOOK_TARGET:-$(uname -m)-apple-macos12.0}"
+
+build() {
+    name=$1
+    source=$2
+    framework=$3
+    appex="build/$name.appex"
+    rm -rf "$appex"
+    mkdir -p "$appex/Contents/MacOS"
+    cp "$name.plist" "$appex/Contents/Info.plist"
+    # An extension has no main of its own; NSExtensionMain runs the principal class
+    xcrun swiftc -O -parse-as-library -application-extension \
+        -module-name "$name" -target "$target" -framework "$framework" \
+        -Xlinker -e -Xlinker _NSExtensionMain \
+        -o "$appex/Contents/MacOS/$name" "$source"
+    codesign --force --options runtime --sign "$identity" \
+        --entitlements QuickLook.entitlements "$appex"
+}
+
+build SquishPreview PreviewProvider.swift QuickLookUI
+build SquishThumbnail ThumbnailProvider.swift QuickLookThumbnailing
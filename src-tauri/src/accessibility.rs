@@ -0,0 +1,222 @@
+// OS accessibility display preferences. The webview only sees some of these
+// through media queries, and native window effects (vibrancy) need them on
+// the Rust side too, so they're read here and announced when they change.
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub const CHANGED_EVENT: &str = "accessibility://changed";
+// None of the platforms offer a cheap change notification we can hook from
+// here, and these are flipped rarely
+const POLL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessibilityPrefs {
+    pub reduce_motion: bool,
+    pub reduce_transparency: bool,
+    pub increase_contrast: bool,
+}
+
+pub struct AccessibilityState(pub Mutex<AccessibilityPrefs>);
+
+impl AccessibilityState {
+    pub fn snapshot(&self) -> AccessibilityPrefs {
+        self.0.lock().map(|p| *p).unwrap_or_default()
+    }
+}
+
+pub fn load() -> AccessibilityState {
+    AccessibilityState(Mutex::new(platform::read()))
+}
+
+fn refresh(app: &AppHandle) {
+    let prefs = platform::read();
+    let state = app.state::<AccessibilityState>();
+    let Ok(mut current) = state.0.lock() else {
+        return;
+    };
+    if *current == prefs {
+        return;
+    }
+    *current = prefs;
+    drop(current);
+    if let Err(e) = app.emit(CHANGED_EVENT, prefs) {
+        tracing::warn!("Failed to emit accessibility change: {}", e);
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL);
+        refresh(&app);
+    });
+}
+
+#[tauri::command]
+pub fn get_accessibility_prefs(state: State<AccessibilityState>) -> AccessibilityPrefs {
+    state.snapshot()
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::AccessibilityPrefs;
+    use cocoa::base::id;
+    use objc::runtime::{BOOL, NO};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    pub fn read() -> AccessibilityPrefs {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let reduce_motion: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+            let reduce_transparency: BOOL =
+                msg_send![workspace, accessibilityDisplayShouldReduceTransparency];
+            let increase_contrast: BOOL =
+                msg_send![workspace, accessibilityDisplayShouldIncreaseContrast];
+            AccessibilityPrefs {
+                reduce_motion: reduce_motion != NO,
+                reduce_transparency: reduce_transparency != NO,
+                increase_contrast: increase_contrast != NO,
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::AccessibilityPrefs;
+    use std::ffi::c_void;
+
+    const SPI_GETHIGHCONTRAST: u32 = 0x0042;
+    const SPI_GETCLIENTAREAANIMATION: u32 = 0x1042;
+    const HCF_HIGHCONTRASTON: u32 = 0x0000_0001;
+    const HKEY_CURRENT_USER: isize = 0x8000_0001_u32 as i32 as isize;
+    const RRF_RT_REG_DWORD: u32 = 0x0000_0010;
+
+    #[repr(C)]
+    struct HighContrast {
+        size: u32,
+        flags: u32,
+        default_scheme: *mut u16,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn SystemParametersInfoW(action: u32, param: u32, value: *mut c_void, ini: u32) -> i32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn RegGetValueW(
+            key: isize,
+            sub_key: *const u16,
+            value: *const u16,
+            flags: u32,
+            kind: *mut u32,
+            data: *mut c_void,
+            size: *mut u32,
+        ) -> i32;
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn animations_enabled() -> bool {
+        let mut enabled: i32 = 1;
+        unsafe {
+            SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                &mut enabled as *mut i32 as *mut c_void,
+                0,
+            );
+        }
+        enabled != 0
+    }
+
+    fn high_contrast() -> bool {
+        let mut hc = HighContrast {
+            size: std::mem::size_of::<HighContrast>() as u32,
+            flags: 0,
+            default_scheme: std::ptr::null_mut(),
+        };
+        let ok = unsafe {
+            SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                hc.size,
+                &mut hc as *mut HighContrast as *mut c_void,
+                0,
+            )
+        };
+        ok != 0 && hc.flags & HCF_HIGHCONTRASTON != 0
+    }
+
+    // Settings > Personalization > Colors > Transparency effects
+    fn transparency_enabled() -> bool {
+        let key = wide(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize");
+        let value = wide("EnableTransparency");
+        let mut data: u32 = 1;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_DWORD,
+                std::ptr::null_mut(),
+                &mut data as *mut u32 as *mut c_void,
+                &mut size,
+            )
+        };
+        status != 0 || data != 0
+    }
+
+    pub fn read() -> AccessibilityPrefs {
+        AccessibilityPrefs {
+            reduce_motion: !animations_enabled(),
+            reduce_transparency: !transparency_enabled(),
+            increase_contrast: high_contrast(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::AccessibilityPrefs;
+    use std::process::Command;
+
+    // GNOME keeps these in gsettings; other desktops report the defaults
+    fn gsetting(schema: &str, key: &str) -> Option<String> {
+        let output = Command::new("gsettings")
+            .args(["get", schema, key])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn read() -> AccessibilityPrefs {
+        let interface = "org.gnome.desktop.interface";
+        AccessibilityPrefs {
+            reduce_motion: gsetting(interface, "enable-animations").as_deref() == Some("false"),
+            reduce_transparency: false,
+            increase_contrast: gsetting("org.gnome.desktop.a11y.interface", "high-contrast")
+                .as_deref()
+                == Some("true"),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    use super::AccessibilityPrefs;
+
+    pub fn read() -> AccessibilityPrefs {
+        AccessibilityPrefs::default()
+    }
+}
@@ -0,0 +1,230 @@
+// Serves library assets to the webview at asset://localhost/<kind>/<id>
+// (http://asset.localhost/<kind>/<id> on Windows), where kind is `image` or
+// `sticker`. Bytes are read straight out of the SQLite blob, only the range
+// asked for, so an <img> or a canvas can show a large image without it ever
+// passing through IPC. An id always names the same bytes (assets are never
+// rewritten, only replaced under a new id), so responses are cached for good.
+use rusqlite::{Connection, DatabaseName, OptionalExtension};
+use std::io::{Read, Seek, SeekFrom};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+use crate::db::Db;
+
+pub const SCHEME: &str = "asset";
+
+struct Asset {
+    table: &'static str,
+    rowid: i64,
+    mime_type: String,
+    len: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    // Inclusive, as in the header
+    Part(u64, u64),
+    Unsatisfiable,
+}
+
+fn lookup(conn: &Connection, kind: &str, id: &str) -> Result<Option<Asset>, String> {
+    // Stickers are always cut out to PNG
+    let (table, mime_type) = match kind {
+        "image" => ("image_assets", "mime_type"),
+        "sticker" => ("sticker_assets", "'image/png'"),
+        _ => return Ok(None),
+    };
+    conn.query_row(
+        &format!(
+            "SELECT rowid, {}, length(data) FROM {} WHERE id = ?1",
+            mime_type, table
+        ),
+        [id],
+        |row| {
+            Ok(Asset {
+                table,
+                rowid: row.get(0)?,
+                mime_type: row.get(1)?,
+                len: row.get::<_, i64>(2)? as u64,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| format!("Failed to look up {} asset {}: {}", kind, id, e))
+}
+
+// Single ranges only; nothing that loads images asks for several at once.
+// A header we can't parse is ignored and the whole asset sent.
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some((start, end)) = value
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // Suffix range: the last n bytes
+        match end.parse::<u64>() {
+            Ok(n) if n > 0 && len > 0 => Some((len.saturating_sub(n), len - 1)),
+            Ok(_) => None,
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                _ => return ByteRange::Full,
+            }
+        };
+        (start < len).then_some((start, end))
+    };
+    match range {
+        Some((start, end)) => ByteRange::Part(start, end),
+        None => ByteRange::Unsatisfiable,
+    }
+}
+
+fn read_blob(conn: &Connection, asset: &Asset, start: u64, end: u64) -> Result<Vec<u8>, String> {
+    let mut blob = conn
+        .blob_open(DatabaseName::Main, asset.table, "data", asset.rowid, true)
+        .map_err(|e| format!("Failed to open asset data: {}", e))?;
+    let mut body = vec![0; (end - start) as usize];
+    blob.seek(SeekFrom::Start(start))
+        .and_then(|_| blob.read_exact(&mut body))
+        .map_err(|e| format!("Failed to read asset data: {}", e))?;
+    Ok(body)
+}
+
+fn empty(status: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = status;
+    response
+}
+
+fn respond<R: Runtime>(
+    app: &AppHandle<R>,
+    request: &Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    let path = request.uri().path().trim_start_matches('/');
+    let Some((kind, id)) = path.split_once('/') else {
+        return Ok(empty(StatusCode::NOT_FOUND));
+    };
+
+    let db = app.state::<Db>();
+    let conn =
+        db.0.lock()
+            .map_err(|e| format!("Failed to lock database: {}", e))?;
+    let Some(asset) = lookup(&conn, kind, id)? else {
+        return Ok(empty(StatusCode::NOT_FOUND));
+    };
+
+    let etag = format!("\"{}-{}\"", id, asset.len);
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .header(header::ACCEPT_RANGES, "bytes")
+        // Lets the canvas draw assets without tainting itself for export
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+
+    let header_value =
+        |name: header::HeaderName| request.headers().get(name).and_then(|v| v.to_str().ok());
+    if header_value(header::IF_NONE_MATCH) == Some(etag.as_str()) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .map_err(|e| format!("Failed to build asset response: {}", e));
+    }
+
+    let range = header_value(header::RANGE).map_or(ByteRange::Full, |v| parse_range(v, asset.len));
+    let (builder, start, end) = match range {
+        ByteRange::Full => (builder.status(StatusCode::OK), 0, asset.len),
+        ByteRange::Part(start, end) => (
+            builder.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, asset.len),
+            ),
+            start,
+            end + 1,
+        ),
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", asset.len))
+                .body(Vec::new())
+                .map_err(|e| format!("Failed to build asset response: {}", e));
+        }
+    };
+
+    let body = read_blob(&conn, &asset, start, end)?;
+    builder
+        .header(header::CONTENT_TYPE, &asset.mime_type)
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .map_err(|e| format!("Failed to build asset response: {}", e))
+}
+
+// Registered with register_asynchronous_uri_scheme_protocol; the blob read
+// happens on the blocking pool so the webview's networking never waits on it
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = respond(&app, &request).unwrap_or_else(|e| {
+            tracing::warn!("Failed to serve {}: {}", request.uri(), e);
+            empty(StatusCode::INTERNAL_SERVER_ERROR)
+        });
+        responder.respond(response);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_clamped_to_the_asset() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Part(0, 99));
+        assert_eq!(parse_range("bytes=900-", 1000), ByteRange::Part(900, 999));
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            ByteRange::Part(900, 999)
+        );
+    }
+
+    #[test]
+    fn suffix_ranges_count_from_the_end() {
+        assert_eq!(parse_range("bytes=-100", 1000), ByteRange::Part(900, 999));
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Part(0, 999));
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn unparseable_headers_get_the_whole_asset() {
+        for header in [
+            "items=0-1",
+            "bytes=0-1,5-6",
+            "bytes=a-b",
+            "bytes=10-5",
+            "bytes",
+        ] {
+            assert_eq!(parse_range(header, 1000), ByteRange::Full, "{}", header);
+        }
+    }
+}
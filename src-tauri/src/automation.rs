@@ -0,0 +1,392 @@
+// Opt-in HTTP API on localhost so build tools and browser extensions can drive
+// Squish. Off by default. Every request needs the bearer token kept in the
+// keychain, and the Host header must name the loopback interface so a web
+// page can't reach the API through DNS rebinding.
+//
+//   POST /v1/compress  {"paths": [...], "preset": "Web"} or {"paths", "options"}
+//                      -> 202 {"jobId"}
+//   GET  /v1/jobs/<id> -> {"status", "progress", "entries", "error"}
+//   POST /v1/export    {"projectId", "format", "path"} -> 202
+use serde::Deserialize;
+use serde_json::json;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Listener, Manager, State};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::db::Db;
+use crate::deep_link::{self, DeepLinkRequest};
+use crate::error::Error;
+use crate::imaging::CompressOptions;
+use crate::jobs::{self, JobSpec, Priority};
+use crate::safe_path;
+use crate::secrets;
+use crate::settings::{SettingChange, SettingsState, SETTINGS_CHANGED_EVENT};
+
+const SETTING_KEYS: &[&str] = &["automation_api", "automation_api_port"];
+const TOKEN_ACCOUNT: &str = "automation-api-token";
+const MAX_BODY: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompressRequest {
+    paths: Vec<String>,
+    preset: Option<String>,
+    options: Option<CompressOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRequest {
+    project_id: String,
+    format: String,
+    path: String,
+}
+
+#[derive(Default)]
+pub struct AutomationState {
+    // The serving thread is joined on stop so the port is free to rebind
+    server: Mutex<Option<(Arc<Server>, JoinHandle<()>)>>,
+    token: RwLock<Option<String>>,
+}
+
+fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn store_token(token: &str) -> Result<(), String> {
+    secrets::set(TOKEN_ACCOUNT, token)
+}
+
+fn load_token() -> Result<String, String> {
+    match secrets::get(TOKEN_ACCOUNT)? {
+        Some(token) => Ok(token),
+        None => {
+            let token = new_token();
+            store_token(&token)?;
+            Ok(token)
+        }
+    }
+}
+
+// Compares without returning early, so response timing doesn't leak how much
+// of a guessed token was right
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+fn loopback_host(request: &Request) -> bool {
+    let Some(host) = header(request, "Host") else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') && port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    matches!(name, "127.0.0.1" | "localhost" | "[::1]")
+}
+
+fn authorized(app: &AppHandle, request: &Request) -> bool {
+    let state = app.state::<AutomationState>();
+    let Ok(token) = state.token.read() else {
+        return false;
+    };
+    let (Some(expected), Some(given)) = (
+        token.as_deref(),
+        header(request, "Authorization").and_then(|v| v.strip_prefix("Bearer ")),
+    ) else {
+        return false;
+    };
+    token_matches(given.trim(), expected)
+}
+
+fn body<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, String> {
+    let mut text = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid request body: {}", e))
+}
+
+fn respond(request: Request, status: u16, value: serde_json::Value) {
+    let mut response = Response::from_string(value.to_string()).with_status_code(status);
+    if let Ok(content_type) = Header::from_bytes("Content-Type", "application/json") {
+        response.add_header(content_type);
+    }
+    if let Err(e) = request.respond(response) {
+        tracing::debug!("Failed to send API response: {}", e);
+    }
+}
+
+fn resolve_options(app: &AppHandle, body: &CompressRequest) -> Result<CompressOptions, String> {
+    match (&body.preset, &body.options) {
+        (Some(_), Some(_)) => Err("preset and options can't be combined".to_string()),
+        (Some(name), None) => {
+            let db = app.state::<Db>();
+            let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+            Ok(crate::presets::find_by_name(&conn, name)?
+                .ok_or_else(|| format!("No preset named {}", name))?
+                .options)
+        }
+        (None, Some(options)) => Ok(options.clone()),
+        (None, None) => Err("Either preset or options is required".to_string()),
+    }
+}
+
+fn start_compress(app: &AppHandle, body: CompressRequest) -> Result<String, String> {
+    if body.paths.is_empty() {
+        return Err("No paths to compress".to_string());
+    }
+    let options = resolve_options(app, &body)?;
+    // Authenticated callers get the same access a dialog pick would give
+    safe_path::allow_all(app, &body.paths);
+    if let Some(dir) = &options.output_dir {
+        safe_path::allow(app, Path::new(dir));
+    }
+    jobs::enqueue(
+        app,
+        JobSpec::Compress {
+            paths: body.paths,
+            options,
+            project_id: None,
+        },
+        Priority::Normal,
+    )
+}
+
+fn start_export(app: &AppHandle, body: ExportRequest) -> Result<(), String> {
+    if !matches!(body.format.as_str(), "pdf" | "png" | "jpeg") {
+        return Err(format!("Unknown export format {}", body.format));
+    }
+    let exists = {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ?1)",
+            [&body.project_id],
+            |row| row.get::<_, bool>(0),
+        )
+        .map_err(|e| format!("Failed to find project: {}", e))?
+    };
+    if !exists {
+        return Err(format!("No project {}", body.project_id));
+    }
+    safe_path::allow(app, Path::new(&body.path));
+    // Rendering is the UI's job, same as an AppleScript export
+    deep_link::dispatch(
+        app,
+        DeepLinkRequest::Export {
+            project_id: body.project_id,
+            format: body.format,
+            path: body.path,
+        },
+    );
+    Ok(())
+}
+
+fn handle(app: &AppHandle, mut request: Request) {
+    if !loopback_host(&request) {
+        return respond(request, 403, json!({ "error": "Forbidden host" }));
+    }
+    if !authorized(app, &request) {
+        return respond(request, 401, json!({ "error": "Missing or invalid token" }));
+    }
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or_default();
+    match (method, path) {
+        (Method::Post, "/v1/compress") => {
+            match body(&mut request).and_then(|b| start_compress(app, b)) {
+                Ok(id) => respond(request, 202, json!({ "jobId": id })),
+                Err(e) => respond(request, 400, json!({ "error": e })),
+            }
+        }
+        (Method::Post, "/v1/export") => {
+            match body(&mut request).and_then(|b| start_export(app, b)) {
+                Ok(()) => respond(request, 202, json!({})),
+                Err(e) => respond(request, 400, json!({ "error": e })),
+            }
+        }
+        (Method::Get, job) if job.starts_with("/v1/jobs/") => {
+            let id = &job["/v1/jobs/".len()..];
+            match jobs::status(app, id) {
+                Some(job) => respond(
+                    request,
+                    200,
+                    json!({
+                        "status": job.status,
+                        "progress": job.progress,
+                        "entries": job.result.unwrap_or_else(|| json!([])),
+                        "error": job.error,
+                    }),
+                ),
+                None => respond(request, 404, json!({ "error": "No such job" })),
+            }
+        }
+        _ => respond(request, 404, json!({ "error": "Not found" })),
+    }
+}
+
+fn stop(app: &AppHandle) {
+    let state = app.state::<AutomationState>();
+    let server = state.server.lock().ok().and_then(|mut s| s.take());
+    if let Some((server, thread)) = server {
+        server.unblock();
+        let _ = thread.join();
+        tracing::info!("Automation API stopped");
+    }
+}
+
+// Starts, stops or moves the server to match the settings
+fn apply(app: &AppHandle) {
+    stop(app);
+    let settings = app.state::<SettingsState>().snapshot();
+    if !settings.automation_api {
+        return;
+    }
+    let state = app.state::<AutomationState>();
+    match load_token() {
+        Ok(token) => {
+            if let Ok(mut current) = state.token.write() {
+                *current = Some(token);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Automation API not started: {}", e);
+            return;
+        }
+    }
+    let server = match Server::http(("127.0.0.1", settings.automation_api_port)) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to start automation API on port {}: {}",
+                settings.automation_api_port,
+                e
+            );
+            return;
+        }
+    };
+    tracing::info!(
+        "Automation API listening on 127.0.0.1:{}",
+        settings.automation_api_port
+    );
+
+    let handle_app = app.clone();
+    let serving = server.clone();
+    let thread = std::thread::spawn(move || {
+        for request in serving.incoming_requests() {
+            handle(&handle_app, request);
+        }
+    });
+    if let Ok(mut current) = state.server.lock() {
+        *current = Some((server, thread));
+    };
+}
+
+pub fn start(app: &AppHandle) {
+    apply(app);
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if SETTING_KEYS.contains(&change.key.as_str()) {
+            apply(&handle);
+        }
+    });
+}
+
+// Shown in settings so the user can paste it into the tool they're connecting
+#[tauri::command]
+pub fn get_automation_token() -> Result<String, Error> {
+    Ok(load_token()?)
+}
+
+// Invalidates the old token immediately, including for a running server
+#[tauri::command]
+pub fn reset_automation_token(state: State<AutomationState>) -> Result<String, Error> {
+    let token = new_token();
+    store_token(&token)?;
+    let mut current = state
+        .token
+        .write()
+        .map_err(|e| format!("Failed to lock API token: {}", e))?;
+    if current.is_some() {
+        *current = Some(token.clone());
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_http::TestRequest;
+
+    fn request(host: Option<&str>) -> Request {
+        let mut request = TestRequest::new();
+        if let Some(host) = host {
+            request = request.with_header(Header::from_bytes(&b"Host"[..], host).unwrap());
+        }
+        request.into()
+    }
+
+    #[test]
+    fn token_must_match_exactly() {
+        assert!(token_matches("s3cret", "s3cret"));
+        assert!(!token_matches("s3creT", "s3cret"));
+        assert!(!token_matches("s3cre", "s3cret"));
+        assert!(!token_matches("s3crets", "s3cret"));
+        assert!(!token_matches("", "s3cret"));
+    }
+
+    #[test]
+    fn loopback_hosts_are_accepted() {
+        for host in [
+            "127.0.0.1",
+            "127.0.0.1:7777",
+            "localhost",
+            "localhost:80",
+            "[::1]",
+            "[::1]:7777",
+        ] {
+            assert!(loopback_host(&request(Some(host))), "{}", host);
+        }
+    }
+
+    #[test]
+    fn other_hosts_are_rejected() {
+        for host in [
+            "example.com",
+            "localhost.example.com",
+            "localhost:notaport",
+            "evil.com:127.0.0.1",
+            "::1",
+            "0.0.0.0",
+        ] {
+            assert!(!loopback_host(&request(Some(host))), "{}", host);
+        }
+        assert!(!loopback_host(&request(None)));
+    }
+}
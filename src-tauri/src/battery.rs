@@ -0,0 +1,236 @@
+// Battery saver: while running on battery or in Low Power Mode (and the
+// setting is on), batches use half the workers and skip slow encoder passes.
+// The UI is told through power://saver so it can explain why jobs are slower.
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
+
+use crate::error::Error;
+use crate::settings::{SettingChange, SettingsState, SETTINGS_CHANGED_EVENT};
+use crate::workers::WorkerConfig;
+
+pub const SAVER_EVENT: &str = "power://saver";
+const SETTING_KEY: &str = "battery_saver";
+const POLL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    pub low_power_mode: bool,
+    // Whether jobs are currently throttled
+    pub saver_active: bool,
+}
+
+pub struct PowerState(pub Mutex<PowerStatus>);
+
+fn read_status(app: &AppHandle) -> PowerStatus {
+    let on_battery = platform::on_battery();
+    let low_power_mode = platform::low_power_mode();
+    let enabled = app.state::<SettingsState>().snapshot().battery_saver;
+    PowerStatus {
+        on_battery,
+        low_power_mode,
+        saver_active: enabled && (on_battery || low_power_mode),
+    }
+}
+
+// Re-reads the power source and applies it, announcing any change
+fn refresh(app: &AppHandle) {
+    let status = read_status(app);
+    let state = app.state::<PowerState>();
+    let Ok(mut current) = state.0.lock() else {
+        return;
+    };
+    if *current == status {
+        return;
+    }
+    let was_active = current.saver_active;
+    *current = status;
+    drop(current);
+
+    app.state::<WorkerConfig>()
+        .set_throttled(status.saver_active);
+    if status.saver_active != was_active {
+        tracing::info!(
+            "Battery saver {}",
+            if status.saver_active { "on" } else { "off" }
+        );
+    }
+    if let Err(e) = app.emit(SAVER_EVENT, status) {
+        tracing::warn!("Failed to emit power status: {}", e);
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    refresh(app);
+
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if change.key == SETTING_KEY {
+            refresh(&handle);
+        }
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL);
+        refresh(&handle);
+    });
+}
+
+#[tauri::command]
+pub fn get_power_status(state: State<PowerState>) -> Result<PowerStatus, Error> {
+    state
+        .0
+        .lock()
+        .map(|status| *status)
+        .map_err(|_| Error::Lock("power status"))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::id;
+    use objc::runtime::{BOOL, NO};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    // Returned by IOPSGetTimeRemainingEstimate while on AC power
+    const TIME_REMAINING_UNLIMITED: f64 = -2.0;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPSGetTimeRemainingEstimate() -> f64;
+    }
+
+    pub fn on_battery() -> bool {
+        unsafe { IOPSGetTimeRemainingEstimate() != TIME_REMAINING_UNLIMITED }
+    }
+
+    pub fn low_power_mode() -> bool {
+        unsafe {
+            let info: id = msg_send![class!(NSProcessInfo), processInfo];
+            // Only on macOS 12 and later
+            let supported: BOOL = msg_send![info, respondsToSelector: sel!(isLowPowerModeEnabled)];
+            if supported == NO {
+                return false;
+            }
+            let enabled: BOOL = msg_send![info, isLowPowerModeEnabled];
+            enabled != NO
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    // SYSTEM_POWER_STATUS; only the line status and saver flag are read
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    fn status() -> Option<SystemPowerStatus> {
+        let mut status = SystemPowerStatus::default();
+        (unsafe { GetSystemPowerStatus(&mut status) } != 0).then_some(status)
+    }
+
+    pub fn on_battery() -> bool {
+        status().is_some_and(|s| s.ac_line_status == 0)
+    }
+
+    // Windows battery saver
+    pub fn low_power_mode() -> bool {
+        status().is_some_and(|s| s.system_status_flag == 1)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::path::Path;
+
+    const POWER_SUPPLY: &str = "/sys/class/power_supply";
+    const PLATFORM_PROFILE: &str = "/sys/firmware/acpi/platform_profile";
+
+    fn read(path: &Path) -> Option<String> {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    // On battery when there is a mains supply and none of them are online;
+    // desktops without one never count as on battery
+    pub fn on_battery() -> bool {
+        mains_offline(Path::new(POWER_SUPPLY))
+    }
+
+    pub(super) fn mains_offline(power_supply: &Path) -> bool {
+        let Ok(entries) = std::fs::read_dir(power_supply) else {
+            return false;
+        };
+        let mains: Vec<bool> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|dir| read(&dir.join("type")).as_deref() == Some("Mains"))
+            .map(|dir| read(&dir.join("online")).as_deref() == Some("1"))
+            .collect();
+        !mains.is_empty() && !mains.contains(&true)
+    }
+
+    pub fn low_power_mode() -> bool {
+        read(Path::new(PLATFORM_PROFILE)).as_deref() == Some("low-power")
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    pub fn on_battery() -> bool {
+        false
+    }
+
+    pub fn low_power_mode() -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn supply(dir: &Path, name: &str, kind: &str, online: &str) {
+        let supply = dir.join(name);
+        std::fs::create_dir_all(&supply).unwrap();
+        std::fs::write(supply.join("type"), format!("{}\n", kind)).unwrap();
+        std::fs::write(supply.join("online"), format!("{}\n", online)).unwrap();
+    }
+
+    #[test]
+    fn on_battery_only_when_every_mains_supply_is_offline() {
+        let dir = std::env::temp_dir().join(format!("squish-battery-{}", std::process::id()));
+        supply(&dir, "BAT0", "Battery", "0");
+        // A desktop, or a laptop whose charger isn't listed
+        assert!(!platform::mains_offline(&dir));
+
+        supply(&dir, "AC", "Mains", "0");
+        assert!(platform::mains_offline(&dir));
+        supply(&dir, "USB-C", "Mains", "1");
+        assert!(!platform::mains_offline(&dir));
+        assert!(!platform::mains_offline(&dir.join("missing")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
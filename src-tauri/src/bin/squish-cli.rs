@@ -0,0 +1,4 @@
+// Headless entry point for scripts and CI; see cli.rs for the options
+fn main() {
+    std::process::exit(squish_lib::cli::main());
+}
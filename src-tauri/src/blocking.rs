@@ -0,0 +1,67 @@
+// Keeps the IPC thread free. Synchronous commands run on the thread that
+// receives IPC messages, so while one decodes an image or walks the disk,
+// every other call (and on some platforms the UI itself) waits. Heavy
+// commands are `async` and hand their work to the blocking pool with `run`;
+// `watch` times every handler call so anything that still blocks shows up.
+use std::time::{Duration, Instant};
+use tauri::ipc::Invoke;
+
+use crate::perf;
+
+// Longer than a frame at 60Hz is already noticeable
+const BUDGET: Duration = Duration::from_millis(16);
+// Debug builds started with this set panic on an over-budget command, so a
+// dev run or UI test fails instead of just logging
+const STRICT_ENV: &str = "SQUISH_STRICT_IPC";
+
+pub async fn run<T, E, F>(f: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| E::from(format!("Background task failed: {}", e)))?
+}
+
+// Wraps the invoke handler. Async commands return as soon as they're spawned,
+// so only work done on the IPC thread is counted.
+pub fn watch<H>(handler: H) -> impl Fn(Invoke) -> bool + Send + Sync + 'static
+where
+    H: Fn(Invoke) -> bool + Send + Sync + 'static,
+{
+    let strict = cfg!(debug_assertions) && std::env::var_os(STRICT_ENV).is_some();
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        let started = Instant::now();
+        let handled = tracing::info_span!(target: perf::IPC, "ipc", name = command.as_str())
+            .in_scope(|| handler(invoke));
+        let elapsed = started.elapsed();
+        if elapsed > BUDGET {
+            tracing::warn!("Command {} blocked IPC for {:?}", command, elapsed);
+            if strict {
+                panic!(
+                    "Command {} blocked IPC for {:?}, over the {:?} budget",
+                    command, elapsed, BUDGET
+                );
+            }
+        }
+        handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_and_panics_come_back_as_results() {
+        let value = tauri::async_runtime::block_on(run(|| Ok::<_, String>(42)));
+        assert_eq!(value, Ok(42));
+
+        let panicked: Result<(), String> =
+            tauri::async_runtime::block_on(run(|| panic!("decoder crashed")));
+        assert!(panicked.unwrap_err().starts_with("Background task failed"));
+    }
+}
@@ -0,0 +1,222 @@
+// Persistent access to folders the user picked. Under the macOS App Sandbox a
+// dialog pick only grants access until quit; a security-scoped bookmark saved
+// at that moment can be resolved on the next launch to get it back. Watch
+// folders, the default import/output folders and asset directories the UI
+// registers are bookmarked. Elsewhere there's nothing to persist and these
+// calls do nothing.
+use rusqlite::params;
+use std::path::Path;
+use tauri::{AppHandle, Listener, Manager};
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::safe_path::{self, SafePath};
+use crate::settings::{SettingChange, SETTINGS_CHANGED_EVENT};
+
+// Settings holding folders the user picked in a dialog
+const FOLDER_SETTINGS: &[&str] = &["default_output_dir", "default_import_dir"];
+
+pub struct Resolved {
+    pub path: String,
+    // The folder moved or its volume changed; the bookmark should be recreated
+    pub stale: bool,
+}
+
+fn store(app: &AppHandle, path: &str, data: &[u8]) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO bookmarks (path, data) VALUES (?1, ?2)
+         ON CONFLICT(path) DO UPDATE SET data = ?2, updated_at = CURRENT_TIMESTAMP",
+        params![path, data],
+    )
+    .map_err(|e| format!("Failed to save bookmark: {}", e))?;
+    Ok(())
+}
+
+// Has to run while access is still granted, i.e. right after the user chose
+// the folder
+pub fn remember(app: &AppHandle, path: &str) {
+    let data = match platform::create(path) {
+        Ok(Some(data)) => data,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to bookmark {}: {}", path, e);
+            return;
+        }
+    };
+    if let Err(e) = store(app, path, &data) {
+        tracing::warn!("{}", e);
+    }
+}
+
+fn load(app: &AppHandle) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT path, data FROM bookmarks")
+        .map_err(|e| format!("Failed to load bookmarks: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to load bookmarks: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to load bookmarks: {}", e))
+}
+
+fn delete(app: &AppHandle, path: &str) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM bookmarks WHERE path = ?1", [path])
+        .map_err(|e| format!("Failed to remove bookmark: {}", e))?;
+    Ok(())
+}
+
+// Must run before anything touches the bookmarked folders (watch folders are
+// started right after). Access stays open until Squish quits.
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    for (path, data) in load(app)? {
+        let resolved = match platform::resolve(&data) {
+            Ok(resolved) => resolved,
+            // Often just an unmounted drive; keep it for next time
+            Err(e) => {
+                tracing::warn!("Failed to resolve bookmark for {}: {}", path, e);
+                continue;
+            }
+        };
+        safe_path::allow(app, Path::new(&resolved.path));
+        if resolved.path != path {
+            tracing::info!("Bookmarked folder moved: {} -> {}", path, resolved.path);
+            delete(app, &path)?;
+        }
+        if resolved.stale || resolved.path != path {
+            remember(app, &resolved.path);
+        }
+    }
+
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if FOLDER_SETTINGS.contains(&change.key.as_str()) {
+            if let Some(path) = change.value.as_str() {
+                remember(&handle, path);
+            }
+        }
+    });
+    Ok(())
+}
+
+// For folders the UI keeps using across launches (asset directories); call it
+// right after the dialog returns
+#[tauri::command]
+pub fn remember_folder(app: AppHandle, path: String) -> Result<(), Error> {
+    let path = SafePath::read(&app, &path)?.into_string();
+    remember(&app, &path);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn forget_folder(app: AppHandle, path: String) -> Result<(), Error> {
+    Ok(delete(&app, &path)?)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil, BOOL, NO};
+    use cocoa::foundation::{NSAutoreleasePool, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_void};
+
+    use super::Resolved;
+
+    const CREATION_WITH_SECURITY_SCOPE: u64 = 1 << 11;
+    const RESOLUTION_WITHOUT_UI: u64 = 1 << 8;
+    const RESOLUTION_WITH_SECURITY_SCOPE: u64 = 1 << 10;
+
+    unsafe fn rust_string(string: id) -> String {
+        if string == nil {
+            return String::new();
+        }
+        let utf8: *const c_char = msg_send![string, UTF8String];
+        CStr::from_ptr(utf8).to_string_lossy().to_string()
+    }
+
+    unsafe fn describe(error: id) -> String {
+        if error == nil {
+            return "unknown error".to_string();
+        }
+        rust_string(msg_send![error, localizedDescription])
+    }
+
+    pub fn create(path: &str) -> Result<Option<Vec<u8>>, String> {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let ns_path = NSString::alloc(nil).init_str(path).autorelease();
+            let url: id = msg_send![class!(NSURL), fileURLWithPath: ns_path];
+            let mut error: id = nil;
+            let data: id = msg_send![url,
+                bookmarkDataWithOptions: CREATION_WITH_SECURITY_SCOPE
+                includingResourceValuesForKeys: nil
+                relativeToURL: nil
+                error: &mut error];
+            let result = if data == nil {
+                Err(describe(error))
+            } else {
+                let length: usize = msg_send![data, length];
+                let bytes: *const u8 = msg_send![data, bytes];
+                Ok(Some(std::slice::from_raw_parts(bytes, length).to_vec()))
+            };
+            let _: () = msg_send![pool, drain];
+            result
+        }
+    }
+
+    pub fn resolve(bookmark: &[u8]) -> Result<Resolved, String> {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let data: id = msg_send![class!(NSData),
+                dataWithBytes: bookmark.as_ptr() as *const c_void
+                length: bookmark.len()];
+            let mut stale: BOOL = NO;
+            let mut error: id = nil;
+            let url: id = msg_send![class!(NSURL),
+                URLByResolvingBookmarkData: data
+                options: RESOLUTION_WITH_SECURITY_SCOPE | RESOLUTION_WITHOUT_UI
+                relativeToURL: nil
+                bookmarkDataIsStale: &mut stale
+                error: &mut error];
+            let result = if url == nil {
+                Err(describe(error))
+            } else {
+                // Never balanced with stopAccessing: the folders are used for
+                // as long as Squish runs
+                let started: BOOL = msg_send![url, startAccessingSecurityScopedResource];
+                if started == NO {
+                    tracing::debug!("Bookmark resolved without a security scope");
+                }
+                Ok(Resolved {
+                    path: rust_string(msg_send![url, path]),
+                    stale: stale != NO,
+                })
+            };
+            let _: () = msg_send![pool, drain];
+            result
+        }
+    }
+}
+
+// Outside the sandbox a granted path stays reachable, so nothing is stored
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::Resolved;
+
+    pub fn create(_path: &str) -> Result<Option<Vec<u8>>, String> {
+        Ok(None)
+    }
+
+    pub fn resolve(_bookmark: &[u8]) -> Result<Resolved, String> {
+        Err("Bookmarks are only supported on macOS".to_string())
+    }
+}
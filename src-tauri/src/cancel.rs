@@ -0,0 +1,184 @@
+// Cooperative cancellation. Long-running work takes a CancelToken and calls
+// `check` between its expensive steps (decode, resize, every encode attempt,
+// every PDF image or printed page), so a cancel takes effect inside the
+// current file rather than after it. Encoders work in memory and are simply
+// dropped; files are written through PartialOutput, so an abandoned one never
+// shows up half-written.
+//
+// The job queue holds a token per running job. Commands that aren't jobs
+// accept an optional `operation_id` from the UI, which can then be passed to
+// cancel_operation while the command is running.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::Error;
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Tokens of running commands, by the operation id the UI gave them
+#[derive(Default)]
+pub struct Operations(Mutex<HashMap<String, CancelToken>>);
+
+// Unregisters the operation when the command returns
+pub struct Operation {
+    app: AppHandle,
+    id: Option<String>,
+    token: CancelToken,
+}
+
+impl Operation {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let Some(id) = &self.id else {
+            return;
+        };
+        if let Ok(mut operations) = self.app.state::<Operations>().0.lock() {
+            operations.remove(id);
+        }
+    }
+}
+
+// Without an id the command just can't be cancelled
+pub fn register(app: &AppHandle, id: Option<String>) -> Operation {
+    let token = CancelToken::default();
+    if let Some(id) = &id {
+        if let Ok(mut operations) = app.state::<Operations>().0.lock() {
+            operations.insert(id.clone(), token.clone());
+        }
+    }
+    Operation {
+        app: app.clone(),
+        id,
+        token,
+    }
+}
+
+// A file being produced. It's written to a hidden sibling and only moved
+// into place by `finish`; dropped unfinished (cancelled, or a later step
+// failed) the sibling is deleted and whatever was at the destination before is
+// left alone.
+pub struct PartialOutput {
+    temp: PathBuf,
+    dest: PathBuf,
+    finished: bool,
+}
+
+impl PartialOutput {
+    pub fn new(dest: &Path) -> Self {
+        let name = dest
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        PartialOutput {
+            temp: dest.with_file_name(format!(".{}.part", name)),
+            dest: dest.to_path_buf(),
+            finished: false,
+        }
+    }
+
+    // Where to write
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    pub fn finish(mut self) -> Result<PathBuf, String> {
+        std::fs::rename(&self.temp, &self.dest)
+            .map_err(|e| format!("Failed to write {}: {}", self.dest.display(), e))?;
+        self.finished = true;
+        Ok(std::mem::take(&mut self.dest))
+    }
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        if self.finished || !self.temp.exists() {
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.temp) {
+            tracing::warn!("Failed to remove {}: {}", self.temp.display(), e);
+        }
+    }
+}
+
+// Returns false when nothing with that id is running (it may have just finished)
+#[tauri::command]
+pub fn cancel_operation(operations: State<Operations>, id: String) -> Result<bool, Error> {
+    let operations = operations.0.lock().map_err(|_| Error::Lock("operations"))?;
+    let Some(token) = operations.get(&id) else {
+        return Ok(false);
+    };
+    tracing::info!("Cancelling operation {}", id);
+    token.cancel();
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dest(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-cancel-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("out.png")
+    }
+
+    #[test]
+    fn a_cancelled_token_fails_its_checks() {
+        let token = CancelToken::default();
+        let shared = token.clone();
+        assert!(token.check().is_ok());
+        shared.cancel();
+        assert!(matches!(token.check(), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn finishing_moves_the_file_into_place() {
+        let dest = dest("finish");
+        let output = PartialOutput::new(&dest);
+        assert_eq!(output.path().parent(), dest.parent());
+        std::fs::write(output.path(), b"new").unwrap();
+        assert!(!dest.exists());
+        assert_eq!(output.finish().unwrap(), dest);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+    }
+
+    #[test]
+    fn an_abandoned_output_leaves_the_old_file_alone() {
+        let dest = dest("abandon");
+        std::fs::write(&dest, b"old").unwrap();
+        let output = PartialOutput::new(&dest);
+        let temp = output.path().to_path_buf();
+        std::fs::write(&temp, b"half").unwrap();
+        drop(output);
+        assert!(!temp.exists());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"old");
+    }
+}
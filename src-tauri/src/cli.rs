@@ -0,0 +1,320 @@
+// `squish-cli`, the same compression engine and presets as the app without a
+// window. Presets are read from the library of the active (or given) profile.
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::cancel::CancelToken;
+use crate::db::{self, Db};
+use crate::hooks::{self, Export};
+use crate::imaging::{compress_file, BatchEntry, CompressOptions};
+use crate::job_history::record_compress;
+use crate::scripts;
+use crate::workers::{effective_concurrency, run_parallel};
+
+// Same directory Tauri resolves app_config_dir to for our identifier
+const IDENTIFIER: &str = "com.squish.dev";
+
+const USAGE: &str = "Usage: squish-cli [options] <file>...
+       squish-cli --script <file.rhai> [--profile <id>]
+
+Options:
+  --preset <name>       Start from a saved preset
+  --options <json>      Start from CompressOptions JSON instead
+  --format <format>     jpeg, png, webp or jxl
+  --quality <1-100>     Encoder quality
+  --output-dir <dir>    Write outputs here instead of beside the inputs
+  --profile <id>        Profile whose library presets come from
+  --jobs <n>            Files compressed in parallel
+  --json                Print results as JSON
+  --script <file>       Run a Rhai automation script (see scripts.rs)
+  --help                Show this message";
+
+#[derive(Default)]
+struct Args {
+    preset: Option<String>,
+    options: Option<String>,
+    overrides: serde_json::Map<String, Value>,
+    profile: Option<String>,
+    jobs: Option<usize>,
+    json: bool,
+    script: Option<String>,
+    paths: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--preset" => parsed.preset = Some(value("--preset")?),
+            "--options" => parsed.options = Some(value("--options")?),
+            "--format" => {
+                parsed
+                    .overrides
+                    .insert("format".into(), Value::String(value("--format")?));
+            }
+            "--quality" => {
+                let quality: u8 = value("--quality")?
+                    .parse()
+                    .map_err(|e| format!("Invalid --quality: {}", e))?;
+                parsed.overrides.insert("quality".into(), quality.into());
+            }
+            "--output-dir" => {
+                parsed
+                    .overrides
+                    .insert("outputDir".into(), Value::String(value("--output-dir")?));
+            }
+            "--profile" => parsed.profile = Some(value("--profile")?),
+            "--jobs" => {
+                let jobs = value("--jobs")?
+                    .parse()
+                    .map_err(|e| format!("Invalid --jobs: {}", e))?;
+                parsed.jobs = Some(jobs);
+            }
+            "--json" => parsed.json = true,
+            "--script" => parsed.script = Some(value("--script")?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            path => parsed.paths.push(path.to_string()),
+        }
+    }
+    if parsed.preset.is_some() && parsed.options.is_some() {
+        return Err("--preset and --options can't be combined".to_string());
+    }
+    Ok(Some(parsed))
+}
+
+fn library_path(profile: Option<&str>) -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir()
+        .ok_or("Failed to resolve config directory")?
+        .join(IDENTIFIER);
+    let dir = match profile {
+        Some(id) => crate::profiles::profile_dir(&config_dir, id),
+        None => crate::profiles::active_profile_dir(&config_dir),
+    };
+    Ok(dir.join(db::DB_FILE))
+}
+
+// Starts from the preset or JSON, then applies the individual flags on top
+fn resolve_options(args: &Args, library: Option<&Db>) -> Result<CompressOptions, String> {
+    let mut options = match (&args.preset, &args.options) {
+        (Some(name), _) => {
+            let db = library.ok_or("No library to read presets from")?;
+            let conn =
+                db.0.lock()
+                    .map_err(|e| format!("Failed to lock database: {}", e))?;
+            let preset = crate::presets::find_by_name(&conn, name)?
+                .ok_or_else(|| format!("No preset named {}", name))?;
+            serde_json::to_value(preset.options)
+                .map_err(|e| format!("Failed to read preset {}: {}", name, e))?
+        }
+        (None, Some(json)) => {
+            serde_json::from_str(json).map_err(|e| format!("Invalid --options JSON: {}", e))?
+        }
+        (None, None) => serde_json::json!({ "format": "webp", "quality": 80 }),
+    };
+    if let Value::Object(map) = &mut options {
+        map.extend(args.overrides.clone());
+    }
+    serde_json::from_value(options).map_err(|e| format!("Invalid compression options: {}", e))
+}
+
+// Output lines are printed as the script runs, the final value as JSON. A
+// script that fails exits 1 like a failed file.
+fn run_script(script: &str, library: Option<Db>) -> Result<bool, String> {
+    let source =
+        std::fs::read_to_string(script).map_err(|e| format!("Failed to read {}: {}", script, e))?;
+    let host = scripts::Host::Cli(library.map(Arc::new));
+    match scripts::run(host, &source, &CancelToken::default()) {
+        Ok(result) => {
+            if !result.value.is_null() {
+                println!("{}", result.value);
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            Ok(false)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<bool, String> {
+    if let Some(script) = &args.script {
+        let path = library_path(args.profile.as_deref())?;
+        let library = if path.exists() {
+            Some(Db(Mutex::new(db::open_at(&path)?)))
+        } else {
+            None
+        };
+        return run_script(script, library);
+    }
+    if args.paths.is_empty() {
+        return Err("No input files".to_string());
+    }
+    // The library is only needed for presets and job history, so a missing
+    // one isn't an error unless a preset was asked for
+    let path = library_path(args.profile.as_deref())?;
+    let library = if path.exists() {
+        Some(Db(Mutex::new(db::open_at(&path)?)))
+    } else {
+        None
+    };
+    let options = resolve_options(&args, library.as_ref())?;
+
+    // Hooks run once the batch is done, so a slow one doesn't hold up a worker
+    let exports = Mutex::new(Vec::new());
+    let entries = run_parallel(
+        args.paths.clone(),
+        effective_concurrency(args.jobs),
+        |path| {
+            let started = Instant::now();
+            let outcome = compress_file(path.clone(), &options, &CancelToken::default());
+            if let Some(db) = &library {
+                let job = record_compress(db, "cli", &path, &outcome, started, &options);
+                if let (Some(export), Ok(mut exports)) = (Export::new(&job), exports.lock()) {
+                    exports.push(export);
+                }
+            }
+            match outcome {
+                Ok(result) => BatchEntry {
+                    input_path: path,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(e) => BatchEntry {
+                    input_path: path,
+                    result: None,
+                    error: Some(e),
+                },
+            }
+        },
+    );
+    if let (Some(db), Ok(exports)) = (&library, exports.into_inner()) {
+        for export in &exports {
+            hooks::run_all(db, export);
+        }
+    }
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to serialize results: {}", e))?;
+        println!("{}", json);
+    } else {
+        for entry in &entries {
+            match (&entry.result, &entry.error) {
+                (Some(result), _) => println!(
+                    "{} -> {} ({} -> {} bytes)",
+                    entry.input_path, result.output_path, result.input_bytes, result.output_bytes
+                ),
+                (None, Some(e)) => eprintln!("{}: {}", entry.input_path, e),
+                (None, None) => {}
+            }
+        }
+    }
+    Ok(entries.iter().all(|e| e.error.is_none()))
+}
+
+// Exit code: 0 when every file compressed, 1 when any failed, 2 on bad usage
+pub fn main() -> i32 {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    match run(args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imaging::OutputFormat;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn flags_and_paths_are_parsed() {
+        let args = parse(&[
+            "--format",
+            "png",
+            "a.jpg",
+            "--quality",
+            "70",
+            "--json",
+            "b.jpg",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.paths, vec!["a.jpg", "b.jpg"]);
+        assert_eq!(args.overrides["format"], "png");
+        assert_eq!(args.overrides["quality"], 70);
+        assert!(args.json);
+        assert!(parse(&["a.jpg", "--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        assert!(parse(&["--quality"]).is_err());
+        assert!(parse(&["--quality", "300"]).is_err());
+        assert!(parse(&["--jobs", "many"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+        assert!(parse(&["--preset", "Web", "--options", "{}"]).is_err());
+    }
+
+    #[test]
+    fn flags_override_the_starting_options() {
+        let args = parse(&["--quality", "55"]).unwrap().unwrap();
+        let options = resolve_options(&args, None).unwrap();
+        assert_eq!(options.format, OutputFormat::Webp);
+        assert_eq!(options.quality, 55);
+
+        let args = parse(&[
+            "--options",
+            r#"{"format":"jpeg","quality":90}"#,
+            "--format",
+            "png",
+        ])
+        .unwrap()
+        .unwrap();
+        let options = resolve_options(&args, None).unwrap();
+        assert_eq!(options.format, OutputFormat::Png);
+        assert_eq!(options.quality, 90);
+    }
+
+    #[test]
+    fn presets_come_from_the_library() {
+        let args = parse(&["--preset", "Print", "--quality", "60"])
+            .unwrap()
+            .unwrap();
+        assert!(resolve_options(&args, None).is_err());
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            r#"INSERT INTO presets (id, name, options) VALUES ('p1', 'Print', '{"format":"jpeg","quality":95}')"#,
+            [],
+        )
+        .unwrap();
+        let library = Db(Mutex::new(conn));
+        let options = resolve_options(&args, Some(&library)).unwrap();
+        assert_eq!(options.format, OutputFormat::Jpeg);
+        assert_eq!(options.quality, 60);
+    }
+}
@@ -0,0 +1,141 @@
+use image::{DynamicImage, RgbaImage};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Url};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::blocking;
+use crate::error::Error;
+use crate::imaging::{decode_image, encode_image, OutputFormat, SourceFormat};
+use crate::safe_path::SafePath;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardImport {
+    pub asset_id: String,
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Saves whatever image is on the clipboard (bitmap data or a copied image file)
+// into the app cache so it can go through the normal compression pipeline
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn paste_image_from_clipboard(app: AppHandle) -> Result<ClipboardImport, Error> {
+    blocking::run(move || {
+        let no_image = || Error::NotFound("Clipboard does not contain an image".to_string());
+        let image = match app.clipboard().read_image() {
+            Ok(image) => RgbaImage::from_raw(image.width(), image.height(), image.rgba().to_vec())
+                .map(DynamicImage::ImageRgba8)
+                .ok_or_else(|| "Clipboard image has an unexpected buffer size".to_string())?,
+            Err(_) => {
+                let text = app.clipboard().read_text().map_err(|_| no_image())?;
+                let path = file_from_clipboard_text(&text).ok_or_else(no_image)?;
+                decode_image(&path)?
+            }
+        };
+
+        let asset_id = uuid::Uuid::new_v4().to_string();
+        let path = clipboard_dir(&app)?.join(format!("{}.png", asset_id));
+        let bytes = encode_image(&image, OutputFormat::Png, 100)?;
+        std::fs::write(&path, bytes)?;
+
+        tracing::info!("Saved clipboard image to {}", path.display());
+        Ok(ClipboardImport {
+            asset_id,
+            path: path.to_string_lossy().to_string(),
+            width: image.width(),
+            height: image.height(),
+        })
+    })
+    .await
+}
+
+// Puts a (usually freshly compressed) image on the clipboard. With as_file the
+// file itself is copied, which keeps the compressed bytes intact when pasting
+// into Slack or a mail client instead of letting them re-encode the bitmap.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn copy_image_to_clipboard(
+    app: AppHandle,
+    path: String,
+    as_file: Option<bool>,
+) -> Result<(), Error> {
+    blocking::run(move || {
+        let file = SafePath::read(&app, &path)?.into_path_buf();
+        if !file.is_file() {
+            return Err(Error::NotFound(format!("Not a file: {}", path)));
+        }
+
+        crate::clipboard_monitor::ignore_next_change();
+        if as_file.unwrap_or(false) {
+            let uri = Url::from_file_path(&file)
+                .map_err(|_| format!("Failed to make a file URL for {}", path))?;
+            let context = clipboard_rs::ClipboardContext::new()
+                .map_err(|e| format!("Failed to open clipboard: {}", e))?;
+            clipboard_rs::Clipboard::set_files(&context, vec![uri.to_string()])
+                .map_err(|e| format!("Failed to copy file to clipboard: {}", e))?;
+            return Ok(());
+        }
+
+        let rgba = decode_image(&file)?.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        let image = tauri::image::Image::new_owned(rgba.into_raw(), width, height);
+        app.clipboard()
+            .write_image(&image)
+            .map_err(|e| format!("Failed to copy image to clipboard: {}", e))?;
+        Ok(())
+    })
+    .await
+}
+
+pub fn clipboard_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?
+        .join("clipboard");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+// Finder and Explorer put file URLs or plain paths on the clipboard when copying files
+fn file_from_clipboard_text(text: &str) -> Option<PathBuf> {
+    let line = text.lines().next()?.trim();
+    let path = if line.starts_with("file:") {
+        Url::parse(line).ok()?.to_file_path().ok()?
+    } else {
+        PathBuf::from(line)
+    };
+    (path.is_file() && SourceFormat::is_supported(&path)).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copied_files_are_found_by_path_or_url() {
+        let dir = std::env::temp_dir().join(format!("squish-clipboard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("copied image #1.png");
+        std::fs::write(&image, b"").unwrap();
+        let url = Url::from_file_path(&image).unwrap().to_string();
+        assert!(url.contains("%20"));
+
+        assert_eq!(file_from_clipboard_text(&url), Some(image.clone()));
+        let plain = format!("{}\nsecond.png", image.display());
+        assert_eq!(file_from_clipboard_text(&plain), Some(image.clone()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn other_text_is_not_a_file() {
+        assert_eq!(file_from_clipboard_text(""), None);
+        assert_eq!(file_from_clipboard_text("hello world"), None);
+        assert_eq!(file_from_clipboard_text("file:///no/such/image.png"), None);
+        assert_eq!(file_from_clipboard_text("file://server/share/a.png"), None);
+    }
+}
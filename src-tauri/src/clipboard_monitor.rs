@@ -0,0 +1,335 @@
+// Opt-in clipboard monitoring: when an image is copied anywhere, Squish offers
+// to compress it. Off by default and kept deliberately narrow:
+//  - only image data is ever read; text on the clipboard is never touched
+//  - content marked concealed or transient (password managers, screenshot
+//    tools that opt out) is skipped without being read
+//  - the offered image stays in memory and is only written to disk if the
+//    user accepts it; a newer copy or a dismissal discards it
+//  - the UI is told whenever monitoring starts or stops so it can show it
+use image::{DynamicImage, RgbaImage};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::blocking;
+use crate::cancel;
+use crate::error::Error;
+use crate::i18n::tr;
+use crate::imaging::{compress_one, encode_image, CompressOptions, CompressResult, OutputFormat};
+use crate::notifications::{self, NotificationTarget};
+use crate::settings::{SettingChange, SettingsState, SETTINGS_CHANGED_EVENT};
+
+pub const IMAGE_EVENT: &str = "clipboard://image";
+pub const MONITORING_EVENT: &str = "clipboard://monitoring";
+const SETTING_KEY: &str = "clipboard_monitor";
+const POLL: Duration = Duration::from_secs(1);
+
+// Set when Squish itself copies an image, so its own results aren't offered
+static SELF_COPY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardOffer {
+    pub offer_id: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct PendingImage {
+    offer_id: String,
+    image: DynamicImage,
+}
+
+#[derive(Default)]
+pub struct ClipboardMonitor(Mutex<Option<PendingImage>>);
+
+pub fn ignore_next_change() {
+    SELF_COPY.store(true, Ordering::Relaxed);
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.state::<SettingsState>().snapshot().clipboard_monitor
+}
+
+fn read_image(app: &AppHandle) -> Option<DynamicImage> {
+    let image = app.clipboard().read_image().ok()?;
+    RgbaImage::from_raw(image.width(), image.height(), image.rgba().to_vec())
+        .map(DynamicImage::ImageRgba8)
+}
+
+fn content_hash(image: &DynamicImage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    image.as_bytes().hash(&mut hasher);
+    hasher.finish()
+}
+
+// Returns a newly copied image, if any. `last` is the platform change counter
+// or, where there isn't one, a hash of the last image seen.
+fn check(
+    last: &mut Option<u64>,
+    read_image: impl Fn() -> Option<DynamicImage>,
+) -> Option<DynamicImage> {
+    let (marker, image) = match platform::change_count() {
+        Some(count) => (count, None),
+        None => {
+            let image = read_image()?;
+            (content_hash(&image), Some(image))
+        }
+    };
+    // The first look after enabling only records what's already there
+    let first = last.is_none();
+    if *last == Some(marker) {
+        return None;
+    }
+    *last = Some(marker);
+    let own = SELF_COPY.swap(false, Ordering::Relaxed);
+    if first || own || platform::concealed() {
+        return None;
+    }
+    image.or_else(read_image)
+}
+
+fn offer(app: &AppHandle, image: DynamicImage) {
+    let offer = ClipboardOffer {
+        offer_id: uuid::Uuid::new_v4().to_string(),
+        width: image.width(),
+        height: image.height(),
+    };
+    if let Ok(mut pending) = app.state::<ClipboardMonitor>().0.lock() {
+        *pending = Some(PendingImage {
+            offer_id: offer.offer_id.clone(),
+            image,
+        });
+    }
+    tracing::debug!("Offering copied {}x{} image", offer.width, offer.height);
+    if let Err(e) = app.emit(IMAGE_EVENT, offer.clone()) {
+        tracing::warn!("Failed to emit clipboard image: {}", e);
+    }
+    notifications::job_finished(
+        app,
+        &tr(app, "clipboard-image", &[]),
+        &tr(
+            app,
+            "clipboard-image-body",
+            &[
+                ("width", offer.width.into()),
+                ("height", offer.height.into()),
+            ],
+        ),
+        NotificationTarget {
+            job_kind: "clipboard".to_string(),
+            output_paths: Vec::new(),
+            failed: false,
+        },
+    );
+}
+
+fn announce(app: &AppHandle, monitoring: bool) {
+    tracing::info!(
+        "Clipboard monitoring {}",
+        if monitoring { "on" } else { "off" }
+    );
+    if !monitoring {
+        if let Ok(mut pending) = app.state::<ClipboardMonitor>().0.lock() {
+            pending.take();
+        }
+    }
+    if let Err(e) = app.emit(MONITORING_EVENT, monitoring) {
+        tracing::warn!("Failed to emit clipboard monitoring state: {}", e);
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if change.key == SETTING_KEY {
+            announce(&handle, enabled(&handle));
+        }
+    });
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut last = None;
+        loop {
+            std::thread::sleep(POLL);
+            if !enabled(&app) {
+                last = None;
+                continue;
+            }
+            if let Some(image) = check(&mut last, || read_image(&app)) {
+                offer(&app, image);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_clipboard_monitoring(app: AppHandle) -> bool {
+    enabled(&app)
+}
+
+// Saves the offered image and compresses it like compress_image would; the
+// offer is used up either way
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn accept_clipboard_image(
+    app: AppHandle,
+    offer_id: String,
+    options: CompressOptions,
+    operation_id: Option<String>,
+) -> Result<CompressResult, Error> {
+    blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let pending = {
+            let monitor = app.state::<ClipboardMonitor>();
+            let mut pending = monitor
+                .0
+                .lock()
+                .map_err(|_| Error::Lock("clipboard offer"))?;
+            match pending.take() {
+                Some(p) if p.offer_id == offer_id => p,
+                other => {
+                    *pending = other;
+                    return Err(Error::NotFound(
+                        "Clipboard image is no longer available".to_string(),
+                    ));
+                }
+            }
+        };
+        let path = crate::clipboard::clipboard_dir(&app)?.join(format!("{}.png", offer_id));
+        let bytes = encode_image(&pending.image, OutputFormat::Png, 100)?;
+        std::fs::write(&path, bytes)?;
+        compress_one(
+            &app,
+            "clipboard",
+            path.to_string_lossy().to_string(),
+            options,
+            None,
+            operation.token(),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn dismiss_clipboard_image(monitor: State<ClipboardMonitor>, offer_id: String) {
+    if let Ok(mut pending) = monitor.0.lock() {
+        if pending.as_ref().is_some_and(|p| p.offer_id == offer_id) {
+            pending.take();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSArray, NSAutoreleasePool, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    // http://nspasteboard.org markers honored by clipboard managers
+    const PRIVATE_TYPES: &[&str] = &[
+        "org.nspasteboard.ConcealedType",
+        "org.nspasteboard.TransientType",
+    ];
+
+    pub fn change_count() -> Option<u64> {
+        unsafe {
+            let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+            let count: i64 = msg_send![pasteboard, changeCount];
+            Some(count as u64)
+        }
+    }
+
+    pub fn concealed() -> bool {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let types: Vec<id> = PRIVATE_TYPES
+                .iter()
+                .map(|t| NSString::alloc(nil).init_str(t).autorelease())
+                .collect();
+            let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+            let found: id = msg_send![pasteboard,
+                availableTypeFromArray: NSArray::arrayWithObjects(nil, &types)];
+            let _: () = msg_send![pool, drain];
+            found != nil
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    // Formats apps add to keep content out of clipboard history and monitors
+    const PRIVATE_FORMATS: &[&str] = &[
+        "ExcludeClipboardContentFromMonitorProcessing",
+        "Clipboard Viewer Ignore",
+    ];
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetClipboardSequenceNumber() -> u32;
+        fn RegisterClipboardFormatW(name: *const u16) -> u32;
+        fn IsClipboardFormatAvailable(format: u32) -> i32;
+    }
+
+    pub fn change_count() -> Option<u64> {
+        Some(unsafe { GetClipboardSequenceNumber() } as u64)
+    }
+
+    pub fn concealed() -> bool {
+        PRIVATE_FORMATS.iter().any(|name| {
+            let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+            unsafe {
+                let format = RegisterClipboardFormatW(wide.as_ptr());
+                format != 0 && IsClipboardFormatAvailable(format) != 0
+            }
+        })
+    }
+}
+
+// No change counter here, so images are compared by content
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    pub fn change_count() -> Option<u64> {
+        None
+    }
+
+    pub fn concealed() -> bool {
+        false
+    }
+}
+
+// Without a change counter, as on Linux, copies are told apart by content
+#[cfg(all(test, not(any(target_os = "macos", windows))))]
+mod tests {
+    use super::*;
+
+    fn copied(value: u8) -> Option<DynamicImage> {
+        Some(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            2,
+            2,
+            image::Rgba([value; 4]),
+        )))
+    }
+
+    #[test]
+    fn only_new_copies_from_other_apps_are_offered() {
+        let mut last = None;
+        // Whatever was copied before monitoring started
+        assert!(check(&mut last, || copied(1)).is_none());
+        assert!(check(&mut last, || copied(1)).is_none());
+        assert!(check(&mut last, || copied(2)).is_some());
+
+        ignore_next_change();
+        assert!(check(&mut last, || copied(3)).is_none());
+        assert!(check(&mut last, || copied(4)).is_some());
+        assert!(check(&mut last, || None).is_none());
+    }
+}
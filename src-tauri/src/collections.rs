@@ -0,0 +1,422 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub position: i64,
+    pub item_count: u32,
+    pub children: Vec<Collection>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionItem {
+    pub kind: String,
+    pub id: String,
+}
+
+fn check_kind(kind: &str) -> Result<(), String> {
+    match kind {
+        "project" | "asset" => Ok(()),
+        _ => Err(format!("Cannot add items of kind {} to a collection", kind)),
+    }
+}
+
+fn clean_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+fn exists(conn: &Connection, id: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT 1 FROM collections WHERE id = ?1",
+        params![id],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+    .map_err(|e| format!("Failed to read collection: {}", e))
+}
+
+// The collection and everything nested under it
+fn subtree(conn: &Connection, id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE tree(id) AS (
+                SELECT ?1
+                UNION ALL
+                SELECT c.id FROM collections c JOIN tree ON c.parent_id = tree.id
+             )
+             SELECT id FROM tree",
+        )
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    let ids = stmt
+        .query_map(params![id], |row| row.get(0))
+        .map_err(|e| format!("Failed to read collections: {}", e))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    Ok(ids)
+}
+
+// Places `id` at `position` among its siblings and renumbers them 0..n
+fn place_among_siblings(
+    conn: &Connection,
+    id: &str,
+    parent_id: Option<&str>,
+    position: Option<usize>,
+) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM collections WHERE parent_id IS ?1 AND id != ?2 ORDER BY position, created_at",
+        )
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    let mut siblings = stmt
+        .query_map(params![parent_id, id], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to read collections: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    let position = position.unwrap_or(siblings.len()).min(siblings.len());
+    siblings.insert(position, id.to_string());
+
+    for (i, sibling) in siblings.iter().enumerate() {
+        conn.execute(
+            "UPDATE collections SET parent_id = ?1, position = ?2 WHERE id = ?3",
+            params![parent_id, i as i64, sibling],
+        )
+        .map_err(|e| format!("Failed to reorder collections: {}", e))?;
+    }
+    Ok(())
+}
+
+// The whole tree in one call, for the sidebar
+#[tauri::command]
+pub fn list_collections(db: State<Db>) -> Result<Vec<Collection>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.id, c.name, c.parent_id, c.position,
+                    (SELECT COUNT(*) FROM collection_items i WHERE i.collection_id = c.id)
+             FROM collections c ORDER BY c.position, c.created_at",
+        )
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    let flat = stmt
+        .query_map([], |row| {
+            Ok(Collection {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                position: row.get(3)?,
+                item_count: row.get(4)?,
+                children: Vec::new(),
+            })
+        })
+        .map_err(|e| format!("Failed to read collections: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+
+    let mut by_parent: HashMap<Option<String>, Vec<Collection>> = HashMap::new();
+    for collection in flat {
+        by_parent
+            .entry(collection.parent_id.clone())
+            .or_default()
+            .push(collection);
+    }
+    fn attach(
+        parent: Option<String>,
+        by_parent: &mut HashMap<Option<String>, Vec<Collection>>,
+    ) -> Vec<Collection> {
+        let mut children = by_parent.remove(&parent).unwrap_or_default();
+        for child in &mut children {
+            child.children = attach(Some(child.id.clone()), by_parent);
+        }
+        children
+    }
+    Ok(attach(None, &mut by_parent))
+}
+
+#[tauri::command]
+pub fn create_collection(
+    db: State<Db>,
+    name: String,
+    parent_id: Option<String>,
+) -> Result<String, Error> {
+    let name = clean_name(&name)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    if let Some(parent) = &parent_id {
+        if !exists(&conn, parent)? {
+            return Err(Error::NotFound(format!("Collection {} not found", parent)));
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO collections (id, name, parent_id) VALUES (?1, ?2, ?3)",
+        params![id, name, parent_id],
+    )
+    .map_err(|e| format!("Failed to create collection: {}", e))?;
+    place_among_siblings(&conn, &id, parent_id.as_deref(), None)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn rename_collection(db: State<Db>, id: String, name: String) -> Result<(), Error> {
+    let name = clean_name(&name)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let updated = conn
+        .execute(
+            "UPDATE collections SET name = ?1 WHERE id = ?2",
+            params![name, id],
+        )
+        .map_err(|e| format!("Failed to rename collection: {}", e))?;
+    if updated == 0 {
+        return Err(Error::NotFound(format!("Collection {} not found", id)));
+    }
+    Ok(())
+}
+
+// Nests or reorders a collection; a position past the end appends
+#[tauri::command]
+pub fn move_collection(
+    db: State<Db>,
+    id: String,
+    parent_id: Option<String>,
+    position: Option<usize>,
+) -> Result<(), Error> {
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    if !exists(&conn, &id)? {
+        return Err(Error::NotFound(format!("Collection {} not found", id)));
+    }
+    if let Some(parent) = &parent_id {
+        if !exists(&conn, parent)? {
+            return Err(Error::NotFound(format!("Collection {} not found", parent)));
+        }
+        if subtree(&conn, &id)?.contains(parent) {
+            return Err(Error::InvalidInput(
+                "A collection cannot be moved inside itself".to_string(),
+            ));
+        }
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    place_among_siblings(&tx, &id, parent_id.as_deref(), position)?;
+    tx.commit()
+        .map_err(|e| format!("Failed to move collection: {}", e))?;
+    Ok(())
+}
+
+// Deletes the collection with everything nested in it; the items themselves stay
+#[tauri::command]
+pub fn delete_collection(db: State<Db>, id: String) -> Result<(), Error> {
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let ids = subtree(&conn, &id)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    for id in ids.iter().rev() {
+        tx.execute("DELETE FROM collections WHERE id = ?1", params![id])
+            .map_err(|e| format!("Failed to delete collection: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to delete collection: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_collection_items(
+    db: State<Db>,
+    collection_id: String,
+) -> Result<Vec<CollectionItem>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT kind, item_id FROM collection_items
+             WHERE collection_id = ?1 ORDER BY position",
+        )
+        .map_err(|e| format!("Failed to read collection: {}", e))?;
+    let items = stmt
+        .query_map(params![collection_id], |row| {
+            Ok(CollectionItem {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read collection: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read collection: {}", e))?;
+    Ok(items)
+}
+
+// Appends items in the given order; ones already in the collection are left in place
+#[tauri::command]
+pub fn add_to_collection(
+    db: State<Db>,
+    collection_id: String,
+    items: Vec<CollectionItem>,
+) -> Result<(), Error> {
+    for item in &items {
+        check_kind(&item.kind)?;
+    }
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    if !exists(&conn, &collection_id)? {
+        return Err(Error::NotFound(format!(
+            "Collection {} not found",
+            collection_id
+        )));
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    for item in &items {
+        tx.execute(
+            "INSERT OR IGNORE INTO collection_items (collection_id, kind, item_id, position)
+             VALUES (?1, ?2, ?3,
+                     (SELECT COALESCE(MAX(position), -1) + 1 FROM collection_items
+                      WHERE collection_id = ?1))",
+            params![collection_id, item.kind, item.id],
+        )
+        .map_err(|e| format!("Failed to add to collection: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to add to collection: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_from_collection(
+    db: State<Db>,
+    collection_id: String,
+    items: Vec<CollectionItem>,
+) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    for item in &items {
+        conn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?1 AND kind = ?2 AND item_id = ?3",
+            params![collection_id, item.kind, item.id],
+        )
+        .map_err(|e| format!("Failed to remove from collection: {}", e))?;
+    }
+    Ok(())
+}
+
+// Rewrites the order from the full list; items missing from `items` keep
+// their relative order after the listed ones
+#[tauri::command]
+pub fn reorder_collection_items(
+    db: State<Db>,
+    collection_id: String,
+    items: Vec<CollectionItem>,
+) -> Result<(), Error> {
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let offset = items.len() as i64;
+    tx.execute(
+        "UPDATE collection_items SET position = position + ?2 WHERE collection_id = ?1",
+        params![collection_id, offset],
+    )
+    .map_err(|e| format!("Failed to reorder collection: {}", e))?;
+    for (i, item) in items.iter().enumerate() {
+        tx.execute(
+            "UPDATE collection_items SET position = ?4
+             WHERE collection_id = ?1 AND kind = ?2 AND item_id = ?3",
+            params![collection_id, item.kind, item.id, i as i64],
+        )
+        .map_err(|e| format!("Failed to reorder collection: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to reorder collection: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // work > (clients > acme), archive
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        for (id, parent, position) in [
+            ("work", None, 0),
+            ("archive", None, 1),
+            ("clients", Some("work"), 0),
+            ("acme", Some("clients"), 0),
+        ] {
+            conn.execute(
+                "INSERT INTO collections (id, name, parent_id, position) VALUES (?1, ?1, ?2, ?3)",
+                params![id, parent, position],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn children(conn: &Connection, parent: Option<&str>) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT id FROM collections WHERE parent_id IS ?1 ORDER BY position")
+            .unwrap();
+        stmt.query_map(params![parent], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_subtree_holds_everything_nested() {
+        let conn = library();
+        let mut ids = subtree(&conn, "work").unwrap();
+        ids.sort();
+        assert_eq!(ids, ["acme", "clients", "work"]);
+        assert_eq!(subtree(&conn, "archive").unwrap(), ["archive"]);
+        assert!(exists(&conn, "acme").unwrap());
+        assert!(!exists(&conn, "nope").unwrap());
+    }
+
+    #[test]
+    fn placing_renumbers_the_siblings() {
+        let conn = library();
+        place_among_siblings(&conn, "acme", None, Some(1)).unwrap();
+        assert_eq!(children(&conn, None), ["work", "acme", "archive"]);
+        assert!(children(&conn, Some("clients")).is_empty());
+        // Past the end, or no position at all, goes last
+        place_among_siblings(&conn, "work", None, Some(99)).unwrap();
+        assert_eq!(children(&conn, None), ["acme", "archive", "work"]);
+        place_among_siblings(&conn, "acme", None, None).unwrap();
+        assert_eq!(children(&conn, None), ["archive", "work", "acme"]);
+    }
+
+    #[test]
+    fn deleting_a_collection_empties_it() {
+        let conn = library();
+        conn.execute(
+            "INSERT INTO collection_items (collection_id, kind, item_id, position)
+             VALUES ('acme', 'project', 'p1', 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM collections WHERE id = 'acme'", [])
+            .unwrap();
+        let left: i64 = conn
+            .query_row("SELECT count(*) FROM collection_items", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,99 @@
+// Network reachability, so sync and other online features can say "you're
+// offline" up front instead of timing out. Probes go through the shared HTTP
+// client, which means a configured proxy counts as the way out.
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::Error;
+
+pub const ONLINE_EVENT: &str = "network://online";
+pub const OFFLINE_EVENT: &str = "network://offline";
+// Already contacted for updates, so probing it reveals nothing new
+const PROBE_URL: &str = "https://github.com";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_secs(20);
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct Connectivity {
+    pub online: bool,
+    // Unix seconds of the last probe; 0 before the first one finishes
+    pub checked_at: u64,
+}
+
+// Assumed online until a probe says otherwise
+pub struct ConnectivityState(pub Mutex<Connectivity>);
+
+impl Default for ConnectivityState {
+    fn default() -> Self {
+        ConnectivityState(Mutex::new(Connectivity {
+            online: true,
+            checked_at: 0,
+        }))
+    }
+}
+
+impl ConnectivityState {
+    pub fn is_online(&self) -> bool {
+        self.0.lock().map(|c| c.online).unwrap_or(true)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Any HTTP response counts; only failing to connect at all means offline
+async fn probe(app: &AppHandle) -> bool {
+    crate::net::client(app)
+        .head(PROBE_URL)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok()
+}
+
+fn update(app: &AppHandle, online: bool) {
+    let state = app.state::<ConnectivityState>();
+    let Ok(mut current) = state.0.lock() else {
+        return;
+    };
+    let changed = current.online != online;
+    *current = Connectivity {
+        online,
+        checked_at: now(),
+    };
+    let connectivity = *current;
+    drop(current);
+
+    if changed {
+        tracing::info!("Network is {}", if online { "online" } else { "offline" });
+        let event = if online { ONLINE_EVENT } else { OFFLINE_EVENT };
+        if let Err(e) = app.emit(event, connectivity) {
+            tracing::warn!("Failed to emit connectivity change: {}", e);
+        }
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let online = tauri::async_runtime::block_on(probe(&app));
+        update(&app, online);
+        std::thread::sleep(POLL);
+    });
+}
+
+#[tauri::command]
+pub fn get_connectivity(state: State<ConnectivityState>) -> Result<Connectivity, Error> {
+    state
+        .0
+        .lock()
+        .map(|c| *c)
+        .map_err(|_| Error::Lock("connectivity"))
+}
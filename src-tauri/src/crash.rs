@@ -0,0 +1,314 @@
+// Crash reports for bug reports. A panic hook covers Rust panics; a watchdog
+// child process covers everything that kills us without unwinding (aborts,
+// segfaults in native codecs, being OOM-killed) by noticing its pipe to us
+// close without a clean goodbye.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+use crate::error::Error;
+
+const WATCHDOG_FLAG: &str = "--crash-watchdog";
+const REPORTS_DIR: &str = "crash-reports";
+const RECENT_LINES: usize = 200;
+// Sent to the watchdog on a normal quit, or once the panic hook wrote a report
+const CLEAN_EXIT: &str = "exit";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct Context {
+    app_version: String,
+    recent_log: VecDeque<String>,
+    active_jobs: BTreeMap<u64, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub id: String,
+    pub created_at: u64,
+    // "panic" or "abnormal-exit"
+    pub kind: String,
+    pub app_version: String,
+    pub os: String,
+    pub message: Option<String>,
+    pub backtrace: Option<String>,
+    pub recent_log: Vec<String>,
+    pub active_jobs: Vec<String>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    app_version: String::new(),
+    recent_log: VecDeque::new(),
+    active_jobs: BTreeMap::new(),
+});
+static WATCHDOG: Mutex<Option<ChildStdin>> = Mutex::new(None);
+static REPORTS: OnceLock<PathBuf> = OnceLock::new();
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Sends the current context so the watchdog has it if we die without warning
+fn sync_watchdog(context: &Context) {
+    let Ok(mut watchdog) = WATCHDOG.try_lock() else {
+        return;
+    };
+    let Some(stdin) = watchdog.as_mut() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(context) else {
+        return;
+    };
+    if writeln!(stdin, "{}", json).is_err() {
+        // The watchdog is gone; keep running without it
+        *watchdog = None;
+    }
+}
+
+fn with_context(f: impl FnOnce(&mut Context)) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        f(&mut context);
+        sync_watchdog(&context);
+    }
+}
+
+// Kept for the "last N log lines" section of a report
+pub fn log_line(line: impl Into<String>) {
+    with_context(|context| {
+        if context.recent_log.len() == RECENT_LINES {
+            context.recent_log.pop_front();
+        }
+        context.recent_log.push_back(line.into());
+    });
+}
+
+// Marks a job as running until the guard is dropped
+pub struct ActiveJob(u64);
+
+pub fn job_started(description: impl Into<String>) -> ActiveJob {
+    let id = NEXT_JOB.fetch_add(1, Ordering::Relaxed);
+    let description = description.into();
+    log_line(format!("Job started: {}", description));
+    with_context(|context| {
+        context.active_jobs.insert(id, description);
+    });
+    ActiveJob(id)
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        with_context(|context| {
+            context.active_jobs.remove(&self.0);
+        });
+    }
+}
+
+fn write_report(dir: &Path, report: &CrashReport) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.json", report.id));
+    let json = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize crash report: {}", e))?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn report_from(
+    kind: &str,
+    context: Context,
+    message: Option<String>,
+    backtrace: Option<String>,
+) -> CrashReport {
+    let created_at = now();
+    CrashReport {
+        id: format!("crash-{}-{}", created_at, std::process::id()),
+        created_at,
+        kind: kind.to_string(),
+        app_version: context.app_version,
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        message,
+        backtrace,
+        recent_log: context.recent_log.into_iter().collect(),
+        active_jobs: context.active_jobs.into_values().collect(),
+    }
+}
+
+// Runs instead of the app when we were started as our own watchdog. Returns
+// false for a normal launch.
+pub fn run_watchdog_if_requested() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(dir) = args
+        .windows(2)
+        .find(|pair| pair[0] == WATCHDOG_FLAG)
+        .map(|pair| PathBuf::from(&pair[1]))
+    else {
+        return false;
+    };
+
+    let mut last = Context::default();
+    let mut clean = false;
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line == CLEAN_EXIT {
+            clean = true;
+            break;
+        }
+        if let Ok(context) = serde_json::from_str(&line) {
+            last = context;
+        }
+    }
+    if !clean {
+        let report = report_from("abnormal-exit", last, None, None);
+        if let Err(e) = write_report(&dir, &report) {
+            eprintln!("{}", e);
+        }
+    }
+    true
+}
+
+fn spawn_watchdog(dir: &Path) -> Result<ChildStdin, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let mut child = Command::new(exe)
+        .arg(WATCHDOG_FLAG)
+        .arg(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start crash watchdog: {}", e))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| "Crash watchdog has no stdin".to_string())
+}
+
+fn reports_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(REPORTS_DIR))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+pub fn install(app: &AppHandle) -> Result<(), String> {
+    let dir = reports_dir(app)?;
+    let _ = REPORTS.set(dir.clone());
+    with_context(|context| context.app_version = app.package_info().version.to_string());
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = REPORTS.get() {
+            // try_lock: the panic may have happened while holding the context
+            let context = CONTEXT.try_lock().map(|c| c.clone()).unwrap_or_default();
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            let report = report_from("panic", context, Some(info.to_string()), Some(backtrace));
+            match write_report(dir, &report) {
+                Ok(path) => tracing::error!("Crash report written to {}", path.display()),
+                Err(e) => tracing::error!("{}", e),
+            }
+        }
+        previous(info);
+    }));
+
+    match spawn_watchdog(&dir) {
+        Ok(stdin) => {
+            if let Ok(mut watchdog) = WATCHDOG.lock() {
+                *watchdog = Some(stdin);
+            }
+            with_context(|_| {});
+        }
+        Err(e) => tracing::warn!("{}", e),
+    }
+    Ok(())
+}
+
+// Tells the watchdog this exit is intentional
+pub fn clean_exit() {
+    if let Ok(mut watchdog) = WATCHDOG.lock() {
+        if let Some(mut stdin) = watchdog.take() {
+            let _ = writeln!(stdin, "{}", CLEAN_EXIT);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_crash_reports(app: AppHandle) -> Result<Vec<CrashReport>, Error> {
+    let dir = reports_dir(&app)?;
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut reports: Vec<CrashReport> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let json = std::fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str(&json).ok()
+        })
+        .collect();
+    reports.sort_by_key(|report| std::cmp::Reverse(report.created_at));
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        CONTEXT.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn jobs_are_active_until_dropped_and_the_log_is_bounded() {
+        let job = job_started("Compressing 3 files");
+        assert!(context()
+            .active_jobs
+            .values()
+            .any(|j| j == "Compressing 3 files"));
+        drop(job);
+        assert!(!context()
+            .active_jobs
+            .values()
+            .any(|j| j == "Compressing 3 files"));
+
+        for i in 0..RECENT_LINES + 10 {
+            log_line(format!("line {}", i));
+        }
+        let log = context().recent_log;
+        assert_eq!(log.len(), RECENT_LINES);
+        assert_eq!(log.back().unwrap(), &format!("line {}", RECENT_LINES + 9));
+    }
+
+    #[test]
+    fn reports_carry_the_context_and_are_written_as_json() {
+        let context = Context {
+            app_version: "1.2.3".to_string(),
+            recent_log: VecDeque::from(vec!["started".to_string()]),
+            active_jobs: BTreeMap::from([(2, "Exporting PDF".to_string())]),
+        };
+        let report = report_from("panic", context, Some("boom".to_string()), None);
+        assert_eq!(report.app_version, "1.2.3");
+        assert_eq!(report.recent_log, vec!["started"]);
+        assert_eq!(report.active_jobs, vec!["Exporting PDF"]);
+
+        let dir = std::env::temp_dir().join(format!("squish-crash-{}", std::process::id()));
+        let path = write_report(&dir, &report).unwrap();
+        let read: CrashReport =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read.id, report.id);
+        assert_eq!(read.message.as_deref(), Some("boom"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+use rusqlite::{params, Connection, DatabaseName, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::blocking;
+use crate::error::Error;
+use crate::safe_path::SafePath;
+
+pub const DB_FILE: &str = "squish.db";
+// Copy of the live library taken right before a restore replaces it
+const PRE_RESTORE_FILE: &str = "squish.pre-restore.db";
+
+// Rust-side connection to the same squish.db the frontend opens through tauri-plugin-sql
+pub struct Db(pub Mutex<Connection>);
+
+// Database of the active profile
+pub fn db_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    let dir = crate::profiles::active_profile_dir(&config_dir);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    Ok(dir.join(DB_FILE))
+}
+
+// Opens and configures a library without touching its schema
+pub fn connect(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+
+    if crate::encryption::is_encrypted_file(path) {
+        let key = crate::encryption::library_key(path)?
+            .ok_or("This library is encrypted, but its key is missing from the keychain")?;
+        crate::encryption::apply_key(&conn, &key)?;
+    }
+
+    // Match the pragmas the frontend sets so both connections agree on WAL
+    conn.execute_batch(
+        "PRAGMA journal_mode=WAL;
+         PRAGMA synchronous=NORMAL;
+         PRAGMA busy_timeout=10000;",
+    )
+    .map_err(|e| format!("Failed to configure database: {}", e))?;
+    Ok(conn)
+}
+
+pub fn open_at(path: &Path) -> Result<Connection, String> {
+    tracing::info!("Opening database at {}", path.display());
+    let conn = connect(path)?;
+    crate::migrations::run(&conn)?;
+    Ok(conn)
+}
+
+// Backend-owned preferences, stored as JSON values keyed by name
+pub fn get_preference(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM preferences WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read preference {}: {}", key, e))
+}
+
+pub fn set_preference(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO preferences (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![key, value],
+    )
+    .map_err(|e| format!("Failed to save preference {}: {}", key, e))?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    pub bytes: u64,
+    pub schema_version: i64,
+}
+
+// Copies the live database page by page with the SQLite online backup API,
+// so it is consistent even while the frontend connection is writing
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn backup_database(app: AppHandle, dest: String) -> Result<BackupInfo, Error> {
+    Ok(blocking::run(move || {
+        let db = app.state::<Db>();
+        let dest = SafePath::write(&app, &dest)?.into_path_buf();
+        if let Some(parent) = dest.parent() {
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                return Err(format!("Folder {} does not exist", parent.display()));
+            }
+        }
+
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        let schema_version = crate::migrations::current_version(&conn)?;
+        conn.backup(DatabaseName::Main, &dest, None)
+            .map_err(|e| format!("Failed to back up database: {}", e))?;
+
+        let bytes = std::fs::metadata(&dest)
+            .map_err(|e| format!("Failed to read backup: {}", e))?
+            .len();
+        tracing::info!("Backed up database to {} ({} bytes)", dest.display(), bytes);
+        Ok(BackupInfo {
+            path: dest.to_string_lossy().to_string(),
+            bytes,
+            schema_version,
+        })
+    })
+    .await?)
+}
+
+// Checks that a file is an intact Squish library this build can open
+pub fn validate_backup(path: &Path) -> Result<i64, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open backup: {}", e))?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|_| "The selected file is not a Squish library".to_string())?;
+    if integrity != "ok" {
+        return Err(format!("Backup is corrupted: {}", integrity));
+    }
+
+    let has_table = |name: &str| -> Result<bool, String> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![name],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n > 0)
+        .map_err(|e| format!("Failed to read backup schema: {}", e))
+    };
+    if !has_table("projects")? {
+        return Err("The selected file is not a Squish library".to_string());
+    }
+
+    // Libraries from before versioned migrations count as version 0
+    let version = if has_table("schema_migrations")? {
+        crate::migrations::current_version(&conn)?
+    } else {
+        0
+    };
+    if version > crate::migrations::latest_version() {
+        return Err(format!(
+            "Backup was made by a newer version of Squish (schema {}). Please update Squish.",
+            version
+        ));
+    }
+    Ok(version)
+}
+
+// Replaces the live library with a backup. The current database is saved next
+// to it first, and older backups are migrated forward after the copy.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn restore_database(app: AppHandle, src: String) -> Result<BackupInfo, Error> {
+    blocking::run(move || {
+        let db = app.state::<Db>();
+        let src = SafePath::read(&app, &src)?.into_path_buf();
+        validate_backup(&src)?;
+
+        let pre_restore = db_path(&app)?.with_file_name(PRE_RESTORE_FILE);
+        let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.backup(DatabaseName::Main, pre_restore, None)
+            .map_err(|e| format!("Failed to save current database before restore: {}", e))?;
+
+        conn.restore(
+            DatabaseName::Main,
+            &src,
+            None::<fn(rusqlite::backup::Progress)>,
+        )
+        .map_err(|e| format!("Failed to restore database: {}", e))?;
+        crate::migrations::run(&conn)?;
+        let schema_version = crate::migrations::current_version(&conn)?;
+        crate::settings::reload(&app, &conn)?;
+        drop(conn);
+
+        let bytes = std::fs::metadata(&src).map(|m| m.len()).unwrap_or(0);
+        tracing::info!("Restored database from {}", src.display());
+        // The frontend holds its own connection and cached state, so it reloads on this
+        if let Err(e) = app.emit("db://restored", ()) {
+            tracing::warn!("Failed to emit restore event: {}", e);
+        }
+        Ok(BackupInfo {
+            path: src.to_string_lossy().to_string(),
+            bytes,
+            schema_version,
+        })
+    })
+    .await
+}
+
+// The frontend opens whichever library the active profile points at
+#[tauri::command]
+pub fn get_database_path(app: AppHandle) -> Result<String, Error> {
+    Ok(db_path(&app)?.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("squish-db-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_backup_reports_its_schema_version() {
+        let dir = temp_dir("backup");
+        let path = dir.join("backup.db");
+        let conn = Connection::open(&path).unwrap();
+        crate::migrations::run(&conn).unwrap();
+        drop(conn);
+        assert_eq!(
+            validate_backup(&path),
+            Ok(crate::migrations::latest_version())
+        );
+
+        // Libraries from before versioned migrations
+        let old = dir.join("old.db");
+        Connection::open(&old)
+            .unwrap()
+            .execute_batch("CREATE TABLE projects (id TEXT PRIMARY KEY)")
+            .unwrap();
+        assert_eq!(validate_backup(&old), Ok(0));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_not_backups() {
+        let dir = temp_dir("invalid");
+        let text = dir.join("notes.db");
+        std::fs::write(&text, "not a database at all, just some text").unwrap();
+        assert!(validate_backup(&text).is_err());
+
+        let other = dir.join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE notes (id INTEGER)")
+            .unwrap();
+        assert!(validate_backup(&other).is_err());
+
+        let newer = dir.join("newer.db");
+        let conn = Connection::open(&newer).unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_migrations (version, description) VALUES (?1, 'future')",
+            params![crate::migrations::latest_version() + 1],
+        )
+        .unwrap();
+        drop(conn);
+        assert!(validate_backup(&newer)
+            .unwrap_err()
+            .contains("newer version"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::error::Error;
+
+pub const DEEP_LINK_EVENT: &str = "deep-link://request";
+// What the Explorer context-menu entry launches with: `squish --open <file>`
+pub const OPEN_FLAG: &str = "--open";
+
+// What another tool asked Squish to do through a squish:// link
+#[derive(Serialize, Clone)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum DeepLinkRequest {
+    // squish://open?path=/a.png&path=/b.jpg
+    Open {
+        paths: Vec<String>,
+    },
+    // squish://compress?url=https://example.com/a.png
+    Compress {
+        url: String,
+    },
+    // From AppleScript; rendering a project is up to the UI. `format` is
+    // "pdf", "png" or "jpeg".
+    #[serde(rename_all = "camelCase")]
+    Export {
+        project_id: String,
+        format: String,
+        path: String,
+    },
+    // A .squish document; the UI opens it with open_project_document
+    OpenDocument {
+        path: String,
+    },
+}
+
+// Links that arrive before the frontend is listening, including the one the
+// app was launched with, wait here until the UI asks for them. None once the
+// UI has drained the queue, after which links are only emitted.
+pub struct PendingDeepLinks(pub Mutex<Option<Vec<DeepLinkRequest>>>);
+
+impl Default for PendingDeepLinks {
+    fn default() -> Self {
+        PendingDeepLinks(Mutex::new(Some(Vec::new())))
+    }
+}
+
+pub fn parse(url: &Url) -> Result<DeepLinkRequest, String> {
+    if url.scheme() != "squish" {
+        return Err(format!("Not a squish:// link: {}", url));
+    }
+    let values = |key: &str| -> Vec<String> {
+        url.query_pairs()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.to_string())
+            .collect()
+    };
+
+    match url.host_str() {
+        Some("open") => {
+            let paths: Vec<String> = values("path")
+                .into_iter()
+                .filter(|p| Path::new(p).is_file())
+                .collect();
+            if paths.is_empty() {
+                return Err("squish://open needs at least one existing path".to_string());
+            }
+            Ok(DeepLinkRequest::Open { paths })
+        }
+        Some("compress") => {
+            let url = values("url")
+                .into_iter()
+                .next()
+                .ok_or("squish://compress needs a url")?;
+            // Only web URLs, so a link can't point Squish at arbitrary local schemes
+            match Url::parse(&url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+                    Ok(DeepLinkRequest::Compress { url })
+                }
+                _ => Err(format!(
+                    "squish://compress needs an http(s) url, got {}",
+                    url
+                )),
+            }
+        }
+        other => Err(format!("Unknown squish:// action: {}", other.unwrap_or(""))),
+    }
+}
+
+fn handle(app: &AppHandle, urls: Vec<Url>) {
+    for url in urls {
+        let request = match parse(&url) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::debug!("Ignoring deep link: {}", e);
+                continue;
+            }
+        };
+        tracing::info!("Deep link: {}", url);
+        dispatch(app, request);
+    }
+}
+
+// Queues the request for the UI or emits it if the UI is already listening.
+// Also the entry point for OS integrations that hand Squish files directly.
+pub fn dispatch(app: &AppHandle, request: DeepLinkRequest) {
+    if let Ok(mut pending) = app.state::<PendingDeepLinks>().0.lock() {
+        if let Some(queue) = pending.as_mut() {
+            queue.push(request);
+            return;
+        }
+    }
+    if let Err(e) = app.emit(DEEP_LINK_EVENT, request) {
+        tracing::warn!("Failed to emit deep link: {}", e);
+    }
+}
+
+// Files from our own launch or one forwarded by the single-instance plugin:
+// `--open <file>` from the Explorer context-menu entry, or bare paths from
+// "Open with" in Explorer and Linux file managers. Relative paths are resolved
+// against the directory the launching process ran in, which for a forwarded
+// launch isn't ours.
+pub fn handle_args(app: &AppHandle, args: &[String], cwd: &Path) {
+    let mut paths = Vec::new();
+    // The first argument is the executable
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let path = if arg == OPEN_FLAG {
+            match args.next() {
+                Some(path) => path,
+                None => break,
+            }
+        } else if arg.starts_with('-') || arg.contains("://") {
+            // Other flags, and links the deep-link plugin handles itself
+            continue;
+        } else {
+            arg
+        };
+        let path = cwd.join(path);
+        if crate::document::is_document(&path) {
+            crate::safe_path::allow(app, &path);
+            dispatch(
+                app,
+                DeepLinkRequest::OpenDocument {
+                    path: path.to_string_lossy().to_string(),
+                },
+            );
+        } else if path.is_file() && crate::importers::can_import(app, &path) {
+            paths.push(path.to_string_lossy().to_string());
+        }
+    }
+    if !paths.is_empty() {
+        tracing::info!("Opened with {} file(s)", paths.len());
+        crate::safe_path::allow_all(app, &paths);
+        dispatch(app, DeepLinkRequest::Open { paths });
+    }
+}
+
+// Files opened from Finder ("Open With", dropping on the Dock icon), which
+// macOS delivers as an event instead of arguments
+#[cfg(target_os = "macos")]
+pub fn handle_opened(app: &AppHandle, urls: &[Url]) {
+    let (documents, files): (Vec<_>, Vec<_>) = urls
+        .iter()
+        .filter(|url| url.scheme() == "file")
+        .filter_map(|url| url.to_file_path().ok())
+        .partition(|path| crate::document::is_document(path));
+    for document in documents {
+        crate::safe_path::allow(app, &document);
+        dispatch(
+            app,
+            DeepLinkRequest::OpenDocument {
+                path: document.to_string_lossy().to_string(),
+            },
+        );
+    }
+    let paths: Vec<String> = files
+        .into_iter()
+        .filter(|path| path.is_file() && crate::importers::can_import(app, path))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    if !paths.is_empty() {
+        tracing::info!("Opened from Finder: {} file(s)", paths.len());
+        crate::safe_path::allow_all(app, &paths);
+        dispatch(app, DeepLinkRequest::Open { paths });
+    }
+}
+
+// Registers the scheme, queues the launch link, and listens for more. With the
+// single-instance plugin, links opened while Squish is running arrive here
+// instead of starting a second copy.
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    // Installed builds register through the bundle; this covers dev and portable runs
+    #[cfg(any(windows, target_os = "linux"))]
+    app.deep_link()
+        .register_all()
+        .map_err(|e| format!("Failed to register squish:// links: {}", e))?;
+
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        handle(app, urls);
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+    handle_args(app, &std::env::args().collect::<Vec<_>>(), &cwd);
+    let handle_app = app.clone();
+    app.deep_link()
+        .on_open_url(move |event| handle(&handle_app, event.urls()));
+    Ok(())
+}
+
+// Drains links the UI hasn't seen yet; called once its listener is registered
+#[tauri::command]
+pub fn take_pending_deep_links(
+    pending: State<PendingDeepLinks>,
+) -> Result<Vec<DeepLinkRequest>, Error> {
+    let mut pending = pending.0.lock().map_err(|_| Error::Lock("deep links"))?;
+    Ok(pending.take().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(url: &str) -> Result<DeepLinkRequest, String> {
+        parse(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn compress_takes_web_urls_only() {
+        assert!(matches!(
+            parse_str("squish://compress?url=https%3A%2F%2Fexample.com%2Fa.png"),
+            Ok(DeepLinkRequest::Compress { url }) if url == "https://example.com/a.png"
+        ));
+        assert!(parse_str("squish://compress?url=file%3A%2F%2F%2Fetc%2Fpasswd").is_err());
+        assert!(parse_str("squish://compress").is_err());
+    }
+
+    #[test]
+    fn open_keeps_only_existing_files() {
+        let file = std::env::temp_dir().join(format!("squish-link-{}.png", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        let mut link = Url::parse("squish://open").unwrap();
+        link.query_pairs_mut()
+            .append_pair("path", &file.to_string_lossy())
+            .append_pair("path", "/no/such/file.png");
+        let request = parse(&link);
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            request,
+            Ok(DeepLinkRequest::Open { paths }) if paths == [file.to_string_lossy()]
+        ));
+        assert!(parse_str("squish://open?path=%2Fno%2Fsuch%2Ffile.png").is_err());
+    }
+
+    #[test]
+    fn other_schemes_and_actions_are_rejected() {
+        assert!(parse_str("https://open?path=%2Fa.png").is_err());
+        assert!(parse_str("squish://delete?path=%2Fa.png").is_err());
+    }
+}
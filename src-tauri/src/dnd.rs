@@ -0,0 +1,179 @@
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, WebviewWindow};
+
+#[cfg(not(target_os = "macos"))]
+use crate::cancel::CancelToken;
+use crate::error::Error;
+use crate::imaging::{check_location, decode_image, encode_image, CompressOptions, OutputFormat};
+use crate::safe_path::{self, SafePath};
+
+#[cfg(target_os = "macos")]
+mod promise;
+
+const DRAG_ICON_SIZE: u32 = 96;
+pub const PROMISE_EVENT: &str = "drag://promise";
+
+// A result that doesn't exist yet: the input and how to compress it
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromisedFile {
+    pub input_path: String,
+    pub options: CompressOptions,
+}
+
+// Sent on drag://promise once a promised file was written (or failed)
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromiseOutcome {
+    pub input_path: String,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DragOutcome {
+    paths: Vec<String>,
+    dropped: bool,
+}
+
+// Starts an OS drag session carrying the given files, so results can be dropped
+// straight into Finder/Explorer or another app
+#[tauri::command]
+pub fn start_drag_out(
+    app: AppHandle,
+    window: WebviewWindow,
+    paths: Vec<String>,
+) -> Result<(), Error> {
+    if paths.is_empty() {
+        return Err(Error::InvalidInput("Nothing to drag".to_string()));
+    }
+    let files: Vec<PathBuf> = safe_path::read_all(&app, paths.clone())?
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(missing) = files.iter().find(|p| !p.exists()) {
+        return Err(Error::NotFound(format!(
+            "File does not exist: {}",
+            missing.display()
+        )));
+    }
+
+    let icon = drag_icon(&files[0]);
+    let target = window.clone();
+
+    // Drag sessions have to be started from the main thread on every platform
+    window
+        .run_on_main_thread(move || {
+            #[cfg(target_os = "linux")]
+            let handle = match target.gtk_window() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    tracing::warn!("Failed to get GTK window for drag: {}", e);
+                    return;
+                }
+            };
+            #[cfg(not(target_os = "linux"))]
+            let handle = target.clone();
+
+            let emitter = target.clone();
+            let result = drag::start_drag(
+                &handle,
+                drag::DragItem::Files(files),
+                icon,
+                move |result, _cursor| {
+                    let outcome = DragOutcome {
+                        paths: paths.clone(),
+                        dropped: matches!(result, drag::DragResult::Dropped),
+                    };
+                    if let Err(e) = emitter.emit("drag://result", outcome) {
+                        tracing::warn!("Failed to emit drag result: {}", e);
+                    }
+                },
+                drag::Options::default(),
+            );
+            if let Err(e) = result {
+                tracing::warn!("Failed to start drag: {}", e);
+            }
+        })
+        .map_err(|e| Error::Internal(format!("Failed to start drag: {}", e)))
+}
+
+// Drags results that are still to be compressed. On macOS the files are
+// promised and only encoded once dropped; elsewhere there's no equivalent the
+// drag crate can offer, so they're compressed first and dragged as usual.
+#[tauri::command]
+pub fn start_promised_drag(
+    app: AppHandle,
+    window: WebviewWindow,
+    mut files: Vec<PromisedFile>,
+) -> Result<(), Error> {
+    if files.is_empty() {
+        return Err(Error::InvalidInput("Nothing to drag".to_string()));
+    }
+    // Validated before anything looks at the disk, so a path outside the
+    // scopes can't be probed for existence
+    for file in &mut files {
+        file.input_path = SafePath::read(&app, &file.input_path)?.into_string();
+        file.options.output_dir = safe_path::write_opt(&app, file.options.output_dir.take())?;
+    }
+    if let Some(missing) = files.iter().find(|f| !Path::new(&f.input_path).exists()) {
+        return Err(Error::NotFound(format!(
+            "File does not exist: {}",
+            missing.input_path
+        )));
+    }
+    for file in &files {
+        check_location(
+            &app,
+            None,
+            std::slice::from_ref(&file.input_path),
+            &file.options,
+        )?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let icon = decode_image(Path::new(&files[0].input_path))
+            .map(|image| image.resize(DRAG_ICON_SIZE, DRAG_ICON_SIZE, FilterType::Triangle))
+            .and_then(|thumbnail| encode_image(&thumbnail, OutputFormat::Png, 100))
+            .ok();
+        let target = window.clone();
+        window
+            .run_on_main_thread(move || promise::start(&app, &target, files, icon))
+            .map_err(|e| Error::Internal(format!("Failed to start drag: {}", e)))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let mut paths = Vec::new();
+        for file in files {
+            let result = crate::imaging::compress_file(
+                file.input_path.clone(),
+                &file.options,
+                &CancelToken::default(),
+            );
+            let outcome = PromiseOutcome {
+                input_path: file.input_path,
+                output_path: result.as_ref().ok().map(|r| r.output_path.clone()),
+                error: result.as_ref().err().cloned(),
+            };
+            if let Err(e) = app.emit(PROMISE_EVENT, &outcome) {
+                tracing::warn!("Failed to emit drag promise result: {}", e);
+            }
+            paths.push(result?.output_path);
+        }
+        safe_path::allow_all(&app, &paths);
+        start_drag_out(app, window, paths)
+    }
+}
+
+// Small thumbnail of the first file, or the file itself when it can't be decoded
+fn drag_icon(path: &Path) -> drag::Image {
+    decode_image(path)
+        .map(|image| image.resize(DRAG_ICON_SIZE, DRAG_ICON_SIZE, FilterType::Triangle))
+        .and_then(|thumbnail| encode_image(&thumbnail, OutputFormat::Png, 100))
+        .map(drag::Image::Raw)
+        .unwrap_or_else(|_| drag::Image::File(path.to_path_buf()))
+}
@@ -0,0 +1,306 @@
+// macOS file-promise drags (NSFilePromiseProvider). Finder only asks for the
+// file once it's dropped, so compression runs then, straight into the drop
+// location, the same way dragging a screenshot thumbnail works.
+use cocoa::base::{id, nil, NO};
+use cocoa::foundation::{NSArray, NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, WebviewWindow};
+
+use super::{PromiseOutcome, PromisedFile, DRAG_ICON_SIZE, PROMISE_EVENT};
+use crate::cancel::CancelToken;
+use crate::imaging::{compress_file, output_path_for, OutputFormat};
+use crate::safe_path;
+
+const ID_IVAR: &str = "squishPromiseId";
+const NS_DRAG_OPERATION_COPY: usize = 1;
+
+// Promises by id; the providers only hold an id so nothing Rust-owned crosses
+// into Objective-C
+static PROMISES: Mutex<Option<HashMap<usize, PromisedFile>>> = Mutex::new(None);
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+static APP: OnceLock<AppHandle> = OnceLock::new();
+// Delegate class, shared drag source, and the queue promises are written on.
+// The default queue is the main one, which would freeze the UI for the whole
+// compression.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+struct Runtime {
+    delegate_class: usize,
+    source: usize,
+    queue: usize,
+}
+
+// Header of an Objective-C block, enough to call the completion handler
+#[repr(C)]
+struct Block {
+    isa: *const c_void,
+    flags: i32,
+    reserved: i32,
+    invoke: unsafe extern "C" fn(*mut Block, id),
+}
+
+unsafe fn ns_string(s: &str) -> id {
+    NSString::alloc(nil).init_str(s).autorelease()
+}
+
+unsafe fn promise_id(this: &Object) -> usize {
+    *this.get_ivar::<usize>(ID_IVAR)
+}
+
+fn promise(id: usize) -> Option<PromisedFile> {
+    PROMISES.lock().ok()?.as_ref()?.get(&id).cloned()
+}
+
+fn file_name(promised: &PromisedFile) -> String {
+    output_path_for(
+        Path::new(&promised.input_path),
+        None,
+        promised.options.format,
+    )
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_else(|| format!("image.{}", promised.options.format.extension()))
+}
+
+fn uti(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Jpeg => "public.jpeg",
+        OutputFormat::Png => "public.png",
+        OutputFormat::Webp => "org.webmproject.webp",
+        OutputFormat::Jxl => "public.jpeg-xl",
+    }
+}
+
+// Compresses into a staging folder and moves the result into place, so a
+// failed encode never leaves a half-written file where the user dropped it
+fn materialize(promised: &PromisedFile, dest: &Path) -> Result<(), String> {
+    let staging = safe_path::temp_dir().join(format!("promise-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+    let mut options = promised.options.clone();
+    options.output_dir = Some(staging.to_string_lossy().to_string());
+    let result = compress_file(
+        promised.input_path.clone(),
+        &options,
+        &CancelToken::default(),
+    )
+    .and_then(|result| {
+        let output = PathBuf::from(&result.output_path);
+        std::fs::rename(&output, dest)
+            .or_else(|_| std::fs::copy(&output, dest).map(|_| ()))
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+extern "C" fn file_name_for_type(this: &Object, _cmd: Sel, _provider: id, _file_type: id) -> id {
+    let name = unsafe { promise(promise_id(this)) }
+        .map(|p| file_name(&p))
+        .unwrap_or_else(|| "image".to_string());
+    unsafe { ns_string(&name) }
+}
+
+extern "C" fn write_promise(this: &Object, _cmd: Sel, _provider: id, url: id, completion: id) {
+    let promised = unsafe { promise(promise_id(this)) };
+    let dest = unsafe {
+        let path: id = msg_send![url, path];
+        let utf8: *const c_char = msg_send![path, UTF8String];
+        PathBuf::from(CStr::from_ptr(utf8).to_string_lossy().to_string())
+    };
+    let result = match &promised {
+        Some(promised) => materialize(promised, &dest),
+        None => Err("Drag expired".to_string()),
+    };
+
+    if let (Some(app), Some(promised)) = (APP.get(), &promised) {
+        let outcome = PromiseOutcome {
+            input_path: promised.input_path.clone(),
+            output_path: result.is_ok().then(|| dest.to_string_lossy().to_string()),
+            error: result.as_ref().err().cloned(),
+        };
+        if let Err(e) = app.emit(PROMISE_EVENT, outcome) {
+            tracing::warn!("Failed to emit drag promise result: {}", e);
+        }
+    }
+
+    unsafe {
+        let error: id = match &result {
+            Ok(()) => nil,
+            Err(message) => {
+                tracing::warn!("Failed to fulfil drag promise: {}", message);
+                let info: id = msg_send![class!(NSDictionary),
+                    dictionaryWithObject: ns_string(message)
+                    forKey: ns_string("NSLocalizedDescription")];
+                msg_send![class!(NSError), errorWithDomain: ns_string("Squish") code: 1i64 userInfo: info]
+            }
+        };
+        let block = completion as *mut Block;
+        ((*block).invoke)(block, error);
+    }
+}
+
+extern "C" fn operation_queue(_this: &Object, _cmd: Sel, _provider: id) -> id {
+    RUNTIME.get().map(|r| r.queue as id).unwrap_or(nil)
+}
+
+extern "C" fn source_operation_mask(
+    _this: &Object,
+    _cmd: Sel,
+    _session: id,
+    _context: i64,
+) -> usize {
+    NS_DRAG_OPERATION_COPY
+}
+
+extern "C" fn delegate_dealloc(this: &Object, _cmd: Sel) {
+    unsafe {
+        if let Ok(mut promises) = PROMISES.lock() {
+            if let Some(map) = promises.as_mut() {
+                map.remove(&promise_id(this));
+            }
+        }
+        let superclass = class!(NSObject);
+        let _: () = msg_send![super(this, superclass), dealloc];
+    }
+}
+
+// Promises are forgotten when their delegate is released together with the
+// provider, since writes can happen after the drag session itself has ended
+fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| unsafe {
+        let mut delegate = ClassDecl::new("SquishFilePromiseDelegate", class!(NSObject))
+            .expect("promise delegate class");
+        delegate.add_ivar::<usize>(ID_IVAR);
+        delegate.add_method(
+            sel!(filePromiseProvider:fileNameForType:),
+            file_name_for_type as extern "C" fn(&Object, Sel, id, id) -> id,
+        );
+        delegate.add_method(
+            sel!(filePromiseProvider:writePromiseToURL:completionHandler:),
+            write_promise as extern "C" fn(&Object, Sel, id, id, id),
+        );
+        delegate.add_method(
+            sel!(operationQueueForFilePromiseProvider:),
+            operation_queue as extern "C" fn(&Object, Sel, id) -> id,
+        );
+        delegate.add_method(
+            sel!(dealloc),
+            delegate_dealloc as extern "C" fn(&Object, Sel),
+        );
+        let delegate_class = delegate.register() as *const Class as usize;
+
+        let mut source =
+            ClassDecl::new("SquishDragSource", class!(NSObject)).expect("drag source class");
+        source.add_method(
+            sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+            source_operation_mask as extern "C" fn(&Object, Sel, id, i64) -> usize,
+        );
+        let source: id = msg_send![source.register(), new];
+        let queue: id = msg_send![class!(NSOperationQueue), new];
+        Runtime {
+            delegate_class,
+            source: source as usize,
+            queue: queue as usize,
+        }
+    })
+}
+
+unsafe fn drag_image(icon: Option<Vec<u8>>) -> id {
+    let Some(png) = icon else {
+        return nil;
+    };
+    let data: id = msg_send![class!(NSData), dataWithBytes: png.as_ptr() length: png.len()];
+    let image: id = msg_send![class!(NSImage), alloc];
+    msg_send![image, initWithData: data]
+}
+
+// Must be called on the main thread
+pub fn start(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    files: Vec<PromisedFile>,
+    icon: Option<Vec<u8>>,
+) {
+    let _ = APP.set(app.clone());
+    let runtime = runtime();
+    let delegate_class = runtime.delegate_class as *const Class;
+    let Ok(ns_window) = window.ns_window() else {
+        tracing::warn!("Failed to get window for drag");
+        return;
+    };
+
+    unsafe {
+        let ns_window = ns_window as id;
+        let view: id = msg_send![ns_window, contentView];
+        let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+        let event: id = msg_send![ns_app, currentEvent];
+        if event == nil {
+            tracing::warn!("Drag started without a mouse event");
+            return;
+        }
+        let location: NSPoint = msg_send![event, locationInWindow];
+        let location: NSPoint = msg_send![view, convertPoint: location fromView: nil];
+        let image = drag_image(icon);
+        let size = DRAG_ICON_SIZE as f64;
+
+        let mut items = Vec::new();
+        for (offset, promised) in files.into_iter().enumerate() {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let file_type = uti(promised.options.format);
+            if let Ok(mut promises) = PROMISES.lock() {
+                promises
+                    .get_or_insert_with(HashMap::new)
+                    .insert(id, promised);
+            }
+
+            let delegate: id = msg_send![delegate_class, new];
+            (*delegate).set_ivar::<usize>(ID_IVAR, id);
+            let provider: id = msg_send![class!(NSFilePromiseProvider), alloc];
+            let provider: id = msg_send![provider,
+                initWithFileType: ns_string(file_type)
+                delegate: delegate];
+            // The provider only holds its delegate weakly, so userInfo is
+            // what keeps it alive until the promise has been written
+            let _: () = msg_send![provider, setUserInfo: delegate];
+            let _: () = msg_send![delegate, release];
+
+            let item: id = msg_send![class!(NSDraggingItem), alloc];
+            let item: id = msg_send![item, initWithPasteboardWriter: provider];
+            let shift = offset as f64 * 8.0;
+            let frame = NSRect::new(
+                NSPoint::new(
+                    location.x - size / 2.0 + shift,
+                    location.y - size / 2.0 - shift,
+                ),
+                NSSize::new(size, size),
+            );
+            let _: () = msg_send![item, setDraggingFrame: frame contents: image];
+            let _: () = msg_send![provider, release];
+            items.push(item);
+        }
+
+        let source = runtime.source as id;
+        let array = NSArray::arrayWithObjects(nil, &items);
+        let session: id = msg_send![view,
+            beginDraggingSessionWithItems: array
+            event: event
+            source: source];
+        if session == nil {
+            tracing::warn!("Failed to start promised drag");
+        } else {
+            let _: () = msg_send![session, setAnimatesToStartingPositionsOnCancelOrFail: NO];
+        }
+        for item in items {
+            let _: () = msg_send![item, release];
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// .squish documents: a library project saved as a file, to hand to someone
+// or keep outside the library. A document is a package, a folder that Finder
+// shows and moves as one file, holding:
+//   project.json             the project as a sync bundle, assets included
+//   QuickLook/Thumbnail.png  Finder's icon for it
+//   QuickLook/Preview.png    what pressing space on it shows
+// The pictures are made here, so the Quick Look extensions (quicklook/) only
+// hand them over and never read the project. Without a picture of the page to
+// make them from they're left out, and Finder falls back to the generic icon.
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::blocking;
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::{decode_image, encode_image, OutputFormat};
+use crate::safe_path::SafePath;
+use crate::sync::bundle::{self, Bundle};
+
+pub const EXTENSION: &str = "squish";
+const PROJECT_FILE: &str = "project.json";
+const QUICK_LOOK_DIR: &str = "QuickLook";
+const THUMBNAIL_EDGE: u32 = 512;
+const PREVIEW_EDGE: u32 = 2048;
+
+pub fn is_document(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(EXTENSION))
+}
+
+// `preview_path` is the page as the UI rendered it; the library thumbnail
+// stands in without one. Returns where the document was written.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn export_project_document(
+    app: AppHandle,
+    project_id: String,
+    path: String,
+    preview_path: Option<String>,
+) -> Result<String, Error> {
+    blocking::run(move || {
+        let dest = SafePath::write(&app, &path)?.into_path_buf();
+        let preview = preview_path
+            .map(|p| SafePath::read(&app, &p).map(SafePath::into_path_buf))
+            .transpose()?;
+        let (bundle, thumbnail) = {
+            let db = app.state::<Db>();
+            let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+            let bundle = bundle::build(&conn, "project", &project_id)?;
+            let thumbnail = crate::library::get_entry(&conn, &project_id)?
+                .and_then(|entry| entry.thumbnail_path)
+                .map(PathBuf::from);
+            (bundle, thumbnail)
+        };
+        if bundle.tables.get("projects").is_none_or(Vec::is_empty) {
+            return Err(Error::NotFound(format!("Project {} not found", project_id)));
+        }
+        let (json, _) = bundle::encode(&bundle)?;
+
+        std::fs::create_dir_all(&dest)?;
+        std::fs::write(dest.join(PROJECT_FILE), json)?;
+        // Pictures from an earlier export would show the old page
+        let quick_look = dest.join(QUICK_LOOK_DIR);
+        if quick_look.exists() {
+            std::fs::remove_dir_all(&quick_look)?;
+        }
+        if let Some(source) = preview.or(thumbnail) {
+            // The document is still whole without them
+            if let Err(e) = write_quick_look(&quick_look, &source) {
+                tracing::warn!("No Quick Look preview for {}: {}", dest.display(), e);
+            }
+        }
+        tracing::info!("Saved project {} to {}", project_id, dest.display());
+        Ok(dest.to_string_lossy().to_string())
+    })
+    .await
+}
+
+fn write_quick_look(dir: &Path, source: &Path) -> Result<(), String> {
+    let page = decode_image(source)?;
+    let fit = |image: &DynamicImage, edge: u32| {
+        if image.width().max(image.height()) > edge {
+            image.thumbnail(edge, edge)
+        } else {
+            image.clone()
+        }
+    };
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    for (name, edge) in [
+        ("Preview.png", PREVIEW_EDGE),
+        ("Thumbnail.png", THUMBNAIL_EDGE),
+    ] {
+        let bytes = encode_image(&fit(&page, edge), OutputFormat::Png, 100)?;
+        let path = dir.join(name);
+        std::fs::write(&path, bytes)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+// Brings the document's project into the library and returns its id for the
+// UI to open. A project that's already in the library (the document was
+// saved from here) is replaced by the document's copy.
+#[tauri::command]
+pub fn open_project_document(app: AppHandle, db: State<Db>, path: String) -> Result<String, Error> {
+    let path = SafePath::read(&app, &path)?.into_path_buf();
+    let json = std::fs::read(path.join(PROJECT_FILE)).map_err(|e| {
+        Error::InvalidInput(format!("{} isn't a Squish document: {}", path.display(), e))
+    })?;
+    let bundle: Bundle = serde_json::from_slice(&json).map_err(|e| {
+        Error::InvalidInput(format!("Invalid Squish document {}: {}", path.display(), e))
+    })?;
+    if bundle.kind != "project" {
+        return Err(Error::InvalidInput(format!(
+            "{} doesn't hold a project",
+            path.display()
+        )));
+    }
+
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    bundle::apply(&mut conn, &bundle)?;
+    crate::library::index(&conn, &bundle.id, None)?;
+    tracing::info!("Opened project {} from {}", bundle.id, path.display());
+    Ok(bundle.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-document-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn only_folders_with_the_extension_are_documents() {
+        let package = dir("Poster.Squish");
+        let file = dir("file.squish");
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(&file, b"").unwrap();
+        assert!(is_document(&package));
+        assert!(!is_document(&file));
+        assert!(!is_document(&dir("missing.squish")));
+        std::fs::remove_dir_all(package).unwrap();
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn quick_look_pictures_fit_their_edges() {
+        let root = dir("quicklook");
+        std::fs::create_dir_all(&root).unwrap();
+        let page = root.join("page.png");
+        RgbImage::new(3000, 1500).save(&page).unwrap();
+
+        write_quick_look(&root.join(QUICK_LOOK_DIR), &page).unwrap();
+        let size =
+            |name: &str| image::image_dimensions(root.join(QUICK_LOOK_DIR).join(name)).unwrap();
+        assert_eq!(size("Preview.png"), (PREVIEW_EDGE, 1024));
+        assert_eq!(size("Thumbnail.png"), (THUMBNAIL_EDGE, 256));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use keyring::Entry;
+use rusqlite::Connection;
+use std::io::Read;
+use std::path::Path;
+use tauri::{AppHandle, State};
+
+use crate::db::Db;
+use crate::error::Error;
+
+const KEYRING_SERVICE: &str = "com.squish.dev";
+const LIBRARY_KEY_ACCOUNT: &str = "library-key";
+// Every plaintext SQLite file starts with this; SQLCipher files look like noise
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+pub fn keychain_entry(account: &str) -> Result<Entry, String> {
+    Entry::new(KEYRING_SERVICE, account).map_err(|e| format!("Failed to access keychain: {}", e))
+}
+
+// Each profile's library has its own key
+fn key_entry(db_path: &Path) -> Result<Entry, String> {
+    let profile = crate::profiles::profile_id_for(db_path);
+    if profile == crate::profiles::DEFAULT_PROFILE_ID {
+        keychain_entry(LIBRARY_KEY_ACCOUNT)
+    } else {
+        keychain_entry(&format!("{}:{}", LIBRARY_KEY_ACCOUNT, profile))
+    }
+}
+
+pub fn library_key(db_path: &Path) -> Result<Option<String>, String> {
+    match key_entry(db_path)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read library key from keychain: {}", e)),
+    }
+}
+
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        // Missing or empty files are new plaintext libraries
+        Err(_) => false,
+    }
+}
+
+// Must run before anything else touches the connection
+#[cfg(feature = "sqlcipher")]
+pub fn apply_key(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.pragma_update(None, "key", key)
+        .map_err(|e| format!("Failed to set library key: {}", e))?;
+    // A wrong key only shows up on the first read
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|_| "The library key in the keychain does not match this library".to_string())
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub fn apply_key(_conn: &Connection, _key: &str) -> Result<(), String> {
+    Err("This library is encrypted, but Squish was built without SQLCipher support".to_string())
+}
+
+#[tauri::command]
+pub fn is_library_encrypted(app: AppHandle) -> Result<bool, Error> {
+    Ok(is_encrypted_file(&crate::db::db_path(&app)?))
+}
+
+// Rewrites the library into an encrypted copy with sqlcipher_export, stores the
+// passphrase in the OS keychain, then swaps the files and reopens. The frontend
+// closes its own connection first and reconnects through the Rust one.
+#[cfg(feature = "sqlcipher")]
+#[tauri::command]
+pub fn enable_library_encryption(
+    app: AppHandle,
+    db: State<Db>,
+    passphrase: String,
+) -> Result<(), Error> {
+    use rusqlite::params;
+    use tauri::Emitter;
+
+    if passphrase.chars().count() < 8 {
+        return Err(Error::InvalidInput(
+            "Passphrase must be at least 8 characters".to_string(),
+        ));
+    }
+    let path = crate::db::db_path(&app)?;
+    if is_encrypted_file(&path) {
+        return Err(Error::InvalidInput(
+            "Library is already encrypted".to_string(),
+        ));
+    }
+    let encrypted_path = path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted_path);
+
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| format!("Failed to checkpoint database: {}", e))?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        params![encrypted_path.to_string_lossy(), passphrase],
+    )
+    .map_err(|e| format!("Failed to create encrypted library: {}", e))?;
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+        .map_err(|e| format!("Failed to encrypt library: {}", e));
+    conn.execute_batch("DETACH DATABASE encrypted;")
+        .map_err(|e| format!("Failed to detach encrypted library: {}", e))?;
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&encrypted_path);
+        return Err(e.into());
+    }
+
+    // Save the key before the plaintext file goes away, so a keychain failure
+    // leaves the library untouched
+    key_entry(&path)?
+        .set_password(&passphrase)
+        .map_err(|e| format!("Failed to save library key to keychain: {}", e))?;
+
+    // Drop our handle on the old file before replacing it
+    *conn =
+        Connection::open_in_memory().map_err(|e| format!("Failed to release database: {}", e))?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    std::fs::rename(&encrypted_path, &path)
+        .map_err(|e| format!("Failed to replace library with encrypted copy: {}", e))?;
+    *conn = crate::db::open_at(&path)?;
+    drop(conn);
+
+    tracing::info!("Library encrypted");
+    if let Err(e) = app.emit("db://reopened", ()) {
+        tracing::warn!("Failed to emit reopen event: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+#[tauri::command]
+pub fn enable_library_encryption(
+    _app: AppHandle,
+    _db: State<Db>,
+    _passphrase: String,
+) -> Result<(), Error> {
+    Err(Error::Internal(
+        "Squish was built without SQLCipher support".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("squish-encryption-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn plaintext_libraries_are_recognised_by_their_header() {
+        let plain = path("plain.db");
+        Connection::open(&plain)
+            .unwrap()
+            .execute_batch("CREATE TABLE projects (id TEXT)")
+            .unwrap();
+        assert!(!is_encrypted_file(&plain));
+        assert!(!is_encrypted_file(&path("missing.db")));
+
+        let noise = path("noise.db");
+        std::fs::write(&noise, [0x5a; 64]).unwrap();
+        assert!(is_encrypted_file(&noise));
+        for file in [plain, noise] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn only_the_right_key_opens_an_encrypted_library() {
+        let encrypted = path("encrypted.db");
+        let conn = Connection::open(&encrypted).unwrap();
+        apply_key(&conn, "correct horse").unwrap();
+        conn.execute_batch("CREATE TABLE projects (id TEXT)")
+            .unwrap();
+        drop(conn);
+        assert!(is_encrypted_file(&encrypted));
+
+        let conn = Connection::open(&encrypted).unwrap();
+        assert!(apply_key(&conn, "battery staple").is_err());
+        let conn = Connection::open(&encrypted).unwrap();
+        apply_key(&conn, "correct horse").unwrap();
+        drop(conn);
+        std::fs::remove_file(encrypted).unwrap();
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn keys_are_refused_without_sqlcipher() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(apply_key(&conn, "correct horse").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+// Errors returned to the frontend. Each kind serializes with a stable `code`
+// the UI can branch on (offer a retry when offline, a file picker when a file
+// is gone) next to a `message` for display. Every command returns it; helpers
+// that still return `Result<_, String>` work with `?`, a String becoming
+// Error::Internal.
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::io::ErrorKind;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    // A mutex was poisoned by a panic on another thread
+    #[error("Failed to lock {0}")]
+    Lock(&'static str),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("You're offline")]
+    Offline,
+    #[error("{0}")]
+    Busy(String),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl Error {
+    // Part of the IPC contract; never rename an existing code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(e) if e.kind() == ErrorKind::NotFound => "not_found",
+            Error::Io(e) if e.kind() == ErrorKind::PermissionDenied => "permission_denied",
+            Error::Io(_) => "io",
+            Error::Database(_) => "database",
+            Error::Lock(_) => "lock",
+            Error::NotFound(_) => "not_found",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::UnsupportedFormat(_) => "unsupported_format",
+            Error::PermissionDenied(_) => "permission_denied",
+            Error::Offline => "offline",
+            Error::Busy(_) => "busy",
+            Error::Cancelled => "cancelled",
+            Error::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("Error", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Internal(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Internal(message.to_string())
+    }
+}
+
+// Lets commands that still return String errors use `?` on an Error
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_a_code_and_message() {
+        let error = Error::NotFound("No such preset".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({"code": "not_found", "message": "No such preset"})
+        );
+        assert_eq!(
+            serde_json::to_value(Error::Offline).unwrap()["message"],
+            "You're offline"
+        );
+    }
+
+    #[test]
+    fn io_errors_map_to_their_kind() {
+        let io = |kind| Error::from(std::io::Error::from(kind)).code();
+        assert_eq!(io(ErrorKind::NotFound), "not_found");
+        assert_eq!(io(ErrorKind::PermissionDenied), "permission_denied");
+        assert_eq!(io(ErrorKind::UnexpectedEof), "io");
+    }
+
+    #[test]
+    fn strings_become_internal_errors_and_back() {
+        let error = Error::from("Failed to read".to_string());
+        assert_eq!(error.code(), "internal");
+        assert_eq!(String::from(error), "Failed to read");
+    }
+}
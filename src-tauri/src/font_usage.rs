@@ -0,0 +1,249 @@
+// Which font faces each project uses, kept in font_usage and refreshed by
+// library::index on every save. The font panel uses it to show where a family
+// is used before it's removed; the missing-font check and font subsetting on
+// export read the faces a project needs from here without opening it.
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FontUsage {
+    pub family: String,
+    // A style name fonts::face_data understands, e.g. "Bold Italic"
+    pub face: String,
+    pub layer_count: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontUser {
+    pub project_id: String,
+    pub title: String,
+    pub faces: Vec<String>,
+    pub layer_count: u32,
+}
+
+// Keep in step with the backfill in migrations/0018_font_usage.sql
+fn face_name(weight: f64, italic: bool) -> String {
+    let weight = match ((weight + 50.0) / 100.0).floor().clamp(1.0, 9.0) as u32 {
+        1 => "Thin",
+        2 => "ExtraLight",
+        3 => "Light",
+        4 => "Regular",
+        5 => "Medium",
+        6 => "SemiBold",
+        7 => "Bold",
+        8 => "ExtraBold",
+        _ => "Black",
+    };
+    match (weight, italic) {
+        ("Regular", true) => "Italic".to_string(),
+        (weight, true) => format!("{} Italic", weight),
+        (weight, false) => weight.to_string(),
+    }
+}
+
+// Replaces the project's rows with what its text layers use now
+pub fn record(conn: &Connection, project_id: &str) -> Result<Vec<FontUsage>, String> {
+    let mut stmt = conn
+        .prepare("SELECT style FROM layers WHERE project_id = ?1 AND type = 'text'")
+        .map_err(|e| format!("Failed to read layers: {}", e))?;
+    let styles = stmt
+        .query_map(params![project_id], |row| row.get::<_, Option<String>>(0))
+        .map_err(|e| format!("Failed to read layers: {}", e))?;
+
+    let mut counts: BTreeMap<(String, String), u32> = BTreeMap::new();
+    for style in styles {
+        let style = style.map_err(|e| format!("Failed to read layer: {}", e))?;
+        let Some(style) = style.and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        else {
+            continue;
+        };
+        let Some(family) = style.get("fontFamily").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if family.trim().is_empty() {
+            continue;
+        }
+        let weight = style
+            .get("fontWeight")
+            .and_then(|v| v.as_f64())
+            .filter(|w| w.is_finite())
+            .unwrap_or(400.0);
+        let italic = style
+            .get("italic")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        *counts
+            .entry((family.to_string(), face_name(weight, italic)))
+            .or_default() += 1;
+    }
+
+    conn.execute(
+        "DELETE FROM font_usage WHERE project_id = ?1",
+        params![project_id],
+    )
+    .map_err(|e| format!("Failed to record font usage: {}", e))?;
+    // Families differing only in case share a row
+    for ((family, face), layer_count) in &counts {
+        conn.execute(
+            "INSERT INTO font_usage (project_id, family, face, layer_count) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(project_id, family, face)
+             DO UPDATE SET layer_count = layer_count + excluded.layer_count",
+            params![project_id, family, face, layer_count],
+        )
+        .map_err(|e| format!("Failed to record font usage: {}", e))?;
+    }
+    fonts_used(conn, project_id)
+}
+
+pub fn fonts_used(conn: &Connection, project_id: &str) -> Result<Vec<FontUsage>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT family, face, layer_count FROM font_usage WHERE project_id = ?1
+             ORDER BY family, face",
+        )
+        .map_err(|e| format!("Failed to read font usage: {}", e))?;
+    let usage = stmt
+        .query_map(params![project_id], |row| {
+            Ok(FontUsage {
+                family: row.get(0)?,
+                face: row.get(1)?,
+                layer_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read font usage: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read font usage: {}", e))?;
+    Ok(usage)
+}
+
+// Projects using any face of the family, most recently saved first
+#[tauri::command]
+pub fn get_projects_using_font(db: State<Db>, family: String) -> Result<Vec<FontUser>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn.prepare(
+        "SELECT u.project_id, p.name, u.face, u.layer_count
+         FROM font_usage u JOIN projects p ON p.id = u.project_id
+         WHERE u.family = ?1
+         ORDER BY p.updated_at DESC, u.project_id, u.face",
+    )?;
+    let rows = stmt
+        .query_map(params![family.trim()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u32>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut users: Vec<FontUser> = Vec::new();
+    for (project_id, title, face, layer_count) in rows {
+        match users.last_mut() {
+            Some(user) if user.project_id == project_id => {
+                user.faces.push(face);
+                user.layer_count += layer_count;
+            }
+            _ => users.push(FontUser {
+                project_id,
+                title,
+                faces: vec![face],
+                layer_count,
+            }),
+        }
+    }
+    Ok(users)
+}
+
+#[tauri::command]
+pub fn get_fonts_used(db: State<Db>, project_id: String) -> Result<Vec<FontUsage>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(fonts_used(&conn, &project_id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::MIGRATIONS;
+
+    const STYLES: &[&str] = &[
+        r#"{"fontFamily": "Inter", "fontWeight": 700}"#,
+        r#"{"fontFamily": "inter", "fontWeight": 650}"#,
+        r#"{"fontFamily": "Inter", "italic": true}"#,
+        r#"{"fontFamily": "Lora", "fontWeight": 300, "italic": true}"#,
+        r#"{"fontFamily": " \t", "fontWeight": 400}"#,
+        r#"{"fontFamily": 12}"#,
+        r#"{"fontSize": 12}"#,
+    ];
+
+    // A library at the schema before font_usage, holding one project
+    fn library_before_font_usage() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version < 18) {
+            conn.execute_batch(migration.sql).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster')",
+            [],
+        )
+        .unwrap();
+        for (i, style) in STYLES.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO layers (id, project_id, type, content, style, transform)
+                 VALUES (?1, 'p1', 'text', 'Hello', ?2, '{}')",
+                params![i.to_string(), style],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn summary(usage: &[FontUsage]) -> Vec<(String, String, u32)> {
+        usage
+            .iter()
+            .map(|u| (u.family.to_lowercase(), u.face.clone(), u.layer_count))
+            .collect()
+    }
+
+    #[test]
+    fn face_names_follow_css_weights() {
+        assert_eq!(face_name(400.0, false), "Regular");
+        assert_eq!(face_name(400.0, true), "Italic");
+        assert_eq!(face_name(649.0, false), "SemiBold");
+        assert_eq!(face_name(650.0, true), "Bold Italic");
+        assert_eq!(face_name(1.0, false), "Thin");
+        assert_eq!(face_name(1000.0, false), "Black");
+    }
+
+    #[test]
+    fn counts_layers_per_face_ignoring_family_case() {
+        let conn = library_before_font_usage();
+        conn.execute_batch(MIGRATIONS[17].sql).unwrap();
+        let usage = record(&conn, "p1").unwrap();
+        assert_eq!(
+            summary(&usage),
+            [
+                ("inter".to_string(), "Bold".to_string(), 2),
+                ("inter".to_string(), "Italic".to_string(), 1),
+                ("lora".to_string(), "Light Italic".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_migration_backfill_matches_recording() {
+        let conn = library_before_font_usage();
+        assert_eq!(MIGRATIONS[17].version, 18);
+        conn.execute_batch(MIGRATIONS[17].sql).unwrap();
+        let backfilled = summary(&fonts_used(&conn, "p1").unwrap());
+        let recorded = summary(&record(&conn, "p1").unwrap());
+        assert_eq!(backfilled, recorded);
+    }
+}
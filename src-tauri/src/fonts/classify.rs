@@ -0,0 +1,125 @@
+use font_kit::font::Font;
+use serde::Serialize;
+
+const OS2: u32 = u32::from_be_bytes(*b"OS/2");
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Category {
+    Serif,
+    SansSerif,
+    Monospace,
+    Script,
+    Display,
+    Symbol,
+}
+
+// Rough typographic category of a face: PANOSE and the IBM family class from
+// its OS/2 table, falling back to the family name for fonts that leave both
+// unset (many do)
+pub fn classify(font: &Font) -> Category {
+    if font.is_monospace() {
+        return Category::Monospace;
+    }
+    font.load_font_table(OS2)
+        .and_then(|table| from_os2(&table))
+        .unwrap_or_else(|| from_name(&font.family_name()))
+}
+
+fn from_os2(table: &[u8]) -> Option<Category> {
+    let panose = table.get(32..42)?;
+    let from_panose = match panose[0] {
+        // Latin text: proportion 9 is monospaced, serif styles 11-13 are sans
+        2 if panose[3] == 9 => Some(Category::Monospace),
+        2 => match panose[1] {
+            2..=10 => Some(Category::Serif),
+            11..=13 => Some(Category::SansSerif),
+            _ => None,
+        },
+        3 => Some(Category::Script),
+        4 => Some(Category::Display),
+        5 => Some(Category::Symbol),
+        _ => None,
+    };
+    // High byte of sFamilyClass
+    from_panose.or(match table.get(30)? {
+        1..=5 | 7 => Some(Category::Serif),
+        8 => Some(Category::SansSerif),
+        9 => Some(Category::Display),
+        10 => Some(Category::Script),
+        12 => Some(Category::Symbol),
+        _ => None,
+    })
+}
+
+// For families that aren't installed, or whose tables say nothing
+pub fn from_name(family: &str) -> Category {
+    let name = family.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+    if has(&["mono", "code", "courier", "consol"]) {
+        Category::Monospace
+    } else if has(&["sans", "grotesk", "grotesque", "gothic"]) {
+        Category::SansSerif
+    } else if has(&["serif", "times", "georgia", "garamond", "roman", "slab"]) {
+        Category::Serif
+    } else if has(&["script", "hand", "brush"]) {
+        Category::Script
+    } else {
+        Category::SansSerif
+    }
+}
+
+// What pairing suggestions compare fonts by
+#[derive(Clone, Copy)]
+pub struct Profile {
+    pub category: Category,
+    // x-height as a fraction of the em; None when the font doesn't say
+    pub x_height: Option<f32>,
+}
+
+pub fn profile(font: &Font) -> Profile {
+    let metrics = font.metrics();
+    Profile {
+        category: classify(font),
+        x_height: (metrics.x_height > 0.0 && metrics.units_per_em > 0)
+            .then(|| metrics.x_height / metrics.units_per_em as f32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An OS/2 table long enough to carry the family class and PANOSE
+    fn os2(family_class: u8, panose: [u8; 4]) -> Vec<u8> {
+        let mut table = vec![0; 42];
+        table[30] = family_class;
+        table[32..36].copy_from_slice(&panose);
+        table
+    }
+
+    #[test]
+    fn panose_decides_first() {
+        assert_eq!(from_os2(&os2(0, [2, 2, 0, 0])), Some(Category::Serif));
+        assert_eq!(from_os2(&os2(0, [2, 11, 0, 0])), Some(Category::SansSerif));
+        assert_eq!(from_os2(&os2(0, [2, 11, 0, 9])), Some(Category::Monospace));
+        assert_eq!(from_os2(&os2(8, [3, 0, 0, 0])), Some(Category::Script));
+    }
+
+    #[test]
+    fn family_class_fills_in_for_unset_panose() {
+        assert_eq!(from_os2(&os2(8, [0; 4])), Some(Category::SansSerif));
+        assert_eq!(from_os2(&os2(1, [2, 0, 0, 0])), Some(Category::Serif));
+        assert_eq!(from_os2(&os2(0, [0; 4])), None);
+        assert_eq!(from_os2(&[0; 20]), None);
+    }
+
+    #[test]
+    fn names_pick_a_category() {
+        assert_eq!(from_name("Fira Code"), Category::Monospace);
+        assert_eq!(from_name("Source Sans Pro"), Category::SansSerif);
+        assert_eq!(from_name("EB Garamond"), Category::Serif);
+        assert_eq!(from_name("Pacific Brush"), Category::Script);
+        assert_eq!(from_name("Inter"), Category::SansSerif);
+    }
+}
@@ -0,0 +1,545 @@
+// Fonts embedded in imported documents: the font programs behind a PDF's font
+// descriptors, and data: URLs in an SVG's @font-face rules. They're copied to
+// <app data>/project-fonts and registered to the project the document was
+// imported into, so its text keeps its face without installing anything.
+// Fonts imported outside a project, or whose project is gone, are swept on
+// the next launch.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lopdf::{Document, Object};
+use resvg::usvg::fontdb;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tauri::ipc::Response;
+use tauri::{AppHandle, Manager, State};
+
+use crate::cancel::CancelToken;
+use crate::db::Db;
+use crate::error::Error;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FontFormat {
+    TrueType,
+    OpenType,
+    // Bare CFF and Type 1 programs; kept, but a webview can't load them
+    Cff,
+    Type1,
+    Woff,
+    Woff2,
+}
+
+impl FontFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FontFormat::TrueType => "truetype",
+            FontFormat::OpenType => "opentype",
+            FontFormat::Cff => "cff",
+            FontFormat::Type1 => "type1",
+            FontFormat::Woff => "woff",
+            FontFormat::Woff2 => "woff2",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        [
+            FontFormat::TrueType,
+            FontFormat::OpenType,
+            FontFormat::Cff,
+            FontFormat::Type1,
+            FontFormat::Woff,
+            FontFormat::Woff2,
+        ]
+        .into_iter()
+        .find(|format| format.as_str() == value)
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            FontFormat::TrueType => "ttf",
+            FontFormat::OpenType => "otf",
+            FontFormat::Cff => "cff",
+            FontFormat::Type1 => "pfb",
+            FontFormat::Woff => "woff",
+            FontFormat::Woff2 => "woff2",
+        }
+    }
+
+    fn from_mime(mime: &str) -> Option<Self> {
+        match mime.trim().to_lowercase().as_str() {
+            "font/ttf" | "application/x-font-ttf" | "font/sfnt" | "application/font-sfnt" => {
+                Some(FontFormat::TrueType)
+            }
+            "font/otf" | "application/x-font-opentype" | "application/font-otf" => {
+                Some(FontFormat::OpenType)
+            }
+            "font/woff" | "application/font-woff" | "application/x-font-woff" => {
+                Some(FontFormat::Woff)
+            }
+            "font/woff2" | "application/font-woff2" => Some(FontFormat::Woff2),
+            _ => None,
+        }
+    }
+
+    pub fn loadable(&self) -> bool {
+        !matches!(self, FontFormat::Cff | FontFormat::Type1)
+    }
+}
+
+// A font program pulled out of a document, not yet stored
+pub struct EmbeddedFont {
+    // As the document names it, e.g. "ABCDEF+Helvetica-Bold"
+    pub name: String,
+    pub family: String,
+    pub format: FontFormat,
+    // Only the glyphs the document uses
+    pub subset: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFont {
+    pub id: String,
+    pub project_id: Option<String>,
+    pub family: String,
+    pub name: String,
+    pub format: FontFormat,
+    pub subset: bool,
+    // Whether the webview can load it with FontFace
+    pub loadable: bool,
+    // The document it came from, and "pdf" or "svg"
+    pub origin_path: String,
+    pub origin_kind: String,
+}
+
+// Family from the font's own name table; only sfnt fonts have one we can read
+fn family_of(data: &[u8], format: FontFormat) -> Option<String> {
+    if !matches!(format, FontFormat::TrueType | FontFormat::OpenType) {
+        return None;
+    }
+    let mut db = fontdb::Database::new();
+    db.load_font_data(data.to_vec());
+    let face = db.faces().next()?;
+    face.families.first().map(|(name, _)| name.clone())
+}
+
+// "ABCDEF+Helvetica-Bold" is a subset of Helvetica
+fn is_subset(name: &str) -> bool {
+    name.len() > 7
+        && name.as_bytes()[6] == b'+'
+        && name.as_bytes()[..6].iter().all(u8::is_ascii_uppercase)
+}
+
+fn family_from_postscript(name: &str) -> String {
+    let name = if is_subset(name) { &name[7..] } else { name };
+    name.split(['-', ','])
+        .next()
+        .filter(|family| !family.is_empty())
+        .unwrap_or(name)
+        .to_string()
+}
+
+pub fn extract_pdf(path: &Path, cancel: &CancelToken) -> Result<Vec<EmbeddedFont>, String> {
+    let doc = Document::load(path)
+        .map_err(|e| format!("Failed to open PDF {}: {}", path.display(), e))?;
+    let programs: [(&[u8], FontFormat); 3] = [
+        (b"FontFile", FontFormat::Type1),
+        (b"FontFile2", FontFormat::TrueType),
+        (b"FontFile3", FontFormat::Cff),
+    ];
+
+    let mut fonts = Vec::new();
+    for object in doc.objects.values() {
+        let Ok(descriptor) = object.as_dict() else {
+            continue;
+        };
+        if !matches!(
+            descriptor.get(b"Type").and_then(Object::as_name),
+            Ok(b"FontDescriptor")
+        ) {
+            continue;
+        }
+        cancel.check()?;
+        let name = descriptor
+            .get(b"FontName")
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .unwrap_or_default();
+
+        for (key, format) in programs {
+            let Ok(stream) = descriptor
+                .get(key)
+                .and_then(Object::as_reference)
+                .and_then(|id| doc.get_object(id))
+                .and_then(Object::as_stream)
+            else {
+                continue;
+            };
+            // FontFile3 holds bare CFF unless its subtype says OpenType
+            let format = match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+                Ok(b"OpenType") => FontFormat::OpenType,
+                _ => format,
+            };
+            let data = stream
+                .decompressed_content()
+                .unwrap_or_else(|_| stream.content.clone());
+            if data.is_empty() {
+                continue;
+            }
+            fonts.push(EmbeddedFont {
+                family: family_of(&data, format).unwrap_or_else(|| family_from_postscript(&name)),
+                subset: is_subset(&name),
+                name: name.clone(),
+                format,
+                data,
+            });
+        }
+    }
+    Ok(fonts)
+}
+
+// Value of a CSS declaration in a rule body, unquoted
+fn declaration<'a>(body: &'a str, property: &str) -> Option<&'a str> {
+    let start = body.find(property)? + property.len();
+    let value = body[start..].trim_start().strip_prefix(':')?;
+    let value = value.split(';').next()?.trim();
+    Some(value.trim_matches(['"', '\'']))
+}
+
+pub fn extract_svg(path: &Path) -> Result<Vec<EmbeddedFont>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut fonts = Vec::new();
+    for rule in text.split("@font-face").skip(1) {
+        let Some(body) = rule
+            .split_once('{')
+            .and_then(|(_, rest)| rest.split_once('}'))
+            .map(|(body, _)| body)
+        else {
+            continue;
+        };
+        let declared = declaration(body, "font-family").unwrap_or_default();
+        for url in body.split("url(").skip(1) {
+            let url = url.split(')').next().unwrap_or_default();
+            let Some((mime, payload)) = url
+                .trim()
+                .trim_matches(['"', '\''])
+                .strip_prefix("data:")
+                .and_then(|url| url.split_once(";base64,"))
+            else {
+                continue;
+            };
+            let Some(format) = FontFormat::from_mime(mime) else {
+                continue;
+            };
+            let payload: String = payload.split_whitespace().collect();
+            let Ok(data) = STANDARD.decode(payload) else {
+                tracing::debug!("Skipping undecodable font in {}", path.display());
+                continue;
+            };
+            let family = family_of(&data, format).unwrap_or_else(|| declared.to_string());
+            if family.is_empty() {
+                continue;
+            }
+            fonts.push(EmbeddedFont {
+                name: declared.to_string(),
+                family,
+                format,
+                subset: false,
+                data,
+            });
+        }
+    }
+    Ok(fonts)
+}
+
+fn fonts_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join("project-fonts");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+// Stores the fonts and records where they came from. The same font imported
+// twice into a project is registered once.
+pub fn register(
+    app: &AppHandle,
+    project_id: Option<&str>,
+    origin: &Path,
+    origin_kind: &str,
+    fonts: Vec<EmbeddedFont>,
+) -> Result<Vec<ProjectFont>, String> {
+    let dir = fonts_dir(app)?;
+    let db = app.state::<Db>();
+    let origin_path = origin.to_string_lossy().to_string();
+
+    let mut registered = Vec::new();
+    for font in fonts {
+        let sha256 = format!("{:x}", Sha256::digest(&font.data));
+        let file = dir.join(format!("{}.{}", sha256, font.format.extension()));
+        if !file.exists() {
+            std::fs::write(&file, &font.data)
+                .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+        }
+
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        let existing: Option<String> = conn
+            .query_row(
+                "SELECT id FROM project_fonts WHERE project_id IS ?1 AND sha256 = ?2",
+                params![project_id, sha256],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up project font: {}", e))?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO project_fonts
+                        (id, project_id, family, name, format, sha256, subset, origin_path, origin_kind)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        id,
+                        project_id,
+                        font.family,
+                        font.name,
+                        font.format.as_str(),
+                        sha256,
+                        font.subset,
+                        origin_path,
+                        origin_kind
+                    ],
+                )
+                .map_err(|e| format!("Failed to register project font: {}", e))?;
+                id
+            }
+        };
+        registered.push(ProjectFont {
+            id,
+            project_id: project_id.map(str::to_string),
+            family: font.family,
+            name: font.name,
+            format: font.format,
+            subset: font.subset,
+            loadable: font.format.loadable(),
+            origin_path: origin_path.clone(),
+            origin_kind: origin_kind.to_string(),
+        });
+    }
+    if !registered.is_empty() {
+        tracing::info!(
+            "Registered {} embedded font(s) from {}",
+            registered.len(),
+            origin.display()
+        );
+    }
+    Ok(registered)
+}
+
+// Drops fonts of sessions and projects that are gone, then any file no
+// registration points at
+fn sweep(app: &AppHandle) -> Result<(), String> {
+    let dir = fonts_dir(app)?;
+    let kept: HashSet<String> = {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.execute(
+            "DELETE FROM project_fonts
+             WHERE project_id IS NULL OR project_id NOT IN (SELECT id FROM projects)",
+            [],
+        )
+        .map_err(|e| format!("Failed to sweep project fonts: {}", e))?;
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT sha256 FROM project_fonts")
+            .map_err(|e| format!("Failed to sweep project fonts: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to sweep project fonts: {}", e))?;
+        rows.flatten().collect()
+    };
+
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !kept.contains(&stem) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = sweep(&app) {
+            tracing::warn!("{}", e);
+        }
+    });
+}
+
+// Fonts registered to a project, or to this session when no project is given
+#[tauri::command]
+pub fn list_project_fonts(
+    db: State<Db>,
+    project_id: Option<String>,
+) -> Result<Vec<ProjectFont>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn.prepare(
+        "SELECT id, project_id, family, name, format, subset, origin_path, origin_kind
+         FROM project_fonts WHERE project_id IS ?1 ORDER BY family, created_at",
+    )?;
+    let rows = stmt.query_map([&project_id], |row| {
+        let format = FontFormat::parse(&row.get::<_, String>(4)?).unwrap_or(FontFormat::TrueType);
+        Ok(ProjectFont {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            family: row.get(2)?,
+            name: row.get(3)?,
+            format,
+            subset: row.get(5)?,
+            loadable: format.loadable(),
+            origin_path: row.get(6)?,
+            origin_kind: row.get(7)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+// The font file as raw bytes, for FontFace in the webview
+#[tauri::command]
+pub fn read_project_font(app: AppHandle, id: String) -> Result<Response, Error> {
+    let (sha256, format) = {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.query_row(
+            "SELECT sha256, format FROM project_fonts WHERE id = ?1",
+            [&id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("No project font {}", id)))?
+    };
+    let format = FontFormat::parse(&format)
+        .ok_or_else(|| Error::Internal(format!("Project font {} has an unknown format", id)))?;
+    let file = fonts_dir(&app)?.join(format!("{}.{}", sha256, format.extension()));
+    Ok(Response::new(std::fs::read(file)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    // Any installed face, with the family its name table gives
+    fn installed() -> (Vec<u8>, String) {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let face = db
+            .faces()
+            .find(|face| face.index == 0 && !face.families.is_empty())
+            .expect("a font is installed");
+        let data = db.with_face_data(face.id, |data, _| data.to_vec()).unwrap();
+        (data, face.families[0].0.clone())
+    }
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("squish-embedded-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn subset_prefixes_are_recognised() {
+        assert!(is_subset("ABCDEF+Helvetica-Bold"));
+        assert!(!is_subset("Helvetica-Bold"));
+        assert!(!is_subset("ABCDe+Helvetica"));
+        assert_eq!(family_from_postscript("ABCDEF+Helvetica-Bold"), "Helvetica");
+        assert_eq!(family_from_postscript("Arial,Italic"), "Arial");
+        assert_eq!(family_from_postscript("-Odd"), "-Odd");
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        for format in [FontFormat::TrueType, FontFormat::Cff, FontFormat::Woff2] {
+            assert_eq!(FontFormat::parse(format.as_str()), Some(format));
+        }
+        assert_eq!(
+            FontFormat::from_mime(" Font/WOFF2 "),
+            Some(FontFormat::Woff2)
+        );
+        assert_eq!(FontFormat::from_mime("image/png"), None);
+        assert!(!FontFormat::Type1.loadable());
+        assert!(FontFormat::OpenType.loadable());
+    }
+
+    #[test]
+    fn svg_data_urls_are_extracted() {
+        let (data, family) = installed();
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><style>
+                @font-face {{ font-family: "Brand"; src: url("data:font/ttf;base64,{}"); }}
+                @font-face {{ font-family: 'Other'; src: url(data:font/woff2;base64,d29mMg==); }}
+                @font-face {{ font-family: Remote; src: url(https://example.com/a.woff2); }}
+            </style></svg>"#,
+            STANDARD.encode(&data)
+        );
+        let path = path("fonts.svg");
+        std::fs::write(&path, svg).unwrap();
+        let fonts = extract_svg(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(fonts.len(), 2);
+        assert_eq!(fonts[0].family, family);
+        assert_eq!(fonts[0].name, "Brand");
+        assert_eq!(fonts[0].data, data);
+        // Not an sfnt, so the declared family is all there is
+        assert_eq!(fonts[1].family, "Other");
+        assert_eq!(fonts[1].format, FontFormat::Woff2);
+    }
+
+    #[test]
+    fn pdf_font_programs_are_extracted() {
+        let (data, family) = installed();
+        let mut doc = Document::with_version("1.5");
+        let program = doc.add_object(Stream::new(dictionary! {}, data.clone()));
+        let cff = doc.add_object(Stream::new(dictionary! {}, b"cff".to_vec()));
+        doc.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => "ABCDEF+Brand-Bold",
+            "FontFile2" => program,
+        });
+        doc.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => "Legacy-Roman",
+            "FontFile3" => cff,
+        });
+        let path = path("fonts.pdf");
+        doc.save(&path).unwrap();
+        let mut fonts = extract_pdf(&path, &CancelToken::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        fonts.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(fonts.len(), 2);
+        assert_eq!(fonts[0].family, family);
+        assert!(fonts[0].subset);
+        assert_eq!(fonts[0].format, FontFormat::TrueType);
+        assert_eq!(fonts[1].family, "Legacy");
+        assert_eq!(fonts[1].format, FontFormat::Cff);
+        assert!(!fonts[1].subset);
+    }
+}
@@ -0,0 +1,116 @@
+// Hyphenation for paragraph layout. The TeX pattern dictionaries ship inside
+// the binary (hyphenation's embed_all), so exports hyphenate the same on every
+// machine. Each language is loaded on first use and kept for the session.
+use hyphenation::{Hyphenator, Language, Load, Standard};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::error::Error;
+
+pub const DEFAULT_LANGUAGE: &str = "en-us";
+
+const LANGUAGES: &[(&str, Language)] = &[
+    ("en-us", Language::EnglishUS),
+    ("en-gb", Language::EnglishGB),
+    ("de", Language::German1996),
+    ("de-ch", Language::GermanSwiss),
+    ("fr", Language::French),
+    ("es", Language::Spanish),
+    ("it", Language::Italian),
+    ("pt", Language::Portuguese),
+    ("nl", Language::Dutch),
+    ("da", Language::Danish),
+    ("sv", Language::Swedish),
+    ("nb", Language::NorwegianBokmal),
+    ("nn", Language::NorwegianNynorsk),
+    ("fi", Language::Finnish),
+    ("pl", Language::Polish),
+    ("cs", Language::Czech),
+    ("hu", Language::Hungarian),
+    ("tr", Language::Turkish),
+    ("ru", Language::Russian),
+    ("uk", Language::Ukrainian),
+];
+
+static DICTIONARIES: OnceLock<Mutex<HashMap<&'static str, Arc<Standard>>>> = OnceLock::new();
+
+// Takes BCP 47 tags; a region we have no dictionary for falls back to the
+// bare language, and English without one of ours (en, en-AU) to US English
+pub fn dictionary(language: &str) -> Result<Arc<Standard>, Error> {
+    let tag = language.trim().to_lowercase().replace('_', "-");
+    let tag = match tag.as_str() {
+        "en" => DEFAULT_LANGUAGE.to_string(),
+        _ => tag,
+    };
+    let (code, language) = LANGUAGES
+        .iter()
+        .find(|(code, _)| *code == tag)
+        .or_else(|| {
+            let base = match tag.split('-').next().unwrap_or_default() {
+                "en" => DEFAULT_LANGUAGE,
+                base => base,
+            };
+            LANGUAGES.iter().find(|(code, _)| *code == base)
+        })
+        .ok_or_else(|| Error::InvalidInput(format!("No hyphenation dictionary for {}", tag)))?;
+
+    let mut loaded = DICTIONARIES
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|_| Error::Lock("hyphenation dictionaries"))?;
+    if let Some(dictionary) = loaded.get(code) {
+        return Ok(dictionary.clone());
+    }
+    let dictionary = Arc::new(
+        Standard::from_embedded(*language)
+            .map_err(|e| Error::Internal(format!("Failed to load {} hyphenation: {}", code, e)))?,
+    );
+    loaded.insert(code, dictionary.clone());
+    tracing::debug!("Loaded {} hyphenation dictionary", code);
+    Ok(dictionary)
+}
+
+// Byte offsets inside `word` where it may be split with a hyphen
+pub fn points(dictionary: &Standard, word: &str) -> Vec<usize> {
+    dictionary.hyphenate(word).breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_fall_back_to_a_dictionary_we_have() {
+        for tag in ["en", "EN_us", "en-AU", "en-CA", "en-IN"] {
+            assert_eq!(
+                dictionary(tag).unwrap().language(),
+                Language::EnglishUS,
+                "{}",
+                tag
+            );
+        }
+        assert_eq!(dictionary("en-GB").unwrap().language(), Language::EnglishGB);
+        assert_eq!(
+            dictionary("de-AT").unwrap().language(),
+            Language::German1996
+        );
+        assert_eq!(
+            dictionary("de-CH").unwrap().language(),
+            Language::GermanSwiss
+        );
+    }
+
+    #[test]
+    fn unknown_languages_are_an_error() {
+        assert!(matches!(dictionary("tlh"), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn points_fall_inside_the_word() {
+        let us = dictionary(DEFAULT_LANGUAGE).unwrap();
+        let word = "hyphenation";
+        let points = points(&us, word);
+        assert!(!points.is_empty());
+        assert!(points.iter().all(|&p| p > 0 && p < word.len()));
+    }
+}
@@ -0,0 +1,564 @@
+// Paragraph layout shared by the editor and the PDF/PNG exporters, so a text
+// box wraps the same everywhere. Text is shaped with rustybuzz, broken at
+// UAX #14 opportunities (greedy, first fit), and each line is reordered by
+// the UAX #9 bidi algorithm before its runs are placed left to right.
+//
+// With hyphenation on, a word that doesn't fit is split at the last
+// dictionary hyphenation point that still fits, rather than moved whole to the
+// next line, which keeps justified lines from opening up wide gaps.
+//
+// Positions are in pixels, y down: a line's glyphs sit on its baseline, and
+// the first baseline is one ascent below the top of the box.
+use hyphenation::Standard;
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use super::hyphenate;
+use crate::blocking;
+use crate::error::Error;
+
+const DEFAULT_LINE_HEIGHT: f32 = 1.2;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontSpec {
+    pub family: String,
+    pub style: Option<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    // Left in left-to-right paragraphs, right in right-to-left ones
+    #[default]
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LayoutOptions {
+    pub align: Align,
+    // Baseline to baseline, as a multiple of the size
+    pub line_height: Option<f32>,
+    // Base direction; detected per paragraph from the first strong character
+    // when not given
+    pub rtl: Option<bool>,
+    pub hyphenate: bool,
+    // BCP 47 tag picking the hyphenation dictionary; US English by default
+    pub language: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionedGlyph {
+    pub glyph_id: u16,
+    // Byte offset in the text of the cluster this glyph belongs to
+    pub cluster: usize,
+    pub x: f32,
+    pub y: f32,
+    pub advance: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaidOutLine {
+    // Byte range of the text on this line, trailing spaces and breaks included
+    pub start: usize,
+    pub end: usize,
+    pub baseline: f32,
+    // Of the ink-carrying part, without trailing spaces
+    pub width: f32,
+    pub rtl: bool,
+    // In visual order
+    pub glyphs: Vec<PositionedGlyph>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParagraphLayout {
+    pub lines: Vec<LaidOutLine>,
+    pub width: f32,
+    pub height: f32,
+    // A word was wider than the box and sticks out of it
+    pub overflow: bool,
+}
+
+// A place a line may end, as a byte offset
+#[derive(Clone, Copy)]
+struct Break {
+    offset: usize,
+    mandatory: bool,
+}
+
+// Shapes with one face at one size; also used for text on a path
+pub(super) struct Shaper<'a> {
+    face: Face<'a>,
+    scale: f32,
+}
+
+impl<'a> Shaper<'a> {
+    pub(super) fn new(face: Face<'a>, size: f32) -> Self {
+        Shaper {
+            scale: size / face.units_per_em() as f32,
+            face,
+        }
+    }
+
+    // Glyphs of the range in visual order, x from 0 at the run's left edge
+    pub(super) fn shape(&self, text: &str, range: Range<usize>, rtl: bool) -> Vec<PositionedGlyph> {
+        if range.is_empty() {
+            return Vec::new();
+        }
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(&text[range.clone()]);
+        buffer.set_direction(if rtl {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+        let shaped = rustybuzz::shape(&self.face, &[], buffer);
+
+        let mut x = 0.0;
+        shaped
+            .glyph_infos()
+            .iter()
+            .zip(shaped.glyph_positions())
+            .map(|(info, position)| {
+                let advance = position.x_advance as f32 * self.scale;
+                let glyph = PositionedGlyph {
+                    glyph_id: info.glyph_id as u16,
+                    cluster: range.start + info.cluster as usize,
+                    x: x + position.x_offset as f32 * self.scale,
+                    y: -position.y_offset as f32 * self.scale,
+                    advance,
+                };
+                x += advance;
+                glyph
+            })
+            .collect()
+    }
+
+    pub(super) fn width(&self, text: &str, range: Range<usize>, rtl: bool) -> f32 {
+        self.shape(text, range, rtl).iter().map(|g| g.advance).sum()
+    }
+}
+
+// End of the range without trailing whitespace (including the line break)
+fn visible_end(text: &str, range: Range<usize>) -> usize {
+    range.start + text[range].trim_end().len()
+}
+
+// End of the range without the line break itself
+fn content_end(text: &str, range: Range<usize>) -> usize {
+    range.start
+        + text[range]
+            .trim_end_matches(['\n', '\r', '\u{2028}', '\u{2029}'])
+            .len()
+}
+
+fn break_opportunities(text: &str) -> Vec<Break> {
+    linebreaks(text)
+        .map(|(offset, opportunity)| Break {
+            offset,
+            mandatory: opportunity == BreakOpportunity::Mandatory,
+        })
+        .collect()
+}
+
+struct LineBreak {
+    range: Range<usize>,
+    // Last line of a paragraph, which justification leaves alone
+    ends_paragraph: bool,
+    // Ends inside a word, so it gets a hyphen
+    hyphen: bool,
+}
+
+// The latest hyphenation point in `word` whose first part still fits in
+// `available` together with the hyphen
+fn hyphen_split(
+    shaper: &Shaper,
+    dictionary: &Standard,
+    text: &str,
+    word: Range<usize>,
+    available: f32,
+    rtl: bool,
+) -> Option<usize> {
+    let hyphen = shaper.width("-", 0..1, rtl);
+    hyphenate::points(dictionary, &text[word.clone()])
+        .into_iter()
+        .rev()
+        .map(|point| word.start + point)
+        .find(|&split| shaper.width(text, word.start..split, rtl) + hyphen <= available)
+}
+
+// First fit: each line takes as many segments as fit, ending early at
+// mandatory breaks. A segment that doesn't fit is hyphenated when a
+// dictionary is given, and otherwise starts the next line.
+fn break_lines(
+    shaper: &Shaper,
+    text: &str,
+    bidi: &BidiInfo,
+    breaks: &[Break],
+    width: f32,
+    dictionary: Option<&Standard>,
+) -> Vec<LineBreak> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut line_width = 0.0;
+    let mut segment_start = 0;
+    for &Break { offset, mandatory } in breaks {
+        let segment = segment_start..offset;
+        segment_start = offset;
+        let rtl = bidi
+            .levels
+            .get(segment.start)
+            .is_some_and(|level| level.is_rtl());
+        let end = visible_end(text, segment.clone());
+        // Start of the part of the segment not placed on a line yet
+        let mut rest = segment.start;
+        while line_width + shaper.width(text, rest..end, rtl) > width {
+            let split = dictionary.and_then(|dictionary| {
+                hyphen_split(shaper, dictionary, text, rest..end, width - line_width, rtl)
+            });
+            if let Some(split) = split {
+                lines.push(LineBreak {
+                    range: line_start..split,
+                    ends_paragraph: false,
+                    hyphen: true,
+                });
+                line_start = split;
+                rest = split;
+            } else if rest > line_start {
+                lines.push(LineBreak {
+                    range: line_start..rest,
+                    ends_paragraph: false,
+                    hyphen: false,
+                });
+                line_start = rest;
+            } else {
+                // Too wide even for a line of its own
+                break;
+            }
+            line_width = 0.0;
+        }
+        line_width += shaper.width(text, rest..content_end(text, segment.clone()), rtl);
+        if mandatory {
+            lines.push(LineBreak {
+                range: line_start..offset,
+                ends_paragraph: true,
+                hyphen: false,
+            });
+            line_start = offset;
+            line_width = 0.0;
+        }
+    }
+    if line_start < text.len() {
+        lines.push(LineBreak {
+            range: line_start..text.len(),
+            ends_paragraph: true,
+            hyphen: false,
+        });
+    }
+    lines
+}
+
+fn is_space(text: &str, cluster: usize) -> bool {
+    text[cluster..]
+        .chars()
+        .next()
+        .is_some_and(char::is_whitespace)
+}
+
+pub fn layout(
+    face: Face,
+    text: &str,
+    size: f32,
+    width: f32,
+    options: &LayoutOptions,
+) -> Result<ParagraphLayout, Error> {
+    let shaper = Shaper::new(face, size);
+    let ascent = shaper.face.ascender() as f32 * shaper.scale;
+    let descent = -shaper.face.descender() as f32 * shaper.scale;
+    let line_gap = options.line_height.unwrap_or(DEFAULT_LINE_HEIGHT) * size;
+    let base_level = options
+        .rtl
+        .map(|rtl| if rtl { Level::rtl() } else { Level::ltr() });
+    let bidi = BidiInfo::new(text, base_level);
+    let dictionary = match options.hyphenate {
+        true => Some(hyphenate::dictionary(
+            options
+                .language
+                .as_deref()
+                .unwrap_or(hyphenate::DEFAULT_LANGUAGE),
+        )?),
+        false => None,
+    };
+
+    let breaks = break_opportunities(text);
+    let mut lines = Vec::new();
+    let mut overflow = false;
+    let line_breaks = break_lines(&shaper, text, &bidi, &breaks, width, dictionary.as_deref());
+    for (index, line) in line_breaks.into_iter().enumerate() {
+        let range = line.range;
+        let baseline = ascent + index as f32 * line_gap;
+        let visible = range.start..visible_end(text, range.clone());
+        let paragraph = bidi
+            .paragraphs
+            .iter()
+            .find(|p| p.range.contains(&range.start))
+            .or(bidi.paragraphs.last());
+        let rtl = paragraph.is_some_and(|p| p.level.is_rtl());
+
+        // Runs in visual order, each shaped in its own direction
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        if let (Some(paragraph), false) = (paragraph, visible.is_empty()) {
+            let (levels, runs) = bidi.visual_runs(paragraph, visible.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                for mut glyph in shaper.shape(text, run, rtl) {
+                    glyph.x += x;
+                    glyph.y += baseline;
+                    x += glyph.advance;
+                    glyphs.push(glyph);
+                }
+            }
+        }
+        // The hyphen goes at the end the text was cut, the left in RTL lines
+        if line.hyphen {
+            let mut hyphen = shaper.shape("-", 0..1, rtl);
+            let hyphen_width: f32 = hyphen.iter().map(|g| g.advance).sum();
+            for glyph in &mut hyphen {
+                glyph.cluster = range.end;
+                glyph.y += baseline;
+            }
+            if rtl {
+                for glyph in &mut glyphs {
+                    glyph.x += hyphen_width;
+                }
+                glyphs.splice(0..0, hyphen);
+            } else {
+                for glyph in &mut hyphen {
+                    glyph.x += x;
+                }
+                glyphs.extend(hyphen);
+            }
+            x += hyphen_width;
+        }
+        let line_width = x;
+        overflow |= line_width > width + 0.01;
+
+        let slack = (width - line_width).max(0.0);
+        let align = match options.align {
+            Align::Justify if line.ends_paragraph => Align::Start,
+            align => align,
+        };
+        let offset = match align {
+            Align::Left => 0.0,
+            Align::Right => slack,
+            Align::Center => slack / 2.0,
+            Align::Start | Align::Justify if !rtl => 0.0,
+            Align::End if rtl => 0.0,
+            Align::Start | Align::End | Align::Justify => slack,
+        };
+        let spaces = glyphs.iter().filter(|g| is_space(text, g.cluster)).count();
+        let extra = if align == Align::Justify && spaces > 0 {
+            slack / spaces as f32
+        } else {
+            0.0
+        };
+        let mut shift = if extra > 0.0 { 0.0 } else { offset };
+        for glyph in &mut glyphs {
+            glyph.x += shift;
+            if extra > 0.0 && is_space(text, glyph.cluster) {
+                glyph.advance += extra;
+                shift += extra;
+            }
+        }
+
+        lines.push(LaidOutLine {
+            start: range.start,
+            end: range.end,
+            baseline,
+            width: line_width + if extra > 0.0 { slack } else { 0.0 },
+            rtl,
+            glyphs,
+        });
+    }
+
+    let height = match lines.len() {
+        0 => 0.0,
+        count => ascent + (count - 1) as f32 * line_gap + descent,
+    };
+    Ok(ParagraphLayout {
+        width: lines.iter().map(|l| l.width).fold(0.0, f32::max),
+        lines,
+        height,
+        overflow,
+    })
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn layout_paragraph(
+    text: String,
+    font: FontSpec,
+    size: f32,
+    width: f32,
+    options: Option<LayoutOptions>,
+) -> Result<ParagraphLayout, Error> {
+    if !size.is_finite() || size <= 0.0 {
+        return Err(Error::InvalidInput(format!("Invalid font size {}", size)));
+    }
+    if !width.is_finite() || width <= 0.0 {
+        return Err(Error::InvalidInput(format!("Invalid box width {}", width)));
+    }
+    blocking::run(move || {
+        let (data, index) = super::face_data(&font.family, font.style.as_deref())?;
+        let face = Face::from_slice(&data, index)
+            .ok_or_else(|| Error::UnsupportedFormat(format!("Failed to parse {}", font.family)))?;
+        layout(face, &text, size, width, &options.unwrap_or_default())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resvg::usvg::fontdb;
+
+    // Any installed sans-serif face; widths are measured with it rather than
+    // assumed, so the font doesn't matter
+    fn sans() -> (Vec<u8>, u32) {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let id = db
+            .query(&fontdb::Query {
+                families: &[fontdb::Family::SansSerif],
+                ..Default::default()
+            })
+            .or_else(|| db.faces().next().map(|face| face.id))
+            .expect("a font is installed");
+        db.with_face_data(id, |data, index| (data.to_vec(), index))
+            .unwrap()
+    }
+
+    fn width_of(face: &Face, text: &str) -> f32 {
+        Shaper::new(face.clone(), 20.0).width(text, 0..text.len(), false)
+    }
+
+    fn line_texts<'a>(text: &'a str, layout: &ParagraphLayout) -> Vec<&'a str> {
+        layout.lines.iter().map(|l| &text[l.start..l.end]).collect()
+    }
+
+    #[test]
+    fn only_line_breaks_are_mandatory() {
+        let breaks = break_opportunities("one two\nthree");
+        let found: Vec<(usize, bool)> = breaks.iter().map(|b| (b.offset, b.mandatory)).collect();
+        assert_eq!(found, [(4, false), (8, true), (13, true)]);
+    }
+
+    #[test]
+    fn trailing_space_and_breaks_are_trimmed() {
+        let text = "word  \nnext";
+        assert_eq!(visible_end(text, 0..7), 4);
+        assert_eq!(content_end(text, 0..7), 6);
+    }
+
+    #[test]
+    fn words_wrap_at_the_first_that_doesnt_fit() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let text = "one two three";
+        let width = width_of(&face, "one two") + 1.0;
+        let layout = layout(face, text, 20.0, width, &LayoutOptions::default()).unwrap();
+        assert_eq!(line_texts(text, &layout), ["one two ", "three"]);
+        assert!(!layout.overflow);
+        assert!(layout.lines[1].baseline > layout.lines[0].baseline);
+    }
+
+    #[test]
+    fn a_line_break_starts_a_new_line() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let text = "one\ntwo";
+        let layout = layout(face, text, 20.0, 1000.0, &LayoutOptions::default()).unwrap();
+        assert_eq!(line_texts(text, &layout), ["one\n", "two"]);
+    }
+
+    #[test]
+    fn a_word_wider_than_the_box_overflows() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let text = "extraordinarily";
+        let width = width_of(&face, "extra");
+        let layout = layout(face, text, 20.0, width, &LayoutOptions::default()).unwrap();
+        assert_eq!(layout.lines.len(), 1);
+        assert!(layout.overflow);
+    }
+
+    #[test]
+    fn a_word_that_doesnt_fit_is_hyphenated() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let text = "the hyphenation";
+        let width = width_of(&face, "the hyphen-") + 1.0;
+        let options = LayoutOptions {
+            hyphenate: true,
+            ..Default::default()
+        };
+        let layout = layout(face, text, 20.0, width, &options).unwrap();
+        assert_eq!(line_texts(text, &layout), ["the hyphen", "ation"]);
+        let first = &layout.lines[0];
+        // The hyphen glyph sits at the cut
+        assert_eq!(first.glyphs.last().unwrap().cluster, first.end);
+        assert!(!layout.overflow);
+    }
+
+    #[test]
+    fn justified_lines_fill_the_box_except_the_last() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let text = "a b c d e f g h";
+        let width = width_of(&face, "a b c d") + 5.0;
+        let options = LayoutOptions {
+            align: Align::Justify,
+            ..Default::default()
+        };
+        let layout = layout(face, text, 20.0, width, &options).unwrap();
+        let (last, full) = layout.lines.split_last().unwrap();
+        assert!(!full.is_empty());
+        for line in full {
+            assert!(
+                (line.width - width).abs() < 0.01,
+                "{} != {}",
+                line.width,
+                width
+            );
+        }
+        assert!(last.width < width);
+    }
+
+    #[test]
+    fn centered_lines_split_the_slack() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let options = LayoutOptions {
+            align: Align::Center,
+            ..Default::default()
+        };
+        let layout = layout(face, "hi", 20.0, 200.0, &options).unwrap();
+        let line = &layout.lines[0];
+        let left = line.glyphs[0].x;
+        assert!((left - (200.0 - line.width) / 2.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+use ttf_parser::{name_id, Face, Permissions};
+
+use crate::blocking;
+use crate::error::Error;
+
+// Windows English (US), the name records most fonts fill in first
+const ENGLISH_US: u16 = 0x0409;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Embedding {
+    Installable,
+    // The vendor's permission is needed to embed it at all
+    Restricted,
+    // Embedded documents may only be viewed and printed
+    PreviewAndPrint,
+    Editable,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontLicenseInfo {
+    pub family: String,
+    pub license: Option<String>,
+    pub license_url: Option<String>,
+    pub description: Option<String>,
+    pub designer: Option<String>,
+    pub designer_url: Option<String>,
+    pub vendor_url: Option<String>,
+    pub copyright: Option<String>,
+    pub trademark: Option<String>,
+    // From the OS/2 table; None when the font doesn't say
+    pub embedding: Option<Embedding>,
+    pub subsetting_allowed: bool,
+}
+
+// Prefers English, then whatever readable record there is
+fn name(face: &Face, id: u16) -> Option<String> {
+    let mut fallback = None;
+    for record in face.names().into_iter().filter(|n| n.name_id == id) {
+        let Some(text) = record.to_string().filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        if record.language_id == ENGLISH_US {
+            return Some(text.trim().to_string());
+        }
+        fallback.get_or_insert_with(|| text.trim().to_string());
+    }
+    fallback
+}
+
+pub fn license_info(face: &Face, family: String) -> FontLicenseInfo {
+    let os2 = face.tables().os2;
+    FontLicenseInfo {
+        family,
+        license: name(face, name_id::LICENSE),
+        license_url: name(face, name_id::LICENSE_URL),
+        description: name(face, name_id::DESCRIPTION),
+        designer: name(face, name_id::DESIGNER),
+        designer_url: name(face, name_id::DESIGNER_URL),
+        vendor_url: name(face, name_id::VENDOR_URL),
+        copyright: name(face, name_id::COPYRIGHT_NOTICE),
+        trademark: name(face, name_id::TRADEMARK),
+        embedding: os2.and_then(|os2| os2.permissions()).map(|p| match p {
+            Permissions::Installable => Embedding::Installable,
+            Permissions::Restricted => Embedding::Restricted,
+            Permissions::PreviewAndPrint => Embedding::PreviewAndPrint,
+            Permissions::Editable => Embedding::Editable,
+        }),
+        subsetting_allowed: os2.is_none_or(|os2| os2.is_subsetting_allowed()),
+    }
+}
+
+// What the font's own tables say about usage rights. It's what the vendor
+// wrote, not legal advice, and many fonts leave it blank.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_font_license_info(
+    family: String,
+    style: Option<String>,
+) -> Result<FontLicenseInfo, Error> {
+    blocking::run(move || {
+        let (data, index) = super::face_data(&family, style.as_deref())?;
+        let face = Face::parse(&data, index)
+            .map_err(|e| Error::UnsupportedFormat(format!("Failed to parse {}: {}", family, e)))?;
+        Ok(license_info(&face, family))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resvg::usvg::fontdb;
+
+    fn installed() -> (Vec<u8>, u32) {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let id = db.faces().next().expect("a font is installed").id;
+        db.with_face_data(id, |data, index| (data.to_vec(), index))
+            .unwrap()
+    }
+
+    #[test]
+    fn info_matches_the_font_tables() {
+        let (data, index) = installed();
+        let face = Face::parse(&data, index).unwrap();
+        let info = license_info(&face, "Sample".to_string());
+        assert_eq!(info.family, "Sample");
+        assert_eq!(info.copyright, name(&face, name_id::COPYRIGHT_NOTICE));
+        match face.tables().os2 {
+            Some(os2) => assert_eq!(info.subsetting_allowed, os2.is_subsetting_allowed()),
+            None => assert!(info.subsetting_allowed && info.embedding.is_none()),
+        }
+    }
+
+    #[test]
+    fn names_are_trimmed_and_blank_ones_skipped() {
+        let (data, index) = installed();
+        let face = Face::parse(&data, index).unwrap();
+        let family = name(&face, name_id::FAMILY).expect("fonts name their family");
+        assert_eq!(family, family.trim());
+        assert!(!family.is_empty());
+        // No font uses this id
+        assert_eq!(name(&face, 0x7fff), None);
+    }
+}
@@ -0,0 +1,411 @@
+use font_kit::family_name::FamilyName;
+use font_kit::font::Font;
+use font_kit::handle::Handle;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use image::DynamicImage;
+use resvg::usvg::{self, fontdb};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::blocking;
+use crate::error::Error;
+use crate::imaging::render_svg;
+use crate::perf;
+use crate::settings::{self, SettingsState};
+
+mod classify;
+pub mod embedded;
+mod hyphenate;
+mod layout;
+mod license;
+mod outline;
+mod pairing;
+mod stack;
+mod strikes;
+mod text_path;
+
+pub use embedded::{list_project_fonts, read_project_font};
+pub use layout::layout_paragraph;
+pub use license::get_font_license_info;
+pub use outline::get_glyph_outline;
+pub use pairing::suggest_font_pairings;
+pub use stack::get_font_stack;
+pub use strikes::get_bitmap_strikes;
+pub use text_path::layout_text_on_path;
+
+const HIDDEN_KEY: &str = "hidden_problem_fonts";
+
+// A font file that is installed but couldn't be loaded
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemFont {
+    // None for fonts the system only hands over in memory
+    pub path: Option<String>,
+    // Face within a collection
+    pub font_index: u32,
+    pub reason: String,
+    // Dismissed by the user; still reported so it can be shown again
+    pub hidden: bool,
+}
+
+#[derive(Default)]
+pub struct FontCatalog {
+    pub families: Vec<String>,
+    pub problems: Vec<ProblemFont>,
+    pub loaded: bool,
+    // Filled in as pairing suggestions need them; None for families that
+    // can't be loaded
+    profiles: HashMap<String, Option<classify::Profile>>,
+}
+
+// Store fonts in app state with a loaded flag
+pub struct FontState(pub(crate) Mutex<FontCatalog>);
+
+// Runs `f` on the font catalog, scanning the system fonts first if needed
+async fn with_catalog<T>(app: &AppHandle, f: impl Fn(&FontCatalog) -> T) -> Result<T, Error> {
+    let state = app.state::<FontState>();
+    {
+        let state_guard = state.0.lock().map_err(|_| Error::Lock("font state"))?;
+        perf::cache("system_fonts", state_guard.loaded);
+        if state_guard.loaded {
+            tracing::debug!("Using cached system fonts");
+            return Ok(f(&state_guard));
+        }
+    }
+
+    // Loading every installed font takes seconds on a well-stocked system, so
+    // it runs off the IPC thread. Two racing first requests both load; the
+    // results are the same.
+    tracing::debug!("Loading system fonts on first request...");
+    let (families, problems) = blocking::run(|| Ok::<_, Error>(initialize_fonts())).await?;
+
+    let mut state_guard = state.0.lock().map_err(|_| Error::Lock("font state"))?;
+    *state_guard = FontCatalog {
+        families,
+        problems,
+        loaded: true,
+        ..FontCatalog::default()
+    };
+    Ok(f(&state_guard))
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_system_fonts(app: AppHandle) -> Result<Vec<String>, Error> {
+    with_catalog(&app, |catalog| catalog.families.clone()).await
+}
+
+// Installed fonts that failed to load, with why
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_problem_fonts(app: AppHandle) -> Result<Vec<ProblemFont>, Error> {
+    let hidden = app.state::<SettingsState>().snapshot().hidden_problem_fonts;
+    with_catalog(&app, |catalog| {
+        catalog
+            .problems
+            .iter()
+            .map(|problem| ProblemFont {
+                hidden: problem.path.as_ref().is_some_and(|p| hidden.contains(p)),
+                ..problem.clone()
+            })
+            .collect()
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn set_problem_font_hidden(app: AppHandle, path: String, hidden: bool) -> Result<(), Error> {
+    let mut paths = app.state::<SettingsState>().snapshot().hidden_problem_fonts;
+    paths.retain(|existing| *existing != path);
+    if hidden {
+        paths.push(path);
+    }
+    Ok(settings::write(&app, HIDDEN_KEY, &paths)?)
+}
+
+// Shows a broken font in Finder / Explorer so the user can remove or replace
+// it. Only paths from the last scan are accepted.
+#[tauri::command]
+pub fn reveal_problem_font(app: AppHandle, path: String) -> Result<(), Error> {
+    let known = {
+        let state = app.state::<FontState>();
+        let catalog = state.0.lock().map_err(|_| Error::Lock("font state"))?;
+        catalog
+            .problems
+            .iter()
+            .any(|problem| problem.path.as_deref() == Some(path.as_str()))
+    };
+    if !known {
+        return Err(Error::NotFound(format!(
+            "{} isn't a known problem font",
+            path
+        )));
+    }
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| Error::Internal(format!("Failed to reveal {}: {}", path, e)))
+}
+
+// Weights by the names styles use for them; compound names come first so
+// "extrabold" isn't taken for "bold"
+const WEIGHTS: &[(&str, f32)] = &[
+    ("extralight", 200.0),
+    ("ultralight", 200.0),
+    ("extrabold", 800.0),
+    ("ultrabold", 800.0),
+    ("semibold", 600.0),
+    ("demibold", 600.0),
+    ("thin", 100.0),
+    ("light", 300.0),
+    ("medium", 500.0),
+    ("bold", 700.0),
+    ("black", 900.0),
+    ("heavy", 900.0),
+];
+
+// Weight and slant from a style name like "Bold Italic" or "semi-bold"
+fn properties(style: Option<&str>) -> Properties {
+    let mut properties = Properties::new();
+    let Some(style) = style else {
+        return properties;
+    };
+    let style: String = style
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    if let Some((_, weight)) = WEIGHTS.iter().find(|(name, _)| style.contains(name)) {
+        properties.weight = Weight(*weight);
+    }
+    if style.contains("italic") {
+        properties.style = Style::Italic;
+    } else if style.contains("oblique") {
+        properties.style = Style::Oblique;
+    }
+    properties
+}
+
+fn select(family: &str, style: Option<&str>) -> Result<Handle, Error> {
+    SystemSource::new()
+        .select_best_match(&[FamilyName::Title(family.to_string())], &properties(style))
+        .map_err(|_| Error::NotFound(format!("Font {} isn't installed", family)))
+}
+
+// The installed face that best matches a family, at regular weight and style
+pub fn find_font(family: &str) -> Result<Font, Error> {
+    select(family, None)?
+        .load()
+        .map_err(|e| Error::UnsupportedFormat(format!("Failed to load font {}: {}", family, e)))
+}
+
+// The file of the face closest to the style, and its index in a collection,
+// for parsing with ttf-parser
+pub fn face_data(family: &str, style: Option<&str>) -> Result<(Arc<Vec<u8>>, u32), Error> {
+    match select(family, style)? {
+        Handle::Path { path, font_index } => Ok((Arc::new(std::fs::read(&path)?), font_index)),
+        Handle::Memory { bytes, font_index } => Ok((bytes, font_index)),
+    }
+}
+
+pub fn initialize_empty_state() -> FontCatalog {
+    FontCatalog::default()
+}
+
+pub fn cache_bytes(app: &AppHandle) -> u64 {
+    let state = app.state::<FontState>();
+    let Ok(guard) = state.0.lock() else {
+        return 0;
+    };
+    guard
+        .families
+        .iter()
+        .map(|name| (name.capacity() + std::mem::size_of::<String>()) as u64)
+        .sum()
+}
+
+// Frees the font list; the next get_system_fonts scans again
+pub fn clear_cache(app: &AppHandle) -> u64 {
+    let freed = cache_bytes(app);
+    let state = app.state::<FontState>();
+    if let Ok(mut guard) = state.0.lock() {
+        *guard = initialize_empty_state();
+    }
+    freed
+}
+
+const SPECIMEN_LINES: &[(u32, &str)] = &[
+    (72, "Aa Bb Cc Dd Ee Ff Gg"),
+    (32, "The quick brown fox jumps over the lazy dog"),
+    (32, "0123456789 !?&@#%"),
+];
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Renders a specimen of a font file (its first face, for collections) and
+// returns it with the family name. The font is only loaded for this render,
+// never installed.
+pub fn specimen(path: &Path) -> Result<(DynamicImage, String), String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_font_data(data);
+    let face = options
+        .fontdb
+        .faces()
+        .next()
+        .ok_or_else(|| format!("{} isn't a font Squish can read", path.display()))?;
+    let family = face
+        .families
+        .first()
+        .map(|(name, _)| name.clone())
+        .unwrap_or_else(|| face.post_script_name.clone());
+    let style = if face.style == fontdb::Style::Normal {
+        "normal"
+    } else {
+        "italic"
+    };
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="400"><rect width="100%" height="100%" fill="white"/><g font-family="{}" font-weight="{}" font-style="{}" fill="black">"#,
+        escape_xml(&family),
+        face.weight.0,
+        style
+    );
+    let mut y = 40;
+    for (size, text) in [(24, family.as_str())].iter().chain(SPECIMEN_LINES) {
+        y += size + 24;
+        svg.push_str(&format!(
+            r#"<text x="40" y="{}" font-size="{}">{}</text>"#,
+            y,
+            size,
+            escape_xml(text)
+        ));
+    }
+    svg.push_str("</g></svg>");
+    Ok((render_svg(&svg, &options)?, family))
+}
+
+fn initialize_fonts() -> (Vec<String>, Vec<ProblemFont>) {
+    tracing::info!("Loading system fonts...");
+    let source = SystemSource::new();
+
+    let fallback_fonts = vec![
+        "Arial".to_string(),
+        "Times New Roman".to_string(),
+        "Helvetica".to_string(),
+        "Courier New".to_string(),
+        "Georgia".to_string(),
+        "Verdana".to_string(),
+        "Inter".to_string(),
+    ];
+
+    match source.all_fonts() {
+        Ok(fonts) => {
+            tracing::debug!("Found {} raw font handles", fonts.len());
+            let mut font_names: Vec<String> = Vec::new();
+            let mut problems = Vec::new();
+
+            // Process each font handle
+            for handle in fonts.iter() {
+                match handle.load() {
+                    Ok(font) => {
+                        let name = font.family_name().to_string();
+                        // Only add valid font names (non-empty and contains valid characters)
+                        if !name.is_empty()
+                            && name.chars().all(|c| c.is_ascii() || c.is_alphabetic())
+                        {
+                            font_names.push(name);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!("Skipping invalid font: {:?}", e);
+                        let (path, font_index) = match handle {
+                            Handle::Path { path, font_index } => {
+                                (Some(path.to_string_lossy().to_string()), *font_index)
+                            }
+                            Handle::Memory { font_index, .. } => (None, *font_index),
+                        };
+                        problems.push(ProblemFont {
+                            path,
+                            font_index,
+                            reason: e.to_string(),
+                            hidden: false,
+                        });
+                    }
+                }
+            }
+
+            if font_names.is_empty() {
+                tracing::debug!("No valid system fonts found, using fallbacks");
+                return (fallback_fonts, problems);
+            }
+
+            tracing::debug!("Collected {} valid font names", font_names.len());
+            if !problems.is_empty() {
+                tracing::warn!("{} installed font(s) failed to load", problems.len());
+            }
+            font_names.sort();
+            font_names.dedup();
+            tracing::debug!("After deduplication: {} unique fonts", font_names.len());
+
+            // Ensure common fonts are available
+            for fallback in fallback_fonts {
+                if !font_names.contains(&fallback) {
+                    font_names.push(fallback);
+                }
+            }
+
+            font_names.sort();
+            (font_names, problems)
+        }
+        Err(e) => {
+            tracing::warn!("Error loading system fonts: {:?}", e);
+            tracing::info!("Using fallback fonts");
+            (fallback_fonts, Vec::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specimen_text_is_escaped() {
+        assert_eq!(
+            escape_xml(r#"Tom & "Jerry" <Bold>"#),
+            "Tom &amp; &quot;Jerry&quot; &lt;Bold&gt;"
+        );
+    }
+
+    #[test]
+    fn a_specimen_names_the_family() {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let face = db
+            .faces()
+            .find(|face| face.index == 0 && !face.families.is_empty())
+            .expect("a font is installed");
+        let data = db.with_face_data(face.id, |data, _| data.to_vec()).unwrap();
+        let path = std::env::temp_dir().join(format!("squish-specimen-{}.ttf", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+
+        let (image, family) = specimen(&path).unwrap();
+        assert_eq!(family, face.families[0].0);
+        assert_eq!((image.width(), image.height()), (1200, 400));
+
+        std::fs::write(&path, b"not a font").unwrap();
+        assert!(specimen(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
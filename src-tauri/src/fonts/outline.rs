@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::fmt::Write;
+use ttf_parser::{Face, OutlineBuilder};
+
+use crate::blocking;
+use crate::error::Error;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlyphBounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// In pixels at the requested size, y down, origin on the baseline at the
+// glyph's left side bearing origin
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlyphOutline {
+    pub glyph_id: u16,
+    // SVG path data; empty for glyphs without ink, like a space
+    pub path: String,
+    pub advance: f32,
+    pub bounds: Option<GlyphBounds>,
+}
+
+// Two decimals is well under a device pixel at any size we render
+fn number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+// Writes the outline as path data, scaling font units to pixels and flipping y
+struct PathData {
+    data: String,
+    scale: f32,
+}
+
+impl PathData {
+    fn point(&mut self, x: f32, y: f32) {
+        let _ = write!(
+            self.data,
+            "{} {}",
+            number(x * self.scale),
+            number(-y * self.scale)
+        );
+    }
+}
+
+impl OutlineBuilder for PathData {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.data.push('M');
+        self.point(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.data.push('L');
+        self.point(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.data.push('Q');
+        self.point(x1, y1);
+        self.data.push(' ');
+        self.point(x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.data.push('C');
+        self.point(x1, y1);
+        self.data.push(' ');
+        self.point(x2, y2);
+        self.data.push(' ');
+        self.point(x, y);
+    }
+
+    fn close(&mut self) {
+        self.data.push('Z');
+    }
+}
+
+pub fn glyph_outline(face: &Face, codepoint: u32, size: f32) -> Result<GlyphOutline, Error> {
+    let character = char::from_u32(codepoint)
+        .ok_or_else(|| Error::InvalidInput(format!("U+{:04X} isn't a character", codepoint)))?;
+    let glyph = face
+        .glyph_index(character)
+        .ok_or_else(|| Error::NotFound(format!("The font has no glyph for U+{:04X}", codepoint)))?;
+    let scale = size / face.units_per_em() as f32;
+
+    let mut path = PathData {
+        data: String::new(),
+        scale,
+    };
+    let bounds = face
+        .outline_glyph(glyph, &mut path)
+        .map(|rect| GlyphBounds {
+            x: rect.x_min as f32 * scale,
+            y: -(rect.y_max as f32) * scale,
+            width: rect.width() as f32 * scale,
+            height: rect.height() as f32 * scale,
+        });
+    Ok(GlyphOutline {
+        glyph_id: glyph.0,
+        path: path.data,
+        advance: face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale,
+        bounds,
+    })
+}
+
+// For converting text to outlines in the editor. Bitmap-only glyphs (most
+// emoji) have no outline and come back with an empty path.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_glyph_outline(
+    family: String,
+    style: Option<String>,
+    codepoint: u32,
+    size: f32,
+) -> Result<GlyphOutline, Error> {
+    if !size.is_finite() || size <= 0.0 {
+        return Err(Error::InvalidInput(format!("Invalid font size {}", size)));
+    }
+    blocking::run(move || {
+        let (data, index) = super::face_data(&family, style.as_deref())?;
+        let face = Face::parse(&data, index)
+            .map_err(|e| Error::UnsupportedFormat(format!("Failed to parse {}: {}", family, e)))?;
+        glyph_outline(&face, codepoint, size)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resvg::usvg::fontdb;
+
+    fn sans() -> (Vec<u8>, u32) {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let id = db
+            .query(&fontdb::Query {
+                families: &[fontdb::Family::SansSerif],
+                ..Default::default()
+            })
+            .or_else(|| db.faces().next().map(|face| face.id))
+            .expect("a font is installed");
+        db.with_face_data(id, |data, index| (data.to_vec(), index))
+            .unwrap()
+    }
+
+    #[test]
+    fn numbers_are_short() {
+        assert_eq!(number(12.0), "12");
+        assert_eq!(number(1.5), "1.5");
+        assert_eq!(number(0.126), "0.13");
+        assert_eq!(number(-0.001), "0");
+    }
+
+    #[test]
+    fn outlines_are_closed_paths_above_the_baseline() {
+        let (data, index) = sans();
+        let face = Face::parse(&data, index).unwrap();
+        let outline = glyph_outline(&face, 'H' as u32, 100.0).unwrap();
+        assert!(outline.path.starts_with('M') && outline.path.ends_with('Z'));
+        let bounds = outline.bounds.unwrap();
+        // y points down, so a capital sits at negative y
+        assert!(bounds.y < -50.0 && bounds.height > 50.0);
+        assert!(outline.advance > bounds.width);
+    }
+
+    #[test]
+    fn a_space_has_no_ink() {
+        let (data, index) = sans();
+        let face = Face::parse(&data, index).unwrap();
+        let outline = glyph_outline(&face, ' ' as u32, 100.0).unwrap();
+        assert!(outline.path.is_empty() && outline.bounds.is_none());
+        assert!(outline.advance > 0.0);
+        assert!(matches!(
+            glyph_outline(&face, 0xD800, 100.0),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+// Heading/body pairs for a family: the curated table from the database first,
+// then installed fonts of a contrasting category, ranked by how close their
+// x-heights are (pairs with similar x-heights sit well together on a line
+// and across a page).
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
+use tauri::{AppHandle, Manager};
+
+use super::classify::{self, Category, Profile};
+use super::FontState;
+use crate::blocking;
+use crate::db::Db;
+use crate::error::Error;
+
+const MAX_SUGGESTIONS: usize = 8;
+// x-height difference (fraction of the em) at which similarity reaches zero
+const X_HEIGHT_TOLERANCE: f32 = 0.15;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontPairing {
+    pub heading: String,
+    pub body: String,
+    pub curated: bool,
+    // 0 to 1; curated pairs are 1
+    pub score: f32,
+    pub note: Option<String>,
+    // Both families are installed
+    pub installed: bool,
+}
+
+// Whether the family leads (heading) and which categories partner it
+fn partners(category: Category) -> (bool, &'static [Category]) {
+    match category {
+        Category::Serif => (true, &[Category::SansSerif]),
+        Category::Display | Category::Script => (true, &[Category::SansSerif, Category::Serif]),
+        Category::Monospace => (true, &[Category::SansSerif]),
+        Category::SansSerif => (false, &[Category::Serif, Category::Display]),
+        Category::Symbol => (true, &[]),
+    }
+}
+
+// Profiles are cached on the font catalog, so only the first suggestion pays
+// for loading every installed family
+fn profile(app: &AppHandle, family: &str) -> Option<Profile> {
+    let state = app.state::<FontState>();
+    if let Ok(catalog) = state.0.lock() {
+        if let Some(profile) = catalog.profiles.get(family) {
+            return *profile;
+        }
+    }
+    let profile = super::find_font(family).ok().map(|f| classify::profile(&f));
+    if let Ok(mut catalog) = state.0.lock() {
+        catalog.profiles.insert(family.to_string(), profile);
+    }
+    profile
+}
+
+fn curated(
+    conn: &Connection,
+    family: &str,
+) -> Result<Vec<(String, String, Option<String>)>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT heading, body, note FROM font_pairings
+         WHERE heading = ?1 OR body = ?1 ORDER BY rowid",
+    )?;
+    let rows = stmt.query_map([family], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn similarity(a: Option<f32>, b: Option<f32>) -> f32 {
+    match (a, b) {
+        (Some(a), Some(b)) => 1.0 - ((a - b).abs() / X_HEIGHT_TOLERANCE).min(1.0),
+        // Unknown metrics neither help nor disqualify
+        _ => 0.5,
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn suggest_font_pairings(
+    app: AppHandle,
+    family: String,
+) -> Result<Vec<FontPairing>, Error> {
+    let installed = super::with_catalog(&app, |catalog| catalog.families.clone()).await?;
+    blocking::run(move || {
+        let is_installed = |name: &str| installed.iter().any(|f| f.eq_ignore_ascii_case(name));
+        let rows = {
+            let db = app.state::<Db>();
+            let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+            curated(&conn, &family)?
+        };
+        let mut seen = HashSet::new();
+        let mut pairings: Vec<FontPairing> = rows
+            .into_iter()
+            .map(|(heading, body, note)| {
+                seen.insert((heading.to_lowercase(), body.to_lowercase()));
+                FontPairing {
+                    installed: is_installed(&heading) && is_installed(&body),
+                    heading,
+                    body,
+                    curated: true,
+                    score: 1.0,
+                    note,
+                }
+            })
+            .collect();
+
+        let Some(own) = profile(&app, &family) else {
+            // Not installed: nothing to measure against
+            return Ok(pairings);
+        };
+        let (leads, categories) = partners(own.category);
+        let mut suggested: Vec<FontPairing> = installed
+            .iter()
+            .filter(|candidate| !candidate.eq_ignore_ascii_case(&family))
+            .filter_map(|candidate| {
+                let other = profile(&app, candidate)?;
+                if !categories.contains(&other.category) {
+                    return None;
+                }
+                let (heading, body) = if leads {
+                    (family.clone(), candidate.clone())
+                } else {
+                    (candidate.clone(), family.clone())
+                };
+                if seen.contains(&(heading.to_lowercase(), body.to_lowercase())) {
+                    return None;
+                }
+                Some(FontPairing {
+                    heading,
+                    body,
+                    curated: false,
+                    score: similarity(own.x_height, other.x_height),
+                    note: None,
+                    installed: true,
+                })
+            })
+            .collect();
+        suggested.sort_by(|a, b| b.score.total_cmp(&a.score));
+        suggested.truncate(MAX_SUGGESTIONS);
+        pairings.extend(suggested);
+        Ok(pairings)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closer_x_heights_score_higher() {
+        assert_eq!(similarity(Some(0.5), Some(0.5)), 1.0);
+        assert!(similarity(Some(0.5), Some(0.55)) > similarity(Some(0.5), Some(0.6)));
+        assert_eq!(similarity(Some(0.3), Some(0.6)), 0.0);
+        assert_eq!(similarity(None, Some(0.5)), 0.5);
+    }
+
+    #[test]
+    fn partners_are_a_contrasting_category() {
+        for category in [
+            Category::Serif,
+            Category::SansSerif,
+            Category::Display,
+            Category::Script,
+            Category::Monospace,
+        ] {
+            let (_, others) = partners(category);
+            assert!(!others.is_empty() && !others.contains(&category));
+        }
+        // Sans bodies sit under a serif heading, not the other way round
+        assert!(partners(Category::Serif).0);
+        assert!(!partners(Category::SansSerif).0);
+    }
+
+    #[test]
+    fn curated_pairs_match_either_role_in_any_case() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        let pairs = curated(&conn, "merriweather").unwrap();
+        assert!(pairs.len() >= 2);
+        assert!(pairs.iter().all(|(_, body, _)| body == "Merriweather"));
+        assert!(curated(&conn, "Lato")
+            .unwrap()
+            .iter()
+            .any(|(heading, _, _)| heading == "Lato"));
+        assert!(curated(&conn, "No Such Family").unwrap().is_empty());
+    }
+}
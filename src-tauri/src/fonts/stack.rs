@@ -0,0 +1,134 @@
+use serde::Serialize;
+
+use super::classify::{self, Category};
+use crate::blocking;
+use crate::error::Error;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontStack {
+    pub family: String,
+    pub category: Category,
+    // Whether the category came from the installed font rather than its name
+    pub installed: bool,
+    // The family, its fallbacks and the generic family, in order
+    pub stack: Vec<String>,
+    // Ready for a font-family declaration
+    pub css: String,
+}
+
+const GENERIC: &[&str] = &["serif", "sans-serif", "monospace", "cursive", "fantasy"];
+
+// Fonts shipped with (nearly) every Mac and Windows install, then the generic
+fn fallbacks(category: Category) -> (&'static [&'static str], &'static str) {
+    match category {
+        Category::Serif => (&["Georgia", "Times New Roman", "Times"], "serif"),
+        Category::SansSerif => (&["Helvetica Neue", "Helvetica", "Arial"], "sans-serif"),
+        Category::Monospace => (&["Menlo", "Consolas", "Courier New"], "monospace"),
+        Category::Script => (&["Brush Script MT", "Segoe Script"], "cursive"),
+        Category::Display => (&["Impact", "Arial Black"], "sans-serif"),
+        Category::Symbol => (&["Apple Symbols", "Segoe UI Symbol"], "sans-serif"),
+    }
+}
+
+fn css_name(name: &str) -> String {
+    if GENERIC.contains(&name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+pub fn font_stack(family: &str) -> FontStack {
+    let font = super::find_font(family).ok();
+    let category = font
+        .as_ref()
+        .map(classify::classify)
+        .unwrap_or_else(|| classify::from_name(family));
+    let (fallbacks, generic) = fallbacks(category);
+
+    let mut stack = vec![family.to_string()];
+    for fallback in fallbacks {
+        if !fallback.eq_ignore_ascii_case(family) {
+            stack.push(fallback.to_string());
+        }
+    }
+    stack.push(generic.to_string());
+    let css = stack
+        .iter()
+        .map(|name| css_name(name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    FontStack {
+        family: family.to_string(),
+        category,
+        installed: font.is_some(),
+        stack,
+        css,
+    }
+}
+
+// Fallbacks for web exports, so text still renders in a similar face where
+// the chosen family isn't available
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_font_stack(family: String) -> Result<FontStack, Error> {
+    if family.trim().is_empty() {
+        return Err(Error::InvalidInput("No font family given".to_string()));
+    }
+    blocking::run(move || Ok(font_stack(family.trim()))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_quoted_but_generics_are_not() {
+        assert_eq!(css_name("serif"), "serif");
+        assert_eq!(css_name("Times New Roman"), "\"Times New Roman\"");
+        assert_eq!(css_name("Odd \"Name\""), "\"Odd \\\"Name\\\"\"");
+    }
+
+    #[test]
+    fn every_stack_ends_in_a_generic_family() {
+        for category in [
+            Category::Serif,
+            Category::SansSerif,
+            Category::Monospace,
+            Category::Script,
+            Category::Display,
+            Category::Symbol,
+        ] {
+            let (fallbacks, generic) = fallbacks(category);
+            assert!(!fallbacks.is_empty());
+            assert!(GENERIC.contains(&generic));
+        }
+    }
+
+    #[test]
+    fn a_missing_family_is_classified_by_its_name() {
+        let stack = font_stack("Squish Test Slab Serif");
+        assert!(!stack.installed);
+        assert_eq!(stack.category, Category::Serif);
+        assert_eq!(stack.stack.first().unwrap(), "Squish Test Slab Serif");
+        assert_eq!(stack.stack.last().unwrap(), "serif");
+        assert!(stack
+            .css
+            .starts_with("\"Squish Test Slab Serif\", \"Georgia\""));
+        assert!(stack.css.ends_with(", serif"));
+    }
+
+    #[test]
+    fn the_family_isnt_repeated_as_its_own_fallback() {
+        let stack = font_stack("georgia");
+        assert_eq!(
+            stack
+                .stack
+                .iter()
+                .filter(|name| name.eq_ignore_ascii_case("georgia"))
+                .count(),
+            1
+        );
+    }
+}
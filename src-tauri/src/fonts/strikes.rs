@@ -0,0 +1,200 @@
+// Embedded bitmap strikes: the pre-rendered sizes color emoji (sbix, CBDT)
+// and some pixel fonts (EBDT) carry instead of, or next to, outlines. The
+// preview picks the closest strike rather than scaling one blurry bitmap.
+// ttf-parser only looks strikes up per glyph, so the size tables are read
+// directly.
+use serde::Serialize;
+use ttf_parser::{Face, Tag};
+
+use crate::blocking;
+use crate::error::Error;
+
+// BitmapSize records in CBLC / EBLC
+const BITMAP_SIZE_LEN: usize = 48;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StrikeKind {
+    // Apple's PNG strikes
+    Sbix,
+    // Google's color bitmaps
+    Cbdt,
+    // Monochrome and grayscale bitmaps
+    Ebdt,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Strike {
+    pub kind: StrikeKind,
+    pub pixels_per_em: u16,
+    // sbix only: the resolution the bitmaps were drawn for
+    pub ppi: Option<u16>,
+    // CBDT/EBDT only
+    pub bit_depth: Option<u8>,
+    // Glyphs the strike covers, where the table says
+    pub glyphs: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontStrikes {
+    pub family: String,
+    // Has outlines, so sizes without a strike still render sharply
+    pub scalable: bool,
+    pub strikes: Vec<Strike>,
+    // Strike to use for the requested size, when one was given
+    pub closest: Option<u16>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn sbix(data: &[u8]) -> Vec<Strike> {
+    let count = u32_at(data, 4).unwrap_or(0) as usize;
+    (0..count)
+        .filter_map(|i| {
+            let offset = u32_at(data, 8 + i * 4)? as usize;
+            Some(Strike {
+                kind: StrikeKind::Sbix,
+                pixels_per_em: u16_at(data, offset)?,
+                ppi: u16_at(data, offset + 2),
+                bit_depth: None,
+                glyphs: None,
+            })
+        })
+        .collect()
+}
+
+// CBLC and EBLC share a layout
+fn bitmap_locations(data: &[u8], kind: StrikeKind) -> Vec<Strike> {
+    let count = u32_at(data, 4).unwrap_or(0) as usize;
+    (0..count)
+        .filter_map(|i| {
+            let record = data.get(8 + i * BITMAP_SIZE_LEN..8 + (i + 1) * BITMAP_SIZE_LEN)?;
+            let start = u16_at(record, 40)?;
+            let end = u16_at(record, 42)?;
+            Some(Strike {
+                kind,
+                pixels_per_em: record[45] as u16,
+                ppi: None,
+                bit_depth: Some(record[46]),
+                glyphs: (end >= start).then(|| (end - start) as u32 + 1),
+            })
+        })
+        .collect()
+}
+
+pub fn strikes(face: &Face) -> Vec<Strike> {
+    let raw = face.raw_face();
+    let mut strikes = Vec::new();
+    if let Some(data) = raw.table(Tag::from_bytes(b"sbix")) {
+        strikes.extend(sbix(data));
+    }
+    if let Some(data) = raw.table(Tag::from_bytes(b"CBLC")) {
+        strikes.extend(bitmap_locations(data, StrikeKind::Cbdt));
+    }
+    if let Some(data) = raw.table(Tag::from_bytes(b"EBLC")) {
+        strikes.extend(bitmap_locations(data, StrikeKind::Ebdt));
+    }
+    strikes.sort_by_key(|strike| strike.pixels_per_em);
+    strikes.dedup_by_key(|strike| (strike.kind, strike.pixels_per_em));
+    strikes
+}
+
+// The smallest strike at least as large as the size, since scaling a bitmap
+// down looks far better than scaling it up; the largest one otherwise
+pub fn closest(strikes: &[Strike], size: f32) -> Option<u16> {
+    strikes
+        .iter()
+        .map(|strike| strike.pixels_per_em)
+        .find(|ppem| *ppem as f32 >= size)
+        .or_else(|| strikes.iter().map(|strike| strike.pixels_per_em).max())
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn get_bitmap_strikes(
+    family: String,
+    style: Option<String>,
+    size: Option<f32>,
+) -> Result<FontStrikes, Error> {
+    blocking::run(move || {
+        let (data, index) = super::face_data(&family, style.as_deref())?;
+        let face = Face::parse(&data, index)
+            .map_err(|e| Error::UnsupportedFormat(format!("Failed to parse {}: {}", family, e)))?;
+        let strikes = strikes(&face);
+        let tables = face.tables();
+        Ok(FontStrikes {
+            scalable: tables.glyf.is_some() || tables.cff.is_some() || tables.cff2.is_some(),
+            closest: size.and_then(|size| closest(&strikes, size)),
+            strikes,
+            family,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(strikes: &[Strike]) -> Vec<u16> {
+        strikes.iter().map(|strike| strike.pixels_per_em).collect()
+    }
+
+    fn strike(pixels_per_em: u16) -> Strike {
+        Strike {
+            kind: StrikeKind::Sbix,
+            pixels_per_em,
+            ppi: None,
+            bit_depth: None,
+            glyphs: None,
+        }
+    }
+
+    #[test]
+    fn reads_sbix_strike_headers() {
+        // Version, flags, two strike offsets, then each strike's ppem and ppi
+        let mut data = vec![0, 1, 0, 1, 0, 0, 0, 2, 0, 0, 0, 16, 0, 0, 0, 20];
+        data.extend([0, 20, 0, 72, 0, 160, 0, 144]);
+        let strikes = sbix(&data);
+        assert_eq!(sizes(&strikes), [20, 160]);
+        assert_eq!(strikes[1].ppi, Some(144));
+        // A count running past the table is cut short, not a panic
+        data[7] = 9;
+        assert_eq!(sbix(&data).len(), 2);
+    }
+
+    #[test]
+    fn reads_bitmap_size_records() {
+        let mut data = vec![0, 3, 0, 0, 0, 0, 0, 1];
+        let mut record = [0u8; BITMAP_SIZE_LEN];
+        record[40..44].copy_from_slice(&[0, 4, 0, 13]);
+        record[45] = 109;
+        record[46] = 32;
+        data.extend(record);
+        let strikes = bitmap_locations(&data, StrikeKind::Cbdt);
+        assert_eq!(sizes(&strikes), [109]);
+        assert_eq!(strikes[0].bit_depth, Some(32));
+        assert_eq!(strikes[0].glyphs, Some(10));
+        assert!(bitmap_locations(&data[..20], StrikeKind::Cbdt).is_empty());
+    }
+
+    #[test]
+    fn closest_prefers_scaling_down() {
+        let strikes = [strike(20), strike(40), strike(160)];
+        assert_eq!(closest(&strikes, 12.0), Some(20));
+        assert_eq!(closest(&strikes, 40.0), Some(40));
+        assert_eq!(closest(&strikes, 41.0), Some(160));
+        assert_eq!(closest(&strikes, 300.0), Some(160));
+        assert_eq!(closest(&[], 12.0), None);
+    }
+}
@@ -0,0 +1,379 @@
+// Text on a path: the text is shaped as one line, the path (SVG path data) is
+// flattened into short segments, and each glyph is stood on the path at the
+// point its middle falls on, turned to the path's direction there. Glyphs
+// whose middle falls off either end are left out, as SVG's textPath does.
+//
+// Each glyph comes back with a 2D affine transform [a, b, c, d, e, f] taking
+// glyph space (pixels, y down, origin on the baseline at the glyph's left) to
+// path space, for the canvas and the export renderers alike.
+use resvg::usvg::{self, tiny_skia_path::PathSegment};
+use rustybuzz::Face;
+use serde::{Deserialize, Serialize};
+use unicode_bidi::BidiInfo;
+
+use super::layout::{FontSpec, PositionedGlyph, Shaper};
+use crate::blocking;
+use crate::error::Error;
+
+// Straight pieces per curve are picked so each is about this long
+const FLATTEN_STEP: f32 = 2.0;
+const MAX_CURVE_STEPS: usize = 256;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PathAlign {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TextPathOptions {
+    pub align: PathAlign,
+    // Distance along the path from the aligned end, towards the middle
+    pub offset: f32,
+    // Extra space after each character, in pixels
+    pub spacing: f32,
+    // Moves the baseline off the path; positive is below it, as seen by a
+    // reader following the path
+    pub baseline_shift: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathGlyph {
+    pub glyph_id: u16,
+    pub cluster: usize,
+    pub transform: [f32; 6],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextOnPath {
+    pub glyphs: Vec<PathGlyph>,
+    pub path_length: f32,
+    pub text_width: f32,
+    // Glyphs left out for falling off the path
+    pub hidden: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+struct Piece {
+    from: Point,
+    to: Point,
+    // Path length before this piece
+    start: f32,
+    length: f32,
+}
+
+// The path as straight pieces laid end to end; moves between subpaths add
+// no length
+struct Flattened {
+    pieces: Vec<Piece>,
+    length: f32,
+}
+
+impl Flattened {
+    fn line_to(&mut self, from: Point, to: Point) {
+        let length = (to.x - from.x).hypot(to.y - from.y);
+        if length > f32::EPSILON {
+            self.pieces.push(Piece {
+                from,
+                to,
+                start: self.length,
+                length,
+            });
+            self.length += length;
+        }
+    }
+
+    fn curve_to(&mut self, points: &[Point]) {
+        let hull: f32 = points
+            .windows(2)
+            .map(|pair| (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y))
+            .sum();
+        let steps = ((hull / FLATTEN_STEP).ceil() as usize).clamp(1, MAX_CURVE_STEPS);
+        let mut previous = points[0];
+        for step in 1..=steps {
+            let point = bezier(points, step as f32 / steps as f32);
+            self.line_to(previous, point);
+            previous = point;
+        }
+    }
+
+    // Point and unit direction at a distance along the path
+    fn at(&self, distance: f32) -> Option<(Point, Point)> {
+        if distance < 0.0 || distance > self.length {
+            return None;
+        }
+        let index = self
+            .pieces
+            .partition_point(|piece| piece.start + piece.length < distance)
+            .min(self.pieces.len().checked_sub(1)?);
+        let piece = &self.pieces[index];
+        let t = ((distance - piece.start) / piece.length).clamp(0.0, 1.0);
+        let direction = Point {
+            x: (piece.to.x - piece.from.x) / piece.length,
+            y: (piece.to.y - piece.from.y) / piece.length,
+        };
+        Some((
+            Point {
+                x: piece.from.x + (piece.to.x - piece.from.x) * t,
+                y: piece.from.y + (piece.to.y - piece.from.y) * t,
+            },
+            direction,
+        ))
+    }
+}
+
+// De Casteljau, for quadratics and cubics alike
+fn bezier(points: &[Point], t: f32) -> Point {
+    let mut points = points.to_vec();
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|pair| Point {
+                x: pair[0].x + (pair[1].x - pair[0].x) * t,
+                y: pair[0].y + (pair[1].y - pair[0].y) * t,
+            })
+            .collect();
+    }
+    points[0]
+}
+
+// usvg does the parsing, so arcs, relative and shorthand commands all arrive
+// as moves, lines and curves in absolute coordinates
+fn flatten(data: &str) -> Result<Flattened, Error> {
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><path fill="none" stroke="black" d="{}"/></svg>"#,
+        data.replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('<', "&lt;")
+    );
+    let tree = usvg::Tree::from_str(&svg, &usvg::Options::default())
+        .map_err(|e| Error::InvalidInput(format!("Invalid path: {}", e)))?;
+    let path = tree
+        .root()
+        .children()
+        .iter()
+        .find_map(|node| match node {
+            usvg::Node::Path(path) => Some(path.data().clone()),
+            _ => None,
+        })
+        .ok_or_else(|| Error::InvalidInput("Path has no segments".to_string()))?;
+
+    let mut flattened = Flattened {
+        pieces: Vec::new(),
+        length: 0.0,
+    };
+    let point = |p: usvg::tiny_skia_path::Point| Point { x: p.x, y: p.y };
+    let mut current = Point { x: 0.0, y: 0.0 };
+    let mut subpath_start = current;
+    for segment in path.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                current = point(p);
+                subpath_start = current;
+            }
+            PathSegment::LineTo(p) => {
+                flattened.line_to(current, point(p));
+                current = point(p);
+            }
+            PathSegment::QuadTo(p1, p) => {
+                flattened.curve_to(&[current, point(p1), point(p)]);
+                current = point(p);
+            }
+            PathSegment::CubicTo(p1, p2, p) => {
+                flattened.curve_to(&[current, point(p1), point(p2), point(p)]);
+                current = point(p);
+            }
+            PathSegment::Close => {
+                flattened.line_to(current, subpath_start);
+                current = subpath_start;
+            }
+        }
+    }
+    if flattened.pieces.is_empty() {
+        return Err(Error::InvalidInput("Path has no length".to_string()));
+    }
+    Ok(flattened)
+}
+
+// The text as one line in visual order
+fn shape_line(shaper: &Shaper, text: &str) -> Vec<PositionedGlyph> {
+    let bidi = BidiInfo::new(text, None);
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    for paragraph in &bidi.paragraphs {
+        let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            for mut glyph in shaper.shape(text, run, rtl) {
+                glyph.x += x;
+                x += glyph.advance;
+                glyphs.push(glyph);
+            }
+        }
+    }
+    glyphs
+}
+
+pub fn text_on_path(
+    face: Face,
+    text: &str,
+    size: f32,
+    path: &str,
+    options: &TextPathOptions,
+) -> Result<TextOnPath, Error> {
+    let flattened = flatten(path)?;
+    let shaper = Shaper::new(face, size);
+    // A path has one line; breaks are read as spaces, which keeps byte offsets
+    let text = text.replace(['\n', '\r'], " ");
+    let mut glyphs = shape_line(&shaper, &text);
+
+    // Letter spacing goes between clusters, so a base and its marks stay together
+    let mut extra = 0.0;
+    let mut previous = None;
+    for glyph in &mut glyphs {
+        if previous.is_some_and(|cluster| cluster != glyph.cluster) {
+            extra += options.spacing;
+        }
+        previous = Some(glyph.cluster);
+        glyph.x += extra;
+    }
+    let text_width = glyphs.iter().map(|g| g.x + g.advance).fold(0.0, f32::max);
+
+    let start = match options.align {
+        PathAlign::Start => options.offset,
+        PathAlign::Center => (flattened.length - text_width) / 2.0 + options.offset,
+        PathAlign::End => flattened.length - text_width - options.offset,
+    };
+    let mut placed = Vec::with_capacity(glyphs.len());
+    for glyph in &glyphs {
+        let half = glyph.advance / 2.0;
+        let Some((point, direction)) = flattened.at(start + glyph.x + half) else {
+            continue;
+        };
+        // Glyph y runs along the normal, which is the direction turned a
+        // quarter clockwise in y-down space
+        let normal = Point {
+            x: -direction.y,
+            y: direction.x,
+        };
+        let shift = glyph.y + options.baseline_shift;
+        placed.push(PathGlyph {
+            glyph_id: glyph.glyph_id,
+            cluster: glyph.cluster,
+            transform: [
+                direction.x,
+                direction.y,
+                normal.x,
+                normal.y,
+                point.x - direction.x * half + normal.x * shift,
+                point.y - direction.y * half + normal.y * shift,
+            ],
+        });
+    }
+    Ok(TextOnPath {
+        hidden: glyphs.len() - placed.len(),
+        glyphs: placed,
+        path_length: flattened.length,
+        text_width,
+    })
+}
+
+// `path` is SVG path data in the same units the glyph transforms come back in
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn layout_text_on_path(
+    text: String,
+    font: FontSpec,
+    size: f32,
+    path: String,
+    options: Option<TextPathOptions>,
+) -> Result<TextOnPath, Error> {
+    if !size.is_finite() || size <= 0.0 {
+        return Err(Error::InvalidInput(format!("Invalid font size {}", size)));
+    }
+    blocking::run(move || {
+        let (data, index) = super::face_data(&font.family, font.style.as_deref())?;
+        let face = Face::from_slice(&data, index)
+            .ok_or_else(|| Error::UnsupportedFormat(format!("Failed to parse {}", font.family)))?;
+        text_on_path(face, &text, size, &path, &options.unwrap_or_default())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resvg::usvg::fontdb;
+
+    fn sans() -> (Vec<u8>, u32) {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        let id = db
+            .query(&fontdb::Query {
+                families: &[fontdb::Family::SansSerif],
+                ..Default::default()
+            })
+            .or_else(|| db.faces().next().map(|face| face.id))
+            .expect("a font is installed");
+        db.with_face_data(id, |data, index| (data.to_vec(), index))
+            .unwrap()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn flattens_lines_curves_and_closes() {
+        let square = flatten("M0 0 h10 v10 h-10 z").unwrap();
+        assert!(close(square.length, 40.0));
+        // Moving between subpaths adds nothing
+        let two = flatten("M0 0 L10 0 M100 100 L100 110").unwrap();
+        assert!(close(two.length, 20.0));
+        // A half circle of radius 50
+        let arc = flatten("M0 0 A50 50 0 0 1 100 0").unwrap();
+        assert!((arc.length - std::f32::consts::PI * 50.0).abs() < 0.5);
+        assert!(flatten("M5 5").is_err());
+        assert!(flatten("not a path\"/><x").is_err());
+    }
+
+    #[test]
+    fn finds_points_and_directions_along_the_path() {
+        let path = flatten("M0 0 L10 0 L10 10").unwrap();
+        let (point, direction) = path.at(5.0).unwrap();
+        assert!(close(point.x, 5.0) && close(point.y, 0.0));
+        assert!(close(direction.x, 1.0));
+        let (point, direction) = path.at(15.0).unwrap();
+        assert!(close(point.x, 10.0) && close(point.y, 5.0));
+        assert!(close(direction.y, 1.0));
+        assert!(path.at(-1.0).is_none() && path.at(20.5).is_none());
+    }
+
+    #[test]
+    fn glyphs_follow_the_path_and_fall_off_its_end() {
+        let (data, index) = sans();
+        let face = Face::from_slice(&data, index).unwrap();
+        let options = TextPathOptions::default();
+        // Straight down: glyph x runs along +y, glyph y along -x
+        let down = text_on_path(face.clone(), "ab", 20.0, "M0 0 V500", &options).unwrap();
+        assert_eq!(down.glyphs.len(), 2);
+        let [a, b, c, d, e, _] = down.glyphs[0].transform;
+        assert!(close(a, 0.0) && close(b, 1.0) && close(c, -1.0) && close(d, 0.0));
+        assert!(close(e, 0.0));
+
+        let short = text_on_path(face, "a long line", 20.0, "M0 0 H30", &options).unwrap();
+        assert!(short.hidden > 0);
+        assert_eq!(short.glyphs.len() + short.hidden, 11);
+    }
+}
@@ -0,0 +1,285 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+// Oldest ops beyond this are dropped so long sessions don't grow the log forever
+const HISTORY_LIMIT: i64 = 500;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NewOp {
+    pub project_id: String,
+    pub kind: String,
+    pub label: String,
+    pub layer_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Op {
+    pub id: i64,
+    pub project_id: String,
+    pub kind: String,
+    pub label: String,
+    pub layer_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub undone: bool,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct History {
+    pub ops: Vec<Op>,
+    // Index into ops of the most recently applied op, -1 when everything is undone
+    pub cursor: i64,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+const SELECT_OP: &str =
+    "SELECT id, project_id, kind, label, layer_id, before, after, undone, created_at
+     FROM operation_log";
+
+fn op_from_row(row: &rusqlite::Row) -> rusqlite::Result<Op> {
+    let before: Option<String> = row.get(5)?;
+    let after: Option<String> = row.get(6)?;
+    Ok(Op {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        kind: row.get(2)?,
+        label: row.get(3)?,
+        layer_id: row.get(4)?,
+        before: before.and_then(|s| serde_json::from_str(&s).ok()),
+        after: after.and_then(|s| serde_json::from_str(&s).ok()),
+        undone: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn to_json(value: &Option<Value>) -> Option<String> {
+    value.as_ref().map(Value::to_string)
+}
+
+fn load_history(conn: &Connection, project_id: &str) -> Result<History, String> {
+    let mut stmt = conn
+        .prepare(&format!("{} WHERE project_id = ?1 ORDER BY id", SELECT_OP))
+        .map_err(|e| format!("Failed to read history: {}", e))?;
+    let ops = stmt
+        .query_map(params![project_id], op_from_row)
+        .map_err(|e| format!("Failed to read history: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read history: {}", e))?;
+
+    let applied = ops.iter().filter(|op| !op.undone).count() as i64;
+    Ok(History {
+        cursor: applied - 1,
+        can_undo: applied > 0,
+        can_redo: (applied as usize) < ops.len(),
+        ops,
+    })
+}
+
+// Records a new op, discarding anything that was undone since it can no longer be redone
+#[tauri::command]
+pub fn push_op(db: State<Db>, op: NewOp) -> Result<Op, Error> {
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(push(&mut conn, &op)?)
+}
+
+fn push(conn: &mut Connection, op: &NewOp) -> Result<Op, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    tx.execute(
+        "DELETE FROM operation_log WHERE project_id = ?1 AND undone = 1",
+        params![op.project_id],
+    )
+    .map_err(|e| format!("Failed to clear redo history: {}", e))?;
+    tx.execute(
+        "INSERT INTO operation_log (project_id, kind, label, layer_id, before, after)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            op.project_id,
+            op.kind,
+            op.label,
+            op.layer_id,
+            to_json(&op.before),
+            to_json(&op.after)
+        ],
+    )
+    .map_err(|e| format!("Failed to record operation: {}", e))?;
+    let id = tx.last_insert_rowid();
+    tx.execute(
+        "DELETE FROM operation_log WHERE project_id = ?1 AND id <= ?2 - ?3",
+        params![op.project_id, id, HISTORY_LIMIT],
+    )
+    .map_err(|e| format!("Failed to trim history: {}", e))?;
+
+    let op = tx
+        .query_row(
+            &format!("{} WHERE id = ?1", SELECT_OP),
+            params![id],
+            op_from_row,
+        )
+        .map_err(|e| format!("Failed to read operation: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit operation: {}", e))?;
+    Ok(op)
+}
+
+// Marks the latest applied op as undone and returns it; the caller restores `before`
+#[tauri::command]
+pub fn undo(db: State<Db>, project_id: String) -> Result<Option<Op>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(undo_latest(&conn, &project_id)?)
+}
+
+fn undo_latest(conn: &Connection, project_id: &str) -> Result<Option<Op>, String> {
+    step(
+        conn,
+        &format!(
+            "{} WHERE project_id = ?1 AND undone = 0 ORDER BY id DESC LIMIT 1",
+            SELECT_OP
+        ),
+        project_id,
+        true,
+    )
+}
+
+// Re-applies the earliest undone op and returns it; the caller restores `after`
+#[tauri::command]
+pub fn redo(db: State<Db>, project_id: String) -> Result<Option<Op>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(redo_earliest(&conn, &project_id)?)
+}
+
+fn redo_earliest(conn: &Connection, project_id: &str) -> Result<Option<Op>, String> {
+    step(
+        conn,
+        &format!(
+            "{} WHERE project_id = ?1 AND undone = 1 ORDER BY id ASC LIMIT 1",
+            SELECT_OP
+        ),
+        project_id,
+        false,
+    )
+}
+
+fn step(
+    conn: &Connection,
+    query: &str,
+    project_id: &str,
+    undone: bool,
+) -> Result<Option<Op>, String> {
+    let Some(mut op) = conn
+        .query_row(query, params![project_id], op_from_row)
+        .optional()
+        .map_err(|e| format!("Failed to read history: {}", e))?
+    else {
+        return Ok(None);
+    };
+
+    conn.execute(
+        "UPDATE operation_log SET undone = ?1 WHERE id = ?2",
+        params![undone, op.id],
+    )
+    .map_err(|e| format!("Failed to update history: {}", e))?;
+    op.undone = undone;
+    Ok(Some(op))
+}
+
+#[tauri::command]
+pub fn get_history(db: State<Db>, project_id: String) -> Result<History, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(load_history(&conn, &project_id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn op(label: &str) -> NewOp {
+        NewOp {
+            project_id: "p1".to_string(),
+            kind: "move".to_string(),
+            label: label.to_string(),
+            layer_id: Some("l1".to_string()),
+            before: Some(json!({"x": 0})),
+            after: Some(json!({"x": 10})),
+        }
+    }
+
+    fn labels(conn: &Connection) -> Vec<(String, bool)> {
+        load_history(conn, "p1")
+            .unwrap()
+            .ops
+            .into_iter()
+            .map(|op| (op.label, op.undone))
+            .collect()
+    }
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_log() {
+        let mut conn = library();
+        let first = push(&mut conn, &op("first")).unwrap();
+        assert_eq!(first.after, Some(json!({"x": 10})));
+        push(&mut conn, &op("second")).unwrap();
+
+        assert_eq!(undo_latest(&conn, "p1").unwrap().unwrap().label, "second");
+        assert_eq!(undo_latest(&conn, "p1").unwrap().unwrap().label, "first");
+        assert!(undo_latest(&conn, "p1").unwrap().is_none());
+        let history = load_history(&conn, "p1").unwrap();
+        assert_eq!(history.cursor, -1);
+        assert!(!history.can_undo && history.can_redo);
+
+        assert_eq!(redo_earliest(&conn, "p1").unwrap().unwrap().label, "first");
+        let history = load_history(&conn, "p1").unwrap();
+        assert_eq!(history.cursor, 0);
+        assert!(history.can_undo && history.can_redo);
+    }
+
+    #[test]
+    fn a_new_op_drops_what_was_undone() {
+        let mut conn = library();
+        push(&mut conn, &op("first")).unwrap();
+        push(&mut conn, &op("second")).unwrap();
+        undo_latest(&conn, "p1").unwrap();
+        push(&mut conn, &op("third")).unwrap();
+        assert_eq!(
+            labels(&conn),
+            [("first".to_string(), false), ("third".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn the_log_is_capped() {
+        let mut conn = library();
+        for i in 0..HISTORY_LIMIT + 5 {
+            push(&mut conn, &op(&i.to_string())).unwrap();
+        }
+        let ops = labels(&conn);
+        assert_eq!(ops.len() as i64, HISTORY_LIMIT);
+        assert_eq!(ops[0].0, "5");
+    }
+}
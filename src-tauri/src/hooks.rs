@@ -0,0 +1,470 @@
+// Post-export hooks: a command the user configured runs after every successful
+// export or compression, e.g. to commit the new asset or kick off a deploy.
+// Every job that records its history also hands its export to after_export,
+// so hooks see the app, watch folders, scripts and the CLI alike. In the app
+// they run one at a time on a thread of their own, so a slow hook only delays
+// the hooks queued behind it, never the job or the rest of its batch.
+//
+// Creating or changing a hook runs a program from then on, so it's confirmed
+// in a native dialog each time; the webview can't answer that for the user.
+//
+// A hook is a program and its arguments, never a shell line. The program must
+// be an absolute path; it runs in the output's folder with an emptied
+// environment (only PATH, HOME and the like are passed through), no stdin,
+// and is killed when it runs past its timeout. A hook that fails is logged and
+// remembered on the hook; it never fails the job.
+//
+// Arguments may contain {output}, {input}, {inputBytes}, {outputBytes},
+// {durationMs} and {kind}. The same values are in the environment as
+// SQUISH_OUTPUT_PATH, SQUISH_INPUT_PATH, SQUISH_INPUT_BYTES,
+// SQUISH_OUTPUT_BYTES, SQUISH_DURATION_MS and SQUISH_JOB_KIND.
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::blocking;
+use crate::db::Db;
+use crate::error::Error;
+use crate::job_history::JobRecord;
+use crate::permissions::{self, Capability};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+// Kept from a hook's stderr for the error message
+const MAX_STDERR: usize = 4096;
+// Passed through from our own environment so programs can still be found and
+// scripts can find their config
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "SYSTEMROOT",
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    // Empty for a new hook
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Job kinds to run after ("image", "batch", "watch", "pdf", ...); empty for all
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    pub enabled: bool,
+    pub timeout_secs: Option<u64>,
+    #[serde(default, skip_deserializing)]
+    pub last_run_at: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub last_error: Option<String>,
+}
+
+// A finished export waiting for its hooks. Owned, since the job that produced
+// it has moved on by the time they run.
+pub struct Export {
+    kind: String,
+    input_path: String,
+    output_path: String,
+    input_bytes: u64,
+    output_bytes: u64,
+    duration: Duration,
+}
+
+impl Export {
+    // Failed jobs and jobs without an output don't trigger hooks
+    pub fn new(job: &JobRecord) -> Option<Self> {
+        if job.error.is_some() {
+            return None;
+        }
+        Some(Export {
+            kind: job.kind.to_string(),
+            input_path: job.input_path.to_string(),
+            output_path: job.output_path?.to_string(),
+            input_bytes: job.input_bytes.unwrap_or_default(),
+            output_bytes: job.output_bytes.unwrap_or_default(),
+            duration: job.started.elapsed(),
+        })
+    }
+}
+
+// Started with the first export
+#[derive(Default)]
+pub struct HookQueue(Mutex<Option<Sender<Export>>>);
+
+fn load(conn: &Connection, enabled_only: bool) -> Result<Vec<Hook>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, program, args, kinds, enabled, timeout_secs, last_run_at, last_error
+             FROM export_hooks WHERE enabled = 1 OR ?1 = 0 ORDER BY created_at, rowid",
+        )
+        .map_err(|e| format!("Failed to load hooks: {}", e))?;
+    let rows = stmt
+        .query_map([enabled_only], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, bool>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to load hooks: {}", e))?;
+
+    let mut hooks = Vec::new();
+    for row in rows {
+        let (id, name, program, args, kinds, enabled, timeout_secs, last_run_at, last_error) =
+            row.map_err(|e| format!("Failed to read hook: {}", e))?;
+        hooks.push(Hook {
+            id,
+            name,
+            program,
+            args: serde_json::from_str(&args).unwrap_or_default(),
+            kinds: serde_json::from_str(&kinds).unwrap_or_default(),
+            enabled,
+            timeout_secs: Some(timeout_secs.max(1) as u64),
+            last_run_at,
+            last_error,
+        });
+    }
+    Ok(hooks)
+}
+
+fn values(export: &Export) -> Vec<(&'static str, &'static str, String)> {
+    vec![
+        ("{output}", "SQUISH_OUTPUT_PATH", export.output_path.clone()),
+        ("{input}", "SQUISH_INPUT_PATH", export.input_path.clone()),
+        (
+            "{inputBytes}",
+            "SQUISH_INPUT_BYTES",
+            export.input_bytes.to_string(),
+        ),
+        (
+            "{outputBytes}",
+            "SQUISH_OUTPUT_BYTES",
+            export.output_bytes.to_string(),
+        ),
+        (
+            "{durationMs}",
+            "SQUISH_DURATION_MS",
+            export.duration.as_millis().to_string(),
+        ),
+        ("{kind}", "SQUISH_JOB_KIND", export.kind.clone()),
+    ]
+}
+
+fn run(hook: &Hook, export: &Export) -> Result<(), String> {
+    let values = values(export);
+    let args = hook.args.iter().map(|arg| {
+        values
+            .iter()
+            .fold(arg.clone(), |arg, (placeholder, _, value)| {
+                arg.replace(placeholder, value)
+            })
+    });
+    let dir = Path::new(&export.output_path)
+        .parent()
+        .filter(|dir| dir.is_dir());
+
+    let mut command = Command::new(&hook.program);
+    command
+        .args(args)
+        .env_clear()
+        .envs(
+            PASSTHROUGH_ENV
+                .iter()
+                .filter_map(|name| std::env::var_os(name).map(|value| (name.to_string(), value))),
+        )
+        .envs(values.iter().map(|(_, name, value)| (*name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", hook.program, e))?;
+
+    // Drained on its own thread so a chatty hook can't block on a full pipe
+    let stderr = child.stderr.take().map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = pipe.read_to_end(&mut output);
+            output
+        })
+    });
+
+    let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Timed out after {:?}", timeout));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for {}: {}", hook.program, e)),
+        }
+    };
+    if status.success() {
+        return Ok(());
+    }
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .map(|output| {
+            let output = &output[output.len().saturating_sub(MAX_STDERR)..];
+            String::from_utf8_lossy(output).trim().to_string()
+        })
+        .unwrap_or_default();
+    Err(if stderr.is_empty() {
+        format!("Exited with {}", status)
+    } else {
+        format!("Exited with {}: {}", status, stderr)
+    })
+}
+
+// Queues the job's hooks; returns straight away
+pub fn after_export(app: &AppHandle, job: &JobRecord) {
+    let Some(export) = Export::new(job) else {
+        return;
+    };
+    let queue = app.state::<HookQueue>();
+    let Ok(mut sender) = queue.0.lock() else {
+        return;
+    };
+    let sender = sender.get_or_insert_with(|| {
+        let (sender, exports) = mpsc::channel::<Export>();
+        let app = app.clone();
+        std::thread::spawn(move || {
+            for export in exports {
+                run_all(&app.state::<Db>(), &export);
+            }
+        });
+        sender
+    });
+    if sender.send(export).is_err() {
+        tracing::warn!("Hook runner stopped; skipping hooks for {}", job.input_path);
+    }
+}
+
+// Runs every enabled hook matching the export, one after another on the
+// calling thread
+pub fn run_all(db: &Db, export: &Export) {
+    let hooks = match db.0.lock() {
+        Ok(conn) => load(&conn, true),
+        Err(e) => Err(format!("Failed to lock database: {}", e)),
+    };
+    let hooks = match hooks {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    };
+
+    for hook in hooks
+        .iter()
+        .filter(|hook| hook.kinds.is_empty() || hook.kinds.contains(&export.kind))
+    {
+        let outcome = run(hook, export);
+        match &outcome {
+            Ok(()) => tracing::debug!("Hook {} ran for {}", hook.name, export.input_path),
+            Err(e) => {
+                tracing::warn!("Hook {} failed for {}: {}", hook.name, export.input_path, e);
+                crate::crash::log_line(format!("Hook {} failed: {}", hook.name, e));
+            }
+        }
+        let updated = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
+            conn.execute(
+                "UPDATE export_hooks SET last_run_at = CURRENT_TIMESTAMP, last_error = ?2
+                 WHERE id = ?1",
+                params![hook.id, outcome.err()],
+            )
+            .map_err(|e| e.to_string())
+        });
+        if let Err(e) = updated {
+            tracing::warn!("Failed to record hook run: {}", e);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_hooks(db: State<Db>) -> Result<Vec<Hook>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(load(&conn, false)?)
+}
+
+// Creates the hook when it has no id yet; returns it as stored. Anything that
+// changes what runs (a new hook, its program or arguments, turning it on) is
+// confirmed by the user first.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn save_hook(app: AppHandle, hook: Hook) -> Result<Hook, Error> {
+    blocking::run(move || {
+        let mut hook = hook;
+        if hook.name.trim().is_empty() {
+            return Err(Error::InvalidInput("A hook needs a name".to_string()));
+        }
+        let program = Path::new(&hook.program);
+        if !program.is_absolute() || !program.is_file() {
+            return Err(Error::InvalidInput(format!(
+                "{} isn't the full path to a program",
+                hook.program
+            )));
+        }
+        let db = app.state::<Db>();
+        let existing = {
+            let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+            load(&conn, false)?.into_iter().find(|h| h.id == hook.id)
+        };
+        let unchanged = existing.is_some_and(|h| {
+            h.program == hook.program && h.args == hook.args && (h.enabled || !hook.enabled)
+        });
+        if hook.enabled && !unchanged {
+            let command = std::iter::once(&hook.program)
+                .chain(&hook.args)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+            permissions::check(&app, None, Capability::RunHook, &command)?;
+        }
+
+        if hook.id.is_empty() {
+            hook.id = uuid::Uuid::new_v4().to_string();
+        }
+        let timeout_secs = hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS).max(1);
+        hook.timeout_secs = Some(timeout_secs);
+        let args = serde_json::to_string(&hook.args).map_err(|e| Error::Internal(e.to_string()))?;
+        let kinds =
+            serde_json::to_string(&hook.kinds).map_err(|e| Error::Internal(e.to_string()))?;
+
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.execute(
+            "INSERT INTO export_hooks (id, name, program, args, kinds, enabled, timeout_secs)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, program = excluded.program,
+                args = excluded.args, kinds = excluded.kinds, enabled = excluded.enabled,
+                timeout_secs = excluded.timeout_secs",
+            params![
+                hook.id,
+                hook.name,
+                hook.program,
+                args,
+                kinds,
+                hook.enabled,
+                timeout_secs as i64
+            ],
+        )?;
+        tracing::info!("Saved export hook {} ({})", hook.name, hook.program);
+        Ok(hook)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn delete_hook(db: State<Db>, id: String) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let removed = conn.execute("DELETE FROM export_hooks WHERE id = ?1", [&id])?;
+    if removed == 0 {
+        return Err(Error::NotFound(format!("No hook {}", id)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job<'a>(output_path: Option<&'a str>, error: Option<&'a str>) -> JobRecord<'a> {
+        JobRecord {
+            kind: "image",
+            input_path: "/in/photo.png",
+            output_path,
+            input_bytes: Some(1000),
+            output_bytes: Some(400),
+            started: Instant::now(),
+            settings: serde_json::Value::Null,
+            error,
+        }
+    }
+
+    fn hook(program: &str, args: &[&str], timeout_secs: u64) -> Hook {
+        Hook {
+            id: String::new(),
+            name: "test".to_string(),
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            kinds: Vec::new(),
+            enabled: true,
+            timeout_secs: Some(timeout_secs),
+            last_run_at: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn only_successful_jobs_with_an_output_are_exported() {
+        assert!(Export::new(&job(None, None)).is_none());
+        assert!(Export::new(&job(Some("/out/photo.webp"), Some("failed"))).is_none());
+        let export = Export::new(&job(Some("/out/photo.webp"), None)).unwrap();
+        let values = values(&export);
+        let value = |name| &values.iter().find(|(p, _, _)| *p == name).unwrap().2;
+        assert_eq!(value("{output}"), "/out/photo.webp");
+        assert_eq!(value("{inputBytes}"), "1000");
+        assert_eq!(value("{outputBytes}"), "400");
+        assert_eq!(value("{kind}"), "image");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn placeholders_and_environment_reach_the_program() {
+        let export = Export::new(&job(Some("/out/photo.webp"), None)).unwrap();
+        let check = r#"[ "$1" = /out/photo.webp ] && [ "$2" = image-400 ] &&
+            [ "$SQUISH_INPUT_PATH" = /in/photo.png ] && [ -z "$SQUISH_UNRELATED" ]"#;
+        std::env::set_var("SQUISH_UNRELATED", "leaked");
+        let result = run(
+            &hook(
+                "/bin/sh",
+                &["-c", check, "sh", "{output}", "{kind}-{outputBytes}"],
+                5,
+            ),
+            &export,
+        );
+        assert_eq!(result, Ok(()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failures_report_stderr_and_slow_hooks_are_killed() {
+        let export = Export::new(&job(Some("/out/photo.webp"), None)).unwrap();
+        let error = run(
+            &hook("/bin/sh", &["-c", "echo broken >&2; exit 3"], 5),
+            &export,
+        )
+        .unwrap_err();
+        assert!(error.ends_with("broken"), "{}", error);
+        let started = Instant::now();
+        let error = run(&hook("/bin/sh", &["-c", "sleep 30"], 1), &export).unwrap_err();
+        assert!(error.starts_with("Timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,176 @@
+// Localized strings for surfaces the backend renders itself, from the Fluent
+// catalogs in locales/. English is always loaded underneath the chosen
+// language, so an untranslated string falls back instead of going missing.
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::sync::RwLock;
+use tauri::{AppHandle, Listener, Manager};
+use unic_langid::LanguageIdentifier;
+
+use crate::settings::{AppSettings, SettingChange, SettingsState, SETTINGS_CHANGED_EVENT};
+
+const LOCALE_KEY: &str = "locale";
+const FALLBACK: &str = "en";
+
+// Language subtag to catalog
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+    ("es", include_str!("../locales/es.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+pub struct Strings(pub RwLock<FluentBundle<FluentResource>>);
+
+fn catalog(language: &str) -> Option<&'static str> {
+    CATALOGS
+        .iter()
+        .find(|(lang, _)| *lang == language)
+        .map(|(_, ftl)| *ftl)
+}
+
+pub fn system_locale() -> String {
+    sys_locale::get_locale().unwrap_or_else(|| FALLBACK.to_string())
+}
+
+// The locale setting wins, so the native surfaces follow the frontend when
+// the user picked a language there
+fn build(settings: &AppSettings) -> FluentBundle<FluentResource> {
+    let requested = settings
+        .locale
+        .clone()
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(system_locale);
+    let langid: LanguageIdentifier = requested
+        .replace('_', "-")
+        .parse()
+        .unwrap_or_else(|_| FALLBACK.parse().expect("valid fallback locale"));
+
+    let mut bundle = FluentBundle::new_concurrent(vec![langid.clone()]);
+    // Unicode isolation marks show up as boxes in some notification centers
+    bundle.set_use_isolating(false);
+    let mut add = |ftl: &str, overriding: bool| {
+        let resource = match FluentResource::try_new(ftl.to_string()) {
+            Ok(resource) => resource,
+            Err((resource, errors)) => {
+                tracing::warn!("String catalog has errors: {:?}", errors);
+                resource
+            }
+        };
+        if overriding {
+            bundle.add_resource_overriding(resource);
+        } else if let Err(errors) = bundle.add_resource(resource) {
+            tracing::warn!("Failed to load string catalog: {:?}", errors);
+        }
+    };
+    add(catalog(FALLBACK).expect("fallback catalog"), false);
+    let language = langid.language.as_str();
+    if language != FALLBACK {
+        match catalog(language) {
+            Some(ftl) => add(ftl, true),
+            None => tracing::debug!("No string catalog for {}, using English", requested),
+        }
+    }
+    bundle
+}
+
+pub fn load(settings: &AppSettings) -> Strings {
+    Strings(RwLock::new(build(settings)))
+}
+
+// Picks up a new locale setting without a restart
+pub fn watch_settings(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if change.key == LOCALE_KEY {
+            let bundle = build(&handle.state::<SettingsState>().snapshot());
+            if let Ok(mut strings) = handle.state::<Strings>().0.write() {
+                *strings = bundle;
+            }
+        }
+    });
+}
+
+// Formats a message from the catalog; falls back to the id itself so a
+// missing string is visible rather than blank
+pub fn tr(app: &AppHandle, id: &str, args: &[(&str, FluentValue)]) -> String {
+    let strings = app.state::<Strings>();
+    let Ok(bundle) = strings.0.read() else {
+        return id.to_string();
+    };
+    let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) else {
+        tracing::warn!("Missing string {}", id);
+        return id.to_string();
+    };
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    if !errors.is_empty() {
+        tracing::warn!("Failed to format string {}: {:?}", id, errors);
+    }
+    text.into_owned()
+}
+
+#[tauri::command]
+pub fn get_system_locale() -> String {
+    system_locale()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Message ids, which start a line and are followed by " ="
+    fn ids(ftl: &str) -> Vec<&str> {
+        FluentResource::try_new(ftl.to_string()).expect("catalog parses");
+        let mut ids: Vec<&str> = ftl
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn format(locale: &str, id: &str) -> String {
+        let settings = AppSettings {
+            locale: Some(locale.to_string()),
+            ..Default::default()
+        };
+        let bundle = build(&settings);
+        let pattern = bundle.get_message(id).unwrap().value().unwrap();
+        let mut args = FluentArgs::new();
+        args.set("count", 2);
+        args.set("saved", "1 MB");
+        bundle
+            .format_pattern(pattern, Some(&args), &mut Vec::new())
+            .into_owned()
+    }
+
+    #[test]
+    fn every_catalog_has_the_english_strings() {
+        let english = ids(catalog(FALLBACK).unwrap());
+        for (language, ftl) in CATALOGS {
+            assert_eq!(ids(ftl), english, "{}", language);
+        }
+    }
+
+    #[test]
+    fn picks_the_catalog_by_language() {
+        assert_eq!(format("de_DE", "batch-finished"), "Stapel abgeschlossen");
+        assert_eq!(
+            format("pt-BR", "batch-finished"),
+            format("en", "batch-finished")
+        );
+        assert_eq!(
+            format("not a locale!", "batch-finished"),
+            format("en", "batch-finished")
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use serde::Serialize;
+
+// Pixels at or above this alpha count as opaque (anti-aliasing noise aside)
+const OPAQUE_THRESHOLD: u8 = 250;
+// Flattening is worth a warning once this share of pixels is see-through
+const MEANINGFUL_FRACTION: f32 = 0.001;
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct AlphaStats {
+    pub has_alpha_channel: bool,
+    pub transparent_fraction: f32,
+    // True when flattening will visibly change the image
+    pub meaningful: bool,
+}
+
+pub fn analyze(image: &DynamicImage) -> AlphaStats {
+    if !image.color().has_alpha() {
+        return AlphaStats {
+            has_alpha_channel: false,
+            transparent_fraction: 0.0,
+            meaningful: false,
+        };
+    }
+
+    let rgba = image.to_rgba8();
+    let total = rgba.pixels().len().max(1);
+    let transparent = rgba.pixels().filter(|p| p[3] < OPAQUE_THRESHOLD).count();
+    let transparent_fraction = transparent as f32 / total as f32;
+
+    AlphaStats {
+        has_alpha_channel: true,
+        transparent_fraction,
+        meaningful: transparent_fraction >= MEANINGFUL_FRACTION,
+    }
+}
+
+// Composites onto a solid color; otherwise dropping alpha would expose
+// whatever RGB happens to be stored under transparent pixels
+pub fn flatten(image: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let flattened = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = pixel[3] as u32;
+        let blend =
+            |c: u8, bg: u8| ((c as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+        Rgb([
+            blend(pixel[0], background[0]),
+            blend(pixel[1], background[1]),
+            blend(pixel[2], background[2]),
+        ])
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn opaque_images_need_no_warning() {
+        let stats = analyze(&DynamicImage::new_rgb8(4, 4));
+        assert!(!stats.has_alpha_channel && !stats.meaningful);
+        let opaque = RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, OPAQUE_THRESHOLD]));
+        let stats = analyze(&DynamicImage::ImageRgba8(opaque));
+        assert!(stats.has_alpha_channel && !stats.meaningful);
+    }
+
+    #[test]
+    fn counts_see_through_pixels() {
+        let mut image = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let stats = analyze(&DynamicImage::ImageRgba8(image));
+        assert_eq!(stats.transparent_fraction, 1.0 / 16.0);
+        assert!(stats.meaningful);
+    }
+
+    #[test]
+    fn flattening_blends_onto_the_background() {
+        let mut image = RgbaImage::new(3, 1);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        // Garbage color under a fully transparent pixel
+        image.put_pixel(1, 0, Rgba([0, 255, 0, 0]));
+        image.put_pixel(2, 0, Rgba([0, 0, 0, 128]));
+        let flat = flatten(&DynamicImage::ImageRgba8(image), [255, 255, 255]).to_rgb8();
+        assert_eq!(flat.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(flat.get_pixel(1, 0).0, [255, 255, 255]);
+        assert_eq!(flat.get_pixel(2, 0).0, [127, 127, 127]);
+    }
+}
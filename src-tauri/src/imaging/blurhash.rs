@@ -0,0 +1,53 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+// BlurHash only captures low frequencies, so a tiny thumbnail is plenty
+const SAMPLE_SIZE: u32 = 32;
+const COMPONENTS_X: u32 = 4;
+const COMPONENTS_Y: u32 = 3;
+
+pub fn compute(image: &DynamicImage) -> Result<String, String> {
+    let sample = image
+        .resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle)
+        .to_rgba8();
+    blurhash::encode(
+        COMPONENTS_X,
+        COMPONENTS_Y,
+        sample.width(),
+        sample.height(),
+        sample.as_raw(),
+    )
+    .map_err(|e| format!("Failed to compute BlurHash: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_length_follows_the_component_count() {
+        let hash = compute(&DynamicImage::new_rgb8(64, 48)).unwrap();
+        // Size flag, max AC, four characters of DC and two per AC component
+        let components = (COMPONENTS_X * COMPONENTS_Y) as usize;
+        assert_eq!(hash.len(), 6 + 2 * (components - 1));
+    }
+
+    #[test]
+    fn flat_images_share_the_average_color() {
+        let flat = |width, height, color| {
+            let image = image::RgbImage::from_pixel(width, height, image::Rgb(color));
+            // Characters 2..6 carry the DC component, the average color
+            compute(&DynamicImage::ImageRgb8(image)).unwrap()[2..6].to_string()
+        };
+        assert_eq!(flat(40, 30, [200, 100, 50]), flat(90, 20, [200, 100, 50]));
+        assert_ne!(flat(40, 30, [200, 100, 50]), flat(40, 30, [50, 100, 200]));
+    }
+
+    #[test]
+    fn same_image_same_hash() {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(50, 50, |x, y| {
+            image::Rgb([(x * 5) as u8, (y * 5) as u8, 128])
+        }));
+        assert_eq!(compute(&image).unwrap(), compute(&image).unwrap());
+    }
+}
@@ -0,0 +1,181 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Rgb, RgbImage};
+use serde::Serialize;
+
+// SSIM window size; 8x8 blocks match the JPEG block grid where artifacts show up
+const WINDOW: u32 = 8;
+// SSIM stabilizing constants for 8-bit data
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+// Above this mean SSIM differences are generally invisible at normal viewing distance
+const VISUALLY_LOSSLESS_SSIM: f64 = 0.98;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub ssim: f64,
+    // Worst block, the spot most likely to show visible artifacts
+    pub min_ssim: f64,
+    pub psnr: f64,
+    pub visually_lossless: bool,
+    // True when the second image had to be resized to line up with the first
+    pub resized: bool,
+    #[serde(skip)]
+    pub block_scores: Vec<f64>,
+    #[serde(skip)]
+    pub blocks_x: u32,
+}
+
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Comparison {
+    let resized = a.width() != b.width() || a.height() != b.height();
+    let b = if resized {
+        b.resize_exact(a.width(), a.height(), FilterType::Lanczos3)
+    } else {
+        b.clone()
+    };
+
+    let luma_a = a.to_luma8();
+    let luma_b = b.to_luma8();
+
+    let blocks_x = luma_a.width().div_ceil(WINDOW);
+    let blocks_y = luma_a.height().div_ceil(WINDOW);
+    let mut block_scores = Vec::with_capacity((blocks_x * blocks_y) as usize);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            block_scores.push(block_ssim(&luma_a, &luma_b, bx * WINDOW, by * WINDOW));
+        }
+    }
+
+    let ssim = block_scores.iter().sum::<f64>() / block_scores.len().max(1) as f64;
+    let min_ssim = block_scores.iter().copied().fold(1.0, f64::min);
+
+    Comparison {
+        ssim,
+        min_ssim,
+        psnr: psnr(&a.to_rgb8(), &b.to_rgb8()),
+        visually_lossless: ssim >= VISUALLY_LOSSLESS_SSIM,
+        resized,
+        block_scores,
+        blocks_x,
+    }
+}
+
+// Per-block difference map, black where identical through red to yellow for the worst blocks
+pub fn heatmap(comparison: &Comparison, width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let index = (y / WINDOW) * comparison.blocks_x + (x / WINDOW);
+        let score = comparison.block_scores[index as usize];
+        let error = ((1.0 - score) * 4.0).clamp(0.0, 1.0);
+        let red = (error * 2.0).min(1.0);
+        let green = (error * 2.0 - 1.0).max(0.0);
+        Rgb([(red * 255.0) as u8, (green * 255.0) as u8, 0])
+    })
+}
+
+fn block_ssim(a: &GrayImage, b: &GrayImage, x0: u32, y0: u32) -> f64 {
+    let x1 = (x0 + WINDOW).min(a.width());
+    let y1 = (y0 + WINDOW).min(a.height());
+    let n = ((x1 - x0) * (y1 - y0)) as f64;
+
+    let (mut sum_a, mut sum_b) = (0.0, 0.0);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            sum_a += a.get_pixel(x, y)[0] as f64;
+            sum_b += b.get_pixel(x, y)[0] as f64;
+        }
+    }
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+
+    let (mut var_a, mut var_b, mut covar) = (0.0, 0.0, 0.0);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let da = a.get_pixel(x, y)[0] as f64 - mean_a;
+            let db = b.get_pixel(x, y)[0] as f64 - mean_b;
+            var_a += da * da;
+            var_b += db * db;
+            covar += da * db;
+        }
+    }
+    var_a /= n;
+    var_b /= n;
+    covar /= n;
+
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+
+fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(x, y)| {
+            let d = *x as f64 - *y as f64;
+            d * d
+        })
+        .sum();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb([
+                (x * 7 % 256) as u8,
+                (y * 5 % 256) as u8,
+                ((x + y) % 256) as u8,
+            ])
+        }))
+    }
+
+    #[test]
+    fn identical_images_score_perfectly() {
+        let image = gradient(20, 12);
+        let comparison = compare(&image, &image);
+        assert!((comparison.ssim - 1.0).abs() < 1e-9);
+        assert!((comparison.min_ssim - 1.0).abs() < 1e-9);
+        assert_eq!(comparison.psnr, f64::INFINITY);
+        assert!(comparison.visually_lossless);
+        assert!(!comparison.resized);
+        // Partial blocks on the right and bottom edges count too
+        assert_eq!(comparison.block_scores.len(), 3 * 2);
+    }
+
+    #[test]
+    fn a_damaged_block_is_the_worst_one() {
+        let image = gradient(32, 32);
+        let mut damaged = image.to_rgb8();
+        for y in 8..16 {
+            for x in 16..24 {
+                damaged.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        let comparison = compare(&image, &DynamicImage::ImageRgb8(damaged));
+        let worst = comparison
+            .block_scores
+            .iter()
+            .position(|score| *score == comparison.min_ssim)
+            .unwrap();
+        assert_eq!(worst, comparison.blocks_x as usize + 2);
+        assert!(comparison.psnr.is_finite());
+
+        let heatmap = heatmap(&comparison, 32, 32);
+        assert_eq!(heatmap.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_ne!(heatmap.get_pixel(20, 10).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn a_different_size_is_resized_to_match() {
+        let comparison = compare(&gradient(16, 16), &gradient(32, 32));
+        assert!(comparison.resized);
+        assert_eq!(comparison.block_scores.len(), 4);
+    }
+}
@@ -0,0 +1,366 @@
+use image::metadata::Orientation;
+use image::{
+    DynamicImage, GrayAlphaImage, GrayImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+    RgbImage, RgbaImage,
+};
+use std::io::Cursor;
+use std::path::Path;
+
+// Panoramas and scans above this size are decoded from a memory map, see
+// decode_large
+const LARGE_IMAGE_PIXELS: u64 = 100_000_000;
+// Past this, large images are downscaled while decoding so the pixels fit
+pub(super) const MAX_DECODED_PIXELS: u64 = 250_000_000;
+// Allocation cap for decoding a large image at full size: the pixels at up to
+// 16-bit RGBA
+const MAX_DECODE_BYTES: u64 = MAX_DECODED_PIXELS * 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    Heic,
+    Raw,
+    Jxl,
+    Svg,
+    Psd,
+    Standard(ImageFormat),
+}
+
+impl SourceFormat {
+    pub fn detect(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "heic" | "heif" => Ok(SourceFormat::Heic),
+            "jxl" => Ok(SourceFormat::Jxl),
+            "svg" => Ok(SourceFormat::Svg),
+            "psd" => Ok(SourceFormat::Psd),
+            ext if super::raw::is_raw_extension(ext) => Ok(SourceFormat::Raw),
+            _ => {
+                let reader = ImageReader::open(path)
+                    .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+                    .with_guessed_format()
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                reader
+                    .format()
+                    .map(SourceFormat::Standard)
+                    .ok_or_else(|| format!("Unsupported image format: {}", path.display()))
+            }
+        }
+    }
+
+    // Cheap extension-only check, for filtering directory listings and watcher events
+    pub fn is_supported(path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        matches!(extension.as_str(), "heic" | "heif" | "jxl" | "svg" | "psd")
+            || super::raw::is_raw_extension(&extension)
+            || ImageFormat::from_extension(&extension).is_some()
+    }
+
+    // Every extension detect() knows without sniffing the content
+    pub fn extensions() -> Vec<String> {
+        let mut extensions: Vec<String> = ["heic", "heif", "jxl", "svg", "psd"]
+            .iter()
+            .chain(super::raw::RAW_EXTENSIONS)
+            .map(|e| e.to_string())
+            .collect();
+        for format in ImageFormat::all().filter(|f| f.reading_enabled()) {
+            extensions.extend(format.extensions_str().iter().map(|e| e.to_string()));
+        }
+        extensions
+    }
+
+    // Mime type for formats the webview can render without conversion
+    pub fn web_mime_type(&self) -> Option<&'static str> {
+        match self {
+            SourceFormat::Standard(ImageFormat::Png) => Some("image/png"),
+            SourceFormat::Standard(ImageFormat::Jpeg) => Some("image/jpeg"),
+            SourceFormat::Standard(ImageFormat::WebP) => Some("image/webp"),
+            SourceFormat::Standard(ImageFormat::Gif) => Some("image/gif"),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode_image(path: &Path) -> Result<DynamicImage, String> {
+    match SourceFormat::detect(path)? {
+        SourceFormat::Heic => decode_heic(path),
+        SourceFormat::Raw => super::raw::decode(path),
+        SourceFormat::Jxl => super::jxl::decode(path),
+        SourceFormat::Svg => super::svg::decode(path),
+        SourceFormat::Psd => super::psd::decode(path),
+        SourceFormat::Standard(format) => {
+            if let Some((width, height)) = large_dimensions(path) {
+                return decode_large(path, format, width, height);
+            }
+            let mut reader = ImageReader::open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            reader.set_format(format);
+            reader
+                .decode()
+                .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+        }
+    }
+}
+
+fn large_dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::image_dimensions(path)
+        .ok()
+        .filter(|&(width, height)| width as u64 * height as u64 >= LARGE_IMAGE_PIXELS)
+}
+
+// Smallest whole factor that brings the image within MAX_DECODED_PIXELS
+fn downscale_factor(width: u32, height: u32) -> u32 {
+    let mut factor = 1;
+    while (width.div_ceil(factor) as u64) * (height.div_ceil(factor) as u64) > MAX_DECODED_PIXELS {
+        factor += 1;
+    }
+    factor
+}
+
+// Maps the file instead of reading it, so the compressed bytes stay in the page
+// cache rather than on the heap next to the decoded pixels. Up to
+// MAX_DECODED_PIXELS the image is decoded whole under an explicit allocation
+// cap. Past it, PNGs are read a row at a time and averaged down as they go,
+// and JPEGs use libjpeg-turbo's DCT scaling, so only the smaller image is ever
+// held; other formats can't be decoded that way and are refused.
+fn decode_large(
+    path: &Path,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    // Safety: the mapping is read-only and dropped before this function returns
+    let mmap = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|e| format!("Failed to map {}: {}", path.display(), e))?;
+
+    let factor = downscale_factor(width, height);
+    if factor == 1 {
+        tracing::debug!("Decoding large image {} from a memory map", path.display());
+        let mut reader = ImageReader::with_format(Cursor::new(&mmap[..]), format);
+        let mut limits = Limits::no_limits();
+        limits.max_alloc = Some(MAX_DECODE_BYTES);
+        reader.limits(limits);
+        return reader
+            .decode()
+            .map_err(|e| format!("Failed to decode {}: {}", path.display(), e));
+    }
+
+    tracing::warn!(
+        "{} is {}x{}, decoding it at 1/{} size",
+        path.display(),
+        width,
+        height,
+        factor
+    );
+    match format {
+        ImageFormat::Png => decode_png_downscaled(&mmap, factor),
+        ImageFormat::Jpeg => decode_jpeg_downscaled(&mmap, factor),
+        _ => Err(format!(
+            "{} is too large to decode ({}x{})",
+            path.display(),
+            width,
+            height
+        )),
+    }
+    .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+}
+
+// Averages each factor x factor block of source pixels into one, one source
+// row at a time
+fn decode_png_downscaled(data: &[u8], factor: u32) -> Result<DynamicImage, String> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    if reader.info().interlaced {
+        return Err("interlaced PNGs this large can't be downscaled".to_string());
+    }
+    let (color, _) = reader.output_color_type();
+    let channels = color.samples();
+    let (width, height) = (reader.info().width, reader.info().height);
+    let factor = factor as usize;
+    let out_width = width.div_ceil(factor as u32) as usize;
+    let out_height = height.div_ceil(factor as u32) as usize;
+
+    let mut pixels = Vec::with_capacity(out_width * out_height * channels);
+    let mut sums = vec![0u32; out_width * channels];
+    let mut counts = vec![0u32; out_width];
+    let mut y = 0;
+    while let Some(row) = reader.next_row().map_err(|e| e.to_string())? {
+        for (x, pixel) in row.data().chunks_exact(channels).enumerate() {
+            let out = x / factor;
+            counts[out] += 1;
+            for (sum, &value) in sums[out * channels..][..channels].iter_mut().zip(pixel) {
+                *sum += value as u32;
+            }
+        }
+        y += 1;
+        if y % factor == 0 || y == height as usize {
+            for (sums, &count) in sums.chunks_exact(channels).zip(&counts) {
+                pixels.extend(sums.iter().map(|&sum| (sum / count.max(1)) as u8));
+            }
+            sums.fill(0);
+            counts.fill(0);
+        }
+    }
+
+    let (out_width, out_height) = (out_width as u32, out_height as u32);
+    let image = match color {
+        png::ColorType::Grayscale => {
+            GrayImage::from_raw(out_width, out_height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        png::ColorType::GrayscaleAlpha => {
+            GrayAlphaImage::from_raw(out_width, out_height, pixels).map(DynamicImage::ImageLumaA8)
+        }
+        png::ColorType::Rgb => {
+            RgbImage::from_raw(out_width, out_height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        _ => RgbaImage::from_raw(out_width, out_height, pixels).map(DynamicImage::ImageRgba8),
+    };
+    image.ok_or_else(|| "PNG ended early".to_string())
+}
+
+// DCT scaling only goes down to 1/8, in powers of two
+fn decode_jpeg_downscaled(data: &[u8], factor: u32) -> Result<DynamicImage, String> {
+    let denom = factor.next_power_of_two();
+    if denom > 8 {
+        return Err("JPEG is too large to downscale while decoding".to_string());
+    }
+    let scaling = turbojpeg::ScalingFactor::new(1, denom as usize);
+    let mut decompressor = turbojpeg::Decompressor::new().map_err(|e| e.to_string())?;
+    decompressor
+        .set_scaling_factor(scaling)
+        .map_err(|e| e.to_string())?;
+    let header = decompressor
+        .read_header(data)
+        .map_err(|e| e.to_string())?
+        .scaled(scaling);
+
+    let mut pixels = vec![0; 3 * header.width * header.height];
+    let image = turbojpeg::Image {
+        pixels: &mut pixels[..],
+        width: header.width,
+        pitch: 3 * header.width,
+        height: header.height,
+        format: turbojpeg::PixelFormat::RGB,
+    };
+    decompressor
+        .decompress(data, image)
+        .map_err(|e| e.to_string())?;
+    RgbImage::from_raw(header.width as u32, header.height as u32, pixels)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| "JPEG has an unexpected size".to_string())
+}
+
+// EXIF orientation of the file, NoTransforms when it has none or can't be read
+pub fn read_orientation(path: &Path) -> Orientation {
+    ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+#[cfg(feature = "heic")]
+fn decode_heic(path: &Path) -> Result<DynamicImage, String> {
+    super::heic::decode(path)
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heic(path: &Path) -> Result<DynamicImage, String> {
+    Err(format!(
+        "HEIC support is not enabled in this build: {}",
+        path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-decode-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn known_extensions_are_detected_without_reading() {
+        let missing = Path::new("/no/such/photo.HEIC");
+        assert_eq!(SourceFormat::detect(missing), Ok(SourceFormat::Heic));
+        assert_eq!(
+            SourceFormat::detect(Path::new("/no/such/photo.heif")),
+            Ok(SourceFormat::Heic)
+        );
+        assert!(SourceFormat::detect(Path::new("/no/such/photo.png")).is_err());
+    }
+
+    #[test]
+    fn standard_formats_are_sniffed_from_content() {
+        let dir = temp_dir("sniff");
+        let path = dir.join("mislabelled.jpg");
+        RgbImage::new(2, 2)
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+
+        let format = SourceFormat::detect(&path).unwrap();
+        assert_eq!(format, SourceFormat::Standard(ImageFormat::Png));
+        assert_eq!(format.web_mime_type(), Some("image/png"));
+        assert_eq!(SourceFormat::Heic.web_mime_type(), None);
+        assert_eq!(decode_image(&path).unwrap().width(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn supported_files_are_judged_by_extension() {
+        for name in [
+            "a.PNG", "a.jpeg", "a.heic", "a.svg", "a.psd", "a.nef", "a.jxl",
+        ] {
+            assert!(SourceFormat::is_supported(Path::new(name)), "{}", name);
+        }
+        for name in ["a.txt", "a.pdf", "a"] {
+            assert!(!SourceFormat::is_supported(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn huge_images_are_downscaled_by_the_smallest_factor() {
+        assert_eq!(downscale_factor(10_000, 10_000), 1);
+        assert_eq!(downscale_factor(20_000, 20_000), 2);
+        assert_eq!(downscale_factor(40_000, 40_000), 3);
+    }
+
+    #[test]
+    fn downscaled_pngs_average_each_block() {
+        let image = GrayImage::from_fn(3, 3, |x, y| image::Luma([(x * 10 + y * 100) as u8]));
+        let mut data = Vec::new();
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+
+        let decoded = decode_png_downscaled(&data, 2).unwrap().into_luma8();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        // Edge blocks average only the pixels they have
+        assert_eq!(decoded.into_raw(), vec![55, 70, 205, 220]);
+    }
+
+    #[test]
+    fn extensions_cover_every_detected_format() {
+        let extensions = SourceFormat::extensions();
+        for extension in ["heic", "jxl", "svg", "psd", "dng", "png", "jpg", "webp"] {
+            assert!(extensions.iter().any(|e| e == extension), "{}", extension);
+        }
+        assert!(extensions
+            .iter()
+            .all(|e| SourceFormat::is_supported(Path::new(&format!("a.{}", e)))));
+    }
+}
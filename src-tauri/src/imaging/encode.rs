@@ -0,0 +1,179 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{DynamicImage, ImageEncoder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+    Jxl,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jxl => "jxl",
+        }
+    }
+}
+
+pub fn encode_image(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<u8>, String> {
+    let quality = quality.clamp(1, 100);
+    let mut bytes = Vec::new();
+
+    match format {
+        OutputFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut bytes, quality)
+                .encode_image(&rgb)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        }
+        OutputFormat::Png => {
+            let rgba = image.to_rgba8();
+            PngEncoder::new(&mut bytes)
+                .write_image(
+                    rgba.as_raw(),
+                    rgba.width(),
+                    rgba.height(),
+                    image::ExtendedColorType::Rgba8,
+                )
+                .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        }
+        OutputFormat::Webp => {
+            let rgba = DynamicImage::ImageRgba8(image.to_rgba8());
+            let encoder = webp::Encoder::from_image(&rgba)
+                .map_err(|e| format!("Failed to prepare WebP: {}", e))?;
+            bytes = encoder.encode(quality as f32).to_vec();
+        }
+        OutputFormat::Jxl => {
+            bytes = super::jxl::encode(image, quality)?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+// Scan layout toggles. Both are applied as a lossless pass over the plain
+// encode, so the pixels are identical either way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeSettings {
+    pub progressive: bool,
+    pub interlaced: bool,
+}
+
+// Size cost (or saving) of the progressive/interlaced layout versus baseline.
+// Progressive JPEG is usually a few percent smaller above ~10 KB, while Adam7
+// PNG is almost always larger.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutImpact {
+    pub baseline_bytes: u64,
+    pub output_bytes: u64,
+    pub larger: bool,
+}
+
+pub fn encode_image_with(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    settings: EncodeSettings,
+) -> Result<(Vec<u8>, Option<LayoutImpact>), String> {
+    let encoded = encode_image(image, format, quality)?;
+
+    // The baseline goes through the same pass with the layout left alone, so
+    // the impact is the layout's alone and not what the pass itself saves
+    let (baseline, relaid) = match format {
+        OutputFormat::Jpeg if settings.progressive => {
+            (jpeg_pass(&encoded, false)?, jpeg_pass(&encoded, true)?)
+        }
+        OutputFormat::Png if settings.interlaced => {
+            (png_pass(&encoded, false)?, png_pass(&encoded, true)?)
+        }
+        _ => return Ok((encoded, None)),
+    };
+
+    let impact = LayoutImpact {
+        baseline_bytes: baseline.len() as u64,
+        output_bytes: relaid.len() as u64,
+        larger: relaid.len() > baseline.len(),
+    };
+    Ok((relaid, Some(impact)))
+}
+
+// Progressive scans always get optimized Huffman tables, so sequential ones
+// do too
+fn jpeg_pass(jpeg: &[u8], progressive: bool) -> Result<Vec<u8>, String> {
+    let mut transform = turbojpeg::Transform::default();
+    transform.optimize = true;
+    transform.progressive = progressive;
+    turbojpeg::transform(&transform, jpeg)
+        .map(|buf| buf.to_vec())
+        .map_err(|e| format!("Failed to re-lay JPEG scans: {}", e))
+}
+
+fn png_pass(png: &[u8], interlaced: bool) -> Result<Vec<u8>, String> {
+    let mut options = oxipng::Options::from_preset(2);
+    options.interlace = Some(if interlaced {
+        oxipng::Interlacing::Adam7
+    } else {
+        oxipng::Interlacing::None
+    });
+    oxipng::optimize_from_memory(png, &options).map_err(|e| format!("Failed to re-lay PNG: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, 90])
+        }))
+    }
+
+    #[test]
+    fn interlacing_keeps_the_pixels() {
+        let image = gradient();
+        let settings = EncodeSettings {
+            interlaced: true,
+            ..Default::default()
+        };
+        let (png, impact) = encode_image_with(&image, OutputFormat::Png, 100, settings).unwrap();
+        // The IHDR interlace method byte
+        assert_eq!(png[28], 1);
+        let impact = impact.unwrap();
+        assert_eq!(impact.output_bytes, png.len() as u64);
+        assert_eq!(impact.larger, impact.output_bytes > impact.baseline_bytes);
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn the_plain_encode_reports_no_impact() {
+        let (_, impact) = encode_image_with(
+            &gradient(),
+            OutputFormat::Png,
+            100,
+            EncodeSettings::default(),
+        )
+        .unwrap();
+        assert!(impact.is_none());
+        let settings = EncodeSettings {
+            interlaced: true,
+            ..Default::default()
+        };
+        let (_, impact) = encode_image_with(&gradient(), OutputFormat::Jpeg, 80, settings).unwrap();
+        assert!(impact.is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use image::DynamicImage;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::target::Finish;
+use super::{compare, encode_image, OutputFormat};
+
+// Tiles are cut at full resolution so texture and noise match the real encode
+const TILE_SIZE: u32 = 256;
+const GRID: u32 = 3;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estimate {
+    pub input_bytes: u64,
+    pub estimated_bytes: u64,
+    // Mean SSIM of the sampled tiles, None when the output format can't be decoded back
+    pub estimated_ssim: Option<f64>,
+    // Share of the image's pixels that were actually encoded
+    pub sampled_fraction: f32,
+}
+
+pub fn estimate(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    input_bytes: u64,
+    finish: &Finish,
+) -> Result<Estimate, String> {
+    let total_pixels = image.width() as u64 * image.height() as u64;
+    let tiles = sample_tiles(image);
+
+    // Every tile pays the container/header cost once; measure it so it isn't
+    // extrapolated across the whole image
+    let blank = encode_image(&DynamicImage::new_rgb8(8, 8), format, quality)?;
+    let overhead = blank.len() as u64;
+    // The output pays it once too, along with the metadata the profile keeps
+    let fixed = finish(blank)?.len() as u64;
+
+    // Tiles are encoded in parallel on whatever pool this runs in
+    let measured = tiles
+        .par_iter()
+        .map(|tile| {
+            let encoded = encode_image(tile, format, quality)?;
+            let ssim = image::load_from_memory(&encoded)
+                .ok()
+                .map(|decoded| compare::compare(tile, &decoded).ssim);
+            Ok((
+                tile.width() as u64 * tile.height() as u64,
+                encoded.len() as u64,
+                ssim,
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut sampled_pixels = 0u64;
+    let mut payload_bytes = 0u64;
+    let mut ssim_total = 0.0;
+    let mut ssim_count = 0;
+    for (pixels, bytes, ssim) in measured {
+        sampled_pixels += pixels;
+        payload_bytes += bytes.saturating_sub(overhead);
+        if let Some(ssim) = ssim {
+            ssim_total += ssim;
+            ssim_count += 1;
+        }
+    }
+
+    let bytes_per_pixel = payload_bytes as f64 / sampled_pixels.max(1) as f64;
+    Ok(Estimate {
+        input_bytes,
+        estimated_bytes: fixed + (bytes_per_pixel * total_pixels as f64).round() as u64,
+        estimated_ssim: (ssim_count == tiles.len() && ssim_count > 0)
+            .then(|| ssim_total / ssim_count as f64),
+        sampled_fraction: (sampled_pixels as f64 / total_pixels.max(1) as f64).min(1.0) as f32,
+    })
+}
+
+// A GRID x GRID spread of tiles, or the whole image when it's already small
+fn sample_tiles(image: &DynamicImage) -> Vec<DynamicImage> {
+    let (width, height) = (image.width(), image.height());
+    if width <= TILE_SIZE * GRID && height <= TILE_SIZE * GRID {
+        return vec![image.clone()];
+    }
+
+    let tile_w = TILE_SIZE.min(width);
+    let tile_h = TILE_SIZE.min(height);
+    let mut tiles = Vec::with_capacity((GRID * GRID) as usize);
+    for row in 0..GRID {
+        for col in 0..GRID {
+            let x = (width - tile_w) * col / (GRID - 1);
+            let y = (height - tile_h) * row / (GRID - 1);
+            tiles.push(image.crop_imm(x, y, tile_w, tile_h));
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            let v = (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) as u8;
+            image::Rgb([v, v.wrapping_mul(3), v.wrapping_add(y as u8)])
+        }))
+    }
+
+    #[test]
+    fn small_images_are_sampled_whole() {
+        assert_eq!(sample_tiles(&noise(300, 200)).len(), 1);
+    }
+
+    #[test]
+    fn large_images_sample_a_spread_grid() {
+        let image = noise(TILE_SIZE * 4, TILE_SIZE * GRID + 1);
+        let tiles = sample_tiles(&image);
+        assert_eq!(tiles.len(), (GRID * GRID) as usize);
+        assert!(tiles
+            .iter()
+            .all(|t| t.width() == TILE_SIZE && t.height() == TILE_SIZE));
+    }
+
+    #[test]
+    fn a_single_tile_estimate_is_the_real_size() {
+        let image = noise(120, 80);
+        let estimate = estimate(&image, OutputFormat::Jpeg, 80, 1, &Ok).unwrap();
+        let actual = encode_image(&image, OutputFormat::Jpeg, 80).unwrap().len();
+        assert_eq!(estimate.estimated_bytes, actual as u64);
+        assert_eq!(estimate.sampled_fraction, 1.0);
+        assert!(estimate.estimated_ssim.is_some_and(|ssim| ssim < 1.0));
+    }
+}
@@ -0,0 +1,44 @@
+use image::{DynamicImage, RgbImage, RgbaImage};
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+use std::path::Path;
+
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let lib_heif = LibHeif::new();
+    let context = HeifContext::read_from_file(&path.to_string_lossy())
+        .map_err(|e| format!("Failed to open HEIC {}: {}", path.display(), e))?;
+    let handle = context
+        .primary_image_handle()
+        .map_err(|e| format!("Failed to read HEIC image {}: {}", path.display(), e))?;
+
+    let has_alpha = handle.has_alpha_channel();
+    let chroma = if has_alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    let decoded = lib_heif
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(|e| format!("Failed to decode HEIC {}: {}", path.display(), e))?;
+
+    let width = decoded.width();
+    let height = decoded.height();
+    let planes = decoded.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| format!("HEIC {} has no interleaved plane", path.display()))?;
+
+    // libheif pads rows, so copy row by row without the stride padding
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_bytes = width as usize * channels;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+
+    let image = if has_alpha {
+        RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    };
+    image.ok_or_else(|| format!("HEIC {} has an unexpected buffer size", path.display()))
+}
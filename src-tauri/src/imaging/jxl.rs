@@ -0,0 +1,110 @@
+use image::DynamicImage;
+use std::path::Path;
+
+#[cfg(feature = "jxl")]
+use image::{RgbImage, RgbaImage};
+#[cfg(feature = "jxl")]
+use jpegxl_rs::{decode::Data, decoder_builder, encoder_builder};
+
+// Same quality -> Butteraugli distance mapping libjxl uses for cjxl -q
+#[cfg(feature = "jxl")]
+pub fn distance_from_quality(quality: u8) -> f32 {
+    let quality = quality as f32;
+    if quality >= 30.0 {
+        0.1 + (100.0 - quality) * 0.09
+    } else {
+        53.0 / 3000.0 * quality * quality - 23.0 / 20.0 * quality + 25.0
+    }
+}
+
+#[cfg(feature = "jxl")]
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let decoder = decoder_builder()
+        .build()
+        .map_err(|e| format!("Failed to create JXL decoder: {}", e))?;
+    let (metadata, pixels) = decoder
+        .decode_with::<u8>(&data)
+        .map_err(|e| format!("Failed to decode JXL {}: {}", path.display(), e))?;
+
+    let image = if metadata.has_alpha_channel {
+        RgbaImage::from_raw(metadata.width, metadata.height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(metadata.width, metadata.height, pixels).map(DynamicImage::ImageRgb8)
+    };
+    image.ok_or_else(|| format!("JXL {} has an unexpected buffer size", path.display()))
+}
+
+#[cfg(feature = "jxl")]
+pub fn encode(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let has_alpha = image.color().has_alpha();
+    let mut encoder = encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(quality >= 100)
+        .quality(distance_from_quality(quality))
+        .build()
+        .map_err(|e| format!("Failed to create JXL encoder: {}", e))?;
+
+    let result = if has_alpha {
+        let rgba = image.to_rgba8();
+        encoder.encode::<u8, u8>(rgba.as_raw(), rgba.width(), rgba.height())
+    } else {
+        let rgb = image.to_rgb8();
+        encoder.encode::<u8, u8>(rgb.as_raw(), rgb.width(), rgb.height())
+    };
+    result
+        .map(|encoded| encoded.data)
+        .map_err(|e| format!("Failed to encode JXL: {}", e))
+}
+
+// Repacks an existing JPEG bitstream into JXL without touching the DCT
+// coefficients, so the original file can be rebuilt bit for bit later
+#[cfg(feature = "jxl")]
+pub fn transcode_jpeg(jpeg: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = encoder_builder()
+        .use_container(true)
+        .build()
+        .map_err(|e| format!("Failed to create JXL encoder: {}", e))?;
+    encoder
+        .encode_jpeg(jpeg)
+        .map(|encoded: jpegxl_rs::encode::EncoderResult<u8>| encoded.data)
+        .map_err(|e| format!("Failed to transcode JPEG to JXL: {}", e))
+}
+
+#[cfg(feature = "jxl")]
+pub fn reconstruct_jpeg(jxl: &[u8]) -> Result<Vec<u8>, String> {
+    let decoder = decoder_builder()
+        .build()
+        .map_err(|e| format!("Failed to create JXL decoder: {}", e))?;
+    match decoder.reconstruct(jxl) {
+        Ok((_, Data::Jpeg(jpeg))) => Ok(jpeg),
+        Ok((_, Data::Pixels(_))) => {
+            Err("JXL file was not created from a JPEG and cannot be reconstructed".to_string())
+        }
+        Err(e) => Err(format!("Failed to reconstruct JPEG: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    Err(format!(
+        "JPEG XL support is not enabled in this build: {}",
+        path.display()
+    ))
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn encode(_image: &DynamicImage, _quality: u8) -> Result<Vec<u8>, String> {
+    Err("JPEG XL support is not enabled in this build".to_string())
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn transcode_jpeg(_jpeg: &[u8]) -> Result<Vec<u8>, String> {
+    Err("JPEG XL support is not enabled in this build".to_string())
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn reconstruct_jpeg(_jxl: &[u8]) -> Result<Vec<u8>, String> {
+    Err("JPEG XL support is not enabled in this build".to_string())
+}
@@ -0,0 +1,324 @@
+use exif::{Context, Field, In, Tag};
+use img_parts::jpeg::{markers, Jpeg, JpegSegment};
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+
+const XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MetadataPolicy {
+    // Matches what re-encoding has always done
+    #[default]
+    StripAll,
+    // Copyright/artist credit and the ICC color profile
+    KeepCopyrightAndColor,
+    // Everything except GPS location data
+    KeepAllExceptGps,
+    KeepAll,
+}
+
+// Metadata blocks pulled from the source file before it's re-encoded
+#[derive(Default)]
+pub struct SourceMetadata {
+    exif: Option<Bytes>,
+    icc: Option<Bytes>,
+    xmp: Option<Bytes>,
+}
+
+pub fn read(path: &Path) -> SourceMetadata {
+    let Ok(data) = std::fs::read(path) else {
+        return SourceMetadata::default();
+    };
+    let Ok(Some(image)) = DynImage::from_bytes(Bytes::from(data)) else {
+        return SourceMetadata::default();
+    };
+
+    let xmp = match &image {
+        DynImage::Jpeg(jpeg) => jpeg
+            .segments()
+            .iter()
+            .find(|s| s.marker() == markers::APP1 && s.contents().starts_with(XMP_SIGNATURE))
+            .map(|s| s.contents().clone()),
+        _ => None,
+    };
+
+    SourceMetadata {
+        exif: image.exif(),
+        icc: image.icc_profile(),
+        xmp,
+    }
+}
+
+// GPS coordinates in the EXIF block or in XMP
+pub fn has_location(path: &Path) -> bool {
+    let source = read(path);
+    let in_exif = source
+        .exif
+        .and_then(|raw| exif::Reader::new().read_raw(raw.to_vec()).ok())
+        .is_some_and(|exif| {
+            exif.fields()
+                .any(|f| f.tag.context() == Context::Gps && !is_structural(f.tag))
+        });
+    in_exif || source.xmp.is_some_and(|xmp| contains(&xmp, b"exif:GPS"))
+}
+
+// Writes the metadata the policy allows into freshly encoded JPEG/PNG/WebP bytes.
+// Other formats are returned untouched.
+pub fn apply(
+    encoded: Vec<u8>,
+    source: &SourceMetadata,
+    policy: MetadataPolicy,
+) -> Result<Vec<u8>, String> {
+    if policy == MetadataPolicy::StripAll {
+        return Ok(encoded);
+    }
+    let Ok(Some(mut image)) = DynImage::from_bytes(Bytes::from(encoded.clone())) else {
+        return Ok(encoded);
+    };
+
+    let exif = match &source.exif {
+        Some(raw) => filter_exif(raw, policy)?,
+        None => None,
+    };
+    image.set_exif(exif);
+    image.set_icc_profile(source.icc.clone());
+
+    if let (DynImage::Jpeg(jpeg), Some(xmp)) = (&mut image, &source.xmp) {
+        let keep_xmp = match policy {
+            MetadataPolicy::KeepAll => true,
+            // XMP can carry its own copy of the coordinates
+            MetadataPolicy::KeepAllExceptGps => !contains(xmp, b"exif:GPS"),
+            _ => false,
+        };
+        if keep_xmp {
+            insert_xmp(jpeg, xmp.clone());
+        }
+    }
+
+    Ok(image.encoder().bytes().to_vec())
+}
+
+fn filter_exif(raw: &Bytes, policy: MetadataPolicy) -> Result<Option<Bytes>, String> {
+    let exif = exif::Reader::new()
+        .read_raw(raw.to_vec())
+        .map_err(|e| format!("Failed to parse EXIF: {}", e))?;
+
+    let kept: Vec<&Field> = exif
+        .fields()
+        .filter(|f| f.ifd_num == In::PRIMARY && !is_structural(f.tag))
+        // The pixels were turned upright before encoding, so the tag would
+        // turn them a second time
+        .filter(|f| f.tag != Tag::Orientation)
+        .filter(|f| match policy {
+            MetadataPolicy::KeepAll => true,
+            MetadataPolicy::KeepAllExceptGps => f.tag.context() != Context::Gps,
+            MetadataPolicy::KeepCopyrightAndColor => {
+                matches!(f.tag, Tag::Copyright | Tag::Artist | Tag::ColorSpace)
+            }
+            MetadataPolicy::StripAll => false,
+        })
+        .collect();
+
+    if kept.is_empty() {
+        return Ok(None);
+    }
+
+    let mut writer = exif::experimental::Writer::new();
+    for field in &kept {
+        writer.push_field(field);
+    }
+    let mut out = Cursor::new(Vec::new());
+    writer
+        .write(&mut out, exif.little_endian())
+        .map_err(|e| format!("Failed to write EXIF: {}", e))?;
+    Ok(Some(Bytes::from(out.into_inner())))
+}
+
+// Puts back what changes how a losslessly repacked file is shown, its ICC
+// profile and EXIF orientation, after the pass stripped everything else.
+// Without them the "identical" pixels would render in other colors or turned.
+pub fn restore_rendering(original: &[u8], stripped: Vec<u8>) -> Result<Vec<u8>, String> {
+    let Ok(Some(source)) = DynImage::from_bytes(Bytes::copy_from_slice(original)) else {
+        return Ok(stripped);
+    };
+    let Ok(Some(mut image)) = DynImage::from_bytes(Bytes::from(stripped.clone())) else {
+        return Ok(stripped);
+    };
+    let orientation = match source.exif() {
+        Some(raw) => orientation_only(&raw)?,
+        None => None,
+    };
+    image.set_exif(orientation);
+    image.set_icc_profile(source.icc_profile());
+    Ok(image.encoder().bytes().to_vec())
+}
+
+// An EXIF block holding just the orientation, or None when it's upright anyway
+fn orientation_only(raw: &Bytes) -> Result<Option<Bytes>, String> {
+    let exif = exif::Reader::new()
+        .read_raw(raw.to_vec())
+        .map_err(|e| format!("Failed to parse EXIF: {}", e))?;
+    let Some(field) = exif
+        .get_field(Tag::Orientation, In::PRIMARY)
+        .filter(|f| f.value.get_uint(0).is_some_and(|v| v != 1))
+    else {
+        return Ok(None);
+    };
+    let mut writer = exif::experimental::Writer::new();
+    writer.push_field(field);
+    let mut out = Cursor::new(Vec::new());
+    writer
+        .write(&mut out, exif.little_endian())
+        .map_err(|e| format!("Failed to write EXIF: {}", e))?;
+    Ok(Some(Bytes::from(out.into_inner())))
+}
+
+// Offsets and IFD pointers are regenerated by the writer
+fn is_structural(tag: Tag) -> bool {
+    matches!(
+        tag,
+        Tag::ExifIFDPointer
+            | Tag::GPSInfoIFDPointer
+            | Tag::InteropIFDPointer
+            | Tag::JPEGInterchangeFormat
+            | Tag::JPEGInterchangeFormatLength
+            | Tag::StripOffsets
+            | Tag::StripByteCounts
+            | Tag::TileOffsets
+            | Tag::TileByteCounts
+    )
+}
+
+fn insert_xmp(jpeg: &mut Jpeg, xmp: Bytes) {
+    // Right after the leading APP segments, where readers expect it
+    let position = jpeg
+        .segments()
+        .iter()
+        .position(|s| !(markers::APP0..=markers::APP15).contains(&s.marker()))
+        .unwrap_or(0);
+    jpeg.segments_mut()
+        .insert(position, JpegSegment::new_with_contents(markers::APP1, xmp));
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::Value;
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn ascii(text: &str) -> Value {
+        Value::Ascii(vec![text.as_bytes().to_vec()])
+    }
+
+    fn exif_with(fields: &[Field]) -> Bytes {
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut out = Cursor::new(Vec::new());
+        writer.write(&mut out, false).unwrap();
+        Bytes::from(out.into_inner())
+    }
+
+    fn tags(raw: Option<Bytes>) -> Vec<Tag> {
+        raw.map(|raw| {
+            let exif = exif::Reader::new().read_raw(raw.to_vec()).unwrap();
+            exif.fields()
+                .map(|f| f.tag)
+                .filter(|tag| !is_structural(*tag))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    fn sample() -> Bytes {
+        exif_with(&[
+            field(Tag::Orientation, Value::Short(vec![6])),
+            field(Tag::Artist, ascii("Ann")),
+            field(Tag::Make, ascii("Camera")),
+            field(Tag::GPSLatitudeRef, ascii("N")),
+        ])
+    }
+
+    #[test]
+    fn policies_keep_what_they_name() {
+        let raw = sample();
+        let kept = |policy| tags(filter_exif(&raw, policy).unwrap());
+        assert!(kept(MetadataPolicy::StripAll).is_empty());
+        assert_eq!(kept(MetadataPolicy::KeepCopyrightAndColor), [Tag::Artist]);
+        assert_eq!(
+            kept(MetadataPolicy::KeepAllExceptGps),
+            [Tag::Make, Tag::Artist]
+        );
+        assert!(kept(MetadataPolicy::KeepAll).contains(&Tag::GPSLatitudeRef));
+    }
+
+    #[test]
+    fn orientation_is_never_copied_onto_upright_pixels() {
+        let raw = sample();
+        for policy in [
+            MetadataPolicy::KeepCopyrightAndColor,
+            MetadataPolicy::KeepAllExceptGps,
+            MetadataPolicy::KeepAll,
+        ] {
+            assert!(!tags(filter_exif(&raw, policy).unwrap()).contains(&Tag::Orientation));
+        }
+    }
+
+    #[test]
+    fn xmp_location_counts_as_location() {
+        assert!(contains(b"<x exif:GPSLatitude='1'/>", b"exif:GPS"));
+        assert!(!contains(b"<x exif:Make='1'/>", b"exif:GPS"));
+    }
+
+    fn png_with(exif: Option<Bytes>, icc: Option<Bytes>) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(2, 2)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let mut image = DynImage::from_bytes(Bytes::from(png)).unwrap().unwrap();
+        image.set_exif(exif);
+        image.set_icc_profile(icc);
+        image.encoder().bytes().to_vec()
+    }
+
+    fn parts(data: Vec<u8>) -> (Option<Bytes>, Option<Bytes>) {
+        let image = DynImage::from_bytes(Bytes::from(data)).unwrap().unwrap();
+        (image.exif(), image.icc_profile())
+    }
+
+    #[test]
+    fn stripping_keeps_the_profile_and_orientation() {
+        let icc = Bytes::from_static(b"not really a profile");
+        let original = png_with(Some(sample()), Some(icc.clone()));
+        let restored = restore_rendering(&original, png_with(None, None)).unwrap();
+        let (exif, profile) = parts(restored);
+        assert_eq!(tags(exif), [Tag::Orientation]);
+        assert_eq!(profile, Some(icc));
+    }
+
+    #[test]
+    fn an_upright_image_gets_no_exif_back() {
+        let upright = exif_with(&[
+            field(Tag::Orientation, Value::Short(vec![1])),
+            field(Tag::Artist, ascii("Ann")),
+        ]);
+        let original = png_with(Some(upright), None);
+        let restored = restore_rendering(&original, png_with(None, None)).unwrap();
+        assert_eq!(parts(restored), (None, None));
+    }
+}
@@ -0,0 +1,911 @@
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::ipc::Response;
+use tauri::{AppHandle, Manager, State};
+
+use crate::blocking;
+use crate::cancel::{self, CancelToken, PartialOutput};
+use crate::db::Db;
+use crate::error::Error;
+use crate::fonts::embedded::{self, ProjectFont};
+use crate::importers::{self, ImportKind};
+use crate::job_history::record_compress;
+use crate::memory::measure_peak;
+use crate::notifications;
+use crate::perf;
+use crate::permissions::{self, Capability};
+use crate::safe_path::{self, SafePath};
+use crate::workers::{install, run_parallel, WorkerConfig};
+
+mod alpha;
+mod blurhash;
+mod compare;
+mod decode;
+mod encode;
+mod estimate;
+#[cfg(feature = "heic")]
+mod heic;
+mod jxl;
+mod lossless;
+mod metadata;
+mod palette;
+mod psd;
+mod raw;
+mod resize;
+mod sprites;
+mod svg;
+mod target;
+mod transform;
+
+pub use alpha::{flatten, AlphaStats};
+pub use compare::Comparison;
+pub use decode::{decode_image, read_orientation, SourceFormat};
+pub use encode::{encode_image, encode_image_with, EncodeSettings, LayoutImpact, OutputFormat};
+pub use estimate::Estimate;
+pub use lossless::LosslessOptions;
+pub use metadata::MetadataPolicy;
+pub use palette::Palette;
+pub use resize::{resize, ResizeSpec};
+pub use sprites::{PackOptions, PackResult};
+pub use svg::render as render_svg;
+pub use target::TargetOutcome;
+pub use transform::transform_image;
+
+// Image handed back to the frontend when a file is dropped or opened
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedImage {
+    pub name: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    // Id of the importer that handled it, e.g. "image", "pdf" or "plugin:<id>"
+    pub importer: String,
+    pub kind: ImportKind,
+    pub detail: Option<String>,
+    // Fonts found in the document, registered with the project
+    pub embedded_fonts: Vec<ProjectFont>,
+}
+
+type Imported = (PathBuf, Vec<u8>);
+
+// Bytes converted by import_image, waiting to be fetched by read_image_data.
+// Bounded in case the UI never asks.
+#[derive(Default, Clone)]
+pub struct ImportCache(Arc<Mutex<VecDeque<Imported>>>);
+
+const IMPORT_CACHE_SIZE: usize = 8;
+
+impl ImportCache {
+    fn put(&self, path: &Path, data: Vec<u8>) {
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(p, _)| p != path);
+        if entries.len() >= IMPORT_CACHE_SIZE {
+            entries.pop_front();
+        }
+        entries.push_back((path.to_path_buf(), data));
+    }
+
+    fn take(&self, path: &Path) -> Option<Vec<u8>> {
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let index = entries.iter().position(|(p, _)| p == path)?;
+        entries.remove(index).map(|(_, data)| data)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        let entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().map(|(_, data)| data.len() as u64).sum()
+    }
+
+    // Drops the oldest entry, returning how many bytes it freed
+    pub fn evict_oldest(&self) -> Option<u64> {
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        entries.pop_front().map(|(_, data)| data.len() as u64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressOptions {
+    pub format: OutputFormat,
+    pub quality: u8,
+    pub output_dir: Option<String>,
+    // Repack JPEG inputs losslessly when converting to JXL instead of re-encoding pixels
+    #[serde(default)]
+    pub jpeg_transcode: bool,
+    // Compute a BlurHash placeholder for each output during batch jobs
+    #[serde(default)]
+    pub blurhash: bool,
+    // Maximum output size; quality (and optionally dimensions) are searched to fit it
+    pub target_bytes: Option<u64>,
+    #[serde(default)]
+    pub allow_resize: bool,
+    // Progressive JPEG / Adam7 PNG; ignored in target-size and JXL transcode modes
+    #[serde(default)]
+    pub progressive: bool,
+    #[serde(default)]
+    pub interlaced: bool,
+    #[serde(default)]
+    pub metadata: MetadataPolicy,
+    // Applied before encoding; disables JXL transcoding since pixels change
+    pub resize: Option<ResizeSpec>,
+    // #rrggbb color transparent pixels are flattened onto for formats without
+    // alpha (JPEG); white when omitted
+    pub background: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressResult {
+    pub input_path: String,
+    pub output_path: String,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub blurhash: Option<String>,
+    // Size of the pixels the BlurHash was computed from, stored alongside it
+    #[serde(skip)]
+    pub blurhash_size: Option<(u32, u32)>,
+    pub target: Option<TargetOutcome>,
+    // Heap high-water mark of the job, decoded pixels included
+    pub peak_memory_bytes: u64,
+    pub layout_impact: Option<LayoutImpact>,
+    // Set when real transparency was flattened away, so the UI can warn
+    pub flattened_alpha: Option<AlphaStats>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchEntry {
+    pub input_path: String,
+    pub result: Option<CompressResult>,
+    pub error: Option<String>,
+}
+
+// Metadata only; the bytes come from read_image_data as a raw binary response
+// instead of a JSON array of numbers. Which importer handles the file is up to
+// the importers registry. Fonts embedded in the document are registered with
+// the project (or just this session, without one).
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn import_image(
+    app: AppHandle,
+    cache: State<'_, ImportCache>,
+    path: String,
+    project_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<ImportedImage, Error> {
+    let cache = cache.inner().clone();
+    blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let path = SafePath::read(&app, &path)?.into_path_buf();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (importer, imported) = importers::import(&app, &path, operation.token())?;
+
+        // Converted bytes are kept for the read_image_data call that follows
+        if let Some(data) = imported.data {
+            cache.put(&path, data);
+        }
+        let embedded_fonts = if imported.fonts.is_empty() {
+            Vec::new()
+        } else {
+            let project_id = project_id.as_deref();
+            embedded::register(&app, project_id, &path, &importer.id, imported.fonts)
+                .unwrap_or_else(|e| {
+                    tracing::warn!("{}", e);
+                    Vec::new()
+                })
+        };
+
+        tracing::info!(
+            "Imported {} ({}, {}x{})",
+            name,
+            importer.id,
+            imported.width,
+            imported.height
+        );
+
+        Ok(ImportedImage {
+            name,
+            mime_type: imported.mime_type,
+            width: imported.width,
+            height: imported.height,
+            importer: importer.id,
+            kind: importer.kind,
+            detail: imported.detail,
+            embedded_fonts,
+        })
+    })
+    .await
+}
+
+// The displayable bytes of an imported image, in the mime type import_image
+// reported
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn read_image_data(
+    app: AppHandle,
+    cache: State<'_, ImportCache>,
+    path: String,
+) -> Result<Response, Error> {
+    let cache = cache.inner().clone();
+    blocking::run(move || {
+        let path = SafePath::read(&app, &path)?.into_path_buf();
+        let cached = cache.take(&path);
+        perf::cache("import", cached.is_some());
+        if let Some(data) = cached {
+            return Ok(Response::new(data));
+        }
+        let data = match importers::import(&app, &path, &CancelToken::default())?
+            .1
+            .data
+        {
+            Some(data) => data,
+            None => std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        };
+        Ok(Response::new(data))
+    })
+    .await
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compress_image(
+    app: AppHandle,
+    path: String,
+    options: CompressOptions,
+    project_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<CompressResult, Error> {
+    blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let path = SafePath::read(&app, &path)?.into_string();
+        compress_one(
+            &app,
+            "image",
+            path,
+            options,
+            project_id.as_deref(),
+            operation.token(),
+        )
+    })
+    .await
+}
+
+// The single-file path every feature shares (compress_image, the clipboard,
+// scripts), as compress_tracked is for batches: the destination is checked,
+// location consent asked for and the job recorded the same way each time.
+// `path` must already be validated.
+pub fn compress_one(
+    app: &AppHandle,
+    source: &str,
+    path: String,
+    options: CompressOptions,
+    project_id: Option<&str>,
+    cancel: &CancelToken,
+) -> Result<CompressResult, Error> {
+    let db = app.state::<Db>();
+    let mut options = options;
+    options.output_dir = safe_path::write_opt(app, options.output_dir)?;
+    check_location(app, project_id, std::slice::from_ref(&path), &options)?;
+    let _job = crate::crash::job_started(format!("compress {}", path));
+    let started = Instant::now();
+    let outcome = compress_file(path.clone(), &options, cancel)
+        .and_then(|result| store_placeholder(&db, &result).map(|_| result));
+    let job = record_compress(&db, source, &path, &outcome, started, &options);
+    crate::hooks::after_export(app, &job);
+    let result = outcome?;
+    safe_path::allow(app, Path::new(&result.output_path));
+    Ok(result)
+}
+
+// Keeping every tag carries GPS coordinates into the output, which needs
+// consent. Only files that actually have a location count.
+pub fn check_location(
+    app: &AppHandle,
+    project_id: Option<&str>,
+    paths: &[String],
+    options: &CompressOptions,
+) -> Result<(), Error> {
+    if options.metadata != MetadataPolicy::KeepAll {
+        return Ok(());
+    }
+    if let Some(path) = paths.iter().find(|p| metadata::has_location(Path::new(p))) {
+        permissions::check(app, project_id, Capability::KeepLocation, path)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compress_batch(
+    app: AppHandle,
+    paths: Vec<String>,
+    options: CompressOptions,
+    project_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<BatchEntry>, Error> {
+    Ok(blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        compress_tracked(
+            &app,
+            paths,
+            options,
+            project_id,
+            operation.token(),
+            &|_, _| {},
+        )
+    })
+    .await?)
+}
+
+// Scripting's entry point, which is macOS-only
+#[cfg(target_os = "macos")]
+pub fn compress_paths(
+    app: &AppHandle,
+    paths: Vec<String>,
+    options: CompressOptions,
+    project_id: Option<String>,
+) -> Result<Vec<BatchEntry>, String> {
+    compress_tracked(
+        app,
+        paths,
+        options,
+        project_id,
+        &CancelToken::default(),
+        &|_, _| {},
+    )
+}
+
+// Compresses every input, recording per-file failures instead of aborting the batch.
+// Files are spread over the configured number of workers; results keep input order.
+// `tick(done, total)` runs after each file. Once `cancel` fires, files in
+// progress stop at their next step and the rest are skipped; all of them are
+// reported as cancelled.
+pub fn compress_tracked(
+    app: &AppHandle,
+    paths: Vec<String>,
+    options: CompressOptions,
+    project_id: Option<String>,
+    cancel: &CancelToken,
+    tick: &(dyn Fn(usize, usize) + Sync),
+) -> Result<Vec<BatchEntry>, String> {
+    let db = app.state::<Db>();
+    let workers = app.state::<WorkerConfig>();
+    let paths = safe_path::read_all(app, paths)?;
+    let mut options = options;
+    options.output_dir = safe_path::write_opt(app, options.output_dir)?;
+    check_location(app, project_id.as_deref(), &paths, &options)?;
+    let _job = crate::crash::job_started(format!("batch of {} files", paths.len()));
+    let _awake = crate::power::keep_awake("Compressing a batch of images");
+    let total = paths.len();
+    let done = AtomicUsize::new(0);
+    let entries = run_parallel(paths, workers.get(), |path| {
+        if let Err(e) = cancel.check() {
+            return BatchEntry {
+                input_path: path,
+                result: None,
+                error: Some(e.to_string()),
+            };
+        }
+        let started = Instant::now();
+        let outcome = compress_file(path.clone(), &options, cancel)
+            .and_then(|result| store_placeholder(&db, &result).map(|_| result));
+        let job = record_compress(&db, "batch", &path, &outcome, started, &options);
+        crate::hooks::after_export(app, &job);
+        tick(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+
+        match outcome {
+            Ok(result) => BatchEntry {
+                input_path: path,
+                result: Some(result),
+                error: None,
+            },
+            Err(e) => {
+                tracing::warn!("Failed to compress {}: {}", path, e);
+                BatchEntry {
+                    input_path: path,
+                    result: None,
+                    error: Some(e),
+                }
+            }
+        }
+    });
+
+    safe_path::allow_outputs(app, &entries);
+    notifications::batch_finished(app, "batch", &entries);
+    Ok(entries)
+}
+
+// Lossless-only pipeline: every output is verified pixel-identical to its input.
+// Files with no possible saving are reported with the original as the output.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn optimize_lossless(
+    app: AppHandle,
+    paths: Vec<String>,
+    options: LosslessOptions,
+    operation_id: Option<String>,
+) -> Result<Vec<BatchEntry>, Error> {
+    blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let cancel = operation.token();
+        let db = app.state::<Db>();
+        let workers = app.state::<WorkerConfig>();
+        let paths = safe_path::read_all(&app, paths)?;
+        let _job = crate::crash::job_started(format!("lossless batch of {} files", paths.len()));
+        let _awake = crate::power::keep_awake("Optimizing a batch of images");
+        let mut options = options;
+        options.output_dir = safe_path::write_opt(&app, options.output_dir)?;
+        // Zopfli is several times slower for a few percent; not worth it on battery
+        if workers.is_throttled() {
+            options.zopfli = false;
+        }
+        let entries = run_parallel(paths, workers.get(), |path| {
+            let started = Instant::now();
+            let outcome = optimize_lossless_file(&path, &options, cancel);
+            let job = record_compress(&db, "lossless", &path, &outcome, started, &options);
+            crate::hooks::after_export(&app, &job);
+            match outcome {
+                Ok(result) => BatchEntry {
+                    input_path: path,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(e) => {
+                    tracing::warn!("Failed to optimize {}: {}", path, e);
+                    BatchEntry {
+                        input_path: path,
+                        result: None,
+                        error: Some(e),
+                    }
+                }
+            }
+        });
+
+        safe_path::allow_outputs(&app, &entries);
+        notifications::batch_finished(&app, "lossless", &entries);
+        Ok(entries)
+    })
+    .await
+}
+
+fn optimize_lossless_file(
+    path: &str,
+    options: &LosslessOptions,
+    cancel: &CancelToken,
+) -> Result<CompressResult, String> {
+    cancel.check()?;
+    let input = Path::new(path);
+    let input_bytes = std::fs::metadata(input)
+        .map(|m| m.len())
+        .unwrap_or_default();
+    let (optimized, peak) = measure_peak(|| lossless::optimize(input, options));
+
+    let (output_path, output_bytes) = match optimized? {
+        Some(bytes) => {
+            let extension = input
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let output = suffixed_path(
+                input,
+                options.output_dir.as_deref(),
+                "optimized",
+                &extension,
+            );
+            cancel.check()?;
+            let partial = PartialOutput::new(&output);
+            std::fs::write(partial.path(), &bytes)
+                .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+            let output = partial.finish()?;
+            (output.to_string_lossy().to_string(), bytes.len() as u64)
+        }
+        None => (path.to_string(), input_bytes),
+    };
+
+    Ok(CompressResult {
+        input_path: path.to_string(),
+        output_path,
+        input_bytes,
+        output_bytes,
+        blurhash: None,
+        blurhash_size: None,
+        target: None,
+        peak_memory_bytes: peak,
+        layout_impact: None,
+        flattened_alpha: None,
+    })
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compute_blurhash(app: AppHandle, path: String) -> Result<String, Error> {
+    blocking::run(move || {
+        let db = app.state::<Db>();
+        let path = SafePath::read(&app, &path)?.into_string();
+        let image = decode_image(Path::new(&path))?;
+        let hash = blurhash::compute(&image)?;
+        store_blurhash(&db, &path, &hash, (image.width(), image.height()))?;
+        Ok(hash)
+    })
+    .await
+}
+
+fn store_blurhash(
+    db: &Db,
+    path: &str,
+    hash: &str,
+    (width, height): (u32, u32),
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO blurhashes (path, hash, width, height) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![path, hash, width, height],
+    )
+    .map_err(|e| format!("Failed to store BlurHash: {}", e))?;
+    Ok(())
+}
+
+// Stores the BlurHash compress_file computed, if it was asked for one
+fn store_placeholder(db: &Db, result: &CompressResult) -> Result<(), String> {
+    match (&result.blurhash, result.blurhash_size) {
+        (Some(hash), Some(size)) => store_blurhash(db, &result.output_path, hash, size),
+        _ => Ok(()),
+    }
+}
+
+// A BlurHash and the size of the pixels it was computed from
+type Placeholder = (String, (u32, u32));
+
+// The BlurHash of the pixels about to be encoded, when the options ask for one.
+// Taken before encoding since the output format may have no decoder here.
+fn placeholder(
+    image: &image::DynamicImage,
+    options: &CompressOptions,
+) -> Result<Option<Placeholder>, String> {
+    if !options.blurhash {
+        return Ok(None);
+    }
+    Ok(Some((
+        blurhash::compute(image)?,
+        (image.width(), image.height()),
+    )))
+}
+
+pub fn compress_file(
+    path: String,
+    options: &CompressOptions,
+    cancel: &CancelToken,
+) -> Result<CompressResult, String> {
+    let (result, peak) = measure_peak(|| compress_file_inner(path, options, cancel));
+    result.map(|result| CompressResult {
+        peak_memory_bytes: peak,
+        ..result
+    })
+}
+
+fn compress_file_inner(
+    path: String,
+    options: &CompressOptions,
+    cancel: &CancelToken,
+) -> Result<CompressResult, String> {
+    cancel.check()?;
+    let input = PathBuf::from(&path);
+    let transcode = options.jpeg_transcode
+        && options.resize.is_none()
+        && options.format == OutputFormat::Jxl
+        && SourceFormat::detect(&input)? == SourceFormat::Standard(ImageFormat::Jpeg);
+
+    let mut target = None;
+    let mut layout_impact = None;
+    let mut flattened_alpha = None;
+    let mut hashed = None;
+
+    let bytes = if let Some(target_bytes) = options.target_bytes {
+        let (image, alpha) = prepare_image(&input, options)?;
+        flattened_alpha = alpha;
+        cancel.check()?;
+        let source_metadata = metadata::read(&input);
+        let (bytes, outcome) = target::encode_to_target(
+            &image,
+            options.format,
+            target_bytes,
+            options.allow_resize,
+            cancel,
+            &|bytes| metadata::apply(bytes, &source_metadata, options.metadata),
+        )?;
+        // At the size it was encoded at, which may be scaled down to fit
+        hashed =
+            placeholder(&image, options)?.map(|(hash, _)| (hash, (outcome.width, outcome.height)));
+        target = Some(outcome);
+        bytes
+    } else if transcode {
+        if options.blurhash {
+            hashed = placeholder(&decode_image(&input)?, options)?;
+        }
+        let jpeg = std::fs::read(&input)
+            .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+        jxl::transcode_jpeg(&jpeg)?
+    } else {
+        let (image, alpha) = prepare_image(&input, options)?;
+        flattened_alpha = alpha;
+        hashed = placeholder(&image, options)?;
+        cancel.check()?;
+        let settings = EncodeSettings {
+            progressive: options.progressive,
+            interlaced: options.interlaced,
+        };
+        let (bytes, impact) = encode_image_with(&image, options.format, options.quality, settings)?;
+        layout_impact = impact;
+        bytes
+    };
+
+    // A transcoded JXL already carries the original JPEG's metadata, and a
+    // target-size encode got it with every attempt
+    let bytes = if transcode || target.is_some() {
+        bytes
+    } else {
+        metadata::apply(bytes, &metadata::read(&input), options.metadata)?
+    };
+
+    cancel.check()?;
+    let output = output_path_for(&input, options.output_dir.as_deref(), options.format);
+    let partial = PartialOutput::new(&output);
+    std::fs::write(partial.path(), &bytes)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    let output = partial.finish()?;
+
+    let input_bytes = std::fs::metadata(&input)
+        .map(|m| m.len())
+        .unwrap_or_default();
+
+    let (blurhash, blurhash_size) = hashed.unzip();
+    Ok(CompressResult {
+        input_path: path,
+        output_path: output.to_string_lossy().to_string(),
+        input_bytes,
+        output_bytes: bytes.len() as u64,
+        blurhash,
+        blurhash_size,
+        target,
+        peak_memory_bytes: 0,
+        layout_impact,
+        flattened_alpha,
+    })
+}
+
+// Decodes, resizes, and flattens transparency when the output format has no
+// alpha channel. Returns the alpha stats when meaningful transparency was lost.
+fn prepare_image(
+    input: &Path,
+    options: &CompressOptions,
+) -> Result<(image::DynamicImage, Option<AlphaStats>), String> {
+    // Turned upright here, since the Orientation tag is never carried over
+    let mut image = decode_image(input)?;
+    image.apply_orientation(read_orientation(input));
+    if let Some(spec) = &options.resize {
+        image = resize(&image, spec)?;
+    }
+
+    if options.format != OutputFormat::Jpeg || !image.color().has_alpha() {
+        return Ok((image, None));
+    }
+
+    let stats = alpha::analyze(&image);
+    let background = match &options.background {
+        Some(color) => {
+            let [r, g, b, _] = parse_hex_color(color)?;
+            [r, g, b]
+        }
+        None => [255, 255, 255],
+    };
+    let flattened = alpha::flatten(&image, background);
+    Ok((flattened, stats.meaningful.then_some(stats)))
+}
+
+// Outputs land next to the input unless a destination folder was chosen,
+// with a suffix so the original is never overwritten
+pub fn output_path_for(input: &Path, output_dir: Option<&str>, format: OutputFormat) -> PathBuf {
+    suffixed_path(input, output_dir, "squished", format.extension())
+}
+
+pub fn suffixed_path(
+    input: &Path,
+    output_dir: Option<&str>,
+    suffix: &str,
+    extension: &str,
+) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    let dir = match output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    dir.join(format!("{}-{}.{}", stem, suffix, extension))
+}
+
+// Rebuilds the original JPEG from a JXL that was produced by lossless transcoding
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn reconstruct_jpeg(app: AppHandle, path: String, dest: String) -> Result<u64, Error> {
+    blocking::run(move || {
+        let path = SafePath::read(&app, &path)?.into_string();
+        let dest = SafePath::write(&app, &dest)?.into_string();
+        let jxl = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let jpeg = jxl::reconstruct_jpeg(&jxl)?;
+        std::fs::write(&dest, &jpeg).map_err(|e| format!("Failed to write {}: {}", dest, e))?;
+        Ok(jpeg.len() as u64)
+    })
+    .await
+}
+
+// Renders an SVG to PNG bytes for the library grid and raster exports.
+// background is a #rrggbb or #rrggbbaa color, transparent when omitted.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn rasterize_svg(
+    app: AppHandle,
+    path: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    background: Option<String>,
+) -> Result<Response, Error> {
+    Ok(blocking::run(move || {
+        let path = SafePath::read(&app, &path)?;
+        let pixmap = svg::rasterize(path.as_path(), width, height, background.as_deref())?;
+        pixmap
+            .encode_png()
+            .map(Response::new)
+            .map_err(|e| format!("Failed to encode PNG: {}", e))
+    })
+    .await?)
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn extract_palette(
+    app: AppHandle,
+    path: String,
+    count: Option<usize>,
+) -> Result<Palette, Error> {
+    blocking::run(move || {
+        let image = decode_image(SafePath::read(&app, &path)?.as_path())?;
+        Ok(palette::extract(&image, count.unwrap_or(5)))
+    })
+    .await
+}
+
+// Scores b (usually the compressed output) against a (the original)
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compare_images(app: AppHandle, a: String, b: String) -> Result<Comparison, Error> {
+    blocking::run(move || {
+        let original = decode_image(SafePath::read(&app, &a)?.as_path())?;
+        let candidate = decode_image(SafePath::read(&app, &b)?.as_path())?;
+        Ok(compare::compare(&original, &candidate))
+    })
+    .await
+}
+
+// PNG of where b differs from a, as a raw binary response
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compare_heatmap(app: AppHandle, a: String, b: String) -> Result<Response, Error> {
+    Ok(blocking::run(move || {
+        let original = decode_image(SafePath::read(&app, &a)?.as_path())?;
+        let candidate = decode_image(SafePath::read(&app, &b)?.as_path())?;
+        let comparison = compare::compare(&original, &candidate);
+        let map = compare::heatmap(&comparison, original.width(), original.height());
+        encode_image(&image::DynamicImage::ImageRgb8(map), OutputFormat::Png, 100)
+            .map(Response::new)
+    })
+    .await?)
+}
+
+// Predicts output size and quality from sampled tiles, fast enough to run as the
+// user changes settings
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn estimate_compression(
+    app: AppHandle,
+    path: String,
+    profile: CompressOptions,
+) -> Result<Estimate, Error> {
+    Ok(blocking::run(move || {
+        let path = SafePath::read(&app, &path)?;
+        let input = path.as_path();
+        // Sized and flattened the way the real compress will see it
+        let (image, _) = prepare_image(input, &profile)?;
+        let input_bytes = std::fs::metadata(input)
+            .map(|m| m.len())
+            .unwrap_or_default();
+        let source_metadata = metadata::read(input);
+        let workers = app.state::<WorkerConfig>().get();
+        install(workers, || {
+            estimate::estimate(
+                &image,
+                profile.format,
+                profile.quality,
+                input_bytes,
+                &|bytes| metadata::apply(bytes, &source_metadata, profile.metadata),
+            )
+        })
+    })
+    .await?)
+}
+
+// Lets the UI warn before a PNG with real transparency is converted to JPEG
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn analyze_alpha(app: AppHandle, path: String) -> Result<AlphaStats, Error> {
+    blocking::run(move || {
+        let image = decode_image(SafePath::read(&app, &path)?.as_path())?;
+        Ok(alpha::analyze(&image))
+    })
+    .await
+}
+
+// Bin-packs the images into one atlas PNG plus a JSON file of frame coordinates
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn pack_sprites(
+    app: AppHandle,
+    paths: Vec<String>,
+    options: PackOptions,
+) -> Result<PackResult, Error> {
+    Ok(blocking::run(move || {
+        let paths = safe_path::read_all(&app, paths)?;
+        let mut options = options;
+        options.dest = SafePath::write(&app, &options.dest)?.into_string();
+        sprites::pack(&paths, &options)
+    })
+    .await?)
+}
+
+pub fn parse_hex_color(color: &str) -> Result<[u8; 4], String> {
+    let hex = color.trim().trim_start_matches('#');
+    // Checked before slicing, which would panic inside a multi-byte character
+    if !hex.is_ascii() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color: {}", color));
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("Invalid color: {}", color))
+    };
+    match hex.len() {
+        6 => Ok([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Ok([channel(0)?, channel(2)?, channel(4)?, channel(6)?]),
+        _ => Err(format!("Invalid color: {}", color)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_six_and_eight_digit_colors() {
+        assert_eq!(parse_hex_color("#ff8000"), Ok([255, 128, 0, 255]));
+        assert_eq!(parse_hex_color("FF800080"), Ok([255, 128, 0, 128]));
+        assert_eq!(parse_hex_color("  #0a0B0c "), Ok([10, 11, 12, 255]));
+    }
+
+    #[test]
+    fn rejects_malformed_colors() {
+        for color in [
+            "", "#", "#fff", "#ff80001", "#gg0000", "#ff80 0", "#ffé000", "#ff80€",
+        ] {
+            assert!(parse_hex_color(color).is_err(), "{}", color);
+        }
+    }
+}
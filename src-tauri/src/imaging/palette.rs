@@ -0,0 +1,166 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::Serialize;
+
+// Big enough to keep the palette stable, small enough to cut through quickly
+const SAMPLE_SIZE: u32 = 128;
+// Pixels more transparent than this don't contribute a visible color
+const MIN_ALPHA: u8 = 128;
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteColor {
+    pub hex: String,
+    pub rgb: [u8; 3],
+    // Share of the sampled pixels this color represents
+    pub proportion: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Palette {
+    pub colors: Vec<PaletteColor>,
+    // Dominant color along the image edges, a good default for flattening
+    pub suggested_background: Option<PaletteColor>,
+}
+
+pub fn extract(image: &DynamicImage, count: usize) -> Palette {
+    let sample = image
+        .resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle)
+        .to_rgba8();
+    let (width, height) = sample.dimensions();
+
+    let pixels: Vec<[u8; 3]> = sample
+        .pixels()
+        .filter(|p| p[3] >= MIN_ALPHA)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+
+    let edges: Vec<[u8; 3]> = sample
+        .enumerate_pixels()
+        .filter(|(x, y, p)| {
+            (*x == 0 || *y == 0 || *x == width - 1 || *y == height - 1) && p[3] >= MIN_ALPHA
+        })
+        .map(|(_, _, p)| [p[0], p[1], p[2]])
+        .collect();
+
+    Palette {
+        colors: median_cut(pixels, count.max(1)),
+        suggested_background: median_cut(edges, 1).into_iter().next(),
+    }
+}
+
+// Repeatedly splits the box with the widest channel range at its median
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<PaletteColor> {
+    let total = pixels.len();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range)
+            .map(|(i, channel, _)| (i, channel))
+        else {
+            break;
+        };
+
+        let mut bucket = boxes.swap_remove(index);
+        bucket.sort_unstable_by_key(|p| p[channel]);
+        let upper = bucket.split_off(bucket.len() / 2);
+        boxes.push(bucket);
+        boxes.push(upper);
+    }
+
+    let mut colors: Vec<PaletteColor> = boxes
+        .iter()
+        .map(|bucket| {
+            let mut sum = [0u64; 3];
+            for p in bucket {
+                for (channel_sum, &value) in sum.iter_mut().zip(p.iter()) {
+                    *channel_sum += value as u64;
+                }
+            }
+            let n = bucket.len() as u64;
+            let rgb = [(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8];
+            PaletteColor {
+                hex: format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]),
+                rgb,
+                proportion: bucket.len() as f32 / total as f32,
+            }
+        })
+        .collect();
+
+    colors.sort_by(|a, b| b.proportion.total_cmp(&a.proportion));
+    colors
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let min = pixels.iter().map(|p| p[c]).min().unwrap_or(0);
+            let max = pixels.iter().map(|p| p[c]).max().unwrap_or(0);
+            (c, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_pixels_no_colors() {
+        assert!(median_cut(Vec::new(), 5).is_empty());
+    }
+
+    #[test]
+    fn a_flat_image_stays_one_color() {
+        let colors = median_cut(vec![[10, 20, 30]; 50], 8);
+        assert_eq!(colors.len(), 1);
+        assert_eq!(colors[0].hex, "#0a141e");
+        assert_eq!(colors[0].rgb, [10, 20, 30]);
+        assert_eq!(colors[0].proportion, 1.0);
+    }
+
+    #[test]
+    fn distinct_colors_land_in_their_own_boxes() {
+        let mut pixels = vec![[255, 0, 0]; 20];
+        pixels.extend(vec![[0, 0, 255]; 20]);
+        let colors = median_cut(pixels, 2);
+        let mut hexes: Vec<&str> = colors.iter().map(|c| c.hex.as_str()).collect();
+        hexes.sort_unstable();
+        assert_eq!(hexes, ["#0000ff", "#ff0000"]);
+        assert!(colors.iter().all(|c| c.proportion == 0.5));
+    }
+
+    #[test]
+    fn colors_are_ordered_by_share() {
+        let pixels: Vec<[u8; 3]> = (0..=255u8).map(|v| [v, 0, 0]).collect();
+        let colors = median_cut(pixels, 5);
+        assert_eq!(colors.len(), 5);
+        assert!(colors
+            .windows(2)
+            .all(|pair| pair[0].proportion >= pair[1].proportion));
+        let total: f32 = colors.iter().map(|c| c.proportion).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn transparent_pixels_are_left_out() {
+        let image = image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 0]));
+        let palette = extract(&DynamicImage::ImageRgba8(image), 4);
+        assert!(palette.colors.is_empty());
+        assert!(palette.suggested_background.is_none());
+    }
+}
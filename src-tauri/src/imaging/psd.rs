@@ -0,0 +1,14 @@
+use image::{DynamicImage, RgbaImage};
+use std::path::Path;
+
+// The merged composite Photoshop saves alongside the layers; layers, masks
+// and adjustment effects aren't re-rendered
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let psd = psd::Psd::from_bytes(&data)
+        .map_err(|e| format!("Failed to decode PSD {}: {}", path.display(), e))?;
+    RgbaImage::from_raw(psd.width(), psd.height(), psd.rgba())
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| format!("PSD {} has an unexpected buffer size", path.display()))
+}
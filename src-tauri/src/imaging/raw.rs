@@ -0,0 +1,19 @@
+use image::{DynamicImage, RgbImage};
+use std::path::Path;
+
+// rawloader has no CR3 (ISO-BMFF) support, so Canon's newer bodies aren't listed
+pub const RAW_EXTENSIONS: &[&str] = &["cr2", "nef", "arw", "dng", "raf", "orf", "rw2"];
+
+pub fn is_raw_extension(extension: &str) -> bool {
+    RAW_EXTENSIONS.contains(&extension)
+}
+
+// Runs the full demosaic/white balance/tone pipeline and returns an sRGB image
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let decoded = imagepipe::simple_decode_8bit(path, 0, 0)
+        .map_err(|e| format!("Failed to decode RAW {}: {}", path.display(), e))?;
+
+    RgbImage::from_raw(decoded.width as u32, decoded.height as u32, decoded.data)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| format!("RAW {} has an unexpected buffer size", path.display()))
+}
@@ -0,0 +1,376 @@
+use fast_image_resize::images::Image;
+use fast_image_resize::{FilterType, MulDiv, PixelType, ResizeAlg, ResizeOptions, Resizer};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResampleFilter {
+    // Sharpest for photos, can ring around hard edges
+    #[default]
+    Lanczos3,
+    // Softer, no ringing; a good default for UI screenshots and text
+    Mitchell,
+    CatmullRom,
+    // Pixel art and exact 2x/3x scale-downs
+    Nearest,
+}
+
+// Target box; a missing side follows the aspect ratio. Never upscales.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResizeSpec {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+}
+
+impl ResizeSpec {
+    pub fn target_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale_w = self.width.map(|w| w as f64 / width as f64);
+        let scale_h = self.height.map(|h| h as f64 / height as f64);
+        let scale = match (scale_w, scale_h) {
+            (Some(w), Some(h)) => w.min(h),
+            (Some(s), None) | (None, Some(s)) => s,
+            (None, None) => 1.0,
+        }
+        .min(1.0);
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    }
+}
+
+// Resamples in linear light: averaging sRGB values directly darkens fine detail
+// and shifts colors at high-contrast edges. The result keeps the image's
+// channels and bit depth; float images come back as 16-bit.
+pub fn resize(image: &DynamicImage, spec: &ResizeSpec) -> Result<DynamicImage, String> {
+    let (width, height) = spec.target_size(image.width(), image.height());
+    if width == image.width() && height == image.height() {
+        return Ok(image.clone());
+    }
+
+    let color = image.color();
+    let channels = color.channel_count() as usize;
+    let wide = color.bytes_per_pixel() as usize > channels;
+    let alpha = color.has_alpha();
+    let algorithm = match spec.filter {
+        ResampleFilter::Lanczos3 => ResizeAlg::Convolution(FilterType::Lanczos3),
+        ResampleFilter::Mitchell => ResizeAlg::Convolution(FilterType::Mitchell),
+        ResampleFilter::CatmullRom => ResizeAlg::Convolution(FilterType::CatmullRom),
+        ResampleFilter::Nearest => ResizeAlg::Nearest,
+    };
+
+    // Nearest neighbor never blends, so there's nothing to linearize
+    if spec.filter == ResampleFilter::Nearest {
+        let samples = if wide {
+            to_bytes(samples16(image, channels))
+        } else {
+            samples8(image, channels)
+        };
+        let pixel_type = pixel_type(channels, wide);
+        let src = Image::from_vec_u8(image.width(), image.height(), samples, pixel_type)
+            .map_err(|e| format!("Failed to prepare resize: {}", e))?;
+        let mut dst = Image::new(width, height, pixel_type);
+        Resizer::new()
+            .resize(&src, &mut dst, &ResizeOptions::new().resize_alg(algorithm))
+            .map_err(|e| format!("Failed to resize: {}", e))?;
+        let resized = if wide {
+            from_samples16(width, height, channels, from_bytes(dst.buffer()))
+        } else {
+            from_samples8(width, height, channels, dst.into_vec())
+        };
+        return resized.ok_or_else(|| "Resized image has an unexpected buffer size".to_string());
+    }
+
+    // The last channel is alpha, when there is one; it's already linear
+    let is_alpha = |i: usize| alpha && i % channels == channels - 1;
+    let linear: Vec<u16> = if wide {
+        let to_linear = linear16_lut();
+        samples16(image, channels)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if is_alpha(i) {
+                    v
+                } else {
+                    to_linear[v as usize]
+                }
+            })
+            .collect()
+    } else {
+        let to_linear = linear_lut();
+        samples8(image, channels)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if is_alpha(i) {
+                    v as u16 * 257
+                } else {
+                    to_linear[v as usize]
+                }
+            })
+            .collect()
+    };
+
+    let pixel_type = pixel_type(channels, true);
+    let mut src = Image::from_vec_u8(image.width(), image.height(), to_bytes(linear), pixel_type)
+        .map_err(|e| format!("Failed to prepare resize: {}", e))?;
+    let mut dst = Image::new(width, height, pixel_type);
+    // Colors are weighted by alpha while they're blended, or the color of
+    // fully transparent pixels bleeds into the edges next to them
+    let mul_div = MulDiv::default();
+    if alpha {
+        mul_div
+            .multiply_alpha_inplace(&mut src)
+            .map_err(|e| format!("Failed to prepare resize: {}", e))?;
+    }
+    Resizer::new()
+        .resize(
+            &src,
+            &mut dst,
+            &ResizeOptions::new().resize_alg(algorithm).use_alpha(false),
+        )
+        .map_err(|e| format!("Failed to resize: {}", e))?;
+    if alpha {
+        mul_div
+            .divide_alpha_inplace(&mut dst)
+            .map_err(|e| format!("Failed to resize: {}", e))?;
+    }
+
+    let linear = from_bytes(dst.buffer());
+    let resized = if wide {
+        let to_srgb = srgb16_lut();
+        let samples = linear
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| if is_alpha(i) { v } else { to_srgb[v as usize] })
+            .collect();
+        from_samples16(width, height, channels, samples)
+    } else {
+        let to_srgb = srgb_lut();
+        let samples = linear
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if is_alpha(i) {
+                    (v / 257) as u8
+                } else {
+                    to_srgb[(v >> 4) as usize]
+                }
+            })
+            .collect();
+        from_samples8(width, height, channels, samples)
+    };
+    resized.ok_or_else(|| "Resized image has an unexpected buffer size".to_string())
+}
+
+fn pixel_type(channels: usize, wide: bool) -> PixelType {
+    match (channels, wide) {
+        (1, false) => PixelType::U8,
+        (2, false) => PixelType::U8x2,
+        (3, false) => PixelType::U8x3,
+        (_, false) => PixelType::U8x4,
+        (1, true) => PixelType::U16,
+        (2, true) => PixelType::U16x2,
+        (3, true) => PixelType::U16x3,
+        (_, true) => PixelType::U16x4,
+    }
+}
+
+fn samples8(image: &DynamicImage, channels: usize) -> Vec<u8> {
+    match channels {
+        1 => image.to_luma8().into_raw(),
+        2 => image.to_luma_alpha8().into_raw(),
+        3 => image.to_rgb8().into_raw(),
+        _ => image.to_rgba8().into_raw(),
+    }
+}
+
+fn samples16(image: &DynamicImage, channels: usize) -> Vec<u16> {
+    match channels {
+        1 => image.to_luma16().into_raw(),
+        2 => image.to_luma_alpha16().into_raw(),
+        3 => image.to_rgb16().into_raw(),
+        _ => image.to_rgba16().into_raw(),
+    }
+}
+
+fn from_samples8(
+    width: u32,
+    height: u32,
+    channels: usize,
+    samples: Vec<u8>,
+) -> Option<DynamicImage> {
+    match channels {
+        1 => GrayImage::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+        2 => GrayAlphaImage::from_raw(width, height, samples).map(DynamicImage::ImageLumaA8),
+        3 => RgbImage::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+        _ => RgbaImage::from_raw(width, height, samples).map(DynamicImage::ImageRgba8),
+    }
+}
+
+fn from_samples16(
+    width: u32,
+    height: u32,
+    channels: usize,
+    samples: Vec<u16>,
+) -> Option<DynamicImage> {
+    match channels {
+        1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma16),
+        2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA16),
+        3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16),
+        _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16),
+    }
+}
+
+// fast_image_resize takes 16-bit samples as bytes in native order
+fn to_bytes(samples: Vec<u16>) -> Vec<u8> {
+    samples.into_iter().flat_map(u16::to_ne_bytes).collect()
+}
+
+fn from_bytes(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+        .collect()
+}
+
+fn linear_lut() -> &'static [u16; 256] {
+    static LUT: OnceLock<[u16; 256]> = OnceLock::new();
+    LUT.get_or_init(|| {
+        let mut lut = [0u16; 256];
+        for (i, value) in lut.iter_mut().enumerate() {
+            let c = i as f64 / 255.0;
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            *value = (linear * 65535.0).round() as u16;
+        }
+        lut
+    })
+}
+
+// 12-bit index is fine enough that round-tripping an unscaled pixel is exact
+fn srgb_lut() -> &'static [u8; 4096] {
+    static LUT: OnceLock<[u8; 4096]> = OnceLock::new();
+    LUT.get_or_init(|| {
+        let mut lut = [0u8; 4096];
+        for (i, value) in lut.iter_mut().enumerate() {
+            let linear = (i as f64 + 0.5) / 4096.0;
+            let c = if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            *value = (c * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        lut
+    })
+}
+
+fn linear16_lut() -> &'static [u16] {
+    static LUT: OnceLock<Vec<u16>> = OnceLock::new();
+    LUT.get_or_init(|| {
+        (0..=u16::MAX)
+            .map(|i| {
+                let c = i as f64 / 65535.0;
+                let linear = if c <= 0.04045 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                };
+                (linear * 65535.0).round() as u16
+            })
+            .collect()
+    })
+}
+
+fn srgb16_lut() -> &'static [u16] {
+    static LUT: OnceLock<Vec<u16>> = OnceLock::new();
+    LUT.get_or_init(|| {
+        (0..=u16::MAX)
+            .map(|i| {
+                let linear = i as f64 / 65535.0;
+                let c = if linear <= 0.0031308 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (c * 65535.0).round().clamp(0.0, 65535.0) as u16
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ColorType, GrayImage, Luma, Rgb, Rgb32FImage};
+
+    fn spec(width: Option<u32>, height: Option<u32>) -> ResizeSpec {
+        ResizeSpec {
+            width,
+            height,
+            filter: ResampleFilter::default(),
+        }
+    }
+
+    #[test]
+    fn target_size_fits_the_box_and_never_upscales() {
+        assert_eq!(spec(Some(100), None).target_size(400, 200), (100, 50));
+        assert_eq!(spec(None, Some(50)).target_size(400, 200), (100, 50));
+        assert_eq!(spec(Some(100), Some(100)).target_size(400, 200), (100, 50));
+        assert_eq!(spec(Some(800), None).target_size(400, 200), (400, 200));
+        assert_eq!(spec(Some(1), None).target_size(400, 2), (1, 1));
+    }
+
+    #[test]
+    fn keeps_channels_and_bit_depth() {
+        let images = [
+            DynamicImage::new_luma8(40, 40),
+            DynamicImage::new_luma_a8(40, 40),
+            DynamicImage::new_rgb8(40, 40),
+            DynamicImage::new_rgba8(40, 40),
+            DynamicImage::new_luma16(40, 40),
+            DynamicImage::new_luma_a16(40, 40),
+            DynamicImage::new_rgb16(40, 40),
+            DynamicImage::new_rgba16(40, 40),
+        ];
+        for image in images {
+            let resized = resize(&image, &spec(Some(10), None)).unwrap();
+            assert_eq!(resized.color(), image.color());
+            assert_eq!((resized.width(), resized.height()), (10, 10));
+        }
+    }
+
+    #[test]
+    fn float_images_come_back_as_16_bit() {
+        let image = DynamicImage::ImageRgb32F(Rgb32FImage::from_pixel(40, 40, Rgb([0.5; 3])));
+        let resized = resize(&image, &spec(Some(10), None)).unwrap();
+        assert_eq!(resized.color(), ColorType::Rgb16);
+    }
+
+    #[test]
+    fn averages_in_linear_light() {
+        // A fine black and white checkerboard averages to about 188 in sRGB,
+        // not the 128 a gamma-unaware filter gives
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(64, 64, |x, y| {
+            Luma([if (x + y) % 2 == 0 { 0 } else { 255 }])
+        }));
+        let resized = resize(&image, &spec(Some(8), None)).unwrap().to_luma8();
+        let center = resized.get_pixel(4, 4).0[0];
+        assert!((180..=195).contains(&center), "{}", center);
+    }
+
+    #[test]
+    fn same_size_is_a_copy() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(5, 5, Rgb([1, 2, 3])));
+        assert_eq!(resize(&image, &spec(None, None)).unwrap(), image);
+    }
+}
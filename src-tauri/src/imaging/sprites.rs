@@ -0,0 +1,232 @@
+use image::{imageops, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::{decode_image, encode_image, OutputFormat};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackOptions {
+    // Where the atlas PNG is written; the JSON goes next to it
+    pub dest: String,
+    #[serde(default = "default_padding")]
+    pub padding: u32,
+    #[serde(default = "default_max_width")]
+    pub max_width: u32,
+    // Some GPUs and older engines still want power-of-two textures
+    #[serde(default)]
+    pub power_of_two: bool,
+}
+
+fn default_padding() -> u32 {
+    2
+}
+
+fn default_max_width() -> u32 {
+    4096
+}
+
+#[derive(Serialize, Clone, Copy)]
+pub struct Frame {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackResult {
+    pub atlas_path: String,
+    pub json_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub frames: BTreeMap<String, Frame>,
+}
+
+// Same shape as TexturePacker's "JSON (Hash)" export so existing loaders work
+#[derive(Serialize)]
+struct AtlasJson<'a> {
+    frames: BTreeMap<&'a str, AtlasFrame>,
+    meta: AtlasMeta,
+}
+
+#[derive(Serialize)]
+struct AtlasFrame {
+    frame: Frame,
+}
+
+#[derive(Serialize)]
+struct AtlasMeta {
+    image: String,
+    size: Size,
+}
+
+#[derive(Serialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+pub fn pack(paths: &[String], options: &PackOptions) -> Result<PackResult, String> {
+    if paths.is_empty() {
+        return Err("No images to pack".to_string());
+    }
+
+    let mut sprites: Vec<(String, DynamicImage)> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = Path::new(path);
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if sprites.iter().any(|(existing, _)| *existing == name) {
+            return Err(format!("Duplicate sprite name: {}", name));
+        }
+        sprites.push((name, decode_image(path)?));
+    }
+
+    // Tallest first keeps shelves tight
+    sprites.sort_by(|a, b| b.1.height().cmp(&a.1.height()).then(a.0.cmp(&b.0)));
+
+    let padding = options.padding;
+    let widest = sprites
+        .iter()
+        .map(|(_, s)| s.width() + padding)
+        .max()
+        .unwrap_or(0);
+    if widest > options.max_width {
+        return Err(format!(
+            "A sprite is wider than the maximum atlas width of {}",
+            options.max_width
+        ));
+    }
+    let area: u64 = sprites
+        .iter()
+        .map(|(_, s)| (s.width() + padding) as u64 * (s.height() + padding) as u64)
+        .sum();
+    let shelf_width = ((area as f64).sqrt() * 1.1).ceil() as u32;
+    let shelf_width = shelf_width.clamp(widest, options.max_width);
+
+    let (frames, used_width, used_height) = shelf_pack(&sprites, shelf_width, padding);
+    let (width, height) = if options.power_of_two {
+        (
+            used_width.next_power_of_two(),
+            used_height.next_power_of_two(),
+        )
+    } else {
+        (used_width, used_height)
+    };
+
+    let mut atlas = RgbaImage::new(width.max(1), height.max(1));
+    for (name, sprite) in &sprites {
+        let frame = frames[name];
+        imageops::replace(
+            &mut atlas,
+            &sprite.to_rgba8(),
+            frame.x as i64,
+            frame.y as i64,
+        );
+    }
+
+    let atlas_path = Path::new(&options.dest).with_extension("png");
+    let json_path = atlas_path.with_extension("json");
+    let png = encode_image(&DynamicImage::ImageRgba8(atlas), OutputFormat::Png, 100)?;
+    std::fs::write(&atlas_path, png)
+        .map_err(|e| format!("Failed to write {}: {}", atlas_path.display(), e))?;
+
+    let json = AtlasJson {
+        frames: frames
+            .iter()
+            .map(|(name, frame)| (name.as_str(), AtlasFrame { frame: *frame }))
+            .collect(),
+        meta: AtlasMeta {
+            image: atlas_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size: Size {
+                w: width,
+                h: height,
+            },
+        },
+    };
+    let json = serde_json::to_string_pretty(&json)
+        .map_err(|e| format!("Failed to serialize atlas: {}", e))?;
+    std::fs::write(&json_path, json)
+        .map_err(|e| format!("Failed to write {}: {}", json_path.display(), e))?;
+
+    Ok(PackResult {
+        atlas_path: atlas_path.to_string_lossy().to_string(),
+        json_path: json_path.to_string_lossy().to_string(),
+        width,
+        height,
+        frames,
+    })
+}
+
+// Places sprites left to right on shelves as tall as their first sprite
+fn shelf_pack(
+    sprites: &[(String, DynamicImage)],
+    shelf_width: u32,
+    padding: u32,
+) -> (BTreeMap<String, Frame>, u32, u32) {
+    let mut frames = BTreeMap::new();
+    let (mut x, mut y, mut shelf_height, mut used_width) = (0, 0, 0, 0);
+
+    for (name, sprite) in sprites {
+        let (w, h) = (sprite.width(), sprite.height());
+        if x > 0 && x + w > shelf_width {
+            x = 0;
+            y += shelf_height + padding;
+            shelf_height = 0;
+        }
+        frames.insert(name.clone(), Frame { x, y, w, h });
+        x += w + padding;
+        used_width = used_width.max(x - padding);
+        shelf_height = shelf_height.max(h);
+    }
+
+    (frames, used_width, y + shelf_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(name: &str, w: u32, h: u32) -> (String, DynamicImage) {
+        (name.to_string(), DynamicImage::new_rgba8(w, h))
+    }
+
+    fn overlaps(a: &Frame, b: &Frame, padding: u32) -> bool {
+        a.x < b.x + b.w + padding
+            && b.x < a.x + a.w + padding
+            && a.y < b.y + b.h + padding
+            && b.y < a.y + a.h + padding
+    }
+
+    #[test]
+    fn shelves_wrap_at_the_width() {
+        let sprites = [sprite("a", 10, 10), sprite("b", 10, 8), sprite("c", 10, 6)];
+        let (frames, width, height) = shelf_pack(&sprites, 22, 2);
+        assert_eq!((frames["b"].x, frames["b"].y), (12, 0));
+        assert_eq!((frames["c"].x, frames["c"].y), (0, 12));
+        assert_eq!((width, height), (22, 18));
+    }
+
+    #[test]
+    fn frames_never_touch() {
+        let sprites: Vec<_> = (1..=12)
+            .map(|i| sprite(&i.to_string(), 3 + i * 2, 20 - i))
+            .collect();
+        let (frames, width, height) = shelf_pack(&sprites, 40, 1);
+        let frames: Vec<&Frame> = frames.values().collect();
+        for (i, a) in frames.iter().enumerate() {
+            assert!(a.x + a.w <= width && a.y + a.h <= height);
+            for b in &frames[i + 1..] {
+                assert!(!overlaps(a, b, 1));
+            }
+        }
+    }
+}
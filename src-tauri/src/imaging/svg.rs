@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use resvg::{tiny_skia, usvg};
+
+use super::decode::MAX_DECODED_PIXELS;
+use super::parse_hex_color;
+
+// Renders the SVG into a width x height canvas, preserving its aspect ratio.
+// A missing dimension is derived from the document's own size.
+pub fn rasterize(
+    path: &Path,
+    width: Option<u32>,
+    height: Option<u32>,
+    background: Option<&str>,
+) -> Result<tiny_skia::Pixmap, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut options = usvg::Options {
+        resources_dir: path.parent().map(Path::to_path_buf),
+        // Embedded data URLs only: a file or link in an href would be read from
+        // anywhere on disk and baked into the output
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_string: Box::new(|_, _| None),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    options.fontdb_mut().load_system_fonts();
+
+    let tree = usvg::Tree::from_data(&data, &options)
+        .map_err(|e| format!("Failed to parse SVG {}: {}", path.display(), e))?;
+
+    let size = tree.size();
+    let (target_width, target_height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, (w as f32 * size.height() / size.width()).round() as u32),
+        (None, Some(h)) => ((h as f32 * size.width() / size.height()).round() as u32, h),
+        (None, None) => (size.width().ceil() as u32, size.height().ceil() as u32),
+    };
+
+    check_size(target_width, target_height)?;
+    let mut pixmap = tiny_skia::Pixmap::new(target_width.max(1), target_height.max(1))
+        .ok_or_else(|| format!("Invalid SVG output size {}x{}", target_width, target_height))?;
+
+    if let Some(background) = background {
+        let [r, g, b, a] = parse_hex_color(background)?;
+        pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, a));
+    }
+
+    let scale = (target_width as f32 / size.width()).min(target_height as f32 / size.height());
+    let offset_x = (target_width as f32 - size.width() * scale) / 2.0;
+    let offset_y = (target_height as f32 - size.height() * scale) / 2.0;
+    let transform =
+        tiny_skia::Transform::from_scale(scale, scale).post_translate(offset_x, offset_y);
+
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+pub fn decode(path: &Path) -> Result<image::DynamicImage, String> {
+    let pixmap = rasterize(path, None, None, None)?;
+    pixmap_to_image(pixmap)
+}
+
+// Renders SVG markup at its own size with the fonts already loaded into
+// `options`, for images we draw ourselves
+pub fn render(svg: &str, options: &usvg::Options) -> Result<image::DynamicImage, String> {
+    let tree =
+        usvg::Tree::from_str(svg, options).map_err(|e| format!("Failed to parse SVG: {}", e))?;
+    let size = tree.size().to_int_size();
+    check_size(size.width(), size.height())?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| format!("Invalid SVG output size {}x{}", size.width(), size.height()))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap_to_image(pixmap)
+}
+
+// A tiny document can declare any size, so it's held to the same cap as
+// decoded rasters
+fn check_size(width: u32, height: u32) -> Result<(), String> {
+    if width as u64 * height as u64 > MAX_DECODED_PIXELS {
+        return Err(format!("SVG is too large to render ({}x{})", width, height));
+    }
+    Ok(())
+}
+
+pub fn pixmap_to_image(pixmap: tiny_skia::Pixmap) -> Result<image::DynamicImage, String> {
+    let width = pixmap.width();
+    let height = pixmap.height();
+
+    // tiny-skia stores premultiplied alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(image::DynamicImage::ImageRgba8)
+        .ok_or_else(|| "Rasterized SVG has an unexpected buffer size".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the SVG next to a solid red PNG it can link to
+    fn fixture(name: &str, svg: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("squish-svg-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+            .save(dir.join("red.png"))
+            .unwrap();
+        let path = dir.join("image.svg");
+        std::fs::write(&path, svg).unwrap();
+        path
+    }
+
+    #[test]
+    fn a_missing_side_follows_the_aspect_ratio() {
+        let path = fixture(
+            "aspect",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20"/>"#,
+        );
+        let pixmap = rasterize(&path, Some(100), None, None).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (100, 50));
+        let pixmap = rasterize(&path, None, Some(10), Some("#00ff00")).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (20, 10));
+        let pixel = pixmap.pixel(0, 0).unwrap();
+        assert_eq!((pixel.red(), pixel.green(), pixel.alpha()), (0, 255, 255));
+    }
+
+    #[test]
+    fn linked_files_are_not_read() {
+        let path = fixture(
+            "href",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+                <image href="red.png" width="4" height="4"/>
+            </svg>"#,
+        );
+        let image = decode(&path).unwrap().to_rgba8();
+        assert!(image.pixels().all(|pixel| pixel.0[3] == 0));
+    }
+
+    #[test]
+    fn oversized_documents_are_rejected() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100000" height="100000"/>"#;
+        assert!(render(svg, &usvg::Options::default()).is_err());
+        let path = fixture("huge", svg);
+        assert!(rasterize(&path, None, None, None).is_err());
+        assert!(rasterize(&path, Some(64), None, None).is_ok());
+    }
+}
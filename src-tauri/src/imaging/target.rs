@@ -0,0 +1,177 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::Serialize;
+
+use super::{encode_image, OutputFormat};
+use crate::cancel::CancelToken;
+
+const MIN_QUALITY: u8 = 10;
+const MAX_QUALITY: u8 = 95;
+// Each resize step keeps 85% of the linear dimensions (~72% of the pixels)
+const RESIZE_STEP: f32 = 0.85;
+// Stop shrinking before the output stops being useful
+const MIN_DIMENSION: u32 = 64;
+
+// The settings that produced the final output, so the UI can reuse them
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetOutcome {
+    pub quality: u8,
+    pub width: u32,
+    pub height: u32,
+    // False when even the smallest allowed settings stayed above the target
+    pub met: bool,
+}
+
+// Attaches whatever goes into the file besides the pixels (metadata, ICC
+// profile) to each candidate, so it counts against the target
+pub type Finish<'a> = dyn Fn(Vec<u8>) -> Result<Vec<u8>, String> + 'a;
+
+pub fn encode_to_target(
+    image: &DynamicImage,
+    format: OutputFormat,
+    target_bytes: u64,
+    allow_resize: bool,
+    cancel: &CancelToken,
+    finish: &Finish,
+) -> Result<(Vec<u8>, TargetOutcome), String> {
+    if format == OutputFormat::Png {
+        return Err("Target size requires a lossy output format".to_string());
+    }
+
+    let mut current = image.clone();
+    loop {
+        let (bytes, quality) = search_quality(&current, format, target_bytes, cancel, finish)?;
+        let met = bytes.len() as u64 <= target_bytes;
+        let can_shrink = current.width().min(current.height()) > MIN_DIMENSION;
+
+        if met || !allow_resize || !can_shrink {
+            let outcome = TargetOutcome {
+                quality,
+                width: current.width(),
+                height: current.height(),
+                met,
+            };
+            return Ok((bytes, outcome));
+        }
+
+        let width = ((current.width() as f32 * RESIZE_STEP) as u32).max(1);
+        let height = ((current.height() as f32 * RESIZE_STEP) as u32).max(1);
+        current = image.resize(width, height, FilterType::Lanczos3);
+    }
+}
+
+// Binary search for the highest quality that fits. Falls back to the smallest
+// encode when nothing fits so the caller can decide whether to resize.
+fn search_quality(
+    image: &DynamicImage,
+    format: OutputFormat,
+    target_bytes: u64,
+    cancel: &CancelToken,
+    finish: &Finish,
+) -> Result<(Vec<u8>, u8), String> {
+    let (mut low, mut high) = (MIN_QUALITY, MAX_QUALITY);
+    let mut best: Option<(Vec<u8>, u8)> = None;
+
+    while low <= high {
+        cancel.check()?;
+        let quality = low + (high - low) / 2;
+        let bytes = finish(encode_image(image, format, quality)?)?;
+        if bytes.len() as u64 <= target_bytes {
+            best = Some((bytes, quality));
+            low = quality + 1;
+        } else {
+            if quality == MIN_QUALITY {
+                break;
+            }
+            high = quality - 1;
+        }
+    }
+
+    match best {
+        Some(best) => Ok(best),
+        None => Ok((
+            finish(encode_image(image, format, MIN_QUALITY)?)?,
+            MIN_QUALITY,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Detail that doesn't compress away, so quality makes a difference
+    fn noisy(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            let v = (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) as u8;
+            image::Rgb([v, v.wrapping_mul(3), v.wrapping_add(x as u8)])
+        }))
+    }
+
+    fn unchanged(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        Ok(bytes)
+    }
+
+    #[test]
+    fn png_has_no_quality_to_search() {
+        let image = noisy(32, 32);
+        let cancel = CancelToken::default();
+        assert!(
+            encode_to_target(&image, OutputFormat::Png, 1000, false, &cancel, &unchanged).is_err()
+        );
+    }
+
+    #[test]
+    fn picks_the_highest_quality_that_fits() {
+        let image = noisy(128, 128);
+        let cancel = CancelToken::default();
+        let target = encode_image(&image, OutputFormat::Jpeg, 60).unwrap().len() as u64;
+        let (bytes, outcome) = encode_to_target(
+            &image,
+            OutputFormat::Jpeg,
+            target,
+            false,
+            &cancel,
+            &unchanged,
+        )
+        .unwrap();
+        assert!(outcome.met);
+        assert!(bytes.len() as u64 <= target);
+        assert!(outcome.quality >= 60);
+        let above = encode_image(&image, OutputFormat::Jpeg, outcome.quality + 1).unwrap();
+        assert!(above.len() as u64 > target);
+    }
+
+    #[test]
+    fn extra_bytes_count_against_the_target() {
+        let image = noisy(128, 128);
+        let cancel = CancelToken::default();
+        let target = encode_image(&image, OutputFormat::Jpeg, 60).unwrap().len() as u64;
+        let padded = |mut bytes: Vec<u8>| -> Result<Vec<u8>, String> {
+            bytes.extend(vec![0; 2000]);
+            Ok(bytes)
+        };
+        let (bytes, outcome) =
+            encode_to_target(&image, OutputFormat::Jpeg, target, false, &cancel, &padded).unwrap();
+        assert!(bytes.len() as u64 <= target);
+        assert!(outcome.quality < 60);
+    }
+
+    #[test]
+    fn an_impossible_target_shrinks_down_to_the_minimum() {
+        let image = noisy(200, 100);
+        let cancel = CancelToken::default();
+        let (_, fixed) =
+            encode_to_target(&image, OutputFormat::Jpeg, 10, false, &cancel, &unchanged).unwrap();
+        assert!(!fixed.met);
+        assert_eq!(fixed.quality, MIN_QUALITY);
+        assert_eq!((fixed.width, fixed.height), (200, 100));
+
+        let (_, resized) =
+            encode_to_target(&image, OutputFormat::Jpeg, 10, true, &cancel, &unchanged).unwrap();
+        assert!(!resized.met);
+        assert!(resized.height <= MIN_DIMENSION);
+        assert!(resized.width < 200);
+    }
+}
@@ -0,0 +1,289 @@
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use super::{
+    decode_image, encode_image, read_orientation, suffixed_path, OutputFormat, SourceFormat,
+};
+use crate::blocking;
+use crate::error::Error;
+use crate::permissions;
+use crate::safe_path::{self, SafePath};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TransformOp {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Rotate {
+        degrees: u32,
+    },
+    FlipHorizontal,
+    FlipVertical,
+    NormalizeOrientation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformResult {
+    pub output_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub lossless: bool,
+}
+
+// Re-encode quality used when a JPEG can't be transformed losslessly
+const JPEG_FALLBACK_QUALITY: u8 = 92;
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn transform_image(
+    app: AppHandle,
+    path: String,
+    ops: Vec<TransformOp>,
+    dest: Option<String>,
+    project_id: Option<String>,
+) -> Result<TransformResult, Error> {
+    blocking::run(move || {
+        let input = SafePath::read(&app, &path)?.into_path_buf();
+        let dest = safe_path::write_opt(&app, dest)?;
+        let source = SourceFormat::detect(&input)?;
+        let format = match source {
+            SourceFormat::Standard(ImageFormat::Jpeg) => OutputFormat::Jpeg,
+            SourceFormat::Standard(ImageFormat::WebP) => OutputFormat::Webp,
+            _ => OutputFormat::Png,
+        };
+        let output = dest
+            .map(PathBuf::from)
+            .unwrap_or_else(|| suffixed_path(&input, None, "edited", format.extension()));
+        permissions::check_overwrite(&app, project_id.as_deref(), &input, &output)?;
+        safe_path::allow(&app, &output);
+
+        if format == OutputFormat::Jpeg {
+            match transform_jpeg_lossless(&input, &ops) {
+                Ok(Some(bytes)) => {
+                    std::fs::write(&output, &bytes)
+                        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+                    let image = image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg)
+                        .map_err(|e| format!("Failed to read transformed JPEG: {}", e))?;
+                    return Ok(TransformResult {
+                        output_path: output.to_string_lossy().to_string(),
+                        width: image.width(),
+                        height: image.height(),
+                        lossless: true,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Lossless JPEG transform failed, re-encoding: {}", e),
+            }
+        }
+
+        // Re-encoding drops the EXIF orientation, so the pixels are turned
+        // upright first and the ops apply to the image as it's displayed
+        let mut image = decode_image(&input)?;
+        image.apply_orientation(read_orientation(&input));
+        for op in &ops {
+            image = apply_op(image, *op)?;
+        }
+
+        let quality = if format == OutputFormat::Jpeg {
+            JPEG_FALLBACK_QUALITY
+        } else {
+            100
+        };
+        let bytes = encode_image(&image, format, quality)?;
+        std::fs::write(&output, &bytes)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+
+        Ok(TransformResult {
+            output_path: output.to_string_lossy().to_string(),
+            width: image.width(),
+            height: image.height(),
+            lossless: false,
+        })
+    })
+    .await
+}
+
+pub fn apply_op(image: DynamicImage, op: TransformOp) -> Result<DynamicImage, String> {
+    Ok(match op {
+        TransformOp::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            if width == 0
+                || height == 0
+                || x.saturating_add(width) > image.width()
+                || y.saturating_add(height) > image.height()
+            {
+                return Err(format!(
+                    "Crop {}x{} at {},{} is outside the {}x{} image",
+                    width,
+                    height,
+                    x,
+                    y,
+                    image.width(),
+                    image.height()
+                ));
+            }
+            image.crop_imm(x, y, width, height)
+        }
+        TransformOp::Rotate { degrees } => match degrees % 360 {
+            0 => image,
+            90 => image.rotate90(),
+            180 => image.rotate180(),
+            270 => image.rotate270(),
+            other => return Err(format!("Rotation must be a multiple of 90, got {}", other)),
+        },
+        TransformOp::FlipHorizontal => image.fliph(),
+        TransformOp::FlipVertical => image.flipv(),
+        // Already done on decode
+        TransformOp::NormalizeOrientation => image,
+    })
+}
+
+// Runs the ops through libjpeg-turbo's lossless transforms. Returns None when an
+// op can't be expressed losslessly so the caller falls back to re-encoding.
+fn transform_jpeg_lossless(input: &Path, ops: &[TransformOp]) -> Result<Option<Vec<u8>>, String> {
+    // Markers are copied as-is, so a non-default EXIF orientation would be wrong afterwards
+    if read_orientation(input) != Orientation::NoTransforms
+        || ops
+            .iter()
+            .any(|op| matches!(op, TransformOp::NormalizeOrientation))
+    {
+        return Ok(None);
+    }
+
+    let mut jpeg =
+        std::fs::read(input).map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+
+    for op in ops {
+        let transform = match *op {
+            TransformOp::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let mut transform = turbojpeg::Transform::default();
+                transform.crop = Some(turbojpeg::TransformCrop {
+                    x: x as usize,
+                    y: y as usize,
+                    width: Some(width as usize),
+                    height: Some(height as usize),
+                });
+                transform
+            }
+            TransformOp::Rotate { degrees } => {
+                let op = match degrees % 360 {
+                    0 => continue,
+                    90 => turbojpeg::TransformOp::Rot90,
+                    180 => turbojpeg::TransformOp::Rot180,
+                    270 => turbojpeg::TransformOp::Rot270,
+                    _ => return Ok(None),
+                };
+                turbojpeg::Transform::op(op)
+            }
+            TransformOp::FlipHorizontal => turbojpeg::Transform::op(turbojpeg::TransformOp::Hflip),
+            TransformOp::FlipVertical => turbojpeg::Transform::op(turbojpeg::TransformOp::Vflip),
+            TransformOp::NormalizeOrientation => return Ok(None),
+        };
+
+        // perfect = fail instead of silently dropping partial MCU edges
+        let mut transform = transform;
+        transform.perfect = true;
+        jpeg = turbojpeg::transform(&transform, &jpeg)
+            .map_err(|e| format!("{}", e))?
+            .to_vec();
+    }
+
+    Ok(Some(jpeg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    // 3x2 with a distinct color in the top left corner
+    fn marked() -> DynamicImage {
+        let mut image = RgbImage::new(3, 2);
+        image.put_pixel(0, 0, Rgb([255, 0, 0]));
+        DynamicImage::ImageRgb8(image)
+    }
+
+    fn marker_at(image: &DynamicImage) -> (u32, u32) {
+        let image = image.to_rgb8();
+        let (x, y, _) = image
+            .enumerate_pixels()
+            .find(|(_, _, pixel)| pixel.0 == [255, 0, 0])
+            .unwrap();
+        (x, y)
+    }
+
+    #[test]
+    fn ops_move_pixels_where_expected() {
+        let rotated = apply_op(marked(), TransformOp::Rotate { degrees: 90 }).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(marker_at(&rotated), (1, 0));
+        let rotated = apply_op(marked(), TransformOp::Rotate { degrees: 450 }).unwrap();
+        assert_eq!(marker_at(&rotated), (1, 0));
+        let flipped = apply_op(marked(), TransformOp::FlipHorizontal).unwrap();
+        assert_eq!(marker_at(&flipped), (2, 0));
+        let flipped = apply_op(marked(), TransformOp::FlipVertical).unwrap();
+        assert_eq!(marker_at(&flipped), (0, 1));
+    }
+
+    #[test]
+    fn crops_must_fit_inside_the_image() {
+        let crop = |x, y, width, height| {
+            apply_op(
+                marked(),
+                TransformOp::Crop {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+            )
+        };
+        let cropped = crop(1, 0, 2, 2).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (2, 2));
+        assert!(crop(0, 0, 0, 1).is_err());
+        assert!(crop(2, 0, 2, 1).is_err());
+        assert!(crop(u32::MAX, 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn rotations_are_quarter_turns() {
+        assert!(apply_op(marked(), TransformOp::Rotate { degrees: 45 }).is_err());
+    }
+
+    #[test]
+    fn ops_deserialize_from_tagged_json() {
+        let ops: Vec<TransformOp> = serde_json::from_str(
+            r#"[{"type": "crop", "x": 1, "y": 2, "width": 3, "height": 4},
+                {"type": "rotate", "degrees": 90},
+                {"type": "flipHorizontal"}]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            ops[0],
+            TransformOp::Crop {
+                x: 1,
+                height: 4,
+                ..
+            }
+        ));
+        assert!(matches!(ops[1], TransformOp::Rotate { degrees: 90 }));
+        assert!(matches!(ops[2], TransformOp::FlipHorizontal));
+    }
+}
@@ -0,0 +1,330 @@
+// What happens to a file dropped on the window or opened with Squish. Each
+// format family is an Importer in IMPORTERS, and every enabled WASM plugin adds
+// one for the extensions its manifest lists. The first importer claiming a
+// file's extension handles it, built-ins before plugins. import_image,
+// read_image_data and the open-file paths only go through `import` and
+// `find`, so a new format is one more entry here rather than another branch at
+// every call site.
+//
+// Imports are shown on the canvas: files the webview can display are passed
+// through untouched, everything else is handed over as PNG.
+use image::DynamicImage;
+use serde::Serialize;
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::blocking;
+use crate::cancel::CancelToken;
+use crate::error::Error;
+use crate::fonts::embedded::{self, EmbeddedFont};
+use crate::imaging::{decode_image, encode_image, OutputFormat, SourceFormat};
+use crate::plugins;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportKind {
+    Raster,
+    Vector,
+    Document,
+    Font,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImporterInfo {
+    pub id: String,
+    pub name: String,
+    pub kind: ImportKind,
+    pub extensions: Vec<String>,
+    // Id of the plugin providing it; None for built-ins
+    pub plugin: Option<String>,
+}
+
+pub struct Imported {
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    // The displayable bytes; None when the file itself can be shown as is
+    pub data: Option<Vec<u8>>,
+    // One line for the UI, e.g. a PDF's page count or a font's family
+    pub detail: Option<String>,
+    // Fonts the document carries, to be registered with the project
+    pub fonts: Vec<EmbeddedFont>,
+}
+
+struct Importer {
+    id: &'static str,
+    name: &'static str,
+    kind: ImportKind,
+    extensions: fn() -> Vec<String>,
+    import: fn(&Path, &CancelToken) -> Result<Imported, String>,
+}
+
+const IMAGE: Importer = Importer {
+    id: "image",
+    name: "Images",
+    kind: ImportKind::Raster,
+    extensions: image_extensions,
+    import: import_image,
+};
+
+const IMPORTERS: &[Importer] = &[
+    IMAGE,
+    Importer {
+        id: "svg",
+        name: "SVG",
+        kind: ImportKind::Vector,
+        extensions: || vec!["svg".to_string()],
+        import: import_svg,
+    },
+    Importer {
+        id: "psd",
+        name: "Photoshop documents",
+        kind: ImportKind::Raster,
+        extensions: || vec!["psd".to_string()],
+        import: import_decoded,
+    },
+    Importer {
+        id: "pdf",
+        name: "PDF",
+        kind: ImportKind::Document,
+        extensions: || vec!["pdf".to_string()],
+        import: import_pdf,
+    },
+    Importer {
+        id: "font",
+        name: "Fonts",
+        kind: ImportKind::Font,
+        extensions: || ["ttf", "otf", "ttc", "otc"].map(String::from).to_vec(),
+        import: import_font,
+    },
+];
+
+impl Importer {
+    fn info(&self) -> ImporterInfo {
+        ImporterInfo {
+            id: self.id.to_string(),
+            name: self.name.to_string(),
+            kind: self.kind,
+            extensions: (self.extensions)(),
+            plugin: None,
+        }
+    }
+}
+
+// SVG and PSD have importers of their own
+fn image_extensions() -> Vec<String> {
+    SourceFormat::extensions()
+        .into_iter()
+        .filter(|e| e != "svg" && e != "psd")
+        .collect()
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn builtin(extension: &str) -> Option<&'static Importer> {
+    IMPORTERS
+        .iter()
+        .find(|importer| (importer.extensions)().iter().any(|e| e == extension))
+}
+
+fn as_png(image: DynamicImage, detail: Option<String>) -> Result<Imported, String> {
+    Ok(Imported {
+        mime_type: "image/png".to_string(),
+        width: image.width(),
+        height: image.height(),
+        data: Some(encode_image(&image, OutputFormat::Png, 100)?),
+        detail,
+        fonts: Vec::new(),
+    })
+}
+
+fn import_image(path: &Path, cancel: &CancelToken) -> Result<Imported, String> {
+    let source = SourceFormat::detect(path)?;
+    match source.web_mime_type() {
+        Some(mime) => {
+            let (width, height) = image::image_dimensions(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            Ok(Imported {
+                mime_type: mime.to_string(),
+                width,
+                height,
+                data: None,
+                detail: None,
+                fonts: Vec::new(),
+            })
+        }
+        None => import_decoded(path, cancel),
+    }
+}
+
+fn import_decoded(path: &Path, cancel: &CancelToken) -> Result<Imported, String> {
+    let image = decode_image(path)?;
+    cancel.check()?;
+    as_png(image, None)
+}
+
+// A document whose fonts can't be read still imports, just without them
+fn embedded_fonts(path: &Path, fonts: Result<Vec<EmbeddedFont>, String>) -> Vec<EmbeddedFont> {
+    fonts.unwrap_or_else(|e| {
+        tracing::warn!("Failed to extract fonts from {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+fn import_svg(path: &Path, cancel: &CancelToken) -> Result<Imported, String> {
+    let mut imported = import_decoded(path, cancel)?;
+    imported.fonts = embedded_fonts(path, embedded::extract_svg(path));
+    Ok(imported)
+}
+
+fn import_pdf(path: &Path, cancel: &CancelToken) -> Result<Imported, String> {
+    let (image, pages) = crate::pdf::largest_image(path, cancel)?;
+    cancel.check()?;
+    let mut imported = as_png(image, Some(format!("{} pages", pages)))?;
+    imported.fonts = embedded_fonts(path, embedded::extract_pdf(path, cancel));
+    Ok(imported)
+}
+
+fn import_font(path: &Path, cancel: &CancelToken) -> Result<Imported, String> {
+    let (image, family) = crate::fonts::specimen(path)?;
+    cancel.check()?;
+    as_png(image, Some(family))
+}
+
+fn plugin_importers(app: &AppHandle) -> Result<Vec<ImporterInfo>, String> {
+    Ok(plugins::enabled(app)?
+        .into_iter()
+        .filter(|manifest| !manifest.imports.is_empty())
+        .map(|manifest| ImporterInfo {
+            id: format!("plugin:{}", manifest.id),
+            name: manifest.name,
+            kind: ImportKind::Raster,
+            extensions: manifest.imports,
+            plugin: Some(manifest.id),
+        })
+        .collect())
+}
+
+// The importer that would handle the file, judged by its extension alone.
+// Plugins are only consulted (and compiled) when no built-in claims it.
+pub fn find(app: &AppHandle, path: &Path) -> Result<Option<ImporterInfo>, String> {
+    let extension = extension(path);
+    if let Some(importer) = builtin(&extension) {
+        return Ok(Some(importer.info()));
+    }
+    Ok(plugin_importers(app)?
+        .into_iter()
+        .find(|importer| importer.extensions.contains(&extension)))
+}
+
+pub fn can_import(app: &AppHandle, path: &Path) -> bool {
+    match find(app, path) {
+        Ok(importer) => importer.is_some(),
+        Err(e) => {
+            tracing::warn!("Failed to look up importers: {}", e);
+            false
+        }
+    }
+}
+
+pub fn import(
+    app: &AppHandle,
+    path: &Path,
+    cancel: &CancelToken,
+) -> Result<(ImporterInfo, Imported), String> {
+    let extension = extension(path);
+    if let Some(importer) = builtin(&extension) {
+        return Ok((importer.info(), (importer.import)(path, cancel)?));
+    }
+    if let Some(importer) = plugin_importers(app)?
+        .into_iter()
+        .find(|importer| importer.extensions.contains(&extension))
+    {
+        let image = plugins::decode(app, path)
+            .ok_or_else(|| format!("Plugin for {} is no longer enabled", path.display()))??;
+        cancel.check()?;
+        return Ok((importer, as_png(image, None)?));
+    }
+    // A missing or wrong extension on an image the decoder recognises by content
+    if SourceFormat::detect(path).is_ok() {
+        return Ok((IMAGE.info(), import_image(path, cancel)?));
+    }
+    Err(format!("Squish can't import {}", path.display()))
+}
+
+// Every format that can be imported, plugins included
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn list_importers(app: AppHandle) -> Result<Vec<ImporterInfo>, Error> {
+    blocking::run(move || {
+        let mut importers: Vec<ImporterInfo> = IMPORTERS.iter().map(Importer::info).collect();
+        importers.extend(plugin_importers(&app)?);
+        Ok(importers)
+    })
+    .await
+}
+
+// Which importer would take each path (None for files that can't be
+// imported), so drop targets can react before anything is read
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn find_importers(
+    app: AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<Option<ImporterInfo>>, Error> {
+    Ok(blocking::run(move || {
+        paths
+            .iter()
+            .map(|path| find(&app, Path::new(path)))
+            .collect()
+    })
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn built_in_importers_claim_distinct_extensions() {
+        let mut claimed: Vec<String> = IMPORTERS.iter().flat_map(|i| (i.extensions)()).collect();
+        let count = claimed.len();
+        claimed.sort();
+        claimed.dedup();
+        assert_eq!(claimed.len(), count);
+
+        assert_eq!(builtin("svg").map(|i| i.id), Some("svg"));
+        assert_eq!(builtin("psd").map(|i| i.id), Some("psd"));
+        assert_eq!(builtin("otf").map(|i| i.id), Some("font"));
+        assert_eq!(builtin("png").map(|i| i.id), Some("image"));
+        assert!(builtin("docx").is_none());
+    }
+
+    #[test]
+    fn web_images_pass_through_and_others_become_png() {
+        let dir = std::env::temp_dir().join(format!("squish-importers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cancel = CancelToken::default();
+
+        let png = dir.join("a.png");
+        RgbImage::new(3, 2).save(&png).unwrap();
+        let imported = import_image(&png, &cancel).unwrap();
+        assert_eq!(imported.mime_type, "image/png");
+        assert_eq!((imported.width, imported.height), (3, 2));
+        assert!(imported.data.is_none());
+
+        let bmp = dir.join("a.bmp");
+        RgbImage::new(3, 2).save(&bmp).unwrap();
+        let imported = import_image(&bmp, &cancel).unwrap();
+        assert_eq!(imported.mime_type, "image/png");
+        let data = imported.data.unwrap();
+        assert_eq!(image::load_from_memory(&data).unwrap().width(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::Db;
+use crate::error::Error;
+
+pub const INTEGRITY_EVENT: &str = "db://integrity";
+const BACKUPS_DIR: &str = "backups";
+// A fresh snapshot is taken at most this often, and only of a healthy library
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const SNAPSHOTS_KEPT: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrityOutcome {
+    Healthy,
+    // Only indexes were damaged and REINDEX fixed them
+    Reindexed,
+    RestoredFromBackup,
+    // Nothing worked; the library is opened as-is and may misbehave
+    Unrepaired,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub outcome: IntegrityOutcome,
+    // First problems reported by quick_check
+    pub problems: Vec<String>,
+    pub backup_used: Option<String>,
+    // Where the damaged file was moved before restoring
+    pub corrupt_copy: Option<String>,
+}
+
+// Startup result, kept so a UI that mounts after the event can still ask
+pub struct IntegrityState(pub Mutex<Option<IntegrityReport>>);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn quick_check(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("PRAGMA quick_check(20)")
+        .map_err(|e| format!("Failed to check database: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to check database: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to check database: {}", e))?;
+    Ok(rows.into_iter().filter(|r| r != "ok").collect())
+}
+
+pub fn backups_dir(db_path: &Path) -> PathBuf {
+    db_path.with_file_name(BACKUPS_DIR)
+}
+
+// Automatic snapshots, newest first
+fn snapshots(db_path: &Path) -> Vec<(u64, PathBuf)> {
+    let mut found: Vec<(u64, PathBuf)> = std::fs::read_dir(backups_dir(db_path))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let stamp = path
+                .file_stem()?
+                .to_str()?
+                .strip_prefix("auto-")?
+                .parse()
+                .ok()?;
+            Some((stamp, path))
+        })
+        .collect();
+    found.sort_by_key(|(stamp, _)| std::cmp::Reverse(*stamp));
+    found
+}
+
+fn snapshot_if_due(conn: &Connection, db_path: &Path) -> Result<(), String> {
+    let existing = snapshots(db_path);
+    let now = now_secs();
+    if existing
+        .first()
+        .is_some_and(|(stamp, _)| now.saturating_sub(*stamp) < SNAPSHOT_INTERVAL.as_secs())
+    {
+        return Ok(());
+    }
+
+    let dir = backups_dir(db_path);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backups folder: {}", e))?;
+    conn.backup(
+        rusqlite::DatabaseName::Main,
+        dir.join(format!("auto-{}.db", now)),
+        None,
+    )
+    .map_err(|e| format!("Failed to snapshot database: {}", e))?;
+    for (_, old) in existing.into_iter().skip(SNAPSHOTS_KEPT - 1) {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(())
+}
+
+fn move_aside(db_path: &Path) -> Result<PathBuf, String> {
+    let aside = db_path.with_file_name(format!("squish.corrupt-{}.db", now_secs()));
+    std::fs::rename(db_path, &aside)
+        .map_err(|e| format!("Failed to move damaged library aside: {}", e))?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", db_path.display(), suffix));
+    }
+    Ok(aside)
+}
+
+// Opens the active library after a quick integrity check. Damaged indexes are
+// rebuilt; anything worse is replaced by the newest snapshot that passes
+// validation, keeping the damaged file next to it for support.
+pub fn open_checked(app: &AppHandle) -> Result<(Connection, IntegrityReport), String> {
+    let path = crate::db::db_path(app)?;
+    let mut report = IntegrityReport {
+        outcome: IntegrityOutcome::Healthy,
+        problems: Vec::new(),
+        backup_used: None,
+        corrupt_copy: None,
+    };
+
+    let problems = match crate::db::connect(&path) {
+        Ok(conn) => quick_check(&conn).unwrap_or_else(|e| vec![e]),
+        Err(e) => vec![e],
+    };
+    if problems.is_empty() {
+        return Ok((crate::db::open_at(&path)?, report));
+    }
+
+    tracing::warn!("Database integrity check failed: {:?}", problems);
+    report.problems = problems;
+
+    if let Ok(conn) = crate::db::connect(&path) {
+        if conn.execute_batch("REINDEX;").is_ok() && quick_check(&conn).is_ok_and(|p| p.is_empty())
+        {
+            drop(conn);
+            report.outcome = IntegrityOutcome::Reindexed;
+            return Ok((crate::db::open_at(&path)?, report));
+        }
+    }
+
+    let backup = snapshots(&path)
+        .into_iter()
+        .map(|(_, p)| p)
+        .find(|p| crate::db::validate_backup(p).is_ok());
+    if let Some(backup) = backup {
+        let aside = move_aside(&path)?;
+        std::fs::copy(&backup, &path)
+            .map_err(|e| format!("Failed to restore from {}: {}", backup.display(), e))?;
+        tracing::info!("Restored database from {}", backup.display());
+        report.outcome = IntegrityOutcome::RestoredFromBackup;
+        report.backup_used = Some(backup.to_string_lossy().to_string());
+        report.corrupt_copy = Some(aside.to_string_lossy().to_string());
+        return Ok((crate::db::open_at(&path)?, report));
+    }
+
+    report.outcome = IntegrityOutcome::Unrepaired;
+    Ok((crate::db::open_at(&path)?, report))
+}
+
+// Run after launch rather than while the library opens, where copying a big
+// library would hold up the window
+pub fn snapshot(app: &AppHandle) -> Result<(), String> {
+    let path = crate::db::db_path(app)?;
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    snapshot_if_due(&conn, &path)
+}
+
+// Tells the UI about anything other than a clean check, once a window exists
+pub fn announce(app: &AppHandle, report: &IntegrityReport) {
+    if report.outcome == IntegrityOutcome::Healthy {
+        return;
+    }
+    if let Err(e) = app.emit(INTEGRITY_EVENT, report) {
+        tracing::warn!("Failed to emit integrity report: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_integrity_report(
+    state: State<IntegrityState>,
+) -> Result<Option<IntegrityReport>, Error> {
+    Ok(state
+        .0
+        .lock()
+        .map_err(|_| Error::Lock("integrity state"))?
+        .clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-integrity-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join(BACKUPS_DIR)).unwrap();
+        dir.join("squish.db")
+    }
+
+    #[test]
+    fn a_healthy_library_has_no_problems() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert!(quick_check(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn snapshots_are_listed_newest_first() {
+        let path = library("list");
+        for name in [
+            "auto-100.db",
+            "auto-300.db",
+            "auto-200.db",
+            "manual.db",
+            "auto-x.db",
+        ] {
+            std::fs::write(backups_dir(&path).join(name), b"").unwrap();
+        }
+        let stamps: Vec<u64> = snapshots(&path).into_iter().map(|(s, _)| s).collect();
+        assert_eq!(stamps, vec![300, 200, 100]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned_and_recent_ones_kept() {
+        let path = library("prune");
+        for stamp in [100, 200, 300] {
+            std::fs::write(backups_dir(&path).join(format!("auto-{}.db", stamp)), b"").unwrap();
+        }
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+
+        snapshot_if_due(&conn, &path).unwrap();
+        let kept = snapshots(&path);
+        assert_eq!(kept.len(), SNAPSHOTS_KEPT);
+        assert!(kept[0].0 > 300);
+        assert!(crate::db::validate_backup(&kept[0].1).is_ok());
+        assert_eq!(kept[SNAPSHOTS_KEPT - 1].0, 200);
+
+        // Taken a moment ago, so nothing is due
+        snapshot_if_due(&conn, &path).unwrap();
+        assert_eq!(snapshots(&path).len(), SNAPSHOTS_KEPT);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,384 @@
+use rusqlite::{params, params_from_iter, types::Value as SqlValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Instant;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::CompressResult;
+
+const DEFAULT_PAGE: u32 = 100;
+
+pub struct JobRecord<'a> {
+    pub kind: &'a str,
+    pub input_path: &'a str,
+    pub output_path: Option<&'a str>,
+    pub input_bytes: Option<u64>,
+    pub output_bytes: Option<u64>,
+    pub started: Instant,
+    pub settings: Value,
+    pub error: Option<&'a str>,
+}
+
+// History is best effort: a failed insert is logged, never surfaced as a job
+// failure. Post-export hooks are the caller's to queue, with hooks::after_export.
+pub fn record(db: &Db, job: &JobRecord) {
+    let duration_ms = job.started.elapsed().as_millis() as i64;
+    crate::crash::log_line(match job.error {
+        Some(e) => format!("Job {} failed for {}: {}", job.kind, job.input_path, e),
+        None => format!(
+            "Job {} finished for {} in {}ms",
+            job.kind, job.input_path, duration_ms
+        ),
+    });
+    let result = db
+        .0
+        .lock()
+        .map_err(|e| format!("Failed to lock database: {}", e))
+        .and_then(|conn| {
+            crate::usage::track(&conn, &format!("job.{}", job.kind));
+            conn.execute(
+                "INSERT INTO job_history
+                    (kind, input_path, output_path, input_bytes, output_bytes, duration_ms, settings, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    job.kind,
+                    job.input_path,
+                    job.output_path,
+                    job.input_bytes.map(|b| b as i64),
+                    job.output_bytes.map(|b| b as i64),
+                    duration_ms,
+                    job.settings.to_string(),
+                    job.error
+                ],
+            )
+            .map_err(|e| format!("Failed to record job: {}", e))
+        });
+    if let Err(e) = result {
+        tracing::warn!("{}", e);
+    }
+}
+
+// Returns the record, for the caller to hand on to the hooks
+pub fn record_compress<'a>(
+    db: &Db,
+    kind: &'a str,
+    input_path: &'a str,
+    outcome: &'a Result<CompressResult, String>,
+    started: Instant,
+    settings: &impl Serialize,
+) -> JobRecord<'a> {
+    let settings = serde_json::to_value(settings).unwrap_or(Value::Null);
+    let job = match outcome {
+        Ok(result) => JobRecord {
+            kind,
+            input_path,
+            output_path: Some(&result.output_path),
+            input_bytes: Some(result.input_bytes),
+            output_bytes: Some(result.output_bytes),
+            started,
+            settings,
+            error: None,
+        },
+        Err(e) => JobRecord {
+            kind,
+            input_path,
+            output_path: None,
+            input_bytes: None,
+            output_bytes: None,
+            started,
+            settings,
+            error: Some(e),
+        },
+    };
+    record(db, &job);
+    job
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct JobFilters {
+    pub kind: Option<String>,
+    // None for all, true for successes only, false for failures only
+    pub succeeded: Option<bool>,
+    // SQLite datetimes, e.g. "2024-05-01" or "2024-05-01 12:00:00"
+    pub since: Option<String>,
+    pub until: Option<String>,
+    // Substring of the input or output path
+    pub search: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobEntry {
+    pub id: i64,
+    pub kind: String,
+    pub input_path: String,
+    pub output_path: Option<String>,
+    pub input_bytes: Option<u64>,
+    pub output_bytes: Option<u64>,
+    pub duration_ms: u64,
+    pub settings: Value,
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavingsBucket {
+    // Day ("2024-05-01") or month ("2024-05") depending on the grouping
+    pub period: String,
+    pub jobs: u32,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub saved_bytes: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavingsStats {
+    pub jobs: u32,
+    pub failed: u32,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub saved_bytes: u64,
+    pub total_duration_ms: u64,
+    pub buckets: Vec<SavingsBucket>,
+}
+
+// Builds the shared WHERE clause; values are bound, never interpolated
+fn where_clause(filters: &JobFilters) -> (String, Vec<SqlValue>) {
+    let mut clauses = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+    if let Some(kind) = &filters.kind {
+        values.push(kind.clone().into());
+        clauses.push(format!("kind = ?{}", values.len()));
+    }
+    match filters.succeeded {
+        Some(true) => clauses.push("error IS NULL".to_string()),
+        Some(false) => clauses.push("error IS NOT NULL".to_string()),
+        None => {}
+    }
+    if let Some(since) = &filters.since {
+        values.push(since.clone().into());
+        clauses.push(format!("created_at >= ?{}", values.len()));
+    }
+    if let Some(until) = &filters.until {
+        values.push(until.clone().into());
+        clauses.push(format!("created_at < ?{}", values.len()));
+    }
+    if let Some(search) = &filters.search {
+        values.push(format!("%{}%", search).into());
+        let n = values.len();
+        clauses.push(format!(
+            "(input_path LIKE ?{} OR output_path LIKE ?{})",
+            n, n
+        ));
+    }
+
+    if clauses.is_empty() {
+        (String::new(), values)
+    } else {
+        (format!("WHERE {}", clauses.join(" AND ")), values)
+    }
+}
+
+#[tauri::command]
+pub fn get_job_history(db: State<Db>, filters: Option<JobFilters>) -> Result<Vec<JobEntry>, Error> {
+    let filters = filters.unwrap_or_default();
+    let (clause, mut values) = where_clause(&filters);
+    values.push((filters.limit.unwrap_or(DEFAULT_PAGE) as i64).into());
+    values.push((filters.offset.unwrap_or(0) as i64).into());
+    let query = format!(
+        "SELECT id, kind, input_path, output_path, input_bytes, output_bytes, duration_ms, settings, error, created_at
+         FROM job_history {} ORDER BY id DESC LIMIT ?{} OFFSET ?{}",
+        clause,
+        values.len() - 1,
+        values.len()
+    );
+
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| format!("Failed to query job history: {}", e))?;
+    let entries = stmt
+        .query_map(params_from_iter(values), |row| {
+            let settings: String = row.get(7)?;
+            Ok(JobEntry {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                input_path: row.get(2)?,
+                output_path: row.get(3)?,
+                input_bytes: row.get::<_, Option<i64>>(4)?.map(|b| b as u64),
+                output_bytes: row.get::<_, Option<i64>>(5)?.map(|b| b as u64),
+                duration_ms: row.get::<_, i64>(6)? as u64,
+                settings: serde_json::from_str(&settings).unwrap_or(Value::Null),
+                error: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query job history: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to query job history: {}", e))?;
+    Ok(entries)
+}
+
+// Totals over the filtered jobs, plus per-day or per-month buckets. Savings
+// only count successful jobs, and a job that grew its file saves nothing.
+#[tauri::command]
+pub fn get_savings_stats(
+    db: State<Db>,
+    filters: Option<JobFilters>,
+    group_by: Option<String>,
+) -> Result<SavingsStats, Error> {
+    let filters = filters.unwrap_or_default();
+    let (clause, values) = where_clause(&filters);
+    let period = match group_by.as_deref() {
+        Some("month") => "%Y-%m",
+        Some("day") | None => "%Y-%m-%d",
+        Some(other) => return Err(Error::InvalidInput(format!("Unknown grouping: {}", other))),
+    };
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+
+    let totals = format!(
+        "SELECT COUNT(*),
+                COALESCE(SUM(error IS NOT NULL), 0),
+                COALESCE(SUM(CASE WHEN error IS NULL THEN input_bytes END), 0),
+                COALESCE(SUM(CASE WHEN error IS NULL THEN output_bytes END), 0),
+                COALESCE(SUM(CASE WHEN error IS NULL THEN MAX(input_bytes - output_bytes, 0) END), 0),
+                COALESCE(SUM(duration_ms), 0)
+         FROM job_history {}",
+        clause
+    );
+    let mut stats = conn
+        .query_row(&totals, params_from_iter(values.clone()), |row| {
+            Ok(SavingsStats {
+                jobs: row.get(0)?,
+                failed: row.get(1)?,
+                input_bytes: row.get::<_, i64>(2)? as u64,
+                output_bytes: row.get::<_, i64>(3)? as u64,
+                saved_bytes: row.get::<_, i64>(4)? as u64,
+                total_duration_ms: row.get::<_, i64>(5)? as u64,
+                buckets: Vec::new(),
+            })
+        })
+        .map_err(|e| format!("Failed to compute savings: {}", e))?;
+
+    let success_clause = if clause.is_empty() {
+        "WHERE error IS NULL".to_string()
+    } else {
+        format!("{} AND error IS NULL", clause)
+    };
+    let buckets = format!(
+        "SELECT strftime('{}', created_at) AS period, COUNT(*),
+                COALESCE(SUM(input_bytes), 0), COALESCE(SUM(output_bytes), 0),
+                COALESCE(SUM(MAX(input_bytes - output_bytes, 0)), 0)
+         FROM job_history {} GROUP BY period ORDER BY period",
+        period, success_clause
+    );
+    let mut stmt = conn
+        .prepare(&buckets)
+        .map_err(|e| format!("Failed to compute savings: {}", e))?;
+    stats.buckets = stmt
+        .query_map(params_from_iter(values), |row| {
+            Ok(SavingsBucket {
+                period: row.get(0)?,
+                jobs: row.get(1)?,
+                input_bytes: row.get::<_, i64>(2)? as u64,
+                output_bytes: row.get::<_, i64>(3)? as u64,
+                saved_bytes: row.get::<_, i64>(4)? as u64,
+            })
+        })
+        .map_err(|e| format!("Failed to compute savings: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to compute savings: {}", e))?;
+    Ok(stats)
+}
+
+#[tauri::command]
+pub fn clear_job_history(db: State<Db>) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM job_history", [])
+        .map_err(|e| format!("Failed to clear job history: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn count(conn: &Connection, filters: &JobFilters) -> i64 {
+        let (clause, values) = where_clause(filters);
+        conn.query_row(
+            &format!("SELECT count(*) FROM job_history {}", clause),
+            params_from_iter(values),
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn no_filters_no_clause() {
+        let (clause, values) = where_clause(&JobFilters::default());
+        assert!(clause.is_empty());
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn placeholders_follow_the_bound_values() {
+        let filters = JobFilters {
+            kind: Some("batch".to_string()),
+            succeeded: Some(true),
+            since: Some("2024-05-01".to_string()),
+            search: Some("photo".to_string()),
+            ..Default::default()
+        };
+        let (clause, values) = where_clause(&filters);
+        assert_eq!(
+            clause,
+            "WHERE kind = ?1 AND error IS NULL AND created_at >= ?2 \
+             AND (input_path LIKE ?3 OR output_path LIKE ?3)"
+        );
+        assert_eq!(values.len(), 3);
+    }
+
+    #[test]
+    fn filters_select_matching_jobs() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        for (kind, input, error) in [
+            ("batch", "/photos/a.jpg", None),
+            ("batch", "/photos/b.png", Some("broken")),
+            ("pdf", "/docs/c.pdf", None),
+        ] {
+            conn.execute(
+                "INSERT INTO job_history (kind, input_path, duration_ms, settings, error)
+                 VALUES (?1, ?2, 0, 'null', ?3)",
+                params![kind, input, error],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(count(&conn, &JobFilters::default()), 3);
+        let batch = JobFilters {
+            kind: Some("batch".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(count(&conn, &batch), 2);
+        let failed = JobFilters {
+            succeeded: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(count(&conn, &failed), 1);
+        let photos = JobFilters {
+            search: Some("photos".to_string()),
+            succeeded: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(count(&conn, &photos), 1);
+    }
+}
@@ -0,0 +1,643 @@
+// One queue for long-running work (batch compression, PDF compression, sync,
+// thumbnails) instead of each feature running its own threads:
+//  - jobs run highest priority first, oldest first within a priority, at most
+//    MAX_RUNNING at a time
+//  - every state change and progress step goes out on job://update in the
+//    same shape, whatever the job does
+//  - a queued job can be cancelled outright; a running one stops at its next
+//    checkpoint (see cancel.rs), partway through a file if need be
+//  - queued jobs are saved and picked up again on the next launch, including
+//    ones a quit interrupted
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::cancel::CancelToken;
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::{compress_tracked, decode_image, encode_image, CompressOptions, OutputFormat};
+use crate::pdf::{compress_pdf_file, PdfCompressOptions};
+use crate::perf;
+use crate::safe_path::{self, SafePath};
+use crate::startup;
+
+pub const UPDATE_EVENT: &str = "job://update";
+const MAX_RUNNING: usize = 2;
+// Finished jobs kept around for list_jobs
+const MAX_FINISHED: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+// What to run; saved as JSON while the job is pending
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum JobSpec {
+    #[serde(rename_all = "camelCase")]
+    Compress {
+        paths: Vec<String>,
+        options: CompressOptions,
+        project_id: Option<String>,
+    },
+    CompressPdf {
+        path: String,
+        options: PdfCompressOptions,
+    },
+    Sync,
+    Thumbnail {
+        path: String,
+        size: u32,
+    },
+}
+
+impl JobSpec {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobSpec::Compress { .. } => "compress",
+            JobSpec::CompressPdf { .. } => "compressPdf",
+            JobSpec::Sync => "sync",
+            JobSpec::Thumbnail { .. } => "thumbnail",
+        }
+    }
+
+    // Paths are checked once, when the job is queued
+    pub fn validate(self, app: &AppHandle) -> Result<Self, Error> {
+        Ok(match self {
+            JobSpec::Compress {
+                paths,
+                mut options,
+                project_id,
+            } => {
+                let paths = safe_path::read_all(app, paths)?;
+                options.output_dir = safe_path::write_opt(app, options.output_dir)?;
+                JobSpec::Compress {
+                    paths,
+                    options,
+                    project_id,
+                }
+            }
+            JobSpec::CompressPdf { path, mut options } => {
+                let path = SafePath::read(app, &path)?.into_string();
+                options.dest = safe_path::write_opt(app, options.dest)?;
+                if let Some(dest) = &options.dest {
+                    crate::permissions::check_overwrite(
+                        app,
+                        None,
+                        Path::new(&path),
+                        Path::new(dest),
+                    )?;
+                }
+                JobSpec::CompressPdf { path, options }
+            }
+            JobSpec::Sync => JobSpec::Sync,
+            JobSpec::Thumbnail { path, size } => JobSpec::Thumbnail {
+                path: SafePath::read(app, &path)?.into_string(),
+                size,
+            },
+        })
+    }
+
+    // For jobs restored at launch: they were validated when queued, but the
+    // fs scope starts out empty
+    fn allow(&self, app: &AppHandle) {
+        match self {
+            JobSpec::Compress { paths, options, .. } => {
+                safe_path::allow_all(app, paths);
+                if let Some(dir) = &options.output_dir {
+                    safe_path::allow(app, Path::new(dir));
+                }
+            }
+            JobSpec::CompressPdf { path, options } => {
+                safe_path::allow(app, Path::new(path));
+                if let Some(dest) = &options.dest {
+                    safe_path::allow(app, Path::new(dest));
+                }
+            }
+            JobSpec::Sync => {}
+            JobSpec::Thumbnail { path, .. } => safe_path::allow(app, Path::new(path)),
+        }
+    }
+}
+
+// Payload of job://update and the items of list_jobs
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobUpdate {
+    pub id: String,
+    pub kind: &'static str,
+    pub priority: Priority,
+    pub status: JobStatus,
+    // 0 to 1
+    pub progress: f32,
+    pub message: Option<String>,
+    // Shaped like the result of the matching command
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+impl JobUpdate {
+    fn new(id: &str, kind: &'static str, priority: Priority, status: JobStatus) -> Self {
+        JobUpdate {
+            id: id.to_string(),
+            kind,
+            priority,
+            status,
+            progress: 0.0,
+            message: None,
+            result: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    priority: Priority,
+    spec: JobSpec,
+}
+
+struct Pending {
+    id: String,
+    priority: Priority,
+    seq: u64,
+    spec: JobSpec,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: Vec<Pending>,
+    running: HashMap<String, CancelToken>,
+    // Latest update of every job still worth showing
+    jobs: Vec<JobUpdate>,
+    next_seq: u64,
+}
+
+impl Queue {
+    fn push(&mut self, id: String, priority: Priority, spec: JobSpec) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.pending.push(Pending {
+            id,
+            priority,
+            seq,
+            spec,
+        });
+    }
+
+    // The job to start now, if one may
+    fn take_next(&mut self) -> Option<(Pending, CancelToken)> {
+        if self.running.len() >= MAX_RUNNING {
+            return None;
+        }
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, p)| (p.priority, Reverse(p.seq)))
+            .map(|(index, _)| index)?;
+        let job = self.pending.remove(index);
+        let cancel = CancelToken::default();
+        self.running.insert(job.id.clone(), cancel.clone());
+        Some((job, cancel))
+    }
+
+    // Replaces the job's previous update
+    fn record(&mut self, update: JobUpdate) {
+        match self.jobs.iter_mut().find(|j| j.id == update.id) {
+            Some(job) => *job = update,
+            None => self.jobs.push(update),
+        }
+        let done = |j: &JobUpdate| !matches!(j.status, JobStatus::Queued | JobStatus::Running);
+        let mut excess = self.jobs.iter().filter(|&j| done(j)).count();
+        // Oldest finished jobs go first
+        self.jobs.retain(|j| {
+            let remove = excess > MAX_FINISHED && done(j);
+            excess -= remove as usize;
+            !remove
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Handed to running jobs for reporting progress and checking for cancellation
+pub struct JobContext {
+    app: AppHandle,
+    base: JobUpdate,
+    cancel: CancelToken,
+}
+
+impl JobContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn progress(&self, progress: f32, message: Option<String>) {
+        let mut update = self.base.clone();
+        update.progress = progress.clamp(0.0, 1.0);
+        update.message = message;
+        publish(&self.app, update);
+    }
+}
+
+fn publish(app: &AppHandle, update: JobUpdate) {
+    app.state::<JobQueue>().lock().record(update.clone());
+    if let Err(e) = app.emit(UPDATE_EVENT, update) {
+        tracing::warn!("Failed to emit job update: {}", e);
+    }
+}
+
+fn save(app: &AppHandle, id: &str, job: &SavedJob) -> Result<(), String> {
+    let json = serde_json::to_string(job).map_err(|e| format!("Failed to save job: {}", e))?;
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO job_queue (id, job) VALUES (?1, ?2)",
+        params![id, json],
+    )
+    .map_err(|e| format!("Failed to save job: {}", e))?;
+    Ok(())
+}
+
+fn forget(app: &AppHandle, id: &str) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM job_queue WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to remove job: {}", e))?;
+    Ok(())
+}
+
+fn load(app: &AppHandle) -> Result<Vec<(String, String)>, String> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT id, job FROM job_queue ORDER BY created_at, rowid")
+        .map_err(|e| format!("Failed to load jobs: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to load jobs: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to load jobs: {}", e))
+}
+
+fn push(app: &AppHandle, id: String, priority: Priority, spec: JobSpec) {
+    let update = JobUpdate::new(&id, spec.kind(), priority, JobStatus::Queued);
+    let state = app.state::<JobQueue>();
+    state.lock().push(id, priority, spec);
+    publish(app, update);
+    state.changed.notify_all();
+}
+
+// Spec must already be validated
+pub fn enqueue(app: &AppHandle, spec: JobSpec, priority: Priority) -> Result<String, String> {
+    startup::jobs(app);
+    let id = uuid::Uuid::new_v4().to_string();
+    let saved = SavedJob { priority, spec };
+    save(app, &id, &saved)?;
+    tracing::info!("Queued {} job {}", saved.spec.kind(), id);
+    push(app, id.clone(), priority, saved.spec);
+    Ok(id)
+}
+
+pub fn status(app: &AppHandle, id: &str) -> Option<JobUpdate> {
+    startup::jobs(app);
+    let state = app.state::<JobQueue>();
+    let queue = state.lock();
+    queue.jobs.iter().find(|j| j.id == id).cloned()
+}
+
+// Blocks until a job may start
+fn next(app: &AppHandle) -> (Pending, CancelToken) {
+    let state = app.state::<JobQueue>();
+    let mut queue = state.lock();
+    loop {
+        if let Some(next) = queue.take_next() {
+            return next;
+        }
+        queue = state.changed.wait(queue).unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+fn run(app: &AppHandle, job: Pending, cancel: CancelToken) {
+    let base = JobUpdate::new(&job.id, job.spec.kind(), job.priority, JobStatus::Running);
+    publish(app, base.clone());
+    let context = JobContext {
+        app: app.clone(),
+        base: base.clone(),
+        cancel,
+    };
+    let outcome = tracing::info_span!(target: perf::JOB, "job", name = job.spec.kind())
+        .in_scope(|| execute(&context, job.spec));
+
+    let mut update = base;
+    update.progress = 1.0;
+    update.status = match &outcome {
+        // Whatever finished before the cancel is still reported
+        _ if context.is_cancelled() => JobStatus::Cancelled,
+        Ok(_) => JobStatus::Finished,
+        Err(_) => JobStatus::Failed,
+    };
+    match outcome {
+        Ok(result) => update.result = Some(result),
+        Err(e) => {
+            tracing::warn!("Job {} failed: {}", job.id, e);
+            update.error = Some(e);
+        }
+    }
+    if let Err(e) = forget(app, &job.id) {
+        tracing::warn!("{}", e);
+    }
+    let state = app.state::<JobQueue>();
+    state.lock().running.remove(&job.id);
+    state.changed.notify_all();
+    publish(app, update);
+}
+
+fn execute(context: &JobContext, spec: JobSpec) -> Result<Value, String> {
+    let app = &context.app;
+    let result = match spec {
+        JobSpec::Compress {
+            paths,
+            options,
+            project_id,
+        } => {
+            let entries = compress_tracked(
+                app,
+                paths,
+                options,
+                project_id,
+                &context.cancel,
+                &|done, total| {
+                    context.progress(
+                        done as f32 / total as f32,
+                        Some(format!("{} of {}", done, total)),
+                    );
+                },
+            )?;
+            serde_json::to_value(entries)
+        }
+        JobSpec::CompressPdf { path, options } => {
+            let result = compress_pdf_file(&path, options, &context.cancel)?;
+            safe_path::allow(app, Path::new(&result.output_path));
+            serde_json::to_value(result)
+        }
+        JobSpec::Sync => {
+            let status = tauri::async_runtime::block_on(crate::sync::sync_now(app.clone()))?;
+            serde_json::to_value(status)
+        }
+        JobSpec::Thumbnail { path, size } => {
+            Ok(Value::String(thumbnail(app, &path, size, &context.cancel)?))
+        }
+    };
+    result.map_err(|e| format!("Failed to serialize job result: {}", e))
+}
+
+pub fn thumbnails_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join("thumbnails"))
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))
+}
+
+// Named after the source path and size, so asking again overwrites the same file
+fn thumbnail(
+    app: &AppHandle,
+    path: &str,
+    size: u32,
+    cancel: &CancelToken,
+) -> Result<String, String> {
+    let dir = thumbnails_dir(app)?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut hasher = DefaultHasher::new();
+    (path, size).hash(&mut hasher);
+    let output = dir.join(format!("{:016x}.png", hasher.finish()));
+
+    let image = decode_image(Path::new(path))?;
+    cancel.check()?;
+    let bytes = encode_image(&image.thumbnail(size, size), OutputFormat::Png, 100)?;
+    std::fs::write(&output, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(output.to_string_lossy().to_string())
+}
+
+// Restores jobs saved by the last run and starts dispatching
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    for (id, json) in load(app)? {
+        match serde_json::from_str::<SavedJob>(&json) {
+            Ok(saved) => {
+                saved.spec.allow(app);
+                push(app, id, saved.priority, saved.spec);
+            }
+            Err(e) => {
+                tracing::warn!("Dropping unreadable job {}: {}", id, e);
+                forget(app, &id)?;
+            }
+        }
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let (job, cancel) = next(&app);
+        let app = app.clone();
+        std::thread::spawn(move || run(&app, job, cancel));
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn enqueue_job(
+    app: AppHandle,
+    spec: JobSpec,
+    priority: Option<Priority>,
+) -> Result<String, Error> {
+    let spec = spec.validate(&app)?;
+    Ok(enqueue(&app, spec, priority.unwrap_or_default())?)
+}
+
+#[tauri::command]
+pub fn list_jobs(app: AppHandle, queue: State<JobQueue>) -> Vec<JobUpdate> {
+    startup::jobs(&app);
+    queue.lock().jobs.clone()
+}
+
+#[tauri::command]
+pub fn cancel_job(app: AppHandle, queue: State<JobQueue>, id: String) -> Result<(), Error> {
+    startup::jobs(&app);
+    let removed = {
+        let mut queue = queue.lock();
+        if let Some(cancel) = queue.running.get(&id) {
+            tracing::info!("Cancelling running job {}", id);
+            cancel.cancel();
+            return Ok(());
+        }
+        let index = queue.pending.iter().position(|p| p.id == id);
+        index.map(|i| queue.pending.remove(i))
+    };
+    let Some(job) = removed else {
+        return Err(Error::NotFound(format!("No pending job {}", id)));
+    };
+    forget(&app, &id)?;
+    publish(
+        &app,
+        JobUpdate::new(&id, job.spec.kind(), job.priority, JobStatus::Cancelled),
+    );
+    Ok(())
+}
+
+// Only affects jobs that haven't started
+#[tauri::command]
+pub fn set_job_priority(
+    app: AppHandle,
+    queue: State<JobQueue>,
+    id: String,
+    priority: Priority,
+) -> Result<(), Error> {
+    startup::jobs(&app);
+    let kind = {
+        let mut queue = queue.lock();
+        let job = queue
+            .pending
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| Error::NotFound(format!("No pending job {}", id)))?;
+        job.priority = priority;
+        job.spec.kind()
+    };
+    // Saved too, or a relaunch would bring back the old priority
+    let priority_json =
+        serde_json::to_string(&priority).map_err(|e| Error::Internal(e.to_string()))?;
+    let db = app.state::<Db>();
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "UPDATE job_queue SET job = json_set(job, '$.priority', json(?2)) WHERE id = ?1",
+        params![id, priority_json],
+    )?;
+    drop(conn);
+    publish(&app, JobUpdate::new(&id, kind, priority, JobStatus::Queued));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail_job(path: &str) -> JobSpec {
+        JobSpec::Thumbnail {
+            path: path.to_string(),
+            size: 256,
+        }
+    }
+
+    #[test]
+    fn higher_priority_then_older_jobs_start_first() {
+        let mut queue = Queue::default();
+        queue.push("low".into(), Priority::Low, JobSpec::Sync);
+        queue.push("normal-1".into(), Priority::Normal, JobSpec::Sync);
+        queue.push("high".into(), Priority::High, JobSpec::Sync);
+        queue.push("normal-2".into(), Priority::Normal, JobSpec::Sync);
+
+        let (first, _) = queue.take_next().unwrap();
+        let (second, _) = queue.take_next().unwrap();
+        assert_eq!(
+            (first.id.as_str(), second.id.as_str()),
+            ("high", "normal-1")
+        );
+        // Both slots are taken until one finishes
+        assert!(queue.take_next().is_none());
+        queue.running.remove("high");
+        assert_eq!(queue.take_next().unwrap().0.id, "normal-2");
+    }
+
+    #[test]
+    fn only_the_latest_update_per_job_is_kept() {
+        let mut queue = Queue::default();
+        queue.record(JobUpdate::new(
+            "a",
+            "sync",
+            Priority::Normal,
+            JobStatus::Queued,
+        ));
+        queue.record(JobUpdate::new(
+            "a",
+            "sync",
+            Priority::Normal,
+            JobStatus::Running,
+        ));
+        assert_eq!(queue.jobs.len(), 1);
+        assert_eq!(queue.jobs[0].status, JobStatus::Running);
+    }
+
+    #[test]
+    fn the_oldest_finished_jobs_are_dropped_first() {
+        let mut queue = Queue::default();
+        queue.record(JobUpdate::new(
+            "running",
+            "sync",
+            Priority::Normal,
+            JobStatus::Running,
+        ));
+        for i in 0..MAX_FINISHED + 5 {
+            let id = format!("done-{}", i);
+            queue.record(JobUpdate::new(
+                &id,
+                "sync",
+                Priority::Normal,
+                JobStatus::Finished,
+            ));
+        }
+        assert_eq!(queue.jobs.len(), MAX_FINISHED + 1);
+        assert_eq!(queue.jobs[0].id, "running");
+        assert_eq!(queue.jobs[1].id, "done-5");
+    }
+
+    #[test]
+    fn saved_jobs_round_trip() {
+        let json = serde_json::to_string(&SavedJob {
+            priority: Priority::High,
+            spec: thumbnail_job("/photos/a.jpg"),
+        })
+        .unwrap();
+        assert!(json.contains(r#""kind":"thumbnail""#), "{}", json);
+        let saved: SavedJob = serde_json::from_str(&json).unwrap();
+        assert_eq!(saved.priority, Priority::High);
+        assert_eq!(saved.spec.kind(), "thumbnail");
+        assert!(
+            serde_json::from_str::<SavedJob>(r#"{"priority":"low","spec":{"kind":"format"}}"#)
+                .is_err()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
-use tauri::{Manager, TitleBarStyle, WebviewUrl, WebviewWindowBuilder};
+#[cfg(target_os = "macos")]
+use tauri::TitleBarStyle;
+use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
 
 #[cfg(target_os = "macos")]
 use cocoa::{
@@ -8,23 +10,190 @@ use cocoa::{
     base::id,
 };
 
+mod accessibility;
+mod asset_protocol;
+mod automation;
+mod battery;
+mod blocking;
+mod bookmarks;
+mod cancel;
+pub mod cli;
+mod clipboard;
+mod clipboard_monitor;
+mod collections;
+mod connectivity;
+mod crash;
+mod db;
+mod deep_link;
+mod dnd;
+mod document;
+mod encryption;
+mod error;
+mod font_usage;
 mod fonts;
-use fonts::{get_system_fonts, initialize_empty_state, FontState};
+mod history;
+mod hooks;
+mod i18n;
+mod imaging;
+mod importers;
+mod integrity;
+mod job_history;
+mod jobs;
+mod library;
+mod logging;
+mod login_item;
+mod maintenance;
+mod memory;
+mod migrations;
+mod net;
+mod notifications;
+mod pdf;
+mod perf;
+mod permissions;
+mod plugins;
+mod power;
+mod preset_bundle;
+mod presets;
+mod preview;
+mod print;
+mod profiles;
+mod safe_path;
+#[cfg(target_os = "macos")]
+mod scripting;
+mod scripts;
+mod search;
+mod secrets;
+#[cfg(target_os = "macos")]
+mod services;
+mod session;
+mod settings;
+mod settings_file;
+mod spotlight;
+mod sql;
+mod startup;
+mod sync;
+mod tags;
+mod throttle;
+mod updater;
+mod usage;
+mod video;
+mod watch;
+mod workers;
+use accessibility::get_accessibility_prefs;
+use automation::{get_automation_token, reset_automation_token, AutomationState};
+use battery::{get_power_status, PowerState};
+use bookmarks::{forget_folder, remember_folder};
+use cancel::{cancel_operation, Operations};
+use clipboard::{copy_image_to_clipboard, paste_image_from_clipboard};
+use clipboard_monitor::{
+    accept_clipboard_image, dismiss_clipboard_image, get_clipboard_monitoring, ClipboardMonitor,
+};
+use collections::{
+    add_to_collection, create_collection, delete_collection, list_collection_items,
+    list_collections, move_collection, remove_from_collection, rename_collection,
+    reorder_collection_items,
+};
+use connectivity::{get_connectivity, ConnectivityState};
+use crash::get_crash_reports;
+use db::{backup_database, get_database_path, restore_database};
+use deep_link::{take_pending_deep_links, PendingDeepLinks};
+use dnd::{start_drag_out, start_promised_drag};
+use document::{export_project_document, open_project_document};
+use encryption::{enable_library_encryption, is_library_encrypted};
+use font_usage::{get_fonts_used, get_projects_using_font};
+use fonts::{
+    get_bitmap_strikes, get_font_license_info, get_font_stack, get_glyph_outline,
+    get_problem_fonts, get_system_fonts, initialize_empty_state, layout_paragraph,
+    layout_text_on_path, list_project_fonts, read_project_font, reveal_problem_font,
+    set_problem_font_hidden, suggest_font_pairings, FontState,
+};
+use history::{get_history, push_op, redo, undo};
+use hooks::{delete_hook, list_hooks, save_hook, HookQueue};
+use i18n::get_system_locale;
+use imaging::{
+    analyze_alpha, compare_heatmap, compare_images, compress_batch, compress_image,
+    compute_blurhash, estimate_compression, extract_palette, import_image, optimize_lossless,
+    pack_sprites, rasterize_svg, read_image_data, reconstruct_jpeg, transform_image, ImportCache,
+};
+use importers::{find_importers, list_importers};
+use integrity::{get_integrity_report, IntegrityState};
+use job_history::{clear_job_history, get_job_history, get_savings_stats};
+use jobs::{cancel_job, enqueue_job, list_jobs, set_job_priority, JobQueue};
+use library::{index_project, list_library};
+use logging::{get_log_tail, get_recent_logs, set_log_level};
+use login_item::{get_launch_at_login, set_launch_at_login};
+use maintenance::run_db_maintenance;
+use memory::{get_memory_stats, trim_caches};
+use net::download_url;
+use notifications::LastNotification;
+use pdf::compress_pdf;
+use perf::get_perf_report;
+use permissions::{list_permissions, revoke_permission, set_permission};
+use plugins::{
+    apply_plugin_filter, export_with_plugin, list_plugins, reload_plugins, set_plugin_enabled,
+    PluginHost,
+};
+use preset_bundle::{export_preset_bundle, import_preset_bundle, inspect_preset_bundle};
+use presets::{delete_preset, list_presets, save_preset};
+use preview::{
+    close_mini_preview, close_preview, open_mini_preview, open_preview, update_preview,
+    watch_preview, PreviewState,
+};
+use print::print_document;
+use profiles::{create_profile, list_profiles, switch_profile};
+use scripts::run_script;
+use search::search_library;
+use secrets::{delete_secret, get_secret, store_secret};
+use session::{get_session, get_workspace_state, save_open_documents, save_workspace_state};
+use settings::{get_app_settings, get_setting, set_setting, SettingsState};
+use settings_file::{export_settings, import_settings};
+use sql::{sql_execute, sql_select};
+use sync::{
+    get_sync_config, list_sync_conflicts, resolve_sync_conflict, set_sync_config, sync_now,
+    SyncState,
+};
+use tags::{
+    create_tag, delete_tag, get_item_tags, list_tagged_items, list_tags, tag_item, untag_item,
+    update_tag,
+};
+use updater::{check_for_updates, download_update, UpdateState};
+use usage::{erase_usage_stats, get_usage_stats, track_usage};
+use video::{cancel_video, compress_video, VideoJobs};
+use watch::{
+    add_watch_folder, list_watch_folders, remove_watch_folder, set_watch_folder_enabled, WatchState,
+};
+use workers::{get_worker_concurrency, set_worker_concurrency};
+
+#[global_allocator]
+static ALLOCATOR: memory::TrackingAllocator = memory::TrackingAllocator;
 
 pub fn create_window(app: &tauri::App) -> tauri::Result<()> {
     // Initialize empty font state
     let empty_state = initialize_empty_state();
 
     // Store empty font state
-    println!("Initializing empty font state");
+    tracing::debug!("Initializing empty font state");
     app.manage(FontState(std::sync::Mutex::new(empty_state)));
 
-    let window = WebviewWindowBuilder::new(app, "main", WebviewUrl::default())
+    let mut builder = WebviewWindowBuilder::new(app, "main", WebviewUrl::default())
         .title("Squish")
         .inner_size(1200.0, 800.0)
         .decorations(true)
-        .title_bar_style(TitleBarStyle::Visible)
-        .build()?;
+        .visible(!login_item::launched_in_background());
+    #[cfg(target_os = "macos")]
+    {
+        builder = builder.title_bar_style(TitleBarStyle::Visible);
+    }
+    // Come back where the window was last time
+    if let Some(frame) = session::saved_window(app.handle(), "main") {
+        builder = builder
+            .position(frame.x as f64, frame.y as f64)
+            .inner_size(frame.width as f64, frame.height as f64)
+            .maximized(frame.maximized)
+            .fullscreen(frame.fullscreen);
+    }
+    #[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
+    let window = builder.build()?;
 
     #[cfg(target_os = "macos")]
     {
@@ -47,16 +216,289 @@ pub fn create_window(app: &tauri::App) -> tauri::Result<()> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    if crash::run_watchdog_if_requested() {
+        return;
+    }
+    let builder = tauri::Builder::default();
+    // Must be the first plugin; deep links and context-menu launches while
+    // we're running are forwarded to this instance instead of starting another
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+        tracing::info!("Forwarded a second launch to the running instance");
+        deep_link::handle_args(app, &argv, std::path::Path::new(&cwd));
+        if let Some(window) = app.get_webview_window("main") {
+            // Focus alone doesn't bring back a minimized or hidden window
+            let _ = window.unminimize();
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }));
+    #[cfg(desktop)]
+    let builder = builder.plugin(login_item::plugin());
+    let context = tauri::generate_context!();
+    // Only registered when the config carries an update signing key
+    let builder = if updater::enabled(context.config()) {
+        builder.plugin(tauri_plugin_updater::Builder::new().build())
+    } else {
+        builder
+    };
+    builder
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_sql::Builder::default().build())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_shell::init())
+        .register_asynchronous_uri_scheme_protocol(asset_protocol::SCHEME, asset_protocol::handle)
+        .register_uri_scheme_protocol(preview::SCHEME, preview::handle)
         .setup(|app| {
+            app.manage(startup::Deferred::default());
+            logging::init(app.handle())?;
+            crash::install(app.handle())?;
+            let phase = perf::phase("database");
+            let (conn, integrity_report) = integrity::open_checked(app.handle())?;
+            let app_settings = settings::load(&conn)?;
+            drop(phase);
+            let phase = perf::phase("state");
+            logging::apply(app_settings.log_level);
+            app.manage(workers::load(&app_settings));
+            app.manage(net::load(&app_settings));
+            app.manage(i18n::load(&app_settings));
+            app.manage(SettingsState(std::sync::RwLock::new(app_settings)));
+            app.manage(db::Db(std::sync::Mutex::new(conn)));
+            workers::watch_settings(app.handle());
+            logging::watch_settings(app.handle());
+            net::watch_settings(app.handle());
+            i18n::watch_settings(app.handle());
+            app.manage(WatchState(Default::default()));
+            app.manage(VideoJobs(Default::default()));
+            app.manage(SyncState(Default::default()));
+            app.manage(PendingDeepLinks::default());
+            app.manage(LastNotification(Default::default()));
+            app.manage(UpdateState::default());
+            app.manage(PowerState(Default::default()));
+            app.manage(ConnectivityState::default());
+            app.manage(accessibility::load());
+            app.manage(AutomationState::default());
+            app.manage(ClipboardMonitor::default());
+            app.manage(JobQueue::default());
+            app.manage(ImportCache::default());
+            app.manage(PreviewState::default());
+            app.manage(Operations::default());
+            app.manage(PluginHost::default());
+            app.manage(HookQueue::default());
+            drop(phase);
+            let phase = perf::phase("window");
             create_window(app)?;
+            startup::finish(app.handle());
+            drop(phase);
+            let phase = perf::phase("services");
+            integrity::announce(app.handle(), &integrity_report);
+            app.manage(IntegrityState(std::sync::Mutex::new(Some(
+                integrity_report,
+            ))));
+            maintenance::start(app.handle());
+            fonts::embedded::start(app.handle());
+            deep_link::start(app.handle())?;
+            notifications::start(app.handle());
+            battery::start(app.handle());
+            connectivity::start(app.handle());
+            accessibility::start(app.handle());
+            session::start(app.handle());
+            automation::start(app.handle());
+            clipboard_monitor::start(app.handle());
+            #[cfg(target_os = "macos")]
+            services::start(app.handle());
+            #[cfg(target_os = "macos")]
+            scripting::start(app.handle());
+            drop(phase);
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![get_system_fonts])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(blocking::watch(throttle::wrap(tauri::generate_handler![
+            get_system_fonts,
+            import_image,
+            compress_image,
+            reconstruct_jpeg,
+            transform_image,
+            rasterize_svg,
+            extract_palette,
+            compress_batch,
+            compute_blurhash,
+            compare_images,
+            compare_heatmap,
+            start_drag_out,
+            start_promised_drag,
+            list_watch_folders,
+            add_watch_folder,
+            remove_watch_folder,
+            set_watch_folder_enabled,
+            paste_image_from_clipboard,
+            copy_image_to_clipboard,
+            get_worker_concurrency,
+            set_worker_concurrency,
+            compress_pdf,
+            compress_video,
+            cancel_video,
+            estimate_compression,
+            optimize_lossless,
+            analyze_alpha,
+            pack_sprites,
+            get_setting,
+            set_setting,
+            get_app_settings,
+            index_project,
+            list_library,
+            export_project_document,
+            open_project_document,
+            search_library,
+            push_op,
+            undo,
+            redo,
+            get_history,
+            backup_database,
+            restore_database,
+            run_db_maintenance,
+            is_library_encrypted,
+            enable_library_encryption,
+            sql_execute,
+            sql_select,
+            list_presets,
+            save_preset,
+            delete_preset,
+            get_sync_config,
+            set_sync_config,
+            sync_now,
+            list_sync_conflicts,
+            resolve_sync_conflict,
+            list_tags,
+            create_tag,
+            update_tag,
+            delete_tag,
+            tag_item,
+            untag_item,
+            get_item_tags,
+            list_tagged_items,
+            list_collections,
+            create_collection,
+            rename_collection,
+            move_collection,
+            delete_collection,
+            list_collection_items,
+            add_to_collection,
+            remove_from_collection,
+            reorder_collection_items,
+            export_settings,
+            import_settings,
+            get_database_path,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            get_integrity_report,
+            get_job_history,
+            get_savings_stats,
+            clear_job_history,
+            track_usage,
+            get_usage_stats,
+            erase_usage_stats,
+            take_pending_deep_links,
+            check_for_updates,
+            download_update,
+            get_crash_reports,
+            set_log_level,
+            get_recent_logs,
+            get_power_status,
+            download_url,
+            get_connectivity,
+            get_system_locale,
+            get_accessibility_prefs,
+            print_document,
+            save_open_documents,
+            get_session,
+            get_automation_token,
+            reset_automation_token,
+            set_launch_at_login,
+            get_launch_at_login,
+            get_clipboard_monitoring,
+            accept_clipboard_image,
+            dismiss_clipboard_image,
+            get_log_tail,
+            remember_folder,
+            forget_folder,
+            store_secret,
+            get_secret,
+            delete_secret,
+            list_permissions,
+            set_permission,
+            revoke_permission,
+            enqueue_job,
+            list_jobs,
+            cancel_job,
+            set_job_priority,
+            read_image_data,
+            open_preview,
+            update_preview,
+            close_preview,
+            open_mini_preview,
+            close_mini_preview,
+            watch_preview,
+            get_perf_report,
+            get_memory_stats,
+            trim_caches,
+            cancel_operation,
+            list_plugins,
+            reload_plugins,
+            set_plugin_enabled,
+            export_with_plugin,
+            apply_plugin_filter,
+            run_script,
+            list_hooks,
+            save_hook,
+            delete_hook,
+            export_preset_bundle,
+            inspect_preset_bundle,
+            import_preset_bundle,
+            list_importers,
+            find_importers,
+            get_problem_fonts,
+            set_problem_font_hidden,
+            reveal_problem_font,
+            get_font_stack,
+            suggest_font_pairings,
+            list_project_fonts,
+            read_project_font,
+            get_glyph_outline,
+            get_bitmap_strikes,
+            get_font_license_info,
+            layout_paragraph,
+            layout_text_on_path,
+            get_projects_using_font,
+            get_fonts_used,
+            save_workspace_state,
+            get_workspace_state
+        ])))
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Exit => {
+                session::save_on_exit(app);
+                crash::clean_exit();
+                updater::install_staged(app);
+            }
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Opened { urls } => deep_link::handle_opened(app, &urls),
+            // Clicking the Dock icon after a background launch at login
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Reopen {
+                has_visible_windows: false,
+                ..
+            } => {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            }
+            _ => {}
+        });
 }
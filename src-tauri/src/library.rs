@@ -0,0 +1,283 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tauri::{AppHandle, State};
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::safe_path::SafePath;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryEntry {
+    pub project_id: String,
+    pub title: String,
+    pub page_sizes: Vec<[u32; 2]>,
+    pub fonts: Vec<String>,
+    pub asset_count: u32,
+    pub thumbnail_path: Option<String>,
+    pub updated_at: String,
+}
+
+// Rebuilds the index row for one project from its layers and canvas settings
+pub fn index(
+    conn: &Connection,
+    project_id: &str,
+    thumbnail_path: Option<&str>,
+) -> Result<LibraryEntry, String> {
+    let title: String = conn
+        .query_row(
+            "SELECT name FROM projects WHERE id = ?1",
+            params![project_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read project: {}", e))?
+        .ok_or_else(|| format!("Project {} not found", project_id))?;
+
+    let size: Option<(u32, u32)> = conn
+        .query_row(
+            "SELECT width, height FROM canvas_settings WHERE project_id = ?1",
+            params![project_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read canvas settings: {}", e))?;
+    // Projects without canvas settings render at the frontend default
+    let (width, height) = size.unwrap_or((1920, 1080));
+    let page_sizes = vec![[width, height]];
+
+    let fonts: Vec<String> = crate::font_usage::record(conn, project_id)?
+        .into_iter()
+        .map(|usage| usage.family)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let asset_count: u32 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT COALESCE(image_asset_id, sticker_asset_id))
+             FROM layers WHERE project_id = ?1 AND type IN ('image', 'sticker')",
+            params![project_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count assets: {}", e))?;
+
+    let page_sizes_json = serde_json::to_string(&page_sizes)
+        .map_err(|e| format!("Failed to serialize page sizes: {}", e))?;
+    let fonts_json =
+        serde_json::to_string(&fonts).map_err(|e| format!("Failed to serialize fonts: {}", e))?;
+    conn.execute(
+        "INSERT INTO project_index (project_id, title, page_sizes, fonts, asset_count, thumbnail_path)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(project_id) DO UPDATE SET
+            title = excluded.title,
+            page_sizes = excluded.page_sizes,
+            fonts = excluded.fonts,
+            asset_count = excluded.asset_count,
+            thumbnail_path = COALESCE(excluded.thumbnail_path, project_index.thumbnail_path),
+            updated_at = CURRENT_TIMESTAMP",
+        params![
+            project_id,
+            title,
+            page_sizes_json,
+            fonts_json,
+            asset_count,
+            thumbnail_path
+        ],
+    )
+    .map_err(|e| format!("Failed to index project: {}", e))?;
+    let tags = crate::tags::names_for(conn, "project", project_id)?;
+    crate::search::index_project(conn, project_id, &title, &tags)?;
+
+    let entry = get_entry(conn, project_id)?
+        .ok_or_else(|| format!("Project {} not indexed", project_id))?;
+    crate::spotlight::index_project(&entry, &tags);
+    Ok(entry)
+}
+
+const SELECT_ENTRY: &str =
+    "SELECT project_id, title, page_sizes, fonts, asset_count, thumbnail_path, updated_at
+     FROM project_index";
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<LibraryEntry> {
+    let page_sizes: String = row.get(2)?;
+    let fonts: String = row.get(3)?;
+    Ok(LibraryEntry {
+        project_id: row.get(0)?,
+        title: row.get(1)?,
+        page_sizes: serde_json::from_str(&page_sizes).unwrap_or_default(),
+        fonts: serde_json::from_str(&fonts).unwrap_or_default(),
+        asset_count: row.get(4)?,
+        thumbnail_path: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+pub fn get_entry(conn: &Connection, project_id: &str) -> Result<Option<LibraryEntry>, String> {
+    conn.query_row(
+        &format!("{} WHERE project_id = ?1", SELECT_ENTRY),
+        params![project_id],
+        entry_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read library entry: {}", e))
+}
+
+// Called by the frontend after every save; the thumbnail is kept when not supplied
+#[tauri::command]
+pub fn index_project(
+    app: AppHandle,
+    db: State<Db>,
+    project_id: String,
+    thumbnail_path: Option<String>,
+) -> Result<LibraryEntry, Error> {
+    let thumbnail_path = thumbnail_path
+        .map(|p| SafePath::read(&app, &p).map(SafePath::into_string))
+        .transpose()?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(index(&conn, &project_id, thumbnail_path.as_deref())?)
+}
+
+// Everything the library grid needs in one query, most recently saved first.
+// Projects saved before the index existed are indexed on first listing.
+#[tauri::command]
+pub fn list_library(db: State<Db>) -> Result<Vec<LibraryEntry>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM projects WHERE id NOT IN (SELECT project_id FROM project_index)")
+        .map_err(|e| format!("Failed to read projects: {}", e))?;
+    let missing = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to read projects: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read projects: {}", e))?;
+    for project_id in missing {
+        index(&conn, &project_id, None)?;
+    }
+
+    // Deleted projects leave rows behind when foreign keys are off on the frontend connection
+    let mut stmt = conn
+        .prepare("SELECT project_id FROM project_index WHERE project_id NOT IN (SELECT id FROM projects)")
+        .map_err(|e| format!("Failed to read library index: {}", e))?;
+    let deleted = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to read library index: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read library index: {}", e))?;
+    crate::spotlight::remove_projects(&deleted);
+    conn.execute(
+        "DELETE FROM project_index WHERE project_id NOT IN (SELECT id FROM projects)",
+        [],
+    )
+    .map_err(|e| format!("Failed to prune library index: {}", e))?;
+    conn.execute(
+        "DELETE FROM font_usage WHERE project_id NOT IN (SELECT id FROM projects)",
+        [],
+    )
+    .map_err(|e| format!("Failed to prune font usage: {}", e))?;
+    conn.execute(
+        "DELETE FROM workspace_state WHERE project_id NOT IN (SELECT id FROM projects)",
+        [],
+    )
+    .map_err(|e| format!("Failed to prune workspace state: {}", e))?;
+
+    let mut stmt = conn
+        .prepare(&format!("{} ORDER BY updated_at DESC", SELECT_ENTRY))
+        .map_err(|e| format!("Failed to read library: {}", e))?;
+    let entries = stmt
+        .query_map([], entry_from_row)
+        .map_err(|e| format!("Failed to read library: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read library: {}", e))?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn text_layer(conn: &Connection, id: &str, family: &str) {
+        let style = serde_json::json!({ "fontFamily": family }).to_string();
+        conn.execute(
+            "INSERT INTO layers (id, project_id, type, content, transform, style)
+             VALUES (?1, 'p1', 'text', 'Hello', '{}', ?2)",
+            params![id, style],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn projects_without_canvas_settings_use_the_default_page() {
+        let conn = library();
+        let entry = index(&conn, "p1", None).unwrap();
+        assert_eq!(entry.title, "Poster");
+        assert_eq!(entry.page_sizes, vec![[1920, 1080]]);
+        assert!(entry.fonts.is_empty());
+        assert_eq!(entry.asset_count, 0);
+
+        conn.execute(
+            "INSERT INTO canvas_settings (project_id, width, height) VALUES ('p1', 1080, 1350)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            index(&conn, "p1", None).unwrap().page_sizes,
+            vec![[1080, 1350]]
+        );
+    }
+
+    #[test]
+    fn fonts_are_listed_once_in_order() {
+        let conn = library();
+        text_layer(&conn, "t1", "Roboto");
+        text_layer(&conn, "t2", "Inter");
+        text_layer(&conn, "t3", "Roboto");
+        let entry = index(&conn, "p1", None).unwrap();
+        assert_eq!(entry.fonts, vec!["Inter", "Roboto"]);
+    }
+
+    #[test]
+    fn shared_assets_are_counted_once() {
+        let conn = library();
+        conn.execute(
+            "INSERT INTO image_assets (id, name, mime_type, data) VALUES ('a1', 'a', 'image/png', x'00')",
+            [],
+        )
+        .unwrap();
+        for id in ["i1", "i2"] {
+            conn.execute(
+                "INSERT INTO layers (id, project_id, type, image_asset_id, transform)
+                 VALUES (?1, 'p1', 'image', 'a1', '{}')",
+                params![id],
+            )
+            .unwrap();
+        }
+        assert_eq!(index(&conn, "p1", None).unwrap().asset_count, 1);
+    }
+
+    #[test]
+    fn reindexing_without_a_thumbnail_keeps_the_old_one() {
+        let conn = library();
+        index(&conn, "p1", Some("/thumbs/p1.png")).unwrap();
+        conn.execute("UPDATE projects SET name = 'Flyer' WHERE id = 'p1'", [])
+            .unwrap();
+        let entry = index(&conn, "p1", None).unwrap();
+        assert_eq!(entry.title, "Flyer");
+        assert_eq!(entry.thumbnail_path.as_deref(), Some("/thumbs/p1.png"));
+        assert!(get_entry(&conn, "p2").unwrap().is_none());
+        assert!(index(&conn, "p2", None).is_err());
+    }
+}
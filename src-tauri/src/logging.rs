@@ -0,0 +1,272 @@
+// Structured logging through tracing. Everything goes to stdout and to daily
+// log files under app data, keeping a week of history for support requests.
+// The most recent lines are also kept in memory and streamed to the UI over
+// logs://line for the debug console.
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+use crate::error::Error;
+use crate::perf;
+use crate::settings::{self, LogLevel, SettingChange, SETTINGS_CHANGED_EVENT};
+
+const LOG_LEVEL_KEY: &str = "log_level";
+const LOGS_DIR: &str = "logs";
+const FILE_PREFIX: &str = "squish";
+const FILE_SUFFIX: &str = "log";
+const MAX_FILES: usize = 7;
+pub const LINE_EVENT: &str = "logs://line";
+const TAIL_CAPACITY: usize = 1000;
+
+static FILTER: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+// Flushes buffered lines to the file when dropped, so it lives for the whole process
+static GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+static TAIL: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+thread_local! {
+    // Set while a line is being emitted, so anything logged on the way
+    // doesn't recurse back into the tail
+    static EMITTING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    // Increasing, so the UI can merge get_log_tail with the live stream
+    pub seq: u64,
+    // Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+// The message first, then any structured fields as key=value
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+struct TailLayer;
+
+impl<S: Subscriber> Layer<S> for TailLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if EMITTING.with(|emitting| emitting.replace(true)) {
+            return;
+        }
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let line = LogLine {
+            seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message + visitor.fields.as_str(),
+        };
+        if let Ok(mut tail) = TAIL.lock() {
+            if tail.len() >= TAIL_CAPACITY {
+                tail.pop_front();
+            }
+            tail.push_back(line.clone());
+        }
+        if let Some(app) = APP.get() {
+            let _ = app.emit(LINE_EVENT, line);
+        }
+        EMITTING.with(|emitting| emitting.set(false));
+    }
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+fn logs_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(LOGS_DIR))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+// Installs the global subscriber at the default level; `apply` switches to
+// the saved level once settings are loaded
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let dir = logs_dir(app)?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(FILE_PREFIX)
+        .filename_suffix(FILE_SUFFIX)
+        .max_log_files(MAX_FILES)
+        .build(&dir)
+        .map_err(|e| format!("Failed to open log files in {}: {}", dir.display(), e))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    let _ = GUARD.set(guard);
+
+    let (filter, handle) = reload::Layer::new(level_filter(LogLevel::default()));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(fmt::layer().with_ansi(false).with_writer(writer))
+        .with(TailLayer)
+        .with(perf::layer())
+        .try_init()
+        .map_err(|e| format!("Failed to install logger: {}", e))?;
+    let _ = FILTER.set(handle);
+    let _ = APP.set(app.clone());
+    Ok(())
+}
+
+pub fn apply(level: LogLevel) {
+    let Some(handle) = FILTER.get() else {
+        return;
+    };
+    match handle.modify(|filter| *filter = level_filter(level)) {
+        Ok(()) => tracing::info!("Log level set to {:?}", level),
+        Err(e) => tracing::warn!("Failed to change log level: {}", e),
+    }
+}
+
+// Keeps the live level in sync when the setting is changed from anywhere
+pub fn watch_settings(app: &AppHandle) {
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if change.key == LOG_LEVEL_KEY {
+            if let Ok(level) = serde_json::from_value(change.value) {
+                apply(level);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn set_log_level(app: AppHandle, level: LogLevel) -> Result<(), Error> {
+    Ok(settings::write(&app, LOG_LEVEL_KEY, &level)?)
+}
+
+// The last n lines across the rotated files, oldest first
+#[tauri::command]
+pub fn get_recent_logs(app: AppHandle, n: usize) -> Result<Vec<String>, Error> {
+    Ok(recent_lines(&logs_dir(&app)?, n)?)
+}
+
+fn recent_lines(dir: &Path, n: usize) -> Result<Vec<String>, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    // Rotated files are suffixed with their date, so names sort chronologically
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    files.sort();
+
+    let mut lines = Vec::new();
+    for path in files.iter().rev() {
+        if lines.len() >= n {
+            break;
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut file_lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        let keep = file_lines.len().min(n - lines.len());
+        let mut tail = file_lines.split_off(file_lines.len() - keep);
+        tail.append(&mut lines);
+        lines = tail;
+    }
+    Ok(lines)
+}
+
+// What the debug console shows before it starts listening; at most the last
+// 1000 lines logged in this session
+#[tauri::command]
+pub fn get_log_tail(n: Option<usize>) -> Vec<LogLine> {
+    let Ok(tail) = TAIL.lock() else {
+        return Vec::new();
+    };
+    let n = n.unwrap_or(TAIL_CAPACITY).min(tail.len());
+    tail.iter().skip(tail.len() - n).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_lines_span_the_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("squish-logs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("squish.2026-10-13.log"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(dir.join("squish.2026-10-14.log"), "four\nfive\n").unwrap();
+        std::fs::write(dir.join("other.log"), "unrelated\n").unwrap();
+
+        assert_eq!(recent_lines(&dir, 1).unwrap(), vec!["five"]);
+        assert_eq!(
+            recent_lines(&dir, 3).unwrap(),
+            vec!["three", "four", "five"]
+        );
+        assert_eq!(recent_lines(&dir, 100).unwrap().len(), 5);
+        assert!(recent_lines(&dir.join("missing"), 10).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn events_are_kept_in_the_tail_with_their_fields() {
+        let subscriber = Registry::default().with(TailLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "squish::tail_test", files = 3, "Tail test started");
+            tracing::info!(target: "squish::tail_test", "Tail test finished");
+        });
+        let lines: Vec<LogLine> = get_log_tail(None)
+            .into_iter()
+            .filter(|line| line.target == "squish::tail_test")
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].message, "Tail test started files=3");
+        assert_eq!(lines[0].level, "WARN");
+        assert!(lines[0].seq < lines[1].seq);
+        assert_eq!(get_log_tail(Some(1)).len(), 1);
+    }
+}
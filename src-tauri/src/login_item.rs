@@ -0,0 +1,61 @@
+// Starting Squish at login, for people who rely on watch folders. The
+// autostart plugin registers a LaunchAgent, a Run registry value or an XDG
+// autostart entry; those launches pass BACKGROUND_FLAG so the window stays
+// hidden until the user opens Squish again.
+use tauri::AppHandle;
+
+use crate::error::Error;
+
+pub const BACKGROUND_FLAG: &str = "--background";
+
+pub fn launched_in_background() -> bool {
+    std::env::args().any(|arg| arg == BACKGROUND_FLAG)
+}
+
+#[cfg(desktop)]
+pub fn plugin<R: tauri::Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    tauri_plugin_autostart::init(
+        tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+        Some(vec![BACKGROUND_FLAG]),
+    )
+}
+
+#[cfg(desktop)]
+#[tauri::command]
+pub fn set_launch_at_login(app: AppHandle, enabled: bool) -> Result<(), Error> {
+    use tauri_plugin_autostart::ManagerExt;
+
+    let autolaunch = app.autolaunch();
+    let result = if enabled {
+        autolaunch.enable()
+    } else {
+        autolaunch.disable()
+    };
+    result.map_err(|e| Error::Internal(format!("Failed to update launch at login: {}", e)))
+}
+
+// Read from the OS rather than settings, since the user can remove the login
+// item outside Squish
+#[cfg(desktop)]
+#[tauri::command]
+pub fn get_launch_at_login(app: AppHandle) -> Result<bool, Error> {
+    use tauri_plugin_autostart::ManagerExt;
+
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| Error::Internal(format!("Failed to read launch at login: {}", e)))
+}
+
+#[cfg(mobile)]
+#[tauri::command]
+pub fn set_launch_at_login(_app: AppHandle, _enabled: bool) -> Result<(), Error> {
+    Err(Error::Internal(
+        "Launch at login is not supported on this platform".to_string(),
+    ))
+}
+
+#[cfg(mobile)]
+#[tauri::command]
+pub fn get_launch_at_login(_app: AppHandle) -> Result<bool, Error> {
+    Ok(false)
+}
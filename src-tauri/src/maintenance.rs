@@ -0,0 +1,199 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{get_preference, set_preference, Db};
+use crate::error::Error;
+
+const LAST_RUN_KEY: &str = "db_maintenance_last_run";
+// How often the background pass runs, and how often it checks whether it's due
+const INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+const POLL: Duration = Duration::from_secs(10 * 60);
+// Pages freed per background pass, so it never holds the write lock for long
+const INCREMENTAL_PAGES: u32 = 2048;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub reclaimed_bytes: u64,
+    pub full_vacuum: bool,
+    pub duration_ms: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn pragma_u64(conn: &Connection, pragma: &str) -> Result<u64, String> {
+    conn.query_row(&format!("PRAGMA {}", pragma), [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map(|n| n.max(0) as u64)
+    .map_err(|e| format!("Failed to read PRAGMA {}: {}", pragma, e))
+}
+
+// Size on disk of the main file plus its write-ahead log
+fn disk_usage(conn: &Connection) -> u64 {
+    let Some(path) = conn.path().filter(|p| !p.is_empty()) else {
+        return 0;
+    };
+    let size = |p: &str| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    size(path) + size(&format!("{}-wal", path))
+}
+
+// `thorough` is the manual path: it may rewrite the whole file and waits for
+// readers to finish checkpointing, which the background pass never does
+fn run(conn: &Connection, thorough: bool) -> Result<MaintenanceReport, String> {
+    let started = Instant::now();
+    let bytes_before = disk_usage(conn);
+
+    conn.execute_batch("PRAGMA optimize;")
+        .map_err(|e| format!("Failed to optimize database: {}", e))?;
+
+    // Libraries created before incremental vacuum was enabled need one full
+    // VACUUM for the auto_vacuum mode to take effect
+    let incremental = pragma_u64(conn, "auto_vacuum")? == 2;
+    let full_vacuum = thorough && !incremental;
+    if full_vacuum {
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+            .map_err(|e| format!("Failed to vacuum database: {}", e))?;
+    } else if incremental {
+        let pages = if thorough { 0 } else { INCREMENTAL_PAGES };
+        // incremental_vacuum(0) frees every page on the freelist. It frees
+        // one page per step, so every row has to be read.
+        let vacuum = |e: rusqlite::Error| format!("Failed to vacuum database: {}", e);
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA incremental_vacuum({})", pages))
+            .map_err(vacuum)?;
+        let mut rows = stmt.query([]).map_err(vacuum)?;
+        while rows.next().map_err(vacuum)?.is_some() {}
+    }
+
+    let checkpoint = if thorough { "TRUNCATE" } else { "PASSIVE" };
+    conn.query_row(
+        &format!("PRAGMA wal_checkpoint({})", checkpoint),
+        [],
+        |_| Ok(()),
+    )
+    .map_err(|e| format!("Failed to checkpoint database: {}", e))?;
+
+    set_preference(conn, LAST_RUN_KEY, &now_secs().to_string())?;
+
+    let bytes_after = disk_usage(conn);
+    Ok(MaintenanceReport {
+        bytes_before,
+        bytes_after,
+        reclaimed_bytes: bytes_before.saturating_sub(bytes_after),
+        full_vacuum,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+fn is_due(conn: &Connection) -> bool {
+    let last = get_preference(conn, LAST_RUN_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    now_secs().saturating_sub(last) >= INTERVAL.as_secs()
+}
+
+// Background pass on its own thread; the schedule survives restarts because
+// the last run time is stored with the library
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL);
+        let db = app.state::<Db>();
+        let Ok(conn) = db.0.lock() else {
+            return;
+        };
+        if !is_due(&conn) {
+            continue;
+        }
+        match run(&conn, false) {
+            Ok(report) => tracing::info!(
+                "Database maintenance reclaimed {} bytes in {}ms",
+                report.reclaimed_bytes,
+                report.duration_ms
+            ),
+            Err(e) => tracing::warn!("Database maintenance failed: {}", e),
+        }
+    });
+}
+
+#[tauri::command]
+pub fn run_db_maintenance(db: State<Db>) -> Result<MaintenanceReport, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(run(&conn, true)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str) -> (Connection, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "squish-maintenance-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let conn = Connection::open(dir.join("squish.db")).unwrap();
+        conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        crate::migrations::run(&conn).unwrap();
+        (conn, dir)
+    }
+
+    fn fill_and_delete(conn: &Connection) {
+        conn.execute(
+            "INSERT INTO image_assets (id, name, mime_type, data) VALUES ('a1', 'a', 'image/png', zeroblob(4000000))",
+            [],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM image_assets", []).unwrap();
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .unwrap();
+    }
+
+    #[test]
+    fn a_new_library_is_due() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        assert!(is_due(&conn));
+        set_preference(&conn, LAST_RUN_KEY, &now_secs().to_string()).unwrap();
+        assert!(!is_due(&conn));
+    }
+
+    #[test]
+    fn the_first_thorough_run_switches_to_incremental_vacuum() {
+        let (conn, dir) = library("thorough");
+        fill_and_delete(&conn);
+        let before = pragma_u64(&conn, "auto_vacuum").unwrap();
+
+        let report = run(&conn, true).unwrap();
+        assert_eq!(report.full_vacuum, before != 2);
+        assert!(
+            report.reclaimed_bytes > 1_000_000,
+            "{}",
+            report.reclaimed_bytes
+        );
+        assert_eq!(pragma_u64(&conn, "auto_vacuum").unwrap(), 2);
+        assert!(!is_due(&conn));
+
+        // Incremental from now on: space comes back without a full rewrite
+        fill_and_delete(&conn);
+        let report = run(&conn, true).unwrap();
+        assert!(!report.full_vacuum);
+        assert_eq!(pragma_u64(&conn, "freelist_count").unwrap(), 0);
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
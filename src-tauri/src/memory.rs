@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager};
+
+use crate::imaging::ImportCache;
+use crate::preview::PreviewState;
+
+// Wraps the system allocator to keep per-thread allocation counters, so a job
+// running on a worker thread can report how much heap it needed at its peak
+pub struct TrackingAllocator;
+
+thread_local! {
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+fn record(delta: isize) {
+    // try_with because allocations still happen while thread locals are torn down
+    let _ = CURRENT.try_with(|current| {
+        let value = current.get() + delta;
+        current.set(value);
+        let _ = PEAK.try_with(|peak| {
+            if value > peak.get() {
+                peak.set(value);
+            }
+        });
+    });
+}
+
+// Runs f and returns the peak number of bytes it had allocated on this thread
+pub fn measure_peak<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let start = CURRENT.with(Cell::get);
+    PEAK.with(|peak| peak.set(start));
+    let result = f();
+    let peak = PEAK.with(Cell::get);
+    (result, (peak - start).max(0) as u64)
+}
+
+// Where the memory goes, for the UI and for deciding what trim_caches can free
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    // Resident set of the whole process; None where the OS doesn't say
+    pub rss_bytes: Option<u64>,
+    // Converted imports waiting for read_image_data
+    pub import_cache_bytes: u64,
+    // Open live previews; freed when they're closed, never trimmed
+    pub preview_bytes: u64,
+    pub font_cache_bytes: u64,
+    // Thumbnails are kept on disk rather than in RAM, but trimmed with the rest
+    pub thumbnail_cache_bytes: u64,
+    pub thumbnail_count: u64,
+}
+
+// Oldest first
+fn thumbnails(app: &AppHandle) -> Vec<(SystemTime, u64, PathBuf)> {
+    let Ok(entries) = crate::jobs::thumbnails_dir(app).and_then(|dir| {
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))
+    }) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            metadata
+                .is_file()
+                .then(|| (modified, metadata.len(), entry.path()))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _, _)| *modified);
+    files
+}
+
+fn stats(app: &AppHandle) -> MemoryStats {
+    let thumbnails = thumbnails(app);
+    MemoryStats {
+        rss_bytes: memory_stats::memory_stats().map(|usage| usage.physical_mem as u64),
+        import_cache_bytes: app.state::<ImportCache>().bytes(),
+        preview_bytes: app.state::<PreviewState>().bytes(),
+        font_cache_bytes: crate::fonts::cache_bytes(app),
+        thumbnail_cache_bytes: thumbnails.iter().map(|(_, len, _)| len).sum(),
+        thumbnail_count: thumbnails.len() as u64,
+    }
+}
+
+#[tauri::command]
+pub fn get_memory_stats(app: AppHandle) -> MemoryStats {
+    stats(&app)
+}
+
+// Shrinks the import, thumbnail and font caches to target_bytes combined,
+// cheapest to rebuild first: pending imports, then thumbnails oldest first,
+// then the font list (a rescan takes seconds). Called by the UI when the OS
+// reports memory pressure or the user asks; returns the stats afterwards.
+#[tauri::command]
+pub fn trim_caches(app: AppHandle, target_bytes: u64) -> MemoryStats {
+    let before = stats(&app);
+    let mut total =
+        before.import_cache_bytes + before.thumbnail_cache_bytes + before.font_cache_bytes;
+
+    let imports = app.state::<ImportCache>();
+    while total > target_bytes {
+        let Some(freed) = imports.evict_oldest() else {
+            break;
+        };
+        total = total.saturating_sub(freed);
+    }
+    for (_, len, path) in thumbnails(&app) {
+        if total <= target_bytes {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => total = total.saturating_sub(len),
+            Err(e) => tracing::warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    if total > target_bytes {
+        total = total.saturating_sub(crate::fonts::clear_cache(&app));
+    }
+
+    let after = stats(&app);
+    tracing::info!(
+        "Trimmed caches to {} bytes (target {}), RSS {:?} -> {:?}",
+        total,
+        target_bytes,
+        before.rss_bytes,
+        after.rss_bytes
+    );
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_peak_covers_memory_freed_before_returning() {
+        let (len, peak) = measure_peak(|| {
+            let buffer = vec![1u8; 8 << 20];
+            buffer.len()
+        });
+        assert_eq!(len, 8 << 20);
+        assert!(peak >= 8 << 20, "{}", peak);
+    }
+
+    #[test]
+    fn earlier_allocations_are_not_counted() {
+        let kept = vec![1u8; 8 << 20];
+        let (_, peak) = measure_peak(|| vec![1u8; 1024].len());
+        assert!(peak < 1 << 20, "{}", peak);
+        drop(kept);
+    }
+}
@@ -0,0 +1,207 @@
+use rusqlite::{params, Connection};
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+// Append only: never edit a migration that has shipped, add a new one instead
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline frontend schema",
+        sql: include_str!("../migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "backend tables",
+        sql: include_str!("../migrations/0002_backend_tables.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "project metadata index",
+        sql: include_str!("../migrations/0003_project_index.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "full-text search",
+        sql: include_str!("../migrations/0004_search.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "persistent undo history",
+        sql: include_str!("../migrations/0005_history.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "presets and sync state",
+        sql: include_str!("../migrations/0006_sync.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "tags",
+        sql: include_str!("../migrations/0007_tags.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "collections",
+        sql: include_str!("../migrations/0008_collections.sql"),
+    },
+    Migration {
+        version: 9,
+        description: "job history",
+        sql: include_str!("../migrations/0009_job_history.sql"),
+    },
+    Migration {
+        version: 10,
+        description: "usage stats",
+        sql: include_str!("../migrations/0010_usage_stats.sql"),
+    },
+    Migration {
+        version: 11,
+        description: "session restoration",
+        sql: include_str!("../migrations/0011_session.sql"),
+    },
+    Migration {
+        version: 12,
+        description: "security-scoped bookmarks",
+        sql: include_str!("../migrations/0012_bookmarks.sql"),
+    },
+    Migration {
+        version: 13,
+        description: "permission decisions",
+        sql: include_str!("../migrations/0013_permissions.sql"),
+    },
+    Migration {
+        version: 14,
+        description: "job queue",
+        sql: include_str!("../migrations/0014_job_queue.sql"),
+    },
+    Migration {
+        version: 15,
+        description: "post-export hooks",
+        sql: include_str!("../migrations/0015_export_hooks.sql"),
+    },
+    Migration {
+        version: 16,
+        description: "curated font pairings",
+        sql: include_str!("../migrations/0016_font_pairings.sql"),
+    },
+    Migration {
+        version: 17,
+        description: "project fonts",
+        sql: include_str!("../migrations/0017_project_fonts.sql"),
+    },
+    Migration {
+        version: 18,
+        description: "font usage per project",
+        sql: include_str!("../migrations/0018_font_usage.sql"),
+    },
+    Migration {
+        version: 19,
+        description: "per-document workspace state",
+        sql: include_str!("../migrations/0019_workspace_state.sql"),
+    },
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+// Applies every pending migration in its own transaction. Refuses to touch a
+// database written by a newer build, since its schema may not be understood.
+pub fn run(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .map_err(|e| format!("Failed to create schema_migrations: {}", e))?;
+
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this version of Squish supports ({}). Please update Squish.",
+            current, latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        tracing::info!(
+            "Applying migration {}: {}",
+            migration.version,
+            migration.description
+        );
+        apply(conn, migration)?;
+    }
+
+    tracing::info!("Database schema at version {}", latest);
+    Ok(())
+}
+
+pub fn current_version(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+fn apply(conn: &Connection, migration: &Migration) -> Result<(), String> {
+    let fail = |e: rusqlite::Error| {
+        format!(
+            "Migration {} ({}) failed: {}",
+            migration.version, migration.description, e
+        )
+    };
+
+    conn.execute_batch("BEGIN").map_err(fail)?;
+    let result = conn.execute_batch(migration.sql).and_then(|_| {
+        conn.execute(
+            "INSERT INTO schema_migrations (version, description) VALUES (?1, ?2)",
+            params![migration.version, migration.description],
+        )
+    });
+
+    match result {
+        Ok(_) => conn.execute_batch("COMMIT").map_err(fail),
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            Err(fail(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_count_up_from_one_without_gaps() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(
+                migration.version,
+                i as i64 + 1,
+                "migration \"{}\" is out of order",
+                migration.description
+            );
+        }
+        assert_eq!(latest_version(), MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn every_migration_applies_to_a_new_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        run(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        // A second run finds nothing to do
+        run(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+    }
+}
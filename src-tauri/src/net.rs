@@ -0,0 +1,243 @@
+// The one HTTP client every network feature goes through, so proxy, extra CA
+// and timeout settings apply everywhere. With no proxy configured, the
+// system's proxy settings (and HTTP(S)_PROXY) are honored.
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Listener, Manager};
+
+use crate::error::Error;
+use crate::settings::{AppSettings, SettingChange, SettingsState, SETTINGS_CHANGED_EVENT};
+
+pub const PROGRESS_EVENT: &str = "http://progress";
+const SETTING_KEYS: &[&str] = &["proxy_url", "ca_bundle_path", "http_timeout_secs"];
+const DOWNLOADS_DIR: &str = "downloads";
+// Larger than any image worth compressing; past it the URL is likely not one
+const MAX_DOWNLOAD_BYTES: u64 = 200 * 1024 * 1024;
+// Downloads are imported from where they land, so they're kept a while
+const DOWNLOADS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct HttpState(pub RwLock<reqwest::Client>);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpProgress {
+    // Chosen by the caller so concurrent downloads can be told apart
+    pub request_id: String,
+    pub url: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+pub fn timeout(settings: &AppSettings) -> Duration {
+    Duration::from_secs(settings.http_timeout_secs.max(1))
+}
+
+pub fn build_client(settings: &AppSettings) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout(settings))
+        .user_agent(concat!("Squish/", env!("CARGO_PKG_VERSION")));
+    if let Some(url) = settings.proxy_url.as_deref().filter(|u| !u.is_empty()) {
+        let proxy =
+            reqwest::Proxy::all(url).map_err(|e| format!("Invalid proxy {}: {}", url, e))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = settings.ca_bundle_path.as_deref().filter(|p| !p.is_empty()) {
+        let pem =
+            std::fs::read(path).map_err(|e| format!("Failed to read CA bundle {}: {}", path, e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to configure HTTP client: {}", e))
+}
+
+// Falls back to the defaults when the saved proxy or CA bundle is unusable,
+// so a bad setting can't take every network feature down
+pub fn load(settings: &AppSettings) -> HttpState {
+    let client = build_client(settings).unwrap_or_else(|e| {
+        tracing::warn!("{}; using default HTTP settings", e);
+        build_client(&AppSettings::default()).unwrap_or_default()
+    });
+    HttpState(RwLock::new(client))
+}
+
+// Rebuilds the client when any of its settings change
+pub fn watch_settings(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if !SETTING_KEYS.contains(&change.key.as_str()) {
+            return;
+        }
+        let settings = handle.state::<SettingsState>().snapshot();
+        match build_client(&settings) {
+            Ok(client) => {
+                if let Ok(mut current) = handle.state::<HttpState>().0.write() {
+                    *current = client;
+                }
+            }
+            Err(e) => tracing::warn!("Keeping previous HTTP settings: {}", e),
+        }
+    });
+}
+
+// reqwest clients share their connection pool between clones
+pub fn client(app: &AppHandle) -> reqwest::Client {
+    app.state::<HttpState>()
+        .0
+        .read()
+        .map(|client| client.clone())
+        .unwrap_or_default()
+}
+
+// Streams the body to `dest`, emitting http://progress as chunks arrive.
+// Bodies over MAX_DOWNLOAD_BYTES are refused and the partial file removed.
+pub async fn download_to(
+    app: &AppHandle,
+    request_id: &str,
+    url: &str,
+    dest: &Path,
+) -> Result<u64, String> {
+    let mut response = client(app)
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    let total = response.content_length();
+    if total.is_some_and(|total| total > MAX_DOWNLOAD_BYTES) {
+        return Err(too_large(url));
+    }
+    let mut file = std::fs::File::create(dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+
+    let mut downloaded = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?
+    {
+        downloaded += chunk.len() as u64;
+        if downloaded > MAX_DOWNLOAD_BYTES {
+            drop(file);
+            let _ = std::fs::remove_file(dest);
+            return Err(too_large(url));
+        }
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            HttpProgress {
+                request_id: request_id.to_string(),
+                url: url.to_string(),
+                downloaded,
+                total,
+            },
+        );
+    }
+    Ok(downloaded)
+}
+
+fn too_large(url: &str) -> String {
+    format!(
+        "{} is larger than {} MB, not downloading it",
+        url,
+        MAX_DOWNLOAD_BYTES / 1024 / 1024
+    )
+}
+
+// Removes downloads older than DOWNLOADS_MAX_AGE; each lives in its own folder
+pub fn prune_downloads(app: &AppHandle) {
+    let Ok(dir) = app
+        .path()
+        .app_cache_dir()
+        .map(|dir| dir.join(DOWNLOADS_DIR))
+    else {
+        return;
+    };
+    prune_older_than(&dir, DOWNLOADS_MAX_AGE);
+}
+
+fn prune_older_than(dir: &Path, max_age: Duration) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            if let Err(e) = std::fs::remove_dir_all(entry.path()) {
+                tracing::warn!("Failed to remove {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
+// Fetches a remote image (e.g. from squish://compress?url=...) into the cache
+// and returns its local path for the regular import path
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn download_url(
+    app: AppHandle,
+    url: String,
+    request_id: String,
+) -> Result<String, Error> {
+    let parsed = tauri::Url::parse(&url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(Error::InvalidInput(format!(
+            "Only http(s) URLs can be downloaded, got {}",
+            url
+        )));
+    }
+    let name = parsed
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download");
+
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?
+        .join(DOWNLOADS_DIR)
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let dest = dir.join(name);
+
+    let bytes = download_to(&app, &request_id, &url, &dest).await?;
+    tracing::info!("Downloaded {} ({} bytes)", url, bytes);
+    Ok(dest.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_only_expired_downloads() {
+        let dir = std::env::temp_dir().join(format!("squish-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::write(dir.join("a").join("image.png"), b"png").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        prune_older_than(&dir, Duration::from_secs(60));
+        assert!(dir.join("a").exists());
+        prune_older_than(&dir, Duration::from_millis(10));
+        assert!(!dir.join("a").exists());
+        assert!(dir.exists());
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, WindowEvent};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::i18n::tr;
+use crate::imaging::BatchEntry;
+use crate::settings::SettingsState;
+
+pub const ACTIVATED_EVENT: &str = "notification://activated";
+// Desktop notifications have no click callback, so focusing the app soon
+// after one was shown is taken as the user acting on it
+const ACTIVATION_WINDOW: Duration = Duration::from_secs(60);
+
+// What the UI should bring into view when a notification is acted on
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationTarget {
+    pub job_kind: String,
+    pub output_paths: Vec<String>,
+    pub failed: bool,
+}
+
+pub struct LastNotification(pub Mutex<Option<(NotificationTarget, Instant)>>);
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn window_focused(app: &AppHandle) -> bool {
+    app.get_webview_window("main")
+        .and_then(|w| w.is_focused().ok())
+        .unwrap_or(false)
+}
+
+// Shows a notification unless the user is already looking at Squish or has
+// turned them off
+pub fn job_finished(app: &AppHandle, title: &str, body: &str, target: NotificationTarget) {
+    if !app
+        .state::<SettingsState>()
+        .snapshot()
+        .notifications_enabled
+        || window_focused(app)
+    {
+        return;
+    }
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!("Failed to show notification: {}", e);
+        return;
+    }
+    if let Ok(mut last) = app.state::<LastNotification>().0.lock() {
+        *last = Some((target, Instant::now()));
+    }
+}
+
+pub fn batch_finished(app: &AppHandle, job_kind: &str, entries: &[BatchEntry]) {
+    let failed = entries.iter().filter(|e| e.error.is_some()).count();
+    let saved: u64 = entries
+        .iter()
+        .filter_map(|e| e.result.as_ref())
+        .map(|r| r.input_bytes.saturating_sub(r.output_bytes))
+        .sum();
+    let output_paths = entries
+        .iter()
+        .filter_map(|e| e.result.as_ref())
+        .map(|r| r.output_path.clone())
+        .collect();
+
+    let (title, body) = if failed == 0 {
+        (
+            tr(app, "batch-finished", &[]),
+            tr(
+                app,
+                "batch-finished-body",
+                &[
+                    ("count", entries.len().into()),
+                    ("saved", format_bytes(saved).into()),
+                ],
+            ),
+        )
+    } else {
+        (
+            tr(app, "batch-failed", &[]),
+            tr(
+                app,
+                "batch-failed-body",
+                &[("failed", failed.into()), ("count", entries.len().into())],
+            ),
+        )
+    };
+    job_finished(
+        app,
+        &title,
+        &body,
+        NotificationTarget {
+            job_kind: job_kind.to_string(),
+            output_paths,
+            failed: failed > 0,
+        },
+    );
+}
+
+fn on_focus(app: &AppHandle) {
+    let state = app.state::<LastNotification>();
+    let Ok(mut last) = state.0.lock() else {
+        return;
+    };
+    if let Some((target, shown)) = last.take() {
+        if shown.elapsed() <= ACTIVATION_WINDOW {
+            if let Err(e) = app.emit(ACTIVATED_EVENT, target) {
+                tracing::warn!("Failed to emit notification activation: {}", e);
+            }
+        }
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let handle = app.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Focused(true) = event {
+            on_focus(&handle);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_use_the_largest_whole_unit() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TB");
+    }
+}
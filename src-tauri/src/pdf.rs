@@ -0,0 +1,466 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use lopdf::{Document, Object, Stream};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+use crate::blocking;
+use crate::cancel::{self, CancelToken, PartialOutput};
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::{encode_image, suffixed_path, OutputFormat};
+use crate::job_history::{record, JobRecord};
+use crate::notifications::{self, NotificationTarget};
+use crate::permissions;
+use crate::safe_path::{self, SafePath};
+
+const DEFAULT_MAX_DPI: u32 = 150;
+const DEFAULT_QUALITY: u8 = 75;
+// US Letter, used when a page has no readable MediaBox
+const FALLBACK_PAGE_WIDTH_PT: f32 = 612.0;
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfCompressOptions {
+    pub max_dpi: Option<u32>,
+    pub quality: Option<u8>,
+    pub dest: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfCompressResult {
+    pub output_path: String,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub images_recompressed: u32,
+    pub images_downsampled: u32,
+}
+
+// Re-encodes oversized embedded images and rewrites the PDF. Placement isn't
+// tracked, so DPI is measured as if each image spans the widest page: anything
+// downsampled still meets max_dpi wherever it is drawn.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn compress_pdf(
+    app: AppHandle,
+    path: String,
+    mut options: PdfCompressOptions,
+    project_id: Option<String>,
+    operation_id: Option<String>,
+) -> Result<PdfCompressResult, Error> {
+    Ok(blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let db = app.state::<Db>();
+        let path = SafePath::read(&app, &path)?.into_string();
+        options.dest = safe_path::write_opt(&app, options.dest)?;
+        if let Some(dest) = &options.dest {
+            permissions::check_overwrite(
+                &app,
+                project_id.as_deref(),
+                Path::new(&path),
+                Path::new(dest),
+            )?;
+        }
+        let _job = crate::crash::job_started(format!("compress PDF {}", path));
+        let _awake = crate::power::keep_awake("Compressing a PDF");
+        let started = Instant::now();
+        let settings = serde_json::to_value(&options).unwrap_or_default();
+        let outcome = compress_pdf_file(&path, options, operation.token());
+        let job = JobRecord {
+            kind: "pdf",
+            input_path: &path,
+            output_path: outcome.as_ref().ok().map(|r| r.output_path.as_str()),
+            input_bytes: outcome.as_ref().ok().map(|r| r.input_bytes),
+            output_bytes: outcome.as_ref().ok().map(|r| r.output_bytes),
+            started,
+            settings,
+            error: outcome.as_ref().err().map(String::as_str),
+        };
+        record(&db, &job);
+        crate::hooks::after_export(&app, &job);
+        if let Ok(result) = &outcome {
+            safe_path::allow(&app, Path::new(&result.output_path));
+        }
+        let (title, body) = match &outcome {
+            Ok(result) => ("pdf-compressed", result.output_path.clone()),
+            Err(e) => ("pdf-failed", e.clone()),
+        };
+        notifications::job_finished(
+            &app,
+            &crate::i18n::tr(&app, title, &[]),
+            &body,
+            NotificationTarget {
+                job_kind: "pdf".to_string(),
+                output_paths: outcome.iter().map(|r| r.output_path.clone()).collect(),
+                failed: outcome.is_err(),
+            },
+        );
+        outcome
+    })
+    .await?)
+}
+
+pub fn compress_pdf_file(
+    path: &str,
+    options: PdfCompressOptions,
+    cancel: &CancelToken,
+) -> Result<PdfCompressResult, String> {
+    let input = PathBuf::from(path);
+    let max_dpi = options.max_dpi.unwrap_or(DEFAULT_MAX_DPI).max(1);
+    let quality = options.quality.unwrap_or(DEFAULT_QUALITY);
+
+    let mut doc =
+        Document::load(&input).map_err(|e| format!("Failed to open PDF {}: {}", path, e))?;
+    let page_width_in = widest_page_pt(&doc) / 72.0;
+    let max_width_px = (page_width_in * max_dpi as f32).round() as u32;
+
+    let mut images_recompressed = 0;
+    let mut images_downsampled = 0;
+
+    for object in doc.objects.values_mut() {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        if !is_image(stream) {
+            continue;
+        }
+        cancel.check()?;
+        let Some(image) = decode_stream_image(stream) else {
+            continue;
+        };
+
+        let downsample = image.width() > max_width_px;
+        let resized = if downsample {
+            let height = (image.height() as f32 * max_width_px as f32 / image.width() as f32)
+                .round()
+                .max(1.0) as u32;
+            image.resize_exact(max_width_px, height, FilterType::Lanczos3)
+        } else {
+            image
+        };
+
+        let jpeg = match encode_image(&resized, OutputFormat::Jpeg, quality) {
+            Ok(jpeg) => jpeg,
+            Err(e) => {
+                tracing::debug!("Skipping PDF image: {}", e);
+                continue;
+            }
+        };
+        if jpeg.len() >= stream.content.len() && !downsample {
+            continue;
+        }
+
+        stream
+            .dict
+            .set("Filter", Object::Name(b"DCTDecode".to_vec()));
+        stream.dict.set("Width", resized.width() as i64);
+        stream.dict.set("Height", resized.height() as i64);
+        stream
+            .dict
+            .set("ColorSpace", Object::Name(b"DeviceRGB".to_vec()));
+        stream.dict.set("BitsPerComponent", 8i64);
+        stream.dict.remove(b"DecodeParms");
+        stream.dict.remove(b"Decode");
+        stream.set_content(jpeg);
+
+        images_recompressed += 1;
+        if downsample {
+            images_downsampled += 1;
+        }
+    }
+
+    // Flate-compress any remaining uncompressed streams
+    cancel.check()?;
+    doc.compress();
+
+    let output = options
+        .dest
+        .map(PathBuf::from)
+        .unwrap_or_else(|| suffixed_path(&input, None, "squished", "pdf"));
+    let partial = PartialOutput::new(&output);
+    doc.save(partial.path())
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    cancel.check()?;
+    let output = partial.finish()?;
+
+    tracing::info!(
+        "Compressed PDF {}: {} images re-encoded, {} downsampled",
+        path,
+        images_recompressed,
+        images_downsampled
+    );
+
+    Ok(PdfCompressResult {
+        output_path: output.to_string_lossy().to_string(),
+        input_bytes: file_size(&input),
+        output_bytes: file_size(&output),
+        images_recompressed,
+        images_downsampled,
+    })
+}
+
+// The largest embedded image the decoder understands, with the page count.
+// Pages aren't rendered, so this is how a scanned or photo PDF is imported.
+pub fn largest_image(path: &Path, cancel: &CancelToken) -> Result<(DynamicImage, usize), String> {
+    let doc = Document::load(path)
+        .map_err(|e| format!("Failed to open PDF {}: {}", path.display(), e))?;
+    let mut largest: Option<DynamicImage> = None;
+    for object in doc.objects.values() {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        if !is_image(stream) {
+            continue;
+        }
+        cancel.check()?;
+        let Some(image) = decode_stream_image(stream) else {
+            continue;
+        };
+        let pixels = |image: &DynamicImage| image.width() as u64 * image.height() as u64;
+        if largest.as_ref().is_none_or(|l| pixels(&image) > pixels(l)) {
+            largest = Some(image);
+        }
+    }
+    let image = largest.ok_or_else(|| {
+        format!(
+            "PDF {} has no embedded images that can be imported",
+            path.display()
+        )
+    })?;
+    Ok((image, doc.get_pages().len()))
+}
+
+fn is_image(stream: &Stream) -> bool {
+    matches!(stream.dict.get(b"Subtype").and_then(Object::as_name), Ok(b"Image"))
+        // Masks have to stay lossless and single-channel
+        && !matches!(stream.dict.get(b"ImageMask"), Ok(Object::Boolean(true)))
+}
+
+fn filter_name(stream: &Stream) -> Option<Vec<u8>> {
+    match stream.dict.get(b"Filter").ok()? {
+        Object::Name(name) => Some(name.clone()),
+        Object::Array(filters) if filters.len() == 1 => {
+            filters[0].as_name().ok().map(<[u8]>::to_vec)
+        }
+        _ => None,
+    }
+}
+
+// Handles the common cases: baseline JPEG and 8-bit gray/RGB Flate streams
+fn decode_stream_image(stream: &Stream) -> Option<DynamicImage> {
+    let width = stream.dict.get(b"Width").and_then(Object::as_i64).ok()? as u32;
+    let height = stream.dict.get(b"Height").and_then(Object::as_i64).ok()? as u32;
+
+    match filter_name(stream).as_deref() {
+        Some(b"DCTDecode") => {
+            image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).ok()
+        }
+        Some(b"FlateDecode") | None => {
+            let bits = stream
+                .dict
+                .get(b"BitsPerComponent")
+                .and_then(Object::as_i64)
+                .ok()?;
+            if bits != 8 {
+                return None;
+            }
+            let color_space = stream
+                .dict
+                .get(b"ColorSpace")
+                .and_then(Object::as_name)
+                .ok()?;
+            let pixels = image_pixels(stream)?;
+            match color_space {
+                b"DeviceRGB" => {
+                    RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+                }
+                b"DeviceGray" => {
+                    GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// lopdf won't decompress streams marked as images, so this decompresses an
+// unmarked copy
+fn image_pixels(stream: &Stream) -> Option<Vec<u8>> {
+    if stream.dict.get(b"Filter").is_err() {
+        return Some(stream.content.clone());
+    }
+    let mut dict = stream.dict.clone();
+    dict.remove(b"Subtype");
+    Stream::new(dict, stream.content.clone())
+        .decompressed_content()
+        .ok()
+}
+
+fn widest_page_pt(doc: &Document) -> f32 {
+    doc.get_pages()
+        .values()
+        .filter_map(|id| doc.get_dictionary(*id).ok())
+        .filter_map(|page| page.get(b"MediaBox").and_then(Object::as_array).ok())
+        .filter_map(|media_box| {
+            let coords: Vec<f32> = media_box.iter().filter_map(|v| v.as_float().ok()).collect();
+            (coords.len() == 4).then(|| (coords[2] - coords[0]).abs())
+        })
+        .fold(None, |widest: Option<f32>, width| {
+            Some(widest.map_or(width, |w| w.max(width)))
+        })
+        .unwrap_or(FALLBACK_PAGE_WIDTH_PT)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Dictionary};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("squish-pdf-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn raw_image(width: u32, height: u32) -> Stream {
+        Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width as i64,
+                "Height" => height as i64,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            },
+            vec![128; (width * height * 3) as usize],
+        )
+    }
+
+    // One page per width in points, each 100pt tall
+    fn document(page_widths: &[i64], images: Vec<Stream>) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = page_widths
+            .iter()
+            .map(|&width| {
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), width.into(), 100.into()],
+                })
+                .into()
+            })
+            .collect();
+        let count = kids.len() as i64;
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+            }),
+        );
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        for image in images {
+            doc.add_object(image);
+        }
+        doc
+    }
+
+    #[test]
+    fn dpi_is_measured_against_the_widest_page() {
+        assert_eq!(widest_page_pt(&document(&[144, 612, 300], vec![])), 612.0);
+        assert_eq!(
+            widest_page_pt(&document(&[], vec![])),
+            FALLBACK_PAGE_WIDTH_PT
+        );
+    }
+
+    #[test]
+    fn only_decodable_images_are_touched() {
+        let image = raw_image(4, 2);
+        assert!(is_image(&image));
+        assert_eq!(decode_stream_image(&image).unwrap().width(), 4);
+
+        let mut flate = raw_image(40, 20);
+        flate.compress().unwrap();
+        assert_eq!(filter_name(&flate).as_deref(), Some(&b"FlateDecode"[..]));
+        let decoded = decode_stream_image(&flate).unwrap().into_rgb8();
+        assert_eq!(decoded.into_raw(), vec![128; 2400]);
+
+        let mut mask = raw_image(4, 2);
+        mask.dict.set("ImageMask", true);
+        assert!(!is_image(&mask));
+
+        let mut sixteen_bit = raw_image(4, 2);
+        sixteen_bit.dict.set("BitsPerComponent", 16);
+        assert!(decode_stream_image(&sixteen_bit).is_none());
+
+        let mut cmyk = raw_image(4, 2);
+        cmyk.dict.set("ColorSpace", "DeviceCMYK");
+        assert!(decode_stream_image(&cmyk).is_none());
+        assert!(!is_image(&Stream::new(Dictionary::new(), Vec::new())));
+    }
+
+    #[test]
+    fn oversized_images_are_downsampled_to_the_dpi_limit() {
+        let dir = temp_dir("compress");
+        let path = dir.join("scan.pdf");
+        // A one-inch page, so 100 dpi means 100 pixels across
+        document(&[72], vec![raw_image(400, 200), raw_image(50, 50)])
+            .save(&path)
+            .unwrap();
+        let dest = dir.join("out.pdf");
+        let options = PdfCompressOptions {
+            max_dpi: Some(100),
+            quality: None,
+            dest: Some(dest.to_string_lossy().to_string()),
+        };
+
+        let result =
+            compress_pdf_file(&path.to_string_lossy(), options, &CancelToken::default()).unwrap();
+        assert_eq!(result.images_recompressed, 2);
+        assert_eq!(result.images_downsampled, 1);
+        assert!(result.output_bytes < result.input_bytes);
+
+        let doc = Document::load(&dest).unwrap();
+        let mut widths: Vec<i64> = doc
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| is_image(stream))
+            .map(|stream| {
+                assert_eq!(filter_name(stream).as_deref(), Some(&b"DCTDecode"[..]));
+                stream.dict.get(b"Width").and_then(Object::as_i64).unwrap()
+            })
+            .collect();
+        widths.sort();
+        assert_eq!(widths, vec![50, 100]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn the_largest_image_is_imported() {
+        let dir = temp_dir("import");
+        let path = dir.join("photos.pdf");
+        document(&[612, 612], vec![raw_image(30, 10), raw_image(40, 20)])
+            .save(&path)
+            .unwrap();
+        let (image, pages) = largest_image(&path, &CancelToken::default()).unwrap();
+        assert_eq!((image.width(), image.height(), pages), (40, 20, 2));
+
+        let empty = dir.join("text.pdf");
+        document(&[612], vec![]).save(&empty).unwrap();
+        assert!(largest_image(&empty, &CancelToken::default()).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+// Hard numbers for "it feels slow" reports. Timings come from tracing spans,
+// measured from creation to close by PerfLayer:
+//   perf::startup  one span per setup phase
+//   perf::command  heavy (async) commands, end to end including the wait for
+//                  the blocking pool; put on with #[tracing::instrument]
+//   perf::ipc      every command's time on the IPC thread, from blocking::watch
+//   perf::job      each job the queue runs
+// Spans are info level, so nothing is collected while the log level is set
+// to warn or error. Cache hits and misses are counted directly.
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, EnteredSpan, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::error::Error;
+
+pub const STARTUP: &str = "perf::startup";
+pub const COMMAND: &str = "perf::command";
+pub const IPC: &str = "perf::ipc";
+pub const JOB: &str = "perf::job";
+
+const RECENT_COMMANDS: usize = 200;
+const RECENT_JOBS: usize = 500;
+// Job throughput is averaged over this much recent history
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+
+struct Sample {
+    name: String,
+    ms: f64,
+    finished: Instant,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    count: u64,
+    total_ms: f64,
+    max_ms: f64,
+}
+
+impl Totals {
+    fn add(&mut self, ms: f64) {
+        self.count += 1;
+        self.total_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+    }
+}
+
+struct Stats {
+    startup: Vec<PhaseTiming>,
+    commands: VecDeque<Sample>,
+    ipc: BTreeMap<String, Totals>,
+    jobs: VecDeque<Sample>,
+    jobs_completed: u64,
+    // Hits, misses
+    caches: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Stats {
+            startup: Vec::new(),
+            commands: VecDeque::new(),
+            ipc: BTreeMap::new(),
+            jobs: VecDeque::new(),
+            jobs_completed: 0,
+            caches: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub ms: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandTiming {
+    pub name: String,
+    pub ms: f64,
+    pub seconds_ago: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DurationSummary {
+    pub name: String,
+    pub count: u64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobThroughput {
+    // Since launch
+    pub completed: u64,
+    // Over the last ten minutes, or since launch if that's shorter
+    pub per_minute: f64,
+    pub by_kind: Vec<DurationSummary>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub name: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: Option<f64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerfReport {
+    pub uptime_ms: u64,
+    pub startup: Vec<PhaseTiming>,
+    // Newest first
+    pub recent_commands: Vec<CommandTiming>,
+    // Per command over recent_commands
+    pub commands: Vec<DurationSummary>,
+    // Time each command held the IPC thread, since launch
+    pub ipc: Vec<DurationSummary>,
+    pub jobs: JobThroughput,
+    pub caches: Vec<CacheStats>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Startup,
+    Command,
+    Ipc,
+    Job,
+}
+
+impl Kind {
+    fn of(target: &str) -> Option<Self> {
+        match target {
+            STARTUP => Some(Kind::Startup),
+            COMMAND => Some(Kind::Command),
+            IPC => Some(Kind::Ipc),
+            JOB => Some(Kind::Job),
+            _ => None,
+        }
+    }
+}
+
+struct Timing {
+    kind: Kind,
+    name: String,
+    started: Instant,
+}
+
+// Picks up the span's `name` field, if it has one
+#[derive(Default)]
+struct NameVisitor(Option<String>);
+
+impl Visit for NameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+pub struct PerfLayer;
+
+impl<S> Layer<S> for PerfLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let metadata = attrs.metadata();
+        let Some(kind) = Kind::of(metadata.target()) else {
+            return;
+        };
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = NameVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(Timing {
+            kind,
+            name: visitor.0.unwrap_or_else(|| metadata.name().to_string()),
+            started: Instant::now(),
+        });
+    }
+
+    // An ipc span is the parent of the span an async command runs in, which
+    // keeps it open until the command finishes; the IPC thread is done with
+    // it as soon as it's exited
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if extensions
+            .get_mut::<Timing>()
+            .is_some_and(|timing| matches!(timing.kind, Kind::Ipc))
+        {
+            if let Some(timing) = extensions.remove::<Timing>() {
+                record(timing);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+            return;
+        };
+        record(timing);
+    }
+}
+
+// Added to the subscriber in logging::init; uptime counts from here
+pub fn layer() -> PerfLayer {
+    STARTED.get_or_init(Instant::now);
+    PerfLayer
+}
+
+fn record(timing: Timing) {
+    let finished = Instant::now();
+    let ms = finished.duration_since(timing.started).as_secs_f64() * 1000.0;
+    let Ok(mut stats) = STATS.lock() else {
+        return;
+    };
+    let sample = Sample {
+        name: timing.name,
+        ms,
+        finished,
+    };
+    match timing.kind {
+        Kind::Startup => stats.startup.push(PhaseTiming {
+            phase: sample.name,
+            ms,
+        }),
+        Kind::Command => {
+            if stats.commands.len() >= RECENT_COMMANDS {
+                stats.commands.pop_front();
+            }
+            stats.commands.push_back(sample);
+        }
+        Kind::Ipc => stats.ipc.entry(sample.name).or_default().add(ms),
+        Kind::Job => {
+            stats.jobs_completed += 1;
+            if stats.jobs.len() >= RECENT_JOBS {
+                stats.jobs.pop_front();
+            }
+            stats.jobs.push_back(sample);
+        }
+    }
+}
+
+pub fn since_launch() -> Duration {
+    STARTED.get().map(Instant::elapsed).unwrap_or_default()
+}
+
+// Times a setup phase until the returned guard is dropped
+pub fn phase(name: &str) -> EnteredSpan {
+    tracing::info_span!(target: STARTUP, "startup", name).entered()
+}
+
+pub fn cache(name: &'static str, hit: bool) {
+    if let Ok(mut stats) = STATS.lock() {
+        let (hits, misses) = stats.caches.entry(name).or_default();
+        if hit {
+            *hits += 1;
+        } else {
+            *misses += 1;
+        }
+    }
+}
+
+fn summarize<'a>(samples: impl Iterator<Item = &'a Sample>) -> Vec<DurationSummary> {
+    let mut totals: BTreeMap<&str, Totals> = BTreeMap::new();
+    for sample in samples {
+        totals.entry(&sample.name).or_default().add(sample.ms);
+    }
+    totals
+        .into_iter()
+        .map(|(name, totals)| summary(name, totals))
+        .collect()
+}
+
+fn summary(name: &str, totals: Totals) -> DurationSummary {
+    DurationSummary {
+        name: name.to_string(),
+        count: totals.count,
+        mean_ms: totals.total_ms / totals.count.max(1) as f64,
+        max_ms: totals.max_ms,
+    }
+}
+
+#[tauri::command]
+pub fn get_perf_report() -> Result<PerfReport, Error> {
+    let now = Instant::now();
+    let uptime = STARTED
+        .get()
+        .map(|started| now - *started)
+        .unwrap_or_default();
+    let stats = STATS.lock().map_err(|_| Error::Lock("performance stats"))?;
+
+    let window = THROUGHPUT_WINDOW.min(uptime);
+    let recent_jobs = stats
+        .jobs
+        .iter()
+        .filter(|job| now - job.finished <= window)
+        .count();
+    let per_minute = if window.is_zero() {
+        0.0
+    } else {
+        recent_jobs as f64 / window.as_secs_f64() * 60.0
+    };
+
+    Ok(PerfReport {
+        uptime_ms: uptime.as_millis() as u64,
+        startup: stats.startup.clone(),
+        recent_commands: stats
+            .commands
+            .iter()
+            .rev()
+            .map(|sample| CommandTiming {
+                name: sample.name.clone(),
+                ms: sample.ms,
+                seconds_ago: (now - sample.finished).as_secs_f64(),
+            })
+            .collect(),
+        commands: summarize(stats.commands.iter()),
+        ipc: stats
+            .ipc
+            .iter()
+            .map(|(name, totals)| summary(name, *totals))
+            .collect(),
+        jobs: JobThroughput {
+            completed: stats.jobs_completed,
+            per_minute,
+            by_kind: summarize(stats.jobs.iter()),
+        },
+        caches: stats
+            .caches
+            .iter()
+            .map(|(name, &(hits, misses))| CacheStats {
+                name: name.to_string(),
+                hits,
+                misses,
+                hit_rate: (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn sample(name: &str, ms: f64) -> Sample {
+        Sample {
+            name: name.to_string(),
+            ms,
+            finished: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn samples_are_summarized_per_name() {
+        let samples = [sample("b", 4.0), sample("a", 1.0), sample("b", 8.0)];
+        let summaries = summarize(samples.iter());
+        assert_eq!(summaries.len(), 2);
+        assert_eq!((summaries[0].name.as_str(), summaries[0].count), ("a", 1));
+        assert_eq!(summaries[1].count, 2);
+        assert_eq!(summaries[1].mean_ms, 6.0);
+        assert_eq!(summaries[1].max_ms, 8.0);
+    }
+
+    #[test]
+    fn spans_are_timed_by_target() {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        tracing::subscriber::with_default(subscriber, || {
+            drop(phase("perf-test-phase"));
+            tracing::info_span!(target: COMMAND, "perf_test_command").in_scope(|| {});
+            tracing::info_span!(target: IPC, "ipc", name = "perf_test_ipc").in_scope(|| {});
+            tracing::info_span!(target: "squish::other", "perf_test_ignored").in_scope(|| {});
+        });
+        cache("perf-test-cache", true);
+        cache("perf-test-cache", false);
+
+        let report = get_perf_report().unwrap();
+        assert!(report.startup.iter().any(|p| p.phase == "perf-test-phase"));
+        assert!(report
+            .recent_commands
+            .iter()
+            .any(|c| c.name == "perf_test_command"));
+        assert!(report.ipc.iter().any(|c| c.name == "perf_test_ipc"));
+        assert!(!report
+            .recent_commands
+            .iter()
+            .any(|c| c.name == "perf_test_ignored"));
+        let cache = report
+            .caches
+            .iter()
+            .find(|c| c.name == "perf-test-cache")
+            .unwrap();
+        assert_eq!(cache.hit_rate, Some(0.5));
+    }
+}
@@ -0,0 +1,322 @@
+// Consent for operations that destroy data or leak it: overwriting a file in
+// place, exporting with GPS coordinates kept, uploading the library. The
+// backend checks before doing any of them, whatever the UI did or didn't ask.
+// Without a remembered decision the command fails and the UI is told to ask;
+// the answer is saved per project (or for everything when no project is
+// given) and the command retried. Decisions can be reviewed and revoked.
+//
+// Running a hook is different: the webview could answer its own request, so
+// it's asked in a native dialog instead, every time, and never remembered.
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+use crate::db::Db;
+use crate::error::Error;
+
+pub const REQUIRED_EVENT: &str = "permissions://required";
+// Stored for decisions that apply to every project
+const ALL_PROJECTS: &str = "";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    OverwriteInPlace,
+    KeepLocation,
+    NetworkUpload,
+    RunHook,
+}
+
+impl Capability {
+    fn key(self) -> &'static str {
+        match self {
+            Capability::OverwriteInPlace => "overwrite_in_place",
+            Capability::KeepLocation => "keep_location",
+            Capability::NetworkUpload => "network_upload",
+            Capability::RunHook => "run_hook",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "overwrite_in_place" => Some(Capability::OverwriteInPlace),
+            "keep_location" => Some(Capability::KeepLocation),
+            "network_upload" => Some(Capability::NetworkUpload),
+            "run_hook" => Some(Capability::RunHook),
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Capability::OverwriteInPlace => "overwrite the original file",
+            Capability::KeepLocation => "keep GPS location in exported files",
+            Capability::NetworkUpload => "upload your library",
+            Capability::RunHook => "run a program after every export",
+        }
+    }
+
+    // Asked natively each time rather than remembered
+    fn confirmed_natively(self) -> bool {
+        self == Capability::RunHook
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionRequest {
+    pub capability: Capability,
+    pub project_id: Option<String>,
+    // What triggered it, e.g. the file about to be overwritten
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionDecision {
+    pub capability: Capability,
+    // None for a decision that applies to every project
+    pub project_id: Option<String>,
+    pub allowed: bool,
+    pub decided_at: String,
+}
+
+fn scope(project_id: Option<&str>) -> &str {
+    project_id.unwrap_or(ALL_PROJECTS)
+}
+
+// A project's own decision wins over the one for every project
+fn decision(
+    conn: &Connection,
+    project_id: Option<&str>,
+    capability: Capability,
+) -> Result<Option<bool>, String> {
+    conn.query_row(
+        "SELECT allowed FROM permission_decisions
+         WHERE capability = ?1 AND project_id IN (?2, ?3)
+         ORDER BY project_id = ?3 LIMIT 1",
+        params![capability.key(), scope(project_id), ALL_PROJECTS],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read permission: {}", e))
+}
+
+pub fn check(
+    app: &AppHandle,
+    project_id: Option<&str>,
+    capability: Capability,
+    detail: &str,
+) -> Result<(), Error> {
+    if capability.confirmed_natively() {
+        return confirm(app, capability, detail);
+    }
+    let allowed = {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        decision(&conn, project_id, capability)?
+    };
+    match allowed {
+        Some(true) => Ok(()),
+        Some(false) => Err(Error::PermissionDenied(format!(
+            "You chose not to let Squish {}",
+            capability.describe()
+        ))),
+        None => {
+            tracing::info!("Asking for permission to {}", capability.describe());
+            let request = PermissionRequest {
+                capability,
+                project_id: project_id.map(str::to_string),
+                detail: detail.to_string(),
+            };
+            if let Err(e) = app.emit(REQUIRED_EVENT, request) {
+                tracing::warn!("Failed to emit permission request: {}", e);
+            }
+            Err(Error::PermissionDenied(format!(
+                "Squish needs your permission to {}",
+                capability.describe()
+            )))
+        }
+    }
+}
+
+// Blocks until the user answers, so never call it from the main thread
+fn confirm(app: &AppHandle, capability: Capability, detail: &str) -> Result<(), Error> {
+    tracing::info!("Confirming permission to {}", capability.describe());
+    let allowed = app
+        .dialog()
+        .message(format!(
+            "Allow Squish to {}?\n\n{}",
+            capability.describe(),
+            detail
+        ))
+        .title("Squish")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Allow".to_string(),
+            "Don't Allow".to_string(),
+        ))
+        .blocking_show();
+    if allowed {
+        Ok(())
+    } else {
+        Err(Error::PermissionDenied(format!(
+            "You chose not to let Squish {}",
+            capability.describe()
+        )))
+    }
+}
+
+// For commands that take a destination next to their input
+pub fn check_overwrite(
+    app: &AppHandle,
+    project_id: Option<&str>,
+    input: &Path,
+    output: &Path,
+) -> Result<(), Error> {
+    if input != output {
+        return Ok(());
+    }
+    check(
+        app,
+        project_id,
+        Capability::OverwriteInPlace,
+        &output.to_string_lossy(),
+    )
+}
+
+#[tauri::command]
+pub fn list_permissions(
+    db: State<Db>,
+    project_id: Option<String>,
+) -> Result<Vec<PermissionDecision>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn.prepare(
+        "SELECT project_id, capability, allowed, decided_at FROM permission_decisions
+         WHERE ?1 IS NULL OR project_id = ?1
+         ORDER BY decided_at DESC",
+    )?;
+    let rows = stmt.query_map([&project_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, bool>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+    let mut decisions = Vec::new();
+    for row in rows {
+        let (project, capability, allowed, decided_at) = row?;
+        // Left behind by a newer build
+        let Some(capability) = Capability::from_key(&capability) else {
+            continue;
+        };
+        decisions.push(PermissionDecision {
+            capability,
+            project_id: (project != ALL_PROJECTS).then_some(project),
+            allowed,
+            decided_at,
+        });
+    }
+    Ok(decisions)
+}
+
+// Called with the user's answer to a permission request
+#[tauri::command]
+pub fn set_permission(
+    db: State<Db>,
+    project_id: Option<String>,
+    capability: Capability,
+    allowed: bool,
+) -> Result<(), Error> {
+    if capability.confirmed_natively() {
+        return Err(Error::InvalidInput(format!(
+            "Permission to {} can't be remembered",
+            capability.describe()
+        )));
+    }
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO permission_decisions (project_id, capability, allowed) VALUES (?1, ?2, ?3)
+         ON CONFLICT(project_id, capability)
+         DO UPDATE SET allowed = ?3, decided_at = CURRENT_TIMESTAMP",
+        params![scope(project_id.as_deref()), capability.key(), allowed],
+    )?;
+    Ok(())
+}
+
+// Forgets a decision so the next attempt asks again
+#[tauri::command]
+pub fn revoke_permission(
+    db: State<Db>,
+    project_id: Option<String>,
+    capability: Capability,
+) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "DELETE FROM permission_decisions WHERE project_id = ?1 AND capability = ?2",
+        params![scope(project_id.as_deref()), capability.key()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(decisions: &[(&str, Capability, bool)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        for (project_id, capability, allowed) in decisions {
+            conn.execute(
+                "INSERT INTO permission_decisions (project_id, capability, allowed) VALUES (?1, ?2, ?3)",
+                params![project_id, capability.key(), allowed],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for capability in [
+            Capability::OverwriteInPlace,
+            Capability::KeepLocation,
+            Capability::NetworkUpload,
+        ] {
+            assert_eq!(Capability::from_key(capability.key()), Some(capability));
+        }
+        assert_eq!(Capability::from_key("format_disk"), None);
+    }
+
+    #[test]
+    fn undecided_capabilities_have_no_decision() {
+        let conn = library(&[("p1", Capability::KeepLocation, true)]);
+        assert_eq!(
+            decision(&conn, Some("p1"), Capability::NetworkUpload).unwrap(),
+            None
+        );
+        assert_eq!(
+            decision(&conn, Some("p2"), Capability::KeepLocation).unwrap(),
+            None
+        );
+        assert_eq!(
+            decision(&conn, None, Capability::KeepLocation).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn a_project_decision_wins_over_the_global_one() {
+        let conn = library(&[
+            (ALL_PROJECTS, Capability::OverwriteInPlace, true),
+            ("p1", Capability::OverwriteInPlace, false),
+        ]);
+        let overwrite = Capability::OverwriteInPlace;
+        assert_eq!(decision(&conn, Some("p1"), overwrite).unwrap(), Some(false));
+        assert_eq!(decision(&conn, Some("p2"), overwrite).unwrap(), Some(true));
+        assert_eq!(decision(&conn, None, overwrite).unwrap(), Some(true));
+    }
+}
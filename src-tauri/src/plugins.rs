@@ -0,0 +1,647 @@
+// Third-party codecs and filters as sandboxed WebAssembly modules. Each plugin
+// is a folder in <app data>/plugins holding plugin.json (the manifest) and
+// plugin.wasm. Plugins are off until the user enables them, and only enabled
+// ones are compiled.
+//
+// The sandbox: a module gets no imports at all, so no files, network, clock or
+// WASI; every call runs in a fresh instance with capped memory and fuel, so a
+// plugin can't keep state between calls or spin forever.
+//
+// API version 1. The module exports `memory` plus:
+//   squish_alloc(len: i32) -> i32                  buffer for the host to fill
+//   squish_decode(ptr, len) -> i64                 file bytes -> image
+//   squish_encode(format, ptr, len, width, height, quality) -> i64
+//                                                  RGBA -> file bytes
+//   squish_filter(filter, ptr, len, width, height) -> i64
+//                                                  RGBA -> image
+//   squish_last_error() -> i64                     optional, UTF-8 message
+// `format` and `filter` index the manifest's lists. An i64 result packs a
+// buffer as ptr << 32 | len, with 0 meaning failure. An "image" is width and
+// height as little-endian u32s followed by RGBA8 pixels; RGBA input is bare.
+use image::{DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::ipc::Response;
+use tauri::{AppHandle, Manager};
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::blocking;
+use crate::cancel::PartialOutput;
+use crate::error::Error;
+use crate::imaging::{encode_image, suffixed_path, OutputFormat};
+use crate::permissions;
+use crate::safe_path::{self, SafePath};
+use crate::settings::{self, SettingsState};
+
+pub const API_VERSION: u32 = 1;
+const ENABLED_KEY: &str = "enabled_plugins";
+const MANIFEST: &str = "plugin.json";
+const MODULE: &str = "plugin.wasm";
+const MAX_MEMORY: usize = 1024 * 1024 * 1024;
+// Plenty for decoding a large image; a loop that never ends runs out
+const FUEL: u64 = 50_000_000_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportFormat {
+    pub id: String,
+    pub name: String,
+    pub extension: String,
+    pub mime_type: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub api_version: u32,
+    pub description: Option<String>,
+    // Lowercase file extensions squish_decode understands
+    #[serde(default)]
+    pub imports: Vec<String>,
+    #[serde(default)]
+    pub exports: Vec<ExportFormat>,
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    pub path: String,
+    pub enabled: bool,
+    // Why an enabled plugin couldn't be loaded
+    pub error: Option<String>,
+}
+
+struct Plugin {
+    manifest: Manifest,
+    dir: PathBuf,
+    enabled: bool,
+    // None while disabled
+    module: Option<Result<Module, String>>,
+}
+
+struct Registry {
+    engine: Engine,
+    plugins: Vec<Plugin>,
+}
+
+// Scanned on first use and again by reload_plugins
+#[derive(Default)]
+pub struct PluginHost(Mutex<Option<Registry>>);
+
+fn plugins_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join("plugins");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    let path = dir.join(MANIFEST);
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let manifest: Manifest = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid plugin manifest {}: {}", path.display(), e))?;
+    if manifest.api_version != API_VERSION {
+        return Err(format!(
+            "Plugin {} targets API version {}, this build supports {}",
+            manifest.id, manifest.api_version, API_VERSION
+        ));
+    }
+    // Export extensions end up in output file names
+    if let Some(export) = manifest
+        .exports
+        .iter()
+        .find(|e| !valid_extension(&e.extension))
+    {
+        return Err(format!(
+            "Plugin {} has an invalid export extension {:?}",
+            manifest.id, export.extension
+        ));
+    }
+    Ok(manifest)
+}
+
+fn valid_extension(extension: &str) -> bool {
+    (1..=10).contains(&extension.len())
+        && extension
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+fn scan(app: &AppHandle) -> Result<Registry, String> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine =
+        Engine::new(&config).map_err(|e| format!("Failed to start the plugin engine: {}", e))?;
+    let enabled = app.state::<SettingsState>().snapshot().enabled_plugins;
+
+    let dir = plugins_dir(app)?;
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let manifest = match read_manifest(&dir) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                tracing::warn!("Skipping plugin: {}", e);
+                continue;
+            }
+        };
+        if plugins.iter().any(|p| p.manifest.id == manifest.id) {
+            tracing::warn!(
+                "Skipping duplicate plugin {} in {}",
+                manifest.id,
+                dir.display()
+            );
+            continue;
+        }
+        let is_enabled = enabled.contains(&manifest.id);
+        let module = is_enabled.then(|| {
+            Module::from_file(&engine, dir.join(MODULE))
+                .map_err(|e| format!("Failed to load plugin {}: {}", manifest.id, e))
+        });
+        if let Some(Err(e)) = &module {
+            tracing::warn!("{}", e);
+        }
+        plugins.push(Plugin {
+            manifest,
+            dir,
+            enabled: is_enabled,
+            module,
+        });
+    }
+    plugins.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+    tracing::info!("Found {} plugin(s)", plugins.len());
+    Ok(Registry { engine, plugins })
+}
+
+fn with_registry<T>(app: &AppHandle, f: impl FnOnce(&Registry) -> T) -> Result<T, String> {
+    let host = app.state::<PluginHost>();
+    let mut registry = host.0.lock().map_err(|_| Error::Lock("plugins"))?;
+    if registry.is_none() {
+        *registry = Some(scan(app)?);
+    }
+    registry
+        .as_ref()
+        .map(f)
+        .ok_or_else(|| "Plugins aren't loaded".to_string())
+}
+
+fn infos(registry: &Registry) -> Vec<PluginInfo> {
+    registry
+        .plugins
+        .iter()
+        .map(|plugin| PluginInfo {
+            manifest: plugin.manifest.clone(),
+            path: plugin.dir.to_string_lossy().to_string(),
+            enabled: plugin.enabled,
+            error: match &plugin.module {
+                Some(Err(e)) => Some(e.clone()),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+// Engine and compiled module of an enabled plugin; both are cheap handles, so
+// the call itself runs without holding the registry lock
+fn loaded(
+    app: &AppHandle,
+    pick: impl Fn(&Manifest) -> bool,
+) -> Result<Option<(Engine, Module, Manifest)>, String> {
+    with_registry(app, |registry| {
+        let Some(plugin) = registry
+            .plugins
+            .iter()
+            .find(|p| p.enabled && pick(&p.manifest))
+        else {
+            return Ok(None);
+        };
+        match &plugin.module {
+            Some(Ok(module)) => Ok(Some((
+                registry.engine.clone(),
+                module.clone(),
+                plugin.manifest.clone(),
+            ))),
+            Some(Err(e)) => Err(e.clone()),
+            None => Ok(None),
+        }
+    })?
+}
+
+fn require(app: &AppHandle, id: &str) -> Result<(Engine, Module, Manifest), String> {
+    loaded(app, |manifest| manifest.id == id)?
+        .ok_or_else(|| format!("Plugin {} isn't installed or enabled", id))
+}
+
+// One sandboxed instance, thrown away after a single call
+struct Call {
+    store: Store<StoreLimits>,
+    instance: Instance,
+    memory: Memory,
+    plugin: String,
+}
+
+impl Call {
+    fn new(engine: &Engine, module: &Module, plugin: &str) -> Result<Self, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(FUEL)
+            .map_err(|e| format!("Failed to start plugin {}: {}", plugin, e))?;
+        let instance = Instance::new(&mut store, module, &[])
+            .map_err(|e| format!("Failed to start plugin {}: {}", plugin, e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| format!("Plugin {} doesn't export its memory", plugin))?;
+        Ok(Call {
+            store,
+            instance,
+            memory,
+            plugin: plugin.to_string(),
+        })
+    }
+
+    fn fail(&self, e: impl std::fmt::Display) -> String {
+        format!("Plugin {} failed: {}", self.plugin, e)
+    }
+
+    // Copies bytes into a buffer the plugin allocated
+    fn write(&mut self, bytes: &[u8]) -> Result<(i32, i32), String> {
+        let len = i32::try_from(bytes.len()).map_err(|_| self.fail("input too large"))?;
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "squish_alloc")
+            .map_err(|e| self.fail(e))?;
+        let ptr = alloc.call(&mut self.store, len).map_err(|e| self.fail(e))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)
+            .map_err(|e| self.fail(e))?;
+        Ok((ptr, len))
+    }
+
+    fn read(&mut self, packed: i64) -> Result<Vec<u8>, String> {
+        if packed == 0 {
+            let message = self.last_error();
+            return Err(self.fail(message));
+        }
+        let (ptr, len) = unpack(packed);
+        self.guest_bytes(ptr, len).ok_or_else(|| {
+            self.fail(format!(
+                "returned {} bytes at {:#x}, outside its memory",
+                len, ptr
+            ))
+        })
+    }
+
+    // Copies a range out of the guest's memory; the length comes from the
+    // guest, so it's checked against the memory before anything is allocated
+    fn guest_bytes(&self, ptr: usize, len: usize) -> Option<Vec<u8>> {
+        let end = ptr.checked_add(len)?;
+        if end > self.memory.data_size(&self.store) {
+            return None;
+        }
+        let mut bytes = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn last_error(&mut self) -> String {
+        let message = self
+            .instance
+            .get_typed_func::<(), i64>(&mut self.store, "squish_last_error")
+            .ok()
+            .and_then(|f| f.call(&mut self.store, ()).ok())
+            .filter(|&packed| packed != 0)
+            .and_then(|packed| {
+                let (ptr, len) = unpack(packed);
+                let bytes = self.guest_bytes(ptr, len)?;
+                Some(String::from_utf8_lossy(&bytes).to_string())
+            });
+        message.unwrap_or_else(|| "no error message".to_string())
+    }
+
+    fn read_image(&mut self, packed: i64) -> Result<DynamicImage, String> {
+        let bytes = self.read(packed)?;
+        let header = |i: usize| {
+            bytes
+                .get(i..i + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let (Some(width), Some(height)) = (header(0), header(4)) else {
+            return Err(self.fail("returned a truncated image"));
+        };
+        RgbaImage::from_raw(width, height, bytes[8..].to_vec())
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(|| self.fail(format!("returned a malformed {}x{} image", width, height)))
+    }
+}
+
+// A buffer returned as ptr << 32 | len
+fn unpack(packed: i64) -> (usize, usize) {
+    (
+        (packed as u64 >> 32) as usize,
+        (packed as u64 & 0xffff_ffff) as usize,
+    )
+}
+
+// Manifests of the enabled plugins that loaded, in the order decode tries them
+pub fn enabled(app: &AppHandle) -> Result<Vec<Manifest>, String> {
+    with_registry(app, |registry| {
+        registry
+            .plugins
+            .iter()
+            .filter(|p| p.enabled && matches!(p.module, Some(Ok(_))))
+            .map(|p| p.manifest.clone())
+            .collect()
+    })
+}
+
+// Decodes a file with the first enabled plugin that claims its extension;
+// None when no plugin does
+pub fn decode(app: &AppHandle, path: &Path) -> Option<Result<DynamicImage, String>> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let (engine, module, manifest) = match loaded(app, |m| m.imports.contains(&extension)) {
+        Ok(plugin) => plugin?,
+        Err(e) => return Some(Err(e)),
+    };
+    Some(decode_with(&engine, &module, &manifest.id, path))
+}
+
+fn decode_with(
+    engine: &Engine,
+    module: &Module,
+    plugin: &str,
+    path: &Path,
+) -> Result<DynamicImage, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut call = Call::new(engine, module, plugin)?;
+    let (ptr, len) = call.write(&bytes)?;
+    let decode = call
+        .instance
+        .get_typed_func::<(i32, i32), i64>(&mut call.store, "squish_decode")
+        .map_err(|e| call.fail(e))?;
+    let packed = decode
+        .call(&mut call.store, (ptr, len))
+        .map_err(|e| call.fail(e))?;
+    call.read_image(packed)
+}
+
+pub fn encode(
+    app: &AppHandle,
+    plugin: &str,
+    format: &str,
+    image: &DynamicImage,
+    quality: u8,
+) -> Result<(Vec<u8>, ExportFormat), String> {
+    let (engine, module, manifest) = require(app, plugin)?;
+    let index = manifest
+        .exports
+        .iter()
+        .position(|f| f.id == format)
+        .ok_or_else(|| format!("Plugin {} has no {} export", plugin, format))?;
+    let rgba = image.to_rgba8();
+    let mut call = Call::new(&engine, &module, plugin)?;
+    let (ptr, len) = call.write(rgba.as_raw())?;
+    let encode = call
+        .instance
+        .get_typed_func::<(i32, i32, i32, i32, i32, i32), i64>(&mut call.store, "squish_encode")
+        .map_err(|e| call.fail(e))?;
+    let args = (
+        index as i32,
+        ptr,
+        len,
+        rgba.width() as i32,
+        rgba.height() as i32,
+        quality as i32,
+    );
+    let packed = encode
+        .call(&mut call.store, args)
+        .map_err(|e| call.fail(e))?;
+    Ok((call.read(packed)?, manifest.exports[index].clone()))
+}
+
+pub fn filter(
+    app: &AppHandle,
+    plugin: &str,
+    filter: &str,
+    image: &DynamicImage,
+) -> Result<DynamicImage, String> {
+    let (engine, module, manifest) = require(app, plugin)?;
+    let index = manifest
+        .filters
+        .iter()
+        .position(|f| f.id == filter)
+        .ok_or_else(|| format!("Plugin {} has no {} filter", plugin, filter))?;
+    let rgba = image.to_rgba8();
+    let mut call = Call::new(&engine, &module, plugin)?;
+    let (ptr, len) = call.write(rgba.as_raw())?;
+    let run = call
+        .instance
+        .get_typed_func::<(i32, i32, i32, i32, i32), i64>(&mut call.store, "squish_filter")
+        .map_err(|e| call.fail(e))?;
+    let args = (
+        index as i32,
+        ptr,
+        len,
+        rgba.width() as i32,
+        rgba.height() as i32,
+    );
+    let packed = run.call(&mut call.store, args).map_err(|e| call.fail(e))?;
+    call.read_image(packed)
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
+    Ok(blocking::run(move || with_registry(&app, infos)).await?)
+}
+
+// Rescans the plugins folder, e.g. after the user dropped a new one in
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn reload_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
+    blocking::run(move || {
+        let registry = scan(&app)?;
+        let plugins = infos(&registry);
+        *app.state::<PluginHost>()
+            .0
+            .lock()
+            .map_err(|_| Error::Lock("plugins"))? = Some(registry);
+        Ok(plugins)
+    })
+    .await
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn set_plugin_enabled(
+    app: AppHandle,
+    id: String,
+    enabled: bool,
+) -> Result<Vec<PluginInfo>, Error> {
+    let mut ids = app.state::<SettingsState>().snapshot().enabled_plugins;
+    ids.retain(|existing| *existing != id);
+    if enabled {
+        ids.push(id.clone());
+    }
+    settings::write(&app, ENABLED_KEY, &ids)?;
+    tracing::info!(
+        "{} plugin {}",
+        if enabled { "Enabled" } else { "Disabled" },
+        id
+    );
+    // Compiles (or drops) the module
+    reload_plugins(app).await
+}
+
+// Writes the image in one of a plugin's export formats, next to the source
+// unless a destination is given
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn export_with_plugin(
+    app: AppHandle,
+    path: String,
+    plugin: String,
+    format: String,
+    quality: u8,
+    dest: Option<String>,
+) -> Result<String, Error> {
+    blocking::run(move || {
+        let input = SafePath::read(&app, &path)?.into_path_buf();
+        let dest = safe_path::write_opt(&app, dest)?;
+        let image = crate::imaging::decode_image(&input)?;
+        let (bytes, export) = encode(&app, &plugin, &format, &image, quality)?;
+        let output = dest
+            .map(PathBuf::from)
+            .unwrap_or_else(|| suffixed_path(&input, None, "squished", &export.extension));
+        permissions::check_overwrite(&app, None, &input, &output)?;
+        let partial = PartialOutput::new(&output);
+        std::fs::write(partial.path(), &bytes)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        let output = partial.finish()?;
+        safe_path::allow(&app, &output);
+        tracing::info!(
+            "Exported {} as {} with plugin {}",
+            input.display(),
+            export.name,
+            plugin
+        );
+        Ok(output.to_string_lossy().to_string())
+    })
+    .await
+}
+
+// Runs a plugin filter over an image and returns the result as PNG bytes
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn apply_plugin_filter(
+    app: AppHandle,
+    path: String,
+    plugin: String,
+    filter: String,
+) -> Result<Response, Error> {
+    blocking::run(move || {
+        let input = SafePath::read(&app, &path)?.into_path_buf();
+        let image = crate::imaging::decode_image(&input)?;
+        let filtered = self::filter(&app, &plugin, &filter, &image)?;
+        Ok(Response::new(encode_image(
+            &filtered,
+            OutputFormat::Png,
+            100,
+        )?))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin_dir(name: &str, manifest: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-plugin-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(MANIFEST), manifest).unwrap();
+        dir
+    }
+
+    fn manifest_exporting(extension: &str) -> String {
+        serde_json::json!({
+            "id": "example",
+            "name": "Example",
+            "version": "1.0.0",
+            "apiVersion": API_VERSION,
+            "exports": [{
+                "id": "x",
+                "name": "X",
+                "extension": extension,
+                "mimeType": "image/x",
+            }],
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn extensions_are_short_lowercase_alphanumerics() {
+        for good in ["png", "jxl", "mp4", "a", "abcdefghij"] {
+            assert!(valid_extension(good), "{}", good);
+        }
+        for bad in [
+            "",
+            "PNG",
+            "tar.gz",
+            "../x",
+            "a/b",
+            "abcdefghijk",
+            "é",
+            " png",
+        ] {
+            assert!(!valid_extension(bad), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn manifests_with_unsafe_export_extensions_are_rejected() {
+        let dir = plugin_dir("good", &manifest_exporting("qoi"));
+        assert_eq!(read_manifest(&dir).unwrap().exports[0].extension, "qoi");
+        let dir = plugin_dir("bad", &manifest_exporting("../../evil"));
+        assert!(read_manifest(&dir).is_err());
+    }
+
+    #[test]
+    fn other_api_versions_are_rejected() {
+        let manifest = manifest_exporting("qoi").replace(
+            &format!("\"apiVersion\":{}", API_VERSION),
+            &format!("\"apiVersion\":{}", API_VERSION + 1),
+        );
+        let dir = plugin_dir("version", &manifest);
+        let error = read_manifest(&dir).err().unwrap();
+        assert!(error.contains("API version"), "{}", error);
+    }
+}
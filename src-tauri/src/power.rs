@@ -0,0 +1,223 @@
+// Keeps the machine from going to sleep while batches, exports, or syncs are
+// running, so an overnight batch isn't suspended halfway. Guards are counted
+// and the platform assertion is held while at least one is alive.
+use std::sync::Mutex;
+
+struct Held {
+    count: usize,
+    _assertion: Option<platform::Assertion>,
+}
+
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+// Prevents idle sleep until dropped
+pub struct KeepAwake(());
+
+pub fn keep_awake(reason: &str) -> KeepAwake {
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    match held.as_mut() {
+        Some(held) => held.count += 1,
+        None => {
+            let assertion = match platform::Assertion::acquire(reason) {
+                Ok(assertion) => {
+                    tracing::debug!("Preventing sleep: {}", reason);
+                    Some(assertion)
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to prevent sleep: {}", e);
+                    None
+                }
+            };
+            *held = Some(Held {
+                count: 1,
+                _assertion: assertion,
+            });
+        }
+    }
+    KeepAwake(())
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = held.as_mut() {
+            state.count -= 1;
+            if state.count == 0 {
+                *held = None;
+                tracing::debug!("Allowing sleep again");
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_void, CString};
+    use std::os::raw::c_char;
+
+    const UTF8: u32 = 0x0800_0100;
+    const ASSERTION_LEVEL_ON: u32 = 255;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            alloc: *const c_void,
+            c_str: *const c_char,
+            encoding: u32,
+        ) -> *const c_void;
+        fn CFRelease(cf: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: *const c_void,
+            level: u32,
+            name: *const c_void,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    unsafe fn cf_string(s: &str) -> Result<*const c_void, String> {
+        let c = CString::new(s).map_err(|e| format!("Invalid assertion name: {}", e))?;
+        let cf = CFStringCreateWithCString(std::ptr::null(), c.as_ptr(), UTF8);
+        if cf.is_null() {
+            return Err("Failed to create assertion name".to_string());
+        }
+        Ok(cf)
+    }
+
+    pub struct Assertion(u32);
+
+    impl Assertion {
+        pub fn acquire(reason: &str) -> Result<Self, String> {
+            unsafe {
+                let kind = cf_string("PreventUserIdleSystemSleep")?;
+                let name = match cf_string(reason) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        CFRelease(kind);
+                        return Err(e);
+                    }
+                };
+                let mut id = 0;
+                let result = IOPMAssertionCreateWithName(kind, ASSERTION_LEVEL_ON, name, &mut id);
+                CFRelease(kind);
+                CFRelease(name);
+                if result != 0 {
+                    return Err(format!("IOPMAssertionCreateWithName returned {}", result));
+                }
+                Ok(Self(id))
+            }
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe {
+                IOPMAssertionRelease(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::sync::mpsc::{channel, Sender};
+
+    const ES_CONTINUOUS: u32 = 0x8000_0000;
+    const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetThreadExecutionState(flags: u32) -> u32;
+    }
+
+    // The execution state belongs to the thread that set it and is cleared
+    // when that thread exits, so it's held by a thread of its own until the
+    // sender is dropped
+    pub struct Assertion(#[allow(dead_code)] Sender<()>);
+
+    impl Assertion {
+        pub fn acquire(_reason: &str) -> Result<Self, String> {
+            let (tx, rx) = channel::<()>();
+            let (ready_tx, ready_rx) = channel();
+            std::thread::Builder::new()
+                .name("keep-awake".into())
+                .spawn(move || {
+                    let previous =
+                        unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) };
+                    let _ = ready_tx.send(previous != 0);
+                    let _ = rx.recv();
+                    unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+                })
+                .map_err(|e| format!("Failed to start keep-awake thread: {}", e))?;
+            match ready_rx.recv() {
+                Ok(true) => Ok(Self(tx)),
+                _ => Err("SetThreadExecutionState failed".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::{Child, Command, Stdio};
+
+    // systemd-inhibit holds a logind inhibitor lock for as long as its child runs
+    pub struct Assertion(Child);
+
+    impl Assertion {
+        pub fn acquire(reason: &str) -> Result<Self, String> {
+            Command::new("systemd-inhibit")
+                .args(["--what=sleep:idle", "--who=Squish", "--mode=block"])
+                .arg(format!("--why={}", reason))
+                .args(["sleep", "infinity"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map(Self)
+                .map_err(|e| format!("Failed to run systemd-inhibit: {}", e))
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+mod platform {
+    pub struct Assertion;
+
+    impl Assertion {
+        pub fn acquire(_reason: &str) -> Result<Self, String> {
+            Err("Not supported on this platform".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held() -> Option<usize> {
+        HELD.lock().unwrap().as_ref().map(|held| held.count)
+    }
+
+    #[test]
+    fn sleep_is_allowed_once_every_guard_is_dropped() {
+        let first = keep_awake("test");
+        let second = keep_awake("test");
+        assert_eq!(held(), Some(2));
+        drop(first);
+        assert_eq!(held(), Some(1));
+        drop(second);
+        assert_eq!(held(), None);
+    }
+}
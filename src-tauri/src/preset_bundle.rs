@@ -0,0 +1,407 @@
+// .squishpreset bundles: presets, an optional metadata policy and watch
+// folders in one signed file, so a team can hand around a standard setup.
+//
+// The file is a JSON envelope around the payload, kept as the exact string
+// that was signed. Each install signs with its own Ed25519 key, made on first
+// export and kept in the keychain. A bundle whose signature doesn't verify is
+// refused; the signer's fingerprint is shown by inspect_preset_bundle, and
+// import can be pinned to it so what gets imported is what the user looked at.
+//
+// On import presets are merged by name (a team preset replaces the local one
+// of the same name, keeping its id), the metadata policy is applied to every
+// preset and watch folder in the bundle. The signature only shows the bundle
+// wasn't changed since its key signed it, and the key travels in the bundle,
+// so watch folders (which write into the user's folders unattended) are only
+// imported when the import is pinned to the signer or the bundle is this
+// install's own. Even then each folder has to be one the user granted.
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use tauri::{AppHandle, Manager};
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::{CompressOptions, MetadataPolicy};
+use crate::safe_path::SafePath;
+use crate::settings_file::{self, ExportedWatchFolder};
+
+const FORMAT: &str = "squish-preset";
+const VERSION: u32 = 1;
+const SIGNING_KEY_ACCOUNT: &str = "preset-signing-key";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    format: String,
+    version: u32,
+    // Hex Ed25519 public key and signature over `payload`
+    public_key: String,
+    signature: String,
+    payload: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundlePreset {
+    pub name: String,
+    pub options: CompressOptions,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Contents {
+    name: String,
+    author: Option<String>,
+    presets: Vec<BundlePreset>,
+    metadata_policy: Option<MetadataPolicy>,
+    #[serde(default)]
+    watch_folders: Vec<ExportedWatchFolder>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleInfo {
+    pub name: String,
+    pub author: Option<String>,
+    pub signer: String,
+    // Signed by this install's own key
+    pub own: bool,
+    pub presets: Vec<String>,
+    // Local presets the import would replace
+    pub replaces: Vec<String>,
+    pub metadata_policy: Option<MetadataPolicy>,
+    pub watch_folders: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleImportSummary {
+    pub signer: String,
+    pub presets_added: u32,
+    pub presets_replaced: u32,
+    pub watch_folders: u32,
+    // Watch folders left out: all of them for an unpinned signer, otherwise
+    // those that don't exist here or that the user hasn't granted
+    pub skipped: Vec<String>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn fingerprint(key: &VerifyingKey) -> String {
+    let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+    digest.as_bytes()[..16]
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn signing_key() -> Result<SigningKey, String> {
+    if let Some(stored) = crate::secrets::get(SIGNING_KEY_ACCOUNT)? {
+        let bytes = from_hex::<32>(&stored).ok_or("Stored preset signing key is invalid")?;
+        return Ok(SigningKey::from_bytes(&bytes));
+    }
+    let key = SigningKey::generate(&mut OsRng);
+    crate::secrets::set(SIGNING_KEY_ACCOUNT, &to_hex(&key.to_bytes()))?;
+    tracing::info!(
+        "Created preset signing key {}",
+        fingerprint(&key.verifying_key())
+    );
+    Ok(key)
+}
+
+fn own_key() -> Option<VerifyingKey> {
+    let stored = crate::secrets::get(SIGNING_KEY_ACCOUNT).ok()??;
+    Some(SigningKey::from_bytes(&from_hex::<32>(&stored)?).verifying_key())
+}
+
+fn invalid(path: &str) -> Error {
+    Error::InvalidInput(format!("{} is not a valid Squish preset bundle", path))
+}
+
+// Parses, verifies and validates a bundle; nothing is trusted before this
+fn open(path: &str) -> Result<(Contents, VerifyingKey), Error> {
+    let json = std::fs::read_to_string(path)?;
+    let envelope: Envelope = serde_json::from_str(&json).map_err(|_| invalid(path))?;
+    if envelope.format != FORMAT {
+        return Err(invalid(path));
+    }
+    if envelope.version > VERSION {
+        return Err(Error::UnsupportedFormat(
+            "Preset bundle was made by a newer version of Squish".to_string(),
+        ));
+    }
+    let key = from_hex::<32>(&envelope.public_key)
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| invalid(path))?;
+    let signature = from_hex::<64>(&envelope.signature)
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or_else(|| invalid(path))?;
+    key.verify_strict(envelope.payload.as_bytes(), &signature)
+        .map_err(|_| {
+            Error::InvalidInput(format!("{} has been modified since it was signed", path))
+        })?;
+
+    let contents: Contents = serde_json::from_str(&envelope.payload)
+        .map_err(|e| Error::InvalidInput(format!("Preset bundle {} is invalid: {}", path, e)))?;
+    let mut names = HashSet::new();
+    for preset in &contents.presets {
+        if preset.name.trim().is_empty() || !names.insert(preset.name.as_str()) {
+            return Err(Error::InvalidInput(format!(
+                "Preset bundle {} has a missing or duplicate preset name",
+                path
+            )));
+        }
+        if !(1..=100).contains(&preset.options.quality) {
+            return Err(Error::InvalidInput(format!(
+                "Preset {} has an invalid quality",
+                preset.name
+            )));
+        }
+    }
+    Ok((contents, key))
+}
+
+// Signs with this install's key; returns its fingerprint
+#[tauri::command]
+pub fn export_preset_bundle(
+    app: AppHandle,
+    path: String,
+    name: String,
+    author: Option<String>,
+    preset_ids: Option<Vec<String>>,
+    metadata_policy: Option<MetadataPolicy>,
+    include_watch_folders: bool,
+) -> Result<String, Error> {
+    let path = SafePath::write(&app, &path)?.into_string();
+    let presets = crate::presets::list_presets(app.state::<Db>())?
+        .into_iter()
+        .filter(|p| preset_ids.as_ref().is_none_or(|ids| ids.contains(&p.id)))
+        .map(|p| BundlePreset {
+            name: p.name,
+            options: p.options,
+        })
+        .collect();
+    let watch_folders = if include_watch_folders {
+        settings_file::export_watch_folders(&app)?
+    } else {
+        Vec::new()
+    };
+    let contents = Contents {
+        name,
+        author,
+        presets,
+        metadata_policy,
+        watch_folders,
+    };
+    let payload = serde_json::to_string_pretty(&contents)
+        .map_err(|e| Error::Internal(format!("Failed to serialize preset bundle: {}", e)))?;
+
+    let key = signing_key()?;
+    let envelope = Envelope {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        public_key: to_hex(key.verifying_key().as_bytes()),
+        signature: to_hex(&key.sign(payload.as_bytes()).to_bytes()),
+        payload,
+    };
+    let json = serde_json::to_string_pretty(&envelope)
+        .map_err(|e| Error::Internal(format!("Failed to serialize preset bundle: {}", e)))?;
+    std::fs::write(&path, json)?;
+    tracing::info!("Exported preset bundle {} to {}", contents.name, path);
+    Ok(fingerprint(&key.verifying_key()))
+}
+
+#[tauri::command]
+pub fn inspect_preset_bundle(app: AppHandle, path: String) -> Result<BundleInfo, Error> {
+    let path = SafePath::read(&app, &path)?.into_string();
+    let (contents, key) = open(&path)?;
+    let local: HashSet<String> = crate::presets::list_presets(app.state::<Db>())?
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    Ok(BundleInfo {
+        name: contents.name,
+        author: contents.author,
+        signer: fingerprint(&key),
+        own: own_key() == Some(key),
+        replaces: contents
+            .presets
+            .iter()
+            .filter(|p| local.contains(&p.name))
+            .map(|p| p.name.clone())
+            .collect(),
+        presets: contents.presets.into_iter().map(|p| p.name).collect(),
+        metadata_policy: contents.metadata_policy,
+        watch_folders: contents.watch_folders.into_iter().map(|f| f.path).collect(),
+    })
+}
+
+// `signer` pins the import to the fingerprint the user was shown
+#[tauri::command]
+pub fn import_preset_bundle(
+    app: AppHandle,
+    path: String,
+    signer: Option<String>,
+) -> Result<BundleImportSummary, Error> {
+    let path = SafePath::read(&app, &path)?.into_string();
+    let (mut contents, key) = open(&path)?;
+    let fingerprint = fingerprint(&key);
+    let trusted = signer.is_some() || own_key() == Some(key);
+    if signer.is_some_and(|expected| expected != fingerprint) {
+        return Err(Error::PermissionDenied(format!(
+            "{} is signed by {}, not the expected signer",
+            path, fingerprint
+        )));
+    }
+    if let Some(policy) = contents.metadata_policy {
+        for preset in &mut contents.presets {
+            preset.options.metadata = policy;
+        }
+        for folder in &mut contents.watch_folders {
+            folder.options.metadata = policy;
+        }
+    }
+
+    let mut summary = BundleImportSummary {
+        signer: fingerprint,
+        presets_added: 0,
+        presets_replaced: 0,
+        watch_folders: 0,
+        skipped: Vec::new(),
+    };
+    let local = crate::presets::list_presets(app.state::<Db>())?;
+    for preset in contents.presets {
+        let existing = local.iter().find(|p| p.name == preset.name);
+        if existing.is_some() {
+            summary.presets_replaced += 1;
+        } else {
+            summary.presets_added += 1;
+        }
+        crate::presets::save_preset(
+            app.state::<Db>(),
+            existing.map(|p| p.id.clone()),
+            preset.name,
+            preset.options,
+        )?;
+    }
+    let (added, skipped) = if trusted {
+        settings_file::import_watch_folders(&app, contents.watch_folders)?
+    } else {
+        let paths = contents.watch_folders.into_iter().map(|f| f.path);
+        (0, paths.collect())
+    };
+    summary.watch_folders = added;
+    summary.skipped = skipped;
+
+    tracing::info!(
+        "Imported preset bundle {} signed by {}",
+        contents.name,
+        summary.signer
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn preset(name: &str, quality: u8) -> serde_json::Value {
+        json!({ "name": name, "options": { "format": "webp", "quality": quality } })
+    }
+
+    fn write(name: &str, payload: &str, signed: &str) -> String {
+        let key = key();
+        let envelope = Envelope {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            public_key: to_hex(key.verifying_key().as_bytes()),
+            signature: to_hex(&key.sign(signed.as_bytes()).to_bytes()),
+            payload: payload.to_string(),
+        };
+        let path = std::env::temp_dir().join(format!(
+            "squish-bundle-{}-{}.squishpreset",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, serde_json::to_string(&envelope).unwrap()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn bundle(name: &str, presets: Vec<serde_json::Value>) -> String {
+        let payload = json!({ "name": "Team", "presets": presets }).to_string();
+        write(name, &payload, &payload)
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = [0x00, 0x7f, 0xff, 0x10];
+        assert_eq!(to_hex(&bytes), "007fff10");
+        assert_eq!(from_hex::<4>("007fff10"), Some(bytes));
+        assert_eq!(from_hex::<4>("007fff1"), None);
+        assert_eq!(from_hex::<2>("zz00"), None);
+    }
+
+    #[test]
+    fn fingerprints_are_four_groups_of_four() {
+        let fingerprint = fingerprint(&key().verifying_key());
+        let groups: Vec<&str> = fingerprint.split(':').collect();
+        assert_eq!(groups.len(), 4);
+        assert!(groups
+            .iter()
+            .all(|g| g.len() == 4 && g.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+
+    #[test]
+    fn a_signed_bundle_opens() {
+        let path = bundle("valid", vec![preset("Web", 80), preset("Print", 95)]);
+        let (contents, signer) = open(&path).unwrap();
+        assert_eq!(signer, key().verifying_key());
+        assert_eq!(contents.name, "Team");
+        assert_eq!(contents.presets.len(), 2);
+        assert!(contents.watch_folders.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_modified_payload_is_refused() {
+        let signed = json!({ "name": "Team", "presets": [preset("Web", 80)] }).to_string();
+        let changed = json!({ "name": "Team", "presets": [preset("Web", 20)] }).to_string();
+        let path = write("modified", &changed, &signed);
+        assert!(matches!(open(&path), Err(Error::InvalidInput(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_presets_are_refused() {
+        for (name, presets) in [
+            ("duplicate", vec![preset("Web", 80), preset("Web", 60)]),
+            ("blank", vec![preset("  ", 80)]),
+            ("quality", vec![preset("Web", 0)]),
+        ] {
+            let path = bundle(name, presets);
+            assert!(open(&path).is_err(), "{} bundle was accepted", name);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::CompressOptions;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub id: String,
+    pub name: String,
+    pub options: CompressOptions,
+    pub updated_at: Option<String>,
+}
+
+#[tauri::command]
+pub fn list_presets(db: State<Db>) -> Result<Vec<Preset>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT id, name, options, updated_at FROM presets ORDER BY name")
+        .map_err(|e| format!("Failed to query presets: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to query presets: {}", e))?;
+
+    let mut presets = Vec::new();
+    for row in rows {
+        let (id, name, options, updated_at) =
+            row.map_err(|e| format!("Failed to read preset: {}", e))?;
+        match serde_json::from_str(&options) {
+            Ok(options) => presets.push(Preset {
+                id,
+                name,
+                options,
+                updated_at,
+            }),
+            Err(e) => tracing::debug!("Skipping preset {} with invalid options: {}", id, e),
+        }
+    }
+    Ok(presets)
+}
+
+// Looked up by name for the CLI, where ids aren't something users know
+pub fn find_by_name(conn: &Connection, name: &str) -> Result<Option<Preset>, String> {
+    let row = conn
+        .query_row(
+            "SELECT id, name, options, updated_at FROM presets WHERE name = ?1",
+            params![name],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to query presets: {}", e))?;
+    let Some((id, name, options, updated_at)) = row else {
+        return Ok(None);
+    };
+    let options = serde_json::from_str(&options)
+        .map_err(|e| format!("Preset {} has invalid options: {}", name, e))?;
+    Ok(Some(Preset {
+        id,
+        name,
+        options,
+        updated_at,
+    }))
+}
+
+// Creates the preset when it has no id yet, otherwise updates it in place
+#[tauri::command]
+pub fn save_preset(
+    db: State<Db>,
+    id: Option<String>,
+    name: String,
+    options: CompressOptions,
+) -> Result<String, Error> {
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let options = serde_json::to_string(&options)
+        .map_err(|e| format!("Failed to serialize preset: {}", e))?;
+
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO presets (id, name, options) VALUES (?1, ?2, ?3)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            options = excluded.options,
+            updated_at = CURRENT_TIMESTAMP",
+        params![id, name, options],
+    )
+    .map_err(|e| format!("Failed to save preset: {}", e))?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn delete_preset(db: State<Db>, id: String) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM presets WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete preset: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_found_by_exact_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute_batch(
+            r#"INSERT INTO presets (id, name, options) VALUES ('p1', 'Web', '{"format":"webp","quality":75}');
+               INSERT INTO presets (id, name, options) VALUES ('p2', 'Broken', '{"quality":"high"}');"#,
+        )
+        .unwrap();
+
+        let web = find_by_name(&conn, "Web").unwrap().unwrap();
+        assert_eq!(web.id, "p1");
+        assert_eq!(web.options.quality, 75);
+        assert!(find_by_name(&conn, "web").unwrap().is_none());
+        assert!(find_by_name(&conn, "Print").unwrap().is_none());
+        assert!(find_by_name(&conn, "Broken").is_err());
+    }
+}
@@ -0,0 +1,430 @@
+// Live preview while a quality slider moves. The source is decoded and scaled
+// down once when a preview opens; every update re-encodes it at the new
+// settings and decodes the result into the session's frame buffer, which is
+// reused from frame to frame. Only a small notice goes over the channel the UI
+// passed in; the pixels themselves are read as raw RGBA from
+// preview://localhost/<session>, straight into an ImageData, with no JSON or
+// base64 in between. Updates that are overtaken by a newer one while encoding
+// are dropped, so a fast drag never queues up stale frames.
+//
+// A session can also be shown in the mini preview: a small frameless window
+// kept above other apps, so the output stays in view while working elsewhere.
+// It reads the same frame buffer; its own channel, registered with
+// watch_preview, gets the same notices as the session's.
+use image::DynamicImage;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::ipc::Channel;
+use tauri::{
+    AppHandle, Emitter, Manager, Runtime, State, UriSchemeContext, WebviewUrl, WebviewWindowBuilder,
+};
+
+use crate::blocking;
+use crate::error::Error;
+use crate::imaging::{decode_image, encode_image, OutputFormat};
+use crate::safe_path::SafePath;
+
+pub const SCHEME: &str = "preview";
+// Longest edge of the preview source; bigger than any on-screen preview pane
+const DEFAULT_MAX_EDGE: u32 = 1600;
+const MINI_WINDOW: &str = "mini-preview";
+const MINI_SIZE: (f64, f64) = (320.0, 240.0);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewFrame {
+    pub seq: u64,
+    pub width: u32,
+    pub height: u32,
+    // Size of the encoded preview, to show next to the slider
+    pub encoded_bytes: u64,
+}
+
+#[derive(Default)]
+struct Frame {
+    seq: u64,
+    width: u32,
+    height: u32,
+    encoded_bytes: u64,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    fn notice(&self) -> PreviewFrame {
+        PreviewFrame {
+            seq: self.seq,
+            width: self.width,
+            height: self.height,
+            encoded_bytes: self.encoded_bytes,
+        }
+    }
+}
+
+struct Session {
+    source: DynamicImage,
+    channel: Channel<PreviewFrame>,
+    // Channels of other windows showing this session, the mini preview's
+    watchers: Mutex<Vec<Channel<PreviewFrame>>>,
+    // Bumped by every update; an encode that finishes behind it is dropped
+    requested: AtomicU64,
+    frame: Mutex<Frame>,
+}
+
+#[derive(Default)]
+pub struct PreviewState {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    // Session shown in the mini preview window, if it's open
+    mini: Mutex<Option<String>>,
+}
+
+impl PreviewState {
+    fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().ok()?.get(id).cloned()
+    }
+
+    // Source pixels and frame buffers of every open session
+    pub fn bytes(&self) -> u64 {
+        let Ok(sessions) = self.sessions.lock() else {
+            return 0;
+        };
+        sessions
+            .values()
+            .map(|session| {
+                let frame = session
+                    .frame
+                    .lock()
+                    .map(|frame| frame.pixels.capacity())
+                    .unwrap_or_default();
+                (session.source.as_bytes().len() + frame) as u64
+            })
+            .sum()
+    }
+}
+
+fn render(
+    session: &Session,
+    generation: u64,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<(), String> {
+    let encoded = encode_image(&session.source, format, quality)?;
+    if session.requested.load(Ordering::SeqCst) != generation {
+        return Ok(());
+    }
+    let decoded = image::load_from_memory(&encoded)
+        .map_err(|e| format!("Failed to decode {:?} preview: {}", format, e))?
+        .to_rgba8();
+
+    let notice = {
+        let mut frame = session
+            .frame
+            .lock()
+            .map_err(|_| Error::Lock("preview frame"))?;
+        // A newer update got here first
+        if frame.seq >= generation {
+            return Ok(());
+        }
+        frame.pixels.clear();
+        frame.pixels.extend_from_slice(decoded.as_raw());
+        frame.seq = generation;
+        frame.width = decoded.width();
+        frame.height = decoded.height();
+        frame.encoded_bytes = encoded.len() as u64;
+        frame.notice()
+    };
+    // A watcher whose window has gone away is dropped
+    if let Ok(mut watchers) = session.watchers.lock() {
+        watchers.retain(|watcher| watcher.send(notice.clone()).is_ok());
+    }
+    session
+        .channel
+        .send(notice)
+        .map_err(|e| format!("Failed to send preview frame: {}", e))
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn open_preview(
+    app: AppHandle,
+    path: String,
+    max_edge: Option<u32>,
+    on_frame: Channel<PreviewFrame>,
+) -> Result<String, Error> {
+    blocking::run(move || {
+        let image = decode_image(SafePath::read(&app, &path)?.as_path())?;
+        let max_edge = max_edge.unwrap_or(DEFAULT_MAX_EDGE);
+        let source = if image.width().max(image.height()) > max_edge {
+            image.thumbnail(max_edge, max_edge)
+        } else {
+            image
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = Session {
+            source,
+            channel: on_frame,
+            watchers: Mutex::new(Vec::new()),
+            requested: AtomicU64::new(0),
+            frame: Mutex::new(Frame::default()),
+        };
+        app.state::<PreviewState>()
+            .sessions
+            .lock()
+            .map_err(|_| Error::Lock("previews"))?
+            .insert(id.clone(), Arc::new(session));
+        Ok(id)
+    })
+    .await
+}
+
+// Returns once the frame is ready (or dropped for a newer one); the frame
+// itself arrives through the session's channel
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn update_preview(
+    state: State<'_, PreviewState>,
+    session: String,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<(), Error> {
+    let session = state
+        .get(&session)
+        .ok_or_else(|| format!("No preview session {}", session))?;
+    let generation = session.requested.fetch_add(1, Ordering::SeqCst) + 1;
+    Ok(blocking::run(move || render(&session, generation, format, quality)).await?)
+}
+
+// Closes the mini preview too when it's showing this session
+#[tauri::command]
+pub fn close_preview(
+    app: AppHandle,
+    state: State<PreviewState>,
+    session: String,
+) -> Result<(), Error> {
+    state
+        .sessions
+        .lock()
+        .map_err(|_| Error::Lock("previews"))?
+        .remove(&session);
+    let showing = state
+        .mini
+        .lock()
+        .map(|mini| mini.as_deref() == Some(session.as_str()))
+        .unwrap_or(false);
+    if showing {
+        close_mini_preview(app, state)?;
+    }
+    Ok(())
+}
+
+// Shows the session in the mini preview window, opening it if needed. An open
+// window is switched over with a "mini-preview-session" event instead of
+// being rebuilt, and keeps its place on screen.
+#[tauri::command]
+pub async fn open_mini_preview(
+    app: AppHandle,
+    state: State<'_, PreviewState>,
+    session: String,
+) -> Result<(), Error> {
+    if state.get(&session).is_none() {
+        return Err(Error::NotFound(format!("No preview session {}", session)));
+    }
+    *state.mini.lock().map_err(|_| Error::Lock("previews"))? = Some(session.clone());
+
+    if let Some(window) = app.get_webview_window(MINI_WINDOW) {
+        window
+            .emit("mini-preview-session", &session)
+            .map_err(|e| format!("Failed to switch mini preview: {}", e))?;
+        window
+            .show()
+            .map_err(|e| format!("Failed to show mini preview: {}", e))?;
+        return Ok(());
+    }
+
+    let url = WebviewUrl::App(format!("mini-preview?session={}", session).into());
+    let mut builder = WebviewWindowBuilder::new(&app, MINI_WINDOW, url)
+        .title("Squish Preview")
+        .inner_size(MINI_SIZE.0, MINI_SIZE.1)
+        .min_inner_size(MINI_SIZE.0 / 2.0, MINI_SIZE.1 / 2.0)
+        .decorations(false)
+        .always_on_top(true)
+        .visible_on_all_workspaces(true)
+        .skip_taskbar(true)
+        .shadow(true)
+        // Opening it shouldn't take focus from the document being worked on
+        .focused(false);
+    if let Some(frame) = crate::session::saved_window(&app, MINI_WINDOW) {
+        builder = builder
+            .position(frame.x as f64, frame.y as f64)
+            .inner_size(frame.width as f64, frame.height as f64);
+    }
+    let window = builder
+        .build()
+        .map_err(|e| format!("Failed to open mini preview: {}", e))?;
+    crate::session::remember_frame(&app, &window);
+    tracing::info!("Opened mini preview for session {}", session);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn close_mini_preview(app: AppHandle, state: State<PreviewState>) -> Result<(), Error> {
+    if let Ok(mut mini) = state.mini.lock() {
+        *mini = None;
+    }
+    if let Some(window) = app.get_webview_window(MINI_WINDOW) {
+        window
+            .close()
+            .map_err(|e| format!("Failed to close mini preview: {}", e))?;
+    }
+    Ok(())
+}
+
+// Called by the mini preview window with its own channel. The latest frame,
+// if any, is announced right away so the window doesn't start out blank.
+#[tauri::command]
+pub fn watch_preview(
+    state: State<PreviewState>,
+    session: String,
+    on_frame: Channel<PreviewFrame>,
+) -> Result<(), Error> {
+    let session = state
+        .get(&session)
+        .ok_or_else(|| format!("No preview session {}", session))?;
+    let latest = session
+        .frame
+        .lock()
+        .map_err(|_| Error::Lock("preview frame"))?
+        .notice();
+    if latest.seq > 0 {
+        on_frame
+            .send(latest)
+            .map_err(|e| format!("Failed to send preview frame: {}", e))?;
+    }
+    session
+        .watchers
+        .lock()
+        .map_err(|_| Error::Lock("preview watchers"))?
+        .push(on_frame);
+    Ok(())
+}
+
+// Latest frame of a session as raw RGBA; dimensions and sequence number ride
+// along in headers so a late fetch can tell which frame it got
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let id = request.uri().path().trim_start_matches('/');
+    let frame = ctx
+        .app_handle()
+        .state::<PreviewState>()
+        .get(id)
+        .and_then(|session| {
+            let frame = session.frame.lock().ok()?;
+            (frame.seq > 0).then(|| (frame.seq, frame.width, frame.height, frame.pixels.clone()))
+        });
+    let Some((seq, width, height, pixels)) = frame else {
+        let mut response = Response::new(Vec::new());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    };
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            "X-Preview-Seq, X-Preview-Width, X-Preview-Height",
+        )
+        .header("X-Preview-Seq", seq)
+        .header("X-Preview-Width", width)
+        .header("X-Preview-Height", height)
+        .body(pixels)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to build preview response: {}", e);
+            let mut response = Response::new(Vec::new());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+    use std::sync::atomic::AtomicUsize;
+
+    // A channel that counts the notices sent through it
+    fn channel() -> (Channel<PreviewFrame>, Arc<AtomicUsize>) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let channel = Channel::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        (channel, sent)
+    }
+
+    fn session(channel: Channel<PreviewFrame>) -> Session {
+        Session {
+            source: DynamicImage::ImageRgba8(RgbaImage::new(8, 4)),
+            channel,
+            watchers: Mutex::new(Vec::new()),
+            requested: AtomicU64::new(0),
+            frame: Mutex::new(Frame::default()),
+        }
+    }
+
+    #[test]
+    fn a_rendered_frame_is_announced() {
+        let (channel, sent) = channel();
+        let session = session(channel);
+        let generation = session.requested.fetch_add(1, Ordering::SeqCst) + 1;
+        render(&session, generation, OutputFormat::Png, 80).unwrap();
+
+        let frame = session.frame.lock().unwrap();
+        assert_eq!((frame.seq, frame.width, frame.height), (1, 8, 4));
+        assert_eq!(frame.pixels.len(), 8 * 4 * 4);
+        assert!(frame.encoded_bytes > 0);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn overtaken_updates_are_dropped() {
+        let (channel, sent) = channel();
+        let session = session(channel);
+        session.requested.store(2, Ordering::SeqCst);
+        render(&session, 1, OutputFormat::Png, 80).unwrap();
+        assert_eq!(session.frame.lock().unwrap().seq, 0);
+
+        render(&session, 2, OutputFormat::Png, 80).unwrap();
+        session.requested.store(1, Ordering::SeqCst);
+        render(&session, 1, OutputFormat::Png, 80).unwrap();
+        assert_eq!(session.frame.lock().unwrap().seq, 2);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn watchers_get_the_same_notices() {
+        let (watcher, watched) = channel();
+        let (own, sent) = channel();
+        let session = session(own);
+        session.watchers.lock().unwrap().push(watcher);
+        session.requested.store(1, Ordering::SeqCst);
+        render(&session, 1, OutputFormat::Png, 80).unwrap();
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert_eq!(watched.load(Ordering::SeqCst), 1);
+
+        // A window that has gone away stops being notified
+        let gone = Channel::new(|_| Err(tauri::Error::WebviewNotFound));
+        session.watchers.lock().unwrap().push(gone);
+        session.requested.store(2, Ordering::SeqCst);
+        render(&session, 2, OutputFormat::Png, 80).unwrap();
+        assert_eq!(session.watchers.lock().unwrap().len(), 1);
+        assert_eq!(watched.load(Ordering::SeqCst), 2);
+    }
+}
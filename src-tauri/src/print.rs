@@ -0,0 +1,473 @@
+// Printing proofs without exporting a PDF first. The frontend renders each
+// page to an image; here they're laid out on the chosen paper (margins,
+// scaling, orientation) as a temporary PDF that is handed to the OS printing
+// system.
+use image::{DynamicImage, GenericImageView};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::cancel::{self, CancelToken, PartialOutput};
+use crate::error::Error;
+use crate::imaging::{decode_image, encode_image, flatten, OutputFormat};
+
+const PRINT_DIR: &str = "print";
+const POINTS_PER_INCH: f32 = 72.0;
+const POINTS_PER_MM: f32 = POINTS_PER_INCH / 25.4;
+const JPEG_QUALITY: u8 = 95;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PaperSize {
+    #[default]
+    Letter,
+    Legal,
+    A3,
+    A4,
+    A5,
+    #[serde(rename_all = "camelCase")]
+    Custom {
+        width_mm: f32,
+        height_mm: f32,
+    },
+}
+
+impl PaperSize {
+    // Portrait width and height in points
+    fn points(&self) -> (f32, f32) {
+        match self {
+            PaperSize::Letter => (612.0, 792.0),
+            PaperSize::Legal => (612.0, 1008.0),
+            PaperSize::A3 => (297.0 * POINTS_PER_MM, 420.0 * POINTS_PER_MM),
+            PaperSize::A4 => (210.0 * POINTS_PER_MM, 297.0 * POINTS_PER_MM),
+            PaperSize::A5 => (148.0 * POINTS_PER_MM, 210.0 * POINTS_PER_MM),
+            PaperSize::Custom {
+                width_mm,
+                height_mm,
+            } => (width_mm * POINTS_PER_MM, height_mm * POINTS_PER_MM),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    // Landscape for pages wider than they are tall
+    #[default]
+    Auto,
+    Portrait,
+    Landscape,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    // Whole page visible inside the margins
+    #[default]
+    Fit,
+    // Printable area covered, edges cropped
+    Fill,
+    // Printed at `dpi`, centered and cropped if larger than the paper
+    Actual,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintDocument {
+    pub title: String,
+    // Rendered page images, in order
+    pub pages: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrintOptions {
+    pub paper: PaperSize,
+    pub orientation: Orientation,
+    pub scaling: Scaling,
+    pub margin_mm: f32,
+    // Only used with actual-size scaling; 300 when omitted
+    pub dpi: Option<f32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintResult {
+    pub pdf_path: String,
+    pub pages: usize,
+}
+
+struct Placement {
+    page_width: f32,
+    page_height: f32,
+    // Image rectangle in points, origin bottom-left
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+fn place(image_width: u32, image_height: u32, options: &PrintOptions) -> Placement {
+    let (portrait_width, portrait_height) = options.paper.points();
+    let landscape = match options.orientation {
+        Orientation::Auto => image_width > image_height,
+        Orientation::Portrait => false,
+        Orientation::Landscape => true,
+    };
+    let (page_width, page_height) = if landscape {
+        (portrait_height, portrait_width)
+    } else {
+        (portrait_width, portrait_height)
+    };
+
+    let margin = options.margin_mm.max(0.0) * POINTS_PER_MM;
+    let area_width = (page_width - 2.0 * margin).max(1.0);
+    let area_height = (page_height - 2.0 * margin).max(1.0);
+    let (image_width, image_height) = (image_width.max(1) as f32, image_height.max(1) as f32);
+
+    let (width, height) = match options.scaling {
+        Scaling::Fit | Scaling::Fill => {
+            let fit_x = area_width / image_width;
+            let fit_y = area_height / image_height;
+            let scale = if options.scaling == Scaling::Fit {
+                fit_x.min(fit_y)
+            } else {
+                fit_x.max(fit_y)
+            };
+            (image_width * scale, image_height * scale)
+        }
+        Scaling::Actual => {
+            let dpi = options.dpi.filter(|d| *d > 0.0).unwrap_or(300.0);
+            (
+                image_width * POINTS_PER_INCH / dpi,
+                image_height * POINTS_PER_INCH / dpi,
+            )
+        }
+    };
+    Placement {
+        page_width,
+        page_height,
+        x: (page_width - width) / 2.0,
+        y: (page_height - height) / 2.0,
+        width,
+        height,
+    }
+}
+
+fn page_image(path: &Path) -> Result<(DynamicImage, Vec<u8>), String> {
+    // Paper is white, and JPEG has no alpha
+    let image = flatten(&decode_image(path)?, [255, 255, 255]);
+    let jpeg = encode_image(&image, OutputFormat::Jpeg, JPEG_QUALITY)?;
+    Ok((image, jpeg))
+}
+
+pub fn build_pdf(
+    doc: &PrintDocument,
+    options: &PrintOptions,
+    dest: &Path,
+    cancel: &CancelToken,
+) -> Result<(), String> {
+    if doc.pages.is_empty() {
+        return Err("Nothing to print".to_string());
+    }
+    let mut pdf = Document::with_version("1.5");
+    let pages_id = pdf.new_object_id();
+    let mut kids = Vec::new();
+
+    for path in &doc.pages {
+        cancel.check()?;
+        let (image, jpeg) = page_image(Path::new(path))?;
+        let (image_width, image_height) = image.dimensions();
+        let placement = place(image_width, image_height, options);
+        let margin = options.margin_mm.max(0.0) * POINTS_PER_MM;
+
+        let image_id = pdf.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => image_width as i64,
+                "Height" => image_height as i64,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8i64,
+                "Filter" => "DCTDecode",
+            },
+            jpeg,
+        ));
+
+        // Clip to the margins so filled or oversized pages don't run into them
+        let content = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "re",
+                    vec![
+                        margin.into(),
+                        margin.into(),
+                        (placement.page_width - 2.0 * margin).into(),
+                        (placement.page_height - 2.0 * margin).into(),
+                    ],
+                ),
+                Operation::new("W", vec![]),
+                Operation::new("n", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        placement.width.into(),
+                        0i64.into(),
+                        0i64.into(),
+                        placement.height.into(),
+                        placement.x.into(),
+                        placement.y.into(),
+                    ],
+                ),
+                Operation::new("Do", vec![Object::Name(b"Page".to_vec())]),
+                Operation::new("Q", vec![]),
+            ],
+        };
+        let content_id = pdf.add_object(Stream::new(
+            dictionary! {},
+            content
+                .encode()
+                .map_err(|e| format!("Failed to build print page: {}", e))?,
+        ));
+        let page_id = pdf.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![
+                0i64.into(),
+                0i64.into(),
+                placement.page_width.into(),
+                placement.page_height.into(),
+            ],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Page" => image_id },
+            },
+        });
+        kids.push(page_id.into());
+    }
+
+    let count = kids.len() as i64;
+    pdf.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    let info_id = pdf.add_object(dictionary! {
+        "Title" => Object::string_literal(doc.title.as_str()),
+        "Producer" => Object::string_literal("Squish"),
+    });
+    let catalog_id = pdf.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    pdf.trailer.set("Root", catalog_id);
+    pdf.trailer.set("Info", info_id);
+    pdf.compress();
+    let partial = PartialOutput::new(dest);
+    pdf.save(partial.path())
+        .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+    cancel.check()?;
+    partial.finish()?;
+    Ok(())
+}
+
+fn print_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?
+        .join(PRINT_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+// Lays the pages out and opens the system print dialog for them. The PDF is
+// left in the cache so the dialog's own "Save as PDF" and previews keep working.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn print_document(
+    app: AppHandle,
+    doc: PrintDocument,
+    options: PrintOptions,
+    operation_id: Option<String>,
+) -> Result<PrintResult, Error> {
+    let dest = print_dir(&app)?.join(format!("{}.pdf", uuid::Uuid::new_v4()));
+    let pages = doc.pages.len();
+    {
+        let (doc, options, dest) = (doc.clone(), options.clone(), dest.clone());
+        let operation = cancel::register(&app, operation_id);
+        tauri::async_runtime::spawn_blocking(move || {
+            build_pdf(&doc, &options, &dest, operation.token())
+        })
+        .await
+        .map_err(|e| format!("Failed to prepare print job: {}", e))??;
+    }
+    platform::show_dialog(&app, &dest, &doc.title)?;
+    tracing::info!("Sent {} page(s) of {} to the printer", pages, doc.title);
+    Ok(PrintResult {
+        pdf_path: dest.to_string_lossy().to_string(),
+        pages,
+    })
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil, NO};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::path::Path;
+    use tauri::AppHandle;
+
+    // kPDFPrintPageScaleNone: the PDF is already laid out for the paper
+    const SCALE_NONE: i64 = 0;
+
+    #[link(name = "PDFKit", kind = "framework")]
+    extern "C" {}
+
+    // AppKit printing has to run on the main thread
+    pub fn show_dialog(app: &AppHandle, pdf: &Path, title: &str) -> Result<(), String> {
+        let path = pdf.to_string_lossy().to_string();
+        let title = title.to_string();
+        app.run_on_main_thread(move || unsafe {
+            let url: id =
+                msg_send![class!(NSURL), fileURLWithPath: NSString::alloc(nil).init_str(&path)];
+            let document: id = msg_send![class!(PDFDocument), alloc];
+            let document: id = msg_send![document, initWithURL: url];
+            if document == nil {
+                tracing::warn!("Failed to open print document {}", path);
+                return;
+            }
+            let info: id = msg_send![class!(NSPrintInfo), sharedPrintInfo];
+            let operation: id = msg_send![document,
+                printOperationForPrintInfo: info
+                scalingMode: SCALE_NONE
+                autoRotate: NO];
+            let _: () = msg_send![operation, setJobTitle: NSString::alloc(nil).init_str(&title)];
+            let _: bool = msg_send![operation, runOperation];
+            let _: () = msg_send![document, release];
+        })
+        .map_err(|e| format!("Failed to open print dialog: {}", e))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::path::Path;
+    use std::process::Command;
+    use tauri::AppHandle;
+
+    // Hands the PDF to the default PDF handler's print verb, which shows its dialog
+    pub fn show_dialog(_app: &AppHandle, pdf: &Path, _title: &str) -> Result<(), String> {
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command"])
+            .arg(format!(
+                "Start-Process -FilePath '{}' -Verb Print",
+                pdf.to_string_lossy().replace('\'', "''")
+            ))
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to open print dialog: {}", e))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use std::path::Path;
+    use std::process::Command;
+    use tauri::AppHandle;
+
+    // There's no desktop-neutral print dialog, so this goes straight to CUPS
+    // and the default printer
+    pub fn show_dialog(_app: &AppHandle, pdf: &Path, title: &str) -> Result<(), String> {
+        let status = Command::new("lp")
+            .args(["-t", title])
+            .arg(pdf)
+            .status()
+            .map_err(|e| format!("Failed to run lp: {}", e))?;
+        if !status.success() {
+            return Err(format!("lp exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn options(scaling: Scaling, orientation: Orientation) -> PrintOptions {
+        PrintOptions {
+            scaling,
+            orientation,
+            ..PrintOptions::default()
+        }
+    }
+
+    #[test]
+    fn wide_pages_print_in_landscape_when_auto() {
+        let placement = place(2000, 1000, &options(Scaling::Fit, Orientation::Auto));
+        assert_eq!(
+            (placement.page_width, placement.page_height),
+            (792.0, 612.0)
+        );
+        let placement = place(2000, 1000, &options(Scaling::Fit, Orientation::Portrait));
+        assert_eq!(
+            (placement.page_width, placement.page_height),
+            (612.0, 792.0)
+        );
+    }
+
+    #[test]
+    fn fit_shows_the_whole_page_and_fill_covers_the_paper() {
+        let mut fit = options(Scaling::Fit, Orientation::Portrait);
+        fit.margin_mm = 25.4;
+        let placement = place(1000, 1000, &fit);
+        assert_eq!((placement.width, placement.height), (468.0, 468.0));
+        assert_eq!((placement.x, placement.y), (72.0, 162.0));
+
+        let placement = place(1000, 1000, &options(Scaling::Fill, Orientation::Portrait));
+        assert_eq!((placement.width, placement.height), (792.0, 792.0));
+        assert_eq!(placement.x, -90.0);
+    }
+
+    #[test]
+    fn actual_size_uses_the_dpi() {
+        let mut actual = options(Scaling::Actual, Orientation::Portrait);
+        let placement = place(600, 300, &actual);
+        assert_eq!((placement.width, placement.height), (144.0, 72.0));
+        actual.dpi = Some(72.0);
+        assert_eq!(place(600, 300, &actual).width, 600.0);
+    }
+
+    #[test]
+    fn each_page_becomes_a_pdf_page() {
+        let dir = std::env::temp_dir().join(format!("squish-print-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("page.png");
+        RgbaImage::new(40, 20).save(&page).unwrap();
+        let doc = PrintDocument {
+            title: "Proof".to_string(),
+            pages: vec![page.to_string_lossy().to_string(); 2],
+        };
+        let dest = dir.join("proof.pdf");
+        let options = PrintOptions::default();
+        build_pdf(&doc, &options, &dest, &CancelToken::default()).unwrap();
+
+        let pdf = Document::load(&dest).unwrap();
+        assert_eq!(pdf.get_pages().len(), 2);
+        let empty = PrintDocument {
+            pages: Vec::new(),
+            ..doc
+        };
+        assert!(build_pdf(&empty, &options, &dest, &CancelToken::default()).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::watch::WatchState;
+
+// The library that predates profiles stays where it always was
+pub const DEFAULT_PROFILE_ID: &str = "default";
+const REGISTRY_FILE: &str = "profiles.json";
+const PROFILES_DIR: &str = "profiles";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+}
+
+// Lives beside the databases rather than in one, since it says which one to open
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Registry {
+    active: Option<String>,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<Profile>,
+}
+
+fn config_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))
+}
+
+fn load_registry(config_dir: &Path) -> Registry {
+    std::fs::read_to_string(config_dir.join(REGISTRY_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_registry(config_dir: &Path, registry: &Registry) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(registry)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    std::fs::write(config_dir.join(REGISTRY_FILE), json)
+        .map_err(|e| format!("Failed to save profiles: {}", e))
+}
+
+// Directory holding a profile's database and anything else kept per profile
+pub fn profile_dir(config_dir: &Path, id: &str) -> PathBuf {
+    if id == DEFAULT_PROFILE_ID {
+        config_dir.to_path_buf()
+    } else {
+        config_dir.join(PROFILES_DIR).join(id)
+    }
+}
+
+pub fn active_profile_dir(config_dir: &Path) -> PathBuf {
+    let registry = load_registry(config_dir);
+    match registry.active {
+        Some(id) if registry.profiles.iter().any(|p| p.id == id) => profile_dir(config_dir, &id),
+        _ => config_dir.to_path_buf(),
+    }
+}
+
+// Id of the profile whose database lives at `path`, for keys stored per profile
+pub fn profile_id_for(db_path: &Path) -> String {
+    let dir = db_path.parent();
+    let in_profiles = dir
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .is_some_and(|name| name == PROFILES_DIR);
+    match dir.and_then(Path::file_name) {
+        Some(id) if in_profiles => id.to_string_lossy().to_string(),
+        _ => DEFAULT_PROFILE_ID.to_string(),
+    }
+}
+
+fn all_profiles(registry: &Registry) -> Vec<Profile> {
+    let mut profiles = vec![Profile {
+        id: DEFAULT_PROFILE_ID.to_string(),
+        name: "Default".to_string(),
+        created_at: 0,
+    }];
+    profiles.extend(registry.profiles.iter().cloned());
+    profiles
+}
+
+#[tauri::command]
+pub fn list_profiles(app: AppHandle) -> Result<ProfileList, Error> {
+    let config_dir = config_dir(&app)?;
+    let registry = load_registry(&config_dir);
+    let profiles = all_profiles(&registry);
+    let active = registry
+        .active
+        .filter(|id| profiles.iter().any(|p| &p.id == id))
+        .unwrap_or_else(|| DEFAULT_PROFILE_ID.to_string());
+    Ok(ProfileList { active, profiles })
+}
+
+#[tauri::command]
+pub fn create_profile(app: AppHandle, name: String) -> Result<Profile, Error> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(Error::InvalidInput(
+            "Profile name cannot be empty".to_string(),
+        ));
+    }
+    let config_dir = config_dir(&app)?;
+    let mut registry = load_registry(&config_dir);
+    if all_profiles(&registry)
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(Error::InvalidInput(format!(
+            "A profile named {} already exists",
+            name
+        )));
+    }
+
+    let profile = Profile {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+    std::fs::create_dir_all(profile_dir(&config_dir, &profile.id))
+        .map_err(|e| format!("Failed to create profile folder: {}", e))?;
+    registry.profiles.push(profile.clone());
+    save_registry(&config_dir, &registry)?;
+    tracing::info!("Created profile {} ({})", profile.name, profile.id);
+    Ok(profile)
+}
+
+// Swaps every subsystem over to another profile's library. Watchers and
+// settings are restarted from the new database, and the frontend reconnects
+// on db://reopened.
+#[tauri::command]
+pub fn switch_profile(
+    app: AppHandle,
+    db: State<Db>,
+    watchers: State<WatchState>,
+    id: String,
+) -> Result<(), Error> {
+    let config_dir = config_dir(&app)?;
+    let mut registry = load_registry(&config_dir);
+    if !all_profiles(&registry).iter().any(|p| p.id == id) {
+        return Err(Error::NotFound(format!("Profile {} not found", id)));
+    }
+
+    let dir = profile_dir(&config_dir, &id);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create profile folder: {}", e))?;
+    let conn = crate::db::open_at(&dir.join(crate::db::DB_FILE))?;
+
+    // Otherwise the deferred start could bring back the old profile's folders
+    crate::startup::watchers(&app);
+    crate::watch::stop_all(&watchers)?;
+    {
+        let mut current = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        *current = conn;
+        crate::settings::reload(&app, &current)?;
+    }
+    registry.active = (id != DEFAULT_PROFILE_ID).then(|| id.clone());
+    save_registry(&config_dir, &registry)?;
+    crate::watch::start_all(&app)?;
+
+    tracing::info!("Switched to profile {}", id);
+    if let Err(e) = app.emit("db://reopened", ()) {
+        tracing::warn!("Failed to emit reopen event: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-profiles-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn profile(id: &str) -> Profile {
+        Profile {
+            id: id.to_string(),
+            name: id.to_string(),
+            created_at: 1,
+        }
+    }
+
+    #[test]
+    fn the_default_profile_lives_in_the_config_dir() {
+        let config = Path::new("/config");
+        assert_eq!(profile_dir(config, DEFAULT_PROFILE_ID), config);
+        assert_eq!(
+            profile_dir(config, "work"),
+            Path::new("/config/profiles/work")
+        );
+    }
+
+    #[test]
+    fn the_profile_id_comes_back_from_the_db_path() {
+        let config = Path::new("/config");
+        for id in [DEFAULT_PROFILE_ID, "work"] {
+            let db = profile_dir(config, id).join("squish.db");
+            assert_eq!(profile_id_for(&db), id);
+        }
+        assert_eq!(profile_id_for(Path::new("squish.db")), DEFAULT_PROFILE_ID);
+    }
+
+    #[test]
+    fn an_unknown_active_profile_falls_back_to_the_default() {
+        let config = config_dir("active");
+        assert_eq!(active_profile_dir(&config), config);
+
+        let mut registry = Registry {
+            active: Some("work".to_string()),
+            profiles: vec![profile("work")],
+        };
+        save_registry(&config, &registry).unwrap();
+        assert_eq!(active_profile_dir(&config), profile_dir(&config, "work"));
+
+        registry.profiles.clear();
+        save_registry(&config, &registry).unwrap();
+        assert_eq!(active_profile_dir(&config), config);
+        assert_eq!(all_profiles(&registry)[0].id, DEFAULT_PROFILE_ID);
+    }
+}
@@ -0,0 +1,256 @@
+// Validation for filesystem paths that arrive over IPC. Commands turn their
+// path arguments into a SafePath up front instead of each checking strings
+// their own way:
+//  - the path must be absolute and free of `..` components
+//  - symlinks are resolved before anything is checked, so a link can't point
+//    a permitted location somewhere else
+//  - the resolved location has to be inside the fs scope (the directories in
+//    the capabilities plus whatever the user picked in a dialog, dropped on
+//    the window or opened Squish with) or one of the app's own directories
+use std::path::{Component, Path, PathBuf};
+use tauri::{AppHandle, DragDropEvent, Manager, WindowEvent};
+use tauri_plugin_fs::FsExt;
+
+use crate::error::Error;
+use crate::imaging::BatchEntry;
+use crate::settings::SettingsState;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    // Must already exist
+    Read,
+    // May be created; its nearest existing ancestor is what gets resolved
+    Write,
+}
+
+#[derive(Debug, Clone)]
+pub struct SafePath(PathBuf);
+
+impl SafePath {
+    pub fn new(app: &AppHandle, raw: &str, access: Access) -> Result<Self, Error> {
+        crate::startup::scopes(app);
+        let path = parse(raw)?;
+        let resolved = match access {
+            Access::Read => path.canonicalize()?,
+            Access::Write => resolve_for_write(path)?,
+        };
+        if !permitted(app, &resolved) {
+            tracing::warn!("Rejected path outside the allowed scope: {}", raw);
+            return Err(Error::PermissionDenied(format!(
+                "Squish doesn't have access to {}",
+                raw
+            )));
+        }
+        Ok(SafePath(resolved))
+    }
+
+    pub fn read(app: &AppHandle, raw: &str) -> Result<Self, Error> {
+        Self::new(app, raw, Access::Read)
+    }
+
+    pub fn write(app: &AppHandle, raw: &str) -> Result<Self, Error> {
+        Self::new(app, raw, Access::Write)
+    }
+
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn into_path_buf(self) -> PathBuf {
+        self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+
+// For the optional destination most options structs carry
+pub fn write_opt(app: &AppHandle, path: Option<String>) -> Result<Option<String>, Error> {
+    path.map(|p| SafePath::write(app, &p).map(SafePath::into_string))
+        .transpose()
+}
+
+// Validates every path of a batch, failing on the first bad one
+pub fn read_all(app: &AppHandle, paths: Vec<String>) -> Result<Vec<String>, Error> {
+    paths
+        .iter()
+        .map(|p| SafePath::read(app, p).map(SafePath::into_string))
+        .collect()
+}
+
+// The checks that need nothing but the string itself
+fn parse(raw: &str) -> Result<&Path, Error> {
+    let path = Path::new(raw);
+    if raw.is_empty() || raw.contains('\0') {
+        return Err(Error::InvalidInput(format!("Invalid path: {:?}", raw)));
+    }
+    if !path.is_absolute() {
+        return Err(Error::InvalidInput(format!(
+            "Path must be absolute: {}",
+            raw
+        )));
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err(Error::InvalidInput(format!(
+            "Path must not contain '..': {}",
+            raw
+        )));
+    }
+    Ok(path)
+}
+
+// Resolves the part that exists and re-appends the rest, which can't contain
+// `..` at this point
+fn resolve_for_write(path: &Path) -> Result<PathBuf, Error> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            return Err(Error::InvalidInput(format!(
+                "Invalid path: {}",
+                path.display()
+            )));
+        };
+        rest.push(name);
+        existing = parent;
+    }
+    let mut resolved = existing.canonicalize()?;
+    resolved.extend(rest.into_iter().rev());
+    Ok(resolved)
+}
+
+// Squish's own scratch space; the rest of the temp folder is shared with
+// every other app, so it isn't permitted as a whole
+pub fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join("squish")
+}
+
+fn app_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    let resolver = app.path();
+    [
+        resolver.app_cache_dir(),
+        resolver.app_data_dir(),
+        resolver.app_config_dir(),
+        resolver.app_log_dir(),
+    ]
+    .into_iter()
+    .filter_map(Result::ok)
+    .chain(std::iter::once(temp_dir()))
+    // Compared against resolved paths, so resolve these too (/var is a
+    // symlink to /private/var on macOS)
+    .map(|dir| dir.canonicalize().unwrap_or(dir))
+    .collect()
+}
+
+fn permitted(app: &AppHandle, resolved: &Path) -> bool {
+    app.fs_scope().is_allowed(resolved) || app_dirs(app).iter().any(|dir| resolved.starts_with(dir))
+}
+
+// Results Squish wrote stay readable for later commands (compare, drag out)
+// even when they landed beside an input that was granted on its own
+pub fn allow_outputs(app: &AppHandle, entries: &[BatchEntry]) {
+    for result in entries.iter().filter_map(|e| e.result.as_ref()) {
+        allow(app, Path::new(&result.output_path));
+    }
+}
+
+pub fn allow_all(app: &AppHandle, paths: &[String]) {
+    for path in paths {
+        allow(app, Path::new(path));
+    }
+}
+
+// Grants access to paths the OS handed over directly (Open With, Services,
+// drag and drop), which never went through a dialog
+pub fn allow(app: &AppHandle, path: &Path) {
+    let scope = app.fs_scope();
+    let result = if path.is_dir() {
+        scope.allow_directory(path, true)
+    } else {
+        scope.allow_file(path)
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to allow {}: {}", path.display(), e);
+    }
+}
+
+// Re-grants folders chosen in settings on earlier runs (the scope itself
+// doesn't persist) and files dropped on the window
+pub fn start(app: &AppHandle) {
+    let settings = app.state::<SettingsState>().snapshot();
+    for dir in [settings.default_output_dir, settings.default_import_dir]
+        .into_iter()
+        .flatten()
+    {
+        allow(app, Path::new(&dir));
+    }
+
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let handle = app.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) = event {
+            for path in paths {
+                allow(&handle, path);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh folder under the system temp directory, resolved
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-safe-path-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        let root = if cfg!(windows) { "C:\\" } else { "/" };
+        for raw in [
+            String::new(),
+            format!("{}a\0b.png", root),
+            "photos/a.png".to_string(),
+            format!(
+                "{}photos{}..{}secret",
+                root,
+                std::path::MAIN_SEPARATOR,
+                std::path::MAIN_SEPARATOR
+            ),
+        ] {
+            assert!(
+                matches!(parse(&raw), Err(Error::InvalidInput(_))),
+                "{:?} was accepted",
+                raw
+            );
+        }
+        assert!(parse(&format!("{}photos{}a.png", root, std::path::MAIN_SEPARATOR)).is_ok());
+    }
+
+    #[test]
+    fn missing_parts_of_a_write_path_are_kept() {
+        let dir = scratch("write");
+        let resolved = resolve_for_write(&dir.join("new").join("out.png")).unwrap();
+        assert_eq!(resolved, dir.join("new").join("out.png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_paths_through_a_link_resolve_to_its_target() {
+        let dir = scratch("link");
+        std::fs::create_dir(dir.join("target")).unwrap();
+        std::os::unix::fs::symlink(dir.join("target"), dir.join("link")).unwrap();
+        let resolved = resolve_for_write(&dir.join("link").join("out.png")).unwrap();
+        assert_eq!(resolved, dir.join("target").join("out.png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
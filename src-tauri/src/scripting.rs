@@ -0,0 +1,265 @@
+// AppleScript commands declared in Squish.sdef, which Shortcuts can also run
+// through its "Run AppleScript" action. Cocoa Scripting creates these
+// NSScriptCommand subclasses by name. Compression goes through the same batch
+// job as the UI, with the command suspended so the main thread stays free
+// while it runs.
+use cocoa::base::{id, nil};
+use cocoa::foundation::{NSArray, NSAutoreleasePool, NSString};
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Sel, BOOL, NO};
+use objc::{class, msg_send, sel, sel_impl};
+use rusqlite::{params, OptionalExtension};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+
+use crate::db::Db;
+use crate::deep_link::{self, DeepLinkRequest};
+use crate::imaging::{compress_paths, CompressOptions};
+use crate::safe_path;
+
+// Command callbacks are plain C functions, so they reach the app through here
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+// errOSAGeneralError, shown to the script as the error number
+const SCRIPT_ERROR: i64 = -2700;
+
+unsafe fn ns_string(s: &str) -> id {
+    NSString::alloc(nil).init_str(s).autorelease()
+}
+
+unsafe fn rust_string(string: id) -> Option<String> {
+    if string == nil {
+        return None;
+    }
+    let is_string: BOOL = msg_send![string, isKindOfClass: class!(NSString)];
+    if is_string == NO {
+        return None;
+    }
+    let utf8: *const c_char = msg_send![string, UTF8String];
+    Some(CStr::from_ptr(utf8).to_string_lossy().to_string())
+}
+
+unsafe fn url_path(url: id) -> Option<String> {
+    if url == nil {
+        return None;
+    }
+    rust_string(msg_send![url, path])
+}
+
+// A single file arrives as the URL itself rather than a one-item list
+unsafe fn file_paths(direct: id) -> Vec<String> {
+    if direct == nil {
+        return Vec::new();
+    }
+    let is_list: BOOL = msg_send![direct, isKindOfClass: class!(NSArray)];
+    if is_list == NO {
+        return url_path(direct).into_iter().collect();
+    }
+    (0..direct.count())
+        .filter_map(|i| url_path(direct.objectAtIndex(i)))
+        .collect()
+}
+
+unsafe fn argument(command: id, key: &str) -> id {
+    let args: id = msg_send![command, evaluatedArguments];
+    msg_send![args, objectForKey: ns_string(key)]
+}
+
+unsafe fn fail(command: id, message: &str) -> id {
+    tracing::warn!("AppleScript command failed: {}", message);
+    let _: () = msg_send![command, setScriptErrorNumber: SCRIPT_ERROR];
+    let _: () = msg_send![command, setScriptErrorString: ns_string(message)];
+    nil
+}
+
+fn compress(
+    app: &AppHandle,
+    paths: Vec<String>,
+    preset: Option<String>,
+    output_dir: Option<String>,
+) -> Result<Vec<String>, String> {
+    let mut options: CompressOptions = match preset {
+        Some(name) => {
+            let db = app.state::<Db>();
+            let conn =
+                db.0.lock()
+                    .map_err(|e| format!("Failed to lock database: {}", e))?;
+            crate::presets::find_by_name(&conn, &name)?
+                .ok_or_else(|| format!("No preset named {}", name))?
+                .options
+        }
+        // Same default as squish-cli
+        None => serde_json::from_value(serde_json::json!({ "format": "webp", "quality": 80 }))
+            .map_err(|e| format!("Invalid compression options: {}", e))?,
+    };
+    if output_dir.is_some() {
+        options.output_dir = output_dir;
+    }
+    // The script named these files, not a dialog
+    safe_path::allow_all(app, &paths);
+    if let Some(dir) = &options.output_dir {
+        safe_path::allow(app, Path::new(dir));
+    }
+
+    let entries = compress_paths(app, paths, options, None)?;
+    // Scripts can't easily inspect partial results, so one failure fails the
+    // command, naming the file
+    if let Some(failed) = entries.iter().find(|e| e.error.is_some()) {
+        return Err(format!(
+            "{}: {}",
+            failed.input_path,
+            failed.error.clone().unwrap_or_default()
+        ));
+    }
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| e.result.map(|r| r.output_path))
+        .collect())
+}
+
+unsafe fn resume(command: id, result: Result<Vec<String>, String>) {
+    let value = match result {
+        Ok(paths) => {
+            let strings: Vec<id> = paths.iter().map(|p| ns_string(p)).collect();
+            NSArray::arrayWithObjects(nil, &strings)
+        }
+        Err(e) => fail(command, &e),
+    };
+    let _: () = msg_send![command, resumeExecutionWithResult: value];
+    let _: () = msg_send![command, release];
+}
+
+// compress {file "..."} with preset "Web" into folder "..."
+extern "C" fn perform_compress(this: &Object, _cmd: Sel) -> id {
+    let command = this as *const Object as id;
+    let Some(app) = APP.get() else {
+        return nil;
+    };
+    unsafe {
+        let paths = file_paths(msg_send![command, directParameter]);
+        if paths.is_empty() {
+            return fail(command, "No files to compress");
+        }
+        let preset = rust_string(argument(command, "preset"));
+        let output_dir = url_path(argument(command, "outputFolder"));
+        tracing::info!("AppleScript: compress {} file(s)", paths.len());
+
+        let _: () = msg_send![command, suspendExecution];
+        let _: () = msg_send![command, retain];
+        let command = command as usize;
+        let app = app.clone();
+        std::thread::spawn(move || {
+            let result = compress(&app, paths, preset, output_dir);
+            let resumed = app.run_on_main_thread(move || resume(command as id, result));
+            if let Err(e) = resumed {
+                tracing::warn!("Failed to finish AppleScript command: {}", e);
+            }
+        });
+    }
+    nil
+}
+
+fn export_format(code: u32) -> Option<&'static str> {
+    match &code.to_be_bytes() {
+        b"EfPD" => Some("pdf"),
+        b"EfPN" => Some("png"),
+        b"EfJP" => Some("jpeg"),
+        _ => None,
+    }
+}
+
+fn project_id(app: &AppHandle, name: &str) -> Result<Option<String>, String> {
+    let db = app.state::<Db>();
+    let conn =
+        db.0.lock()
+            .map_err(|e| format!("Failed to lock database: {}", e))?;
+    // Names aren't unique; the most recently edited project wins
+    conn.query_row(
+        "SELECT id FROM projects WHERE name = ?1 ORDER BY updated_at DESC LIMIT 1",
+        params![name],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to find project: {}", e))
+}
+
+// export project "Poster" as PDF to file "..."; pages are rendered by the UI,
+// so this hands the request over the same way a squish:// link does
+extern "C" fn perform_export(this: &Object, _cmd: Sel) -> id {
+    let command = this as *const Object as id;
+    let Some(app) = APP.get() else {
+        return nil;
+    };
+    unsafe {
+        let Some(name) = rust_string(msg_send![command, directParameter]) else {
+            return fail(command, "Missing project name");
+        };
+        let Some(path) = url_path(argument(command, "destination")) else {
+            return fail(command, "Missing export destination");
+        };
+        let format = argument(command, "format");
+        let format = if format == nil {
+            "pdf"
+        } else {
+            let code: u32 = msg_send![format, unsignedIntValue];
+            match export_format(code) {
+                Some(format) => format,
+                None => return fail(command, "Unknown export format"),
+            }
+        };
+        let project_id = match project_id(app, &name) {
+            Ok(Some(id)) => id,
+            Ok(None) => return fail(command, &format!("No project named {}", name)),
+            Err(e) => return fail(command, &e),
+        };
+        tracing::info!("AppleScript: export {} as {}", name, format);
+        safe_path::allow(app, Path::new(&path));
+        deep_link::dispatch(
+            app,
+            DeepLinkRequest::Export {
+                project_id,
+                format: format.to_string(),
+                path,
+            },
+        );
+    }
+    nil
+}
+
+fn register(name: &str, perform: extern "C" fn(&Object, Sel) -> id) {
+    let Some(mut decl) = ClassDecl::new(name, class!(NSScriptCommand)) else {
+        tracing::warn!("Failed to declare {}", name);
+        return;
+    };
+    unsafe {
+        decl.add_method(sel!(performDefaultImplementation), perform);
+    }
+    decl.register();
+}
+
+// The classes only need to exist before the first Apple Event arrives, when
+// Cocoa Scripting loads the sdef and looks them up by name
+pub fn start(app: &AppHandle) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+    register("SquishCompressCommand", perform_compress);
+    register("SquishExportCommand", perform_export);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sdef_format_codes_map_to_exports() {
+        let code = |four: &[u8; 4]| u32::from_be_bytes(*four);
+        assert_eq!(export_format(code(b"EfPD")), Some("pdf"));
+        assert_eq!(export_format(code(b"EfPN")), Some("png"));
+        assert_eq!(export_format(code(b"EfJP")), Some("jpeg"));
+        assert_eq!(export_format(code(b"EfGF")), None);
+    }
+}
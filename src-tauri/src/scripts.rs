@@ -0,0 +1,438 @@
+// Rhai scripts for batch automation (AppleScript lives in scripting.rs), e.g.
+//
+//   for path in files("/Users/me/Exports") {
+//       if extension(path) == "png" && file_size(path) > MB {
+//           let result = compress(path, "webp", 80);
+//           upload(result.outputPath);
+//       }
+//   }
+//
+// Scripts run from the UI (run_script) or the CLI (squish-cli --script). They
+// only reach the outside world through the functions registered below: the
+// engine has no file or network access of its own, paths go through SafePath
+// when running in the app, and uploads need the same consent as sync.
+//
+// Functions: files(dir), walk(dir), is_image(path), extension(path),
+// file_name(path), file_size(path), preset(name), compress(path, options),
+// compress(path, format, quality), enqueue(paths, options), upload(path),
+// upload(path, name). print() and debug() lines are the script's output.
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::blocking;
+use crate::cancel::{self, CancelToken};
+use crate::connectivity::ConnectivityState;
+use crate::db::Db;
+use crate::error::Error;
+use crate::hooks::{self, Export};
+use crate::imaging::{compress_file, compress_one, CompressOptions, CompressResult, SourceFormat};
+use crate::job_history::record_compress;
+use crate::jobs::{self, JobSpec, Priority};
+use crate::permissions::{self, Capability};
+use crate::safe_path::SafePath;
+use crate::settings::{self, SettingsState};
+
+pub const OUTPUT_EVENT: &str = "script://output";
+// Files a single walk() may return
+const MAX_WALK: usize = 100_000;
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptOutput {
+    pub run_id: String,
+    pub line: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptResult {
+    pub output: Vec<String>,
+    // The value of the script's last expression
+    pub value: Value,
+    pub ms: u64,
+}
+
+// What a script runs against: the app, or from the CLI just the library of a
+// profile (if there is one)
+#[derive(Clone)]
+pub enum Host {
+    App(AppHandle),
+    Cli(Option<Arc<Db>>),
+}
+
+type ScriptError = Box<EvalAltResult>;
+
+fn fail(e: impl ToString) -> ScriptError {
+    e.to_string().into()
+}
+
+impl Host {
+    fn library(&self) -> Option<&Db> {
+        match self {
+            Host::App(app) => Some(app.state::<Db>().inner()),
+            Host::Cli(library) => library.as_deref(),
+        }
+    }
+
+    fn path(&self, raw: &str) -> Result<PathBuf, String> {
+        match self {
+            Host::App(app) => Ok(SafePath::read(app, raw)?.into_path_buf()),
+            Host::Cli(_) => Ok(PathBuf::from(raw)),
+        }
+    }
+
+    fn output(&self, run_id: &str, line: String) {
+        match self {
+            Host::App(app) => {
+                let output = ScriptOutput {
+                    run_id: run_id.to_string(),
+                    line,
+                };
+                if let Err(e) = app.emit(OUTPUT_EVENT, output) {
+                    tracing::warn!("Failed to emit script output: {}", e);
+                }
+            }
+            Host::Cli(_) => println!("{}", line),
+        }
+    }
+
+    fn compress(
+        &self,
+        path: &str,
+        options: &CompressOptions,
+        cancel: &CancelToken,
+    ) -> Result<CompressResult, String> {
+        let path = self.path(path)?.to_string_lossy().to_string();
+        match self {
+            Host::App(app) => Ok(compress_one(
+                app,
+                "script",
+                path,
+                options.clone(),
+                None,
+                cancel,
+            )?),
+            Host::Cli(library) => {
+                let started = Instant::now();
+                let outcome = compress_file(path.clone(), options, cancel);
+                if let Some(db) = library {
+                    let job = record_compress(db, "script", &path, &outcome, started, options);
+                    if let Some(export) = Export::new(&job) {
+                        hooks::run_all(db, &export);
+                    }
+                }
+                outcome
+            }
+        }
+    }
+
+    fn preset(&self, name: &str) -> Result<CompressOptions, String> {
+        let db = self.library().ok_or("No library to read presets from")?;
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        crate::presets::find_by_name(&conn, name)?
+            .map(|preset| preset.options)
+            .ok_or_else(|| format!("No preset named {}", name))
+    }
+
+    fn enqueue(&self, paths: Vec<String>, options: CompressOptions) -> Result<String, String> {
+        let Host::App(app) = self else {
+            return Err("Jobs can only be queued while the app is running".to_string());
+        };
+        let spec = JobSpec::Compress {
+            paths,
+            options,
+            project_id: None,
+        }
+        .validate(app)?;
+        jobs::enqueue(app, spec, Priority::Normal)
+    }
+
+    fn upload(&self, path: &str, name: &str) -> Result<String, String> {
+        let path = self.path(path)?;
+        let db = self.library().ok_or("Sync is not configured")?;
+        let (config, timeout) = {
+            let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+            let config = crate::sync::load_config(&conn)?.ok_or("Sync is not configured")?;
+            let timeout = match self {
+                Host::App(app) => crate::net::timeout(&app.state::<SettingsState>().snapshot()),
+                Host::Cli(_) => crate::net::timeout(&settings::load(&conn)?),
+            };
+            (config, timeout)
+        };
+        let client = match self {
+            Host::App(app) => {
+                if !app.state::<ConnectivityState>().is_online() {
+                    return Err(Error::Offline.to_string());
+                }
+                permissions::check(app, None, Capability::NetworkUpload, "script upload")?;
+                crate::net::client(app)
+            }
+            Host::Cli(_) => reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .map_err(|e| format!("Failed to create HTTP client: {}", e))?,
+        };
+        tauri::async_runtime::block_on(crate::sync::upload(&config, client, timeout, &path, name))
+    }
+}
+
+fn options_from(map: Map) -> Result<CompressOptions, ScriptError> {
+    from_dynamic(&Dynamic::from_map(map))
+        .map_err(|e| fail(format!("Invalid compression options: {}", e)))
+}
+
+fn list(dir: &Path, recursive: bool, files: &mut Vec<String>) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Links to folders aren't followed, so a cycle can't recurse forever
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if recursive {
+                list(&path, true, files)?;
+            }
+        } else if file_type.is_symlink() && path.is_dir() {
+            continue;
+        } else if files.len() >= MAX_WALK {
+            return Err(format!(
+                "More than {} files under {}",
+                MAX_WALK,
+                dir.display()
+            ));
+        } else {
+            files.push(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(())
+}
+
+fn register(engine: &mut Engine, host: &Host, cancel: &CancelToken) {
+    let h = host.clone();
+    engine.register_fn("files", move |dir: &str| -> Result<Array, ScriptError> {
+        let mut files = Vec::new();
+        list(&h.path(dir).map_err(fail)?, false, &mut files).map_err(fail)?;
+        files.sort();
+        Ok(files.into_iter().map(Dynamic::from).collect())
+    });
+    let h = host.clone();
+    engine.register_fn("walk", move |dir: &str| -> Result<Array, ScriptError> {
+        let mut files = Vec::new();
+        list(&h.path(dir).map_err(fail)?, true, &mut files).map_err(fail)?;
+        files.sort();
+        Ok(files.into_iter().map(Dynamic::from).collect())
+    });
+    engine.register_fn("is_image", |path: &str| {
+        SourceFormat::is_supported(Path::new(path))
+    });
+    engine.register_fn("extension", |path: &str| {
+        Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    });
+    engine.register_fn("file_name", |path: &str| {
+        Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let h = host.clone();
+    engine.register_fn("file_size", move |path: &str| -> Result<i64, ScriptError> {
+        std::fs::metadata(h.path(path).map_err(fail)?)
+            .map(|m| m.len() as i64)
+            .map_err(|e| fail(format!("Failed to read {}: {}", path, e)))
+    });
+
+    let h = host.clone();
+    engine.register_fn(
+        "preset",
+        move |name: &str| -> Result<Dynamic, ScriptError> {
+            to_dynamic(h.preset(name).map_err(fail)?)
+        },
+    );
+
+    let (h, token) = (host.clone(), cancel.clone());
+    engine.register_fn(
+        "compress",
+        move |path: &str, options: Map| -> Result<Dynamic, ScriptError> {
+            let options = options_from(options)?;
+            to_dynamic(h.compress(path, &options, &token).map_err(fail)?)
+        },
+    );
+    let (h, token) = (host.clone(), cancel.clone());
+    engine.register_fn(
+        "compress",
+        move |path: &str, format: &str, quality: i64| -> Result<Dynamic, ScriptError> {
+            let mut options = Map::new();
+            options.insert("format".into(), Dynamic::from(format.to_string()));
+            options.insert("quality".into(), Dynamic::from(quality.clamp(1, 100)));
+            let options = options_from(options)?;
+            to_dynamic(h.compress(path, &options, &token).map_err(fail)?)
+        },
+    );
+
+    let h = host.clone();
+    engine.register_fn(
+        "enqueue",
+        move |paths: Array, options: Map| -> Result<String, ScriptError> {
+            let paths = paths.into_iter().map(|p| p.to_string()).collect();
+            h.enqueue(paths, options_from(options)?).map_err(fail)
+        },
+    );
+
+    let h = host.clone();
+    engine.register_fn("upload", move |path: &str| -> Result<String, ScriptError> {
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        h.upload(path, &name).map_err(fail)
+    });
+    let h = host.clone();
+    engine.register_fn(
+        "upload",
+        move |path: &str, name: &str| -> Result<String, ScriptError> {
+            h.upload(path, name).map_err(fail)
+        },
+    );
+}
+
+pub fn run(host: Host, source: &str, cancel: &CancelToken) -> Result<ScriptResult, String> {
+    let started = Instant::now();
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let output = Arc::new(Mutex::new(Vec::new()));
+
+    let mut engine = Engine::new();
+    register(&mut engine, &host, cancel);
+    let (h, lines, id) = (host.clone(), output.clone(), run_id.clone());
+    engine.on_print(move |line| {
+        h.output(&id, line.to_string());
+        if let Ok(mut lines) = lines.lock() {
+            lines.push(line.to_string());
+        }
+    });
+    let (h, lines, id) = (host.clone(), output.clone(), run_id.clone());
+    engine.on_debug(move |line, _, position| {
+        let line = format!("[{}] {}", position, line);
+        h.output(&id, line.clone());
+        if let Ok(mut lines) = lines.lock() {
+            lines.push(line);
+        }
+    });
+    let token = cancel.clone();
+    engine.on_progress(move |_| token.is_cancelled().then(|| Dynamic::from("Cancelled")));
+
+    let mut scope = Scope::new();
+    scope.push_constant("KB", 1024_i64);
+    scope.push_constant("MB", 1024_i64 * 1024);
+
+    let value = engine
+        .eval_with_scope::<Dynamic>(&mut scope, source)
+        .map_err(|e| match *e {
+            EvalAltResult::ErrorTerminated(..) => Error::Cancelled.to_string(),
+            e => format!("Script failed: {}", e),
+        })?;
+    let value = from_dynamic::<Value>(&value).unwrap_or(Value::Null);
+    let elapsed = started.elapsed();
+    tracing::info!("Script {} finished in {:?}", run_id, elapsed);
+
+    let output = output
+        .lock()
+        .map(|lines| lines.clone())
+        .map_err(|e| format!("Failed to read script output: {}", e))?;
+    Ok(ScriptResult {
+        output,
+        value,
+        ms: elapsed.as_millis() as u64,
+    })
+}
+
+// Output also streams on script://output while the script runs
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn run_script(
+    app: AppHandle,
+    source: String,
+    operation_id: Option<String>,
+) -> Result<ScriptResult, Error> {
+    Ok(blocking::run(move || {
+        let operation = cancel::register(&app, operation_id);
+        let _awake = crate::power::keep_awake("Running a script");
+        run(Host::App(app.clone()), &source, operation.token())
+    })
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("squish-scripts-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.png"), b"a").unwrap();
+        std::fs::write(dir.join("nested/b.JPG"), b"bb").unwrap();
+        dir
+    }
+
+    fn eval(source: &str) -> Result<ScriptResult, String> {
+        run(Host::Cli(None), source, &CancelToken::default())
+    }
+
+    #[test]
+    fn print_lines_and_the_last_value_are_returned() {
+        let result = eval(r#"print("hello"); 40 + 2"#).unwrap();
+        assert_eq!(result.output, vec!["hello"]);
+        assert_eq!(result.value, serde_json::json!(42));
+    }
+
+    #[test]
+    fn files_lists_one_level_and_walk_recurses() {
+        let dir = folder("walk");
+        let source = format!(
+            r#"let dir = "{}"; [files(dir).len(), walk(dir).len(), extension(walk(dir)[1]), file_size(walk(dir)[1])]"#,
+            dir.display()
+        );
+        let result = eval(&source).unwrap();
+        assert_eq!(result.value, serde_json::json!([1, 2, "jpg", 2]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_to_folders_are_not_followed() {
+        let dir = folder("links");
+        std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+        let mut files = Vec::new();
+        list(&dir, true, &mut files).unwrap();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|f| !f.contains("loop")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_cancelled_script_stops() {
+        let token = CancelToken::default();
+        token.cancel();
+        let error = run(Host::Cli(None), "loop {}", &token).err().unwrap();
+        assert_eq!(error, Error::Cancelled.to_string());
+    }
+
+    #[test]
+    fn script_errors_are_reported() {
+        let error = eval("undefined_function()").err().unwrap();
+        assert!(error.starts_with("Script failed"), "{}", error);
+    }
+}
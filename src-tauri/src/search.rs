@@ -0,0 +1,172 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+const MAX_RESULTS: u32 = 50;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    // Matched text with hits wrapped in <mark></mark>
+    pub snippet: String,
+    // bm25 score, lower is better
+    pub rank: f64,
+}
+
+// Replaces the search row for a project with its current name, tags and text
+pub fn index_project(
+    conn: &Connection,
+    project_id: &str,
+    name: &str,
+    tags: &[String],
+) -> Result<(), String> {
+    let content: String = conn
+        .query_row(
+            "SELECT COALESCE(group_concat(content, ' '), '') FROM layers
+             WHERE project_id = ?1 AND type = 'text'",
+            params![project_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to read project text: {}", e))?;
+
+    conn.execute(
+        "DELETE FROM library_fts WHERE kind = 'project' AND ref_id = ?1",
+        params![project_id],
+    )
+    .map_err(|e| format!("Failed to update search index: {}", e))?;
+    conn.execute(
+        "INSERT INTO library_fts (kind, ref_id, name, tags, content)
+         VALUES ('project', ?1, ?2, ?3, ?4)",
+        params![project_id, name, tags.join(" "), content],
+    )
+    .map_err(|e| format!("Failed to update search index: {}", e))?;
+    Ok(())
+}
+
+// Turns free text into an FTS5 query: every word must match, each as a prefix.
+// Quoting each term keeps user input from being parsed as FTS syntax.
+fn to_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[tauri::command]
+pub fn search_library(db: State<Db>, query: String) -> Result<Vec<SearchHit>, Error> {
+    let Some(match_query) = to_match_query(&query) else {
+        return Ok(Vec::new());
+    };
+
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(search(&conn, &match_query)?)
+}
+
+fn search(conn: &Connection, match_query: &str) -> Result<Vec<SearchHit>, String> {
+    // Name matches outrank tags, which outrank body text
+    let mut stmt = conn
+        .prepare(
+            "SELECT kind, ref_id, name,
+                    snippet(library_fts, -1, '<mark>', '</mark>', '…', 12),
+                    bm25(library_fts, 0.0, 0.0, 10.0, 5.0, 1.0) AS score
+             FROM library_fts
+             WHERE library_fts MATCH ?1
+             ORDER BY score
+             LIMIT ?2",
+        )
+        .map_err(|e| format!("Failed to prepare search: {}", e))?;
+    let hits = stmt
+        .query_map(params![match_query, MAX_RESULTS], |row| {
+            Ok(SearchHit {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+                name: row.get(2)?,
+                snippet: row.get(3)?,
+                rank: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to search library: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to search library: {}", e))?;
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Summer poster')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO layers (id, project_id, type, content, style, transform)
+             VALUES ('l1', 'p1', 'text', 'Beach party on Saturday', '{}', '{}')",
+            [],
+        )
+        .unwrap();
+        index_project(&conn, "p1", "Summer poster", &["events".to_string()]).unwrap();
+        conn
+    }
+
+    fn ids(conn: &Connection, query: &str) -> Vec<String> {
+        search(conn, &to_match_query(query).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect()
+    }
+
+    #[test]
+    fn every_word_is_a_quoted_prefix() {
+        assert_eq!(to_match_query("  "), None);
+        assert_eq!(
+            to_match_query("sum \"post"),
+            Some("\"sum\"* \"\"\"post\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn matches_names_tags_and_text_by_prefix() {
+        let conn = library();
+        assert_eq!(ids(&conn, "summ"), ["p1"]);
+        assert_eq!(ids(&conn, "event"), ["p1"]);
+        assert_eq!(ids(&conn, "beach sat"), ["p1"]);
+        assert!(ids(&conn, "beach winter").is_empty());
+    }
+
+    #[test]
+    fn fts_syntax_in_the_query_is_just_text() {
+        let conn = library();
+        for query in ["NEAR(summer", "poster OR", "\"", "*", "a:b"] {
+            assert!(
+                search(&conn, &to_match_query(query).unwrap()).is_ok(),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn reindexing_replaces_the_row() {
+        let conn = library();
+        index_project(&conn, "p1", "Winter poster", &[]).unwrap();
+        assert!(ids(&conn, "summer").is_empty());
+        assert_eq!(ids(&conn, "winter"), ["p1"]);
+    }
+}
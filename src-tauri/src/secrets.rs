@@ -0,0 +1,76 @@
+// Secrets live in the OS credential store (Keychain, Credential Manager,
+// Secret Service), never in SQLite. The UI gets its own namespace of keys, so
+// it can keep tokens of its own but can't read the library key or the
+// credentials the backend manages.
+use crate::encryption::keychain_entry;
+use crate::error::Error;
+
+const UI_PREFIX: &str = "ui:";
+const MAX_KEY_LEN: usize = 64;
+
+pub fn get(account: &str) -> Result<Option<String>, String> {
+    match keychain_entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read {} from keychain: {}", account, e)),
+    }
+}
+
+pub fn set(account: &str, value: &str) -> Result<(), String> {
+    keychain_entry(account)?
+        .set_password(value)
+        .map_err(|e| format!("Failed to save {} to keychain: {}", account, e))
+}
+
+pub fn delete(account: &str) -> Result<(), String> {
+    match keychain_entry(account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove {} from keychain: {}", account, e)),
+    }
+}
+
+fn ui_account(key: &str) -> Result<String, String> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("Invalid secret key: {}", key));
+    }
+    Ok(format!("{}{}", UI_PREFIX, key))
+}
+
+#[tauri::command]
+pub fn store_secret(key: String, value: String) -> Result<(), Error> {
+    Ok(set(&ui_account(&key)?, &value)?)
+}
+
+#[tauri::command]
+pub fn get_secret(key: String) -> Result<Option<String>, Error> {
+    Ok(get(&ui_account(&key)?)?)
+}
+
+#[tauri::command]
+pub fn delete_secret(key: String) -> Result<(), Error> {
+    Ok(delete(&ui_account(&key)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_keys_are_namespaced() {
+        assert_eq!(ui_account("github.token").unwrap(), "ui:github.token");
+        assert_eq!(ui_account("a-b_c").unwrap(), "ui:a-b_c");
+    }
+
+    #[test]
+    fn ui_keys_cannot_reach_backend_accounts() {
+        for key in ["", "library-key:work", "../library-key", "a b", "é"] {
+            assert!(ui_account(key).is_err(), "{:?} was accepted", key);
+        }
+        assert!(ui_account(&"k".repeat(MAX_KEY_LEN + 1)).is_err());
+    }
+}
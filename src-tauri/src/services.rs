@@ -0,0 +1,84 @@
+// "Compress with Squish" in the macOS Services menu. The entry itself is
+// declared under NSServices in Info.plist; this registers the object AppKit
+// calls when it's picked and forwards the files like a squish://open link.
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSArray;
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::deep_link::{self, DeepLinkRequest};
+
+// The service callback is a plain C function, so it reaches the app through here
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    fn NSUpdateDynamicServices();
+}
+
+unsafe fn file_paths(pboard: id) -> Vec<String> {
+    let classes = NSArray::arrayWithObject(nil, class!(NSURL) as *const Class as id);
+    let urls: id = msg_send![pboard, readObjectsForClasses: classes options: nil];
+    if urls == nil {
+        return Vec::new();
+    }
+    (0..urls.count())
+        .filter_map(|i| {
+            let path: id = msg_send![urls.objectAtIndex(i), path];
+            if path == nil {
+                return None;
+            }
+            let utf8: *const c_char = msg_send![path, UTF8String];
+            CStr::from_ptr(utf8).to_str().ok().map(String::from)
+        })
+        .collect()
+}
+
+extern "C" fn compress_with_squish(
+    _this: &Object,
+    _cmd: Sel,
+    pboard: id,
+    _user_data: id,
+    // NSString **, which objc can't encode as a method argument type
+    _error: *mut c_void,
+) {
+    let Some(app) = APP.get() else {
+        return;
+    };
+    let paths = unsafe { file_paths(pboard) };
+    if paths.is_empty() {
+        tracing::debug!("Ignoring service request without files");
+        return;
+    }
+    tracing::info!("Services menu: {} file(s)", paths.len());
+    crate::safe_path::allow_all(app, &paths);
+    deep_link::dispatch(app, DeepLinkRequest::Open { paths });
+}
+
+// Must run on the main thread, which setup() does on macOS
+pub fn start(app: &AppHandle) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+    let Some(mut decl) = ClassDecl::new("SquishServiceProvider", class!(NSObject)) else {
+        tracing::warn!("Failed to declare the Services provider class");
+        return;
+    };
+    unsafe {
+        decl.add_method(
+            sel!(compressWithSquish:userData:error:),
+            compress_with_squish as extern "C" fn(&Object, Sel, id, id, *mut c_void),
+        );
+        let provider_class = decl.register();
+        let provider: id = msg_send![provider_class, new];
+        let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+        let _: () = msg_send![ns_app, setServicesProvider: provider];
+        // Picks up the Info.plist entry without a logout in dev builds
+        NSUpdateDynamicServices();
+    }
+}
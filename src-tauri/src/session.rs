@@ -0,0 +1,418 @@
+// Session restoration. The open documents and the main window frame are kept
+// in SQLite so that after a reboot or forced quit Squish comes back as it was.
+// On macOS the app also opts into secure state restoration, which is what
+// makes the system relaunch it after a restart with "Reopen windows" checked.
+//
+// Each document's workspace (panels, zoom, scroll position, tool) is kept too,
+// so reopening a document puts the user back where they left it.
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Manager, State, WebviewWindow, WindowEvent};
+
+use crate::db::{get_preference, set_preference, Db};
+use crate::error::Error;
+
+const CLEAN_EXIT_KEY: &str = "session_clean_exit";
+const MIN_WIDTH: u32 = 400;
+const MIN_HEIGHT: u32 = 300;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDocument {
+    pub project_id: String,
+    pub active: bool,
+}
+
+// Logical pixels, so a frame saved on one display scale restores on another
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowFrame {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub documents: Vec<SessionDocument>,
+    pub window: Option<WindowFrame>,
+    // True after a crash, forced quit or reboot, or when the system is set to
+    // reopen windows; the UI restores the documents only then
+    pub should_restore: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceState {
+    // Panel id to whether it's shown; panels not listed keep their default
+    pub panels: BTreeMap<String, bool>,
+    pub zoom: f64,
+    // Canvas pixels at the top left of the viewport
+    pub scroll_x: f64,
+    pub scroll_y: f64,
+    pub tool: Option<String>,
+}
+
+// Whether the previous run quit normally, read once at startup
+pub struct SessionState(pub AtomicBool);
+
+pub fn read_window(conn: &Connection, label: &str) -> Result<Option<WindowFrame>, String> {
+    let frame = conn.query_row(
+        "SELECT x, y, width, height, maximized, fullscreen FROM session_windows WHERE label = ?1",
+        params![label],
+        |row| {
+            Ok(WindowFrame {
+                x: row.get(0)?,
+                y: row.get(1)?,
+                width: row.get(2)?,
+                height: row.get(3)?,
+                maximized: row.get(4)?,
+                fullscreen: row.get(5)?,
+            })
+        },
+    );
+    match frame {
+        Ok(frame) if frame.width >= MIN_WIDTH && frame.height >= MIN_HEIGHT => Ok(Some(frame)),
+        Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(format!("Failed to read window state: {}", e)),
+    }
+}
+
+fn write_window(conn: &Connection, label: &str, frame: &WindowFrame) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO session_windows (label, x, y, width, height, maximized, fullscreen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(label) DO UPDATE SET x = excluded.x, y = excluded.y,
+             width = excluded.width, height = excluded.height,
+             maximized = excluded.maximized, fullscreen = excluded.fullscreen,
+             updated_at = CURRENT_TIMESTAMP",
+        params![
+            label,
+            frame.x,
+            frame.y,
+            frame.width,
+            frame.height,
+            frame.maximized,
+            frame.fullscreen
+        ],
+    )
+    .map_err(|e| format!("Failed to save window state: {}", e))?;
+    Ok(())
+}
+
+// Used when building the main window, before the frontend is up
+pub fn saved_window(app: &AppHandle, label: &str) -> Option<WindowFrame> {
+    let db = app.state::<Db>();
+    let conn = db.0.lock().ok()?;
+    read_window(&conn, label).unwrap_or_else(|e| {
+        tracing::warn!("{}", e);
+        None
+    })
+}
+
+fn frame_of(window: &WebviewWindow) -> Option<WindowFrame> {
+    let scale = window.scale_factor().ok()?;
+    let maximized = window.is_maximized().unwrap_or(false);
+    let fullscreen = window.is_fullscreen().unwrap_or(false);
+    let position = window.outer_position().ok()?.to_logical::<i32>(scale);
+    let size = window.inner_size().ok()?.to_logical::<u32>(scale);
+    Some(WindowFrame {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized,
+        fullscreen,
+    })
+}
+
+fn save_window(app: &AppHandle, window: &WebviewWindow) {
+    let Some(mut frame) = frame_of(window) else {
+        return;
+    };
+    let db = app.state::<Db>();
+    let Ok(conn) = db.0.lock() else {
+        return;
+    };
+    // Keep the restored (un-maximized) frame so un-maximizing after a
+    // relaunch goes back to the right size
+    if frame.maximized || frame.fullscreen {
+        if let Ok(Some(previous)) = read_window(&conn, window.label()) {
+            frame = WindowFrame {
+                maximized: frame.maximized,
+                fullscreen: frame.fullscreen,
+                ..previous
+            };
+        }
+    }
+    if let Err(e) = write_window(&conn, window.label(), &frame) {
+        tracing::warn!("{}", e);
+    }
+}
+
+fn set_clean_exit(app: &AppHandle, clean: bool) {
+    let db = app.state::<Db>();
+    let Ok(conn) = db.0.lock() else {
+        return;
+    };
+    if let Err(e) = set_preference(&conn, CLEAN_EXIT_KEY, &clean.to_string()) {
+        tracing::warn!("{}", e);
+    }
+}
+
+pub fn start(app: &AppHandle) {
+    let previous_clean = {
+        let db = app.state::<Db>();
+        db.0.lock()
+            .ok()
+            .and_then(|conn| get_preference(&conn, CLEAN_EXIT_KEY).ok().flatten())
+            .map(|v| v == "true")
+            .unwrap_or(true)
+    };
+    app.manage(SessionState(AtomicBool::new(previous_clean)));
+    set_clean_exit(app, false);
+
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    remember_frame(app, &window);
+
+    #[cfg(target_os = "macos")]
+    platform::enable_restoration(&window);
+}
+
+// Saved whenever the window loses focus or is closed rather than on every
+// move and resize event; read back with saved_window under the same label
+pub fn remember_frame(app: &AppHandle, window: &WebviewWindow) {
+    let handle = app.clone();
+    let target = window.clone();
+    window.on_window_event(move |event| {
+        if matches!(
+            event,
+            WindowEvent::Focused(false) | WindowEvent::CloseRequested { .. }
+        ) {
+            save_window(&handle, &target);
+        }
+    });
+}
+
+pub fn save_on_exit(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        save_window(app, &window);
+    }
+    set_clean_exit(app, true);
+}
+
+// Replaces the open-document list; called by the UI whenever a document is
+// opened, closed, reordered or focused
+#[tauri::command]
+pub fn save_open_documents(db: State<Db>, documents: Vec<SessionDocument>) -> Result<(), Error> {
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to save session: {}", e))?;
+    tx.execute("DELETE FROM session_documents", [])
+        .map_err(|e| format!("Failed to save session: {}", e))?;
+    for (position, document) in documents.iter().enumerate() {
+        tx.execute(
+            "INSERT OR IGNORE INTO session_documents (project_id, position, active)
+             SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM projects WHERE id = ?1)",
+            params![document.project_id, position as i64, document.active],
+        )
+        .map_err(|e| format!("Failed to save session: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to save session: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_session(db: State<Db>, state: State<SessionState>) -> Result<Session, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT project_id, active FROM session_documents ORDER BY position")
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    let documents = stmt
+        .query_map([], |row| {
+            Ok(SessionDocument {
+                project_id: row.get(0)?,
+                active: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read session: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+
+    let previous_clean = state.0.load(Ordering::Relaxed);
+    Ok(Session {
+        documents,
+        window: read_window(&conn, "main")?,
+        should_restore: !previous_clean || platform::keeps_windows_on_quit(),
+    })
+}
+
+// Called by the UI when a document is switched away from or closed
+#[tauri::command]
+pub fn save_workspace_state(
+    db: State<Db>,
+    project_id: String,
+    state: WorkspaceState,
+) -> Result<(), Error> {
+    if !state.zoom.is_finite() || state.zoom <= 0.0 {
+        return Err(Error::InvalidInput(format!("Invalid zoom {}", state.zoom)));
+    }
+    if !state.scroll_x.is_finite() || !state.scroll_y.is_finite() {
+        return Err(Error::InvalidInput("Invalid scroll position".to_string()));
+    }
+    let panels = serde_json::to_string(&state.panels)
+        .map_err(|e| format!("Failed to serialize panels: {}", e))?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO workspace_state (project_id, panels, zoom, scroll_x, scroll_y, tool)
+         SELECT ?1, ?2, ?3, ?4, ?5, ?6 WHERE EXISTS (SELECT 1 FROM projects WHERE id = ?1)
+         ON CONFLICT(project_id) DO UPDATE SET panels = excluded.panels,
+             zoom = excluded.zoom, scroll_x = excluded.scroll_x,
+             scroll_y = excluded.scroll_y, tool = excluded.tool,
+             updated_at = CURRENT_TIMESTAMP",
+        params![
+            project_id,
+            panels,
+            state.zoom,
+            state.scroll_x,
+            state.scroll_y,
+            state.tool
+        ],
+    )
+    .map_err(|e| format!("Failed to save workspace state: {}", e))?;
+    Ok(())
+}
+
+// None for a document that has never been closed, which opens with defaults
+#[tauri::command]
+pub fn get_workspace_state(
+    db: State<Db>,
+    project_id: String,
+) -> Result<Option<WorkspaceState>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(conn.query_row(
+        "SELECT panels, zoom, scroll_x, scroll_y, tool FROM workspace_state WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            let panels: String = row.get(0)?;
+            Ok(WorkspaceState {
+                panels: serde_json::from_str(&panels).unwrap_or_default(),
+                zoom: row.get(1)?,
+                scroll_x: row.get(2)?,
+                scroll_y: row.get(3)?,
+                tool: row.get(4)?,
+            })
+        },
+    )
+    .optional()?)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil, YES};
+    use cocoa::foundation::NSString;
+    use objc::runtime::{class_addMethod, object_getClass, Class, Imp, Object, Sel, BOOL};
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::WebviewWindow;
+
+    extern "C" fn supports_secure_restorable_state(_this: &Object, _cmd: Sel, _app: id) -> BOOL {
+        YES
+    }
+
+    // The app delegate belongs to the windowing library, so the method is
+    // added to its class at runtime. Without it macOS 14+ logs a warning and
+    // treats the restorable state as insecure.
+    pub fn enable_restoration(window: &WebviewWindow) {
+        unsafe {
+            let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+            let delegate: id = msg_send![ns_app, delegate];
+            if delegate != nil {
+                let imp: Imp = std::mem::transmute(
+                    supports_secure_restorable_state as extern "C" fn(&Object, Sel, id) -> BOOL,
+                );
+                class_addMethod(
+                    object_getClass(delegate as *const Object) as *mut Class,
+                    sel!(applicationSupportsSecureRestorableState:),
+                    imp,
+                    c"c@:@".as_ptr(),
+                );
+            }
+            if let Ok(ns_window) = window.ns_window() {
+                let _: () = msg_send![ns_window as id, setRestorable: YES];
+            }
+        }
+    }
+
+    // System Settings > Desktop & Dock > "Close windows when quitting an
+    // application" unchecked
+    pub fn keeps_windows_on_quit() -> bool {
+        unsafe {
+            let defaults: id = msg_send![class!(NSUserDefaults), standardUserDefaults];
+            let key = NSString::alloc(nil).init_str("NSQuitAlwaysKeepsWindows");
+            let keeps: BOOL = msg_send![defaults, boolForKey: key];
+            let _: () = msg_send![key, release];
+            keeps == YES
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    pub fn keeps_windows_on_quit() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn
+    }
+
+    fn frame(width: u32, height: u32) -> WindowFrame {
+        WindowFrame {
+            x: -40,
+            y: 25,
+            width,
+            height,
+            maximized: true,
+            fullscreen: false,
+        }
+    }
+
+    #[test]
+    fn window_frames_round_trip() {
+        let conn = library();
+        assert!(read_window(&conn, "main").unwrap().is_none());
+        write_window(&conn, "main", &frame(1200, 800)).unwrap();
+        write_window(&conn, "main", &frame(1280, 720)).unwrap();
+        let saved = read_window(&conn, "main").unwrap().unwrap();
+        assert_eq!((saved.x, saved.y), (-40, 25));
+        assert_eq!((saved.width, saved.height), (1280, 720));
+        assert!(saved.maximized && !saved.fullscreen);
+        assert!(read_window(&conn, "settings").unwrap().is_none());
+    }
+
+    #[test]
+    fn frames_too_small_to_use_are_ignored() {
+        let conn = library();
+        write_window(&conn, "main", &frame(MIN_WIDTH - 1, 800)).unwrap();
+        assert!(read_window(&conn, "main").unwrap().is_none());
+        write_window(&conn, "main", &frame(MIN_WIDTH, MIN_HEIGHT)).unwrap();
+        assert!(read_window(&conn, "main").unwrap().is_some());
+    }
+}
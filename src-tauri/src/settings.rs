@@ -0,0 +1,297 @@
+use rusqlite::Connection;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{get_preference, set_preference, Db};
+use crate::error::Error;
+
+pub const SETTINGS_CHANGED_EVENT: &str = "settings://changed";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+// Settings the backend acts on. Each field is stored under its own key in the
+// preferences table; keys the backend doesn't know about are kept for the frontend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // None means the default (physical cores minus one)
+    pub worker_concurrency: Option<usize>,
+    pub theme: Theme,
+    pub default_output_dir: Option<String>,
+    pub default_import_dir: Option<String>,
+    // Action name to accelerator, e.g. "export" -> "CmdOrCtrl+E"
+    pub shortcuts: BTreeMap<String, String>,
+    // Off until the user opts in; see usage.rs for what is counted
+    pub usage_stats_enabled: bool,
+    // OS notifications for jobs that finish while the window is unfocused
+    pub notifications_enabled: bool,
+    pub update_channel: UpdateChannel,
+    // Support can ask for debug to be switched on without a rebuild
+    pub log_level: LogLevel,
+    // Fewer workers and no slow encoder passes on battery or in Low Power Mode
+    pub battery_saver: bool,
+    // Overrides the system proxy for every network feature, e.g. "http://proxy:8080"
+    pub proxy_url: Option<String>,
+    // PEM bundle trusted in addition to the built-in roots, for TLS-inspecting proxies
+    pub ca_bundle_path: Option<String>,
+    pub http_timeout_secs: u64,
+    // BCP 47 tag for backend-rendered strings; None follows the system
+    pub locale: Option<String>,
+    // Localhost HTTP API for build tools and extensions; see automation.rs
+    pub automation_api: bool,
+    pub automation_api_port: u16,
+    // Offer to compress images copied in other apps; see clipboard_monitor.rs
+    pub clipboard_monitor: bool,
+    // Ids of installed plugins the user turned on; see plugins.rs
+    pub enabled_plugins: Vec<String>,
+    // Broken fonts the user chose to stop seeing; see fonts.rs
+    pub hidden_problem_fonts: Vec<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            worker_concurrency: None,
+            theme: Theme::default(),
+            default_output_dir: None,
+            default_import_dir: None,
+            shortcuts: BTreeMap::new(),
+            usage_stats_enabled: false,
+            notifications_enabled: true,
+            update_channel: UpdateChannel::default(),
+            log_level: LogLevel::default(),
+            battery_saver: true,
+            proxy_url: None,
+            ca_bundle_path: None,
+            http_timeout_secs: 30,
+            locale: None,
+            automation_api: false,
+            automation_api_port: 7841,
+            clipboard_monitor: false,
+            enabled_plugins: Vec::new(),
+            hidden_problem_fonts: Vec::new(),
+        }
+    }
+}
+
+pub struct SettingsState(pub RwLock<AppSettings>);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SettingChange {
+    pub key: String,
+    pub value: Value,
+}
+
+impl SettingsState {
+    pub fn snapshot(&self) -> AppSettings {
+        self.0.read().map(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+pub fn load(conn: &Connection) -> Result<AppSettings, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM preferences")
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+
+    let mut map = Map::new();
+    for row in rows {
+        let (key, value) = row.map_err(|e| format!("Failed to read setting: {}", e))?;
+        if let Ok(value) = serde_json::from_str(&value) {
+            map.insert(key, value);
+        }
+    }
+
+    // A bad value for one key shouldn't lose every other setting, so keys are
+    // applied one at a time and any that don't fit keep their default
+    let Value::Object(mut merged) = serde_json::to_value(AppSettings::default())
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?
+    else {
+        return Ok(AppSettings::default());
+    };
+    for (key, value) in map {
+        if !merged.contains_key(&key) {
+            continue;
+        }
+        let previous = merged.insert(key.clone(), value);
+        if let Err(e) = serde_json::from_value::<AppSettings>(Value::Object(merged.clone())) {
+            tracing::warn!("Ignoring invalid value for setting {}: {}", key, e);
+            if let Some(previous) = previous {
+                merged.insert(key, previous);
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(merged))
+        .map_err(|e| format!("Failed to read settings: {}", e))
+}
+
+pub fn read<T: DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, String> {
+    match get_preference(conn, key)? {
+        Some(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| format!("Invalid value for setting {}: {}", key, e)),
+        None => Ok(None),
+    }
+}
+
+// Validates the value against AppSettings, persists it, and notifies listeners
+// on both sides of the bridge through settings://changed
+pub fn write<T: Serialize>(app: &AppHandle, key: &str, value: &T) -> Result<(), String> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| format!("Failed to serialize setting {}: {}", key, e))?;
+
+    let state = app.state::<SettingsState>();
+    let mut updated = serde_json::to_value(state.snapshot())
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    if let Value::Object(map) = &mut updated {
+        if map.contains_key(key) {
+            map.insert(key.to_string(), value.clone());
+        }
+    }
+    let updated: AppSettings = serde_json::from_value(updated)
+        .map_err(|e| format!("Invalid value for setting {}: {}", key, e))?;
+
+    {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        set_preference(&conn, key, &value.to_string())?;
+    }
+    *state.0.write().map_err(|_| Error::Lock("settings"))? = updated;
+
+    let change = SettingChange {
+        key: key.to_string(),
+        value,
+    };
+    if let Err(e) = app.emit(SETTINGS_CHANGED_EVENT, change) {
+        tracing::warn!("Failed to emit settings change: {}", e);
+    }
+    Ok(())
+}
+
+// Re-reads every setting after the database was replaced underneath us,
+// announcing each key so subsystems pick up the restored values
+pub fn reload(app: &AppHandle, conn: &Connection) -> Result<(), String> {
+    let loaded = load(conn)?;
+    let values = serde_json::to_value(&loaded)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    *app.state::<SettingsState>()
+        .0
+        .write()
+        .map_err(|_| Error::Lock("settings"))? = loaded;
+
+    if let Value::Object(map) = values {
+        for (key, value) in map {
+            if let Err(e) = app.emit(SETTINGS_CHANGED_EVENT, SettingChange { key, value }) {
+                tracing::warn!("Failed to emit settings change: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_setting(db: State<Db>, key: String) -> Result<Option<Value>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(read(&conn, &key)?)
+}
+
+#[tauri::command]
+pub fn set_setting(app: AppHandle, key: String, value: Value) -> Result<(), Error> {
+    Ok(write(&app, &key, &value)?)
+}
+
+#[tauri::command]
+pub fn get_app_settings(settings: State<SettingsState>) -> AppSettings {
+    settings.snapshot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preferences(values: &[(&str, &str)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        for (key, value) in values {
+            set_preference(&conn, key, value).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn missing_keys_use_defaults() {
+        let settings = load(&preferences(&[])).unwrap();
+        assert_eq!(settings.theme, Theme::System);
+        assert_eq!(settings.worker_concurrency, None);
+        assert_eq!(settings.default_output_dir, None);
+    }
+
+    #[test]
+    fn stored_values_override_defaults() {
+        let conn = preferences(&[
+            ("theme", "\"dark\""),
+            ("worker_concurrency", "3"),
+            ("default_import_dir", "\"/photos\""),
+        ]);
+        let settings = load(&conn).unwrap();
+        assert_eq!(settings.theme, Theme::Dark);
+        assert_eq!(settings.worker_concurrency, Some(3));
+        assert_eq!(settings.default_import_dir.as_deref(), Some("/photos"));
+    }
+
+    #[test]
+    fn a_bad_value_only_resets_its_own_key() {
+        let conn = preferences(&[
+            ("theme", "\"sepia\""),
+            ("worker_concurrency", "\"many\""),
+            ("default_output_dir", "\"/exports\""),
+            ("frontend_only", "not json"),
+        ]);
+        let settings = load(&conn).unwrap();
+        assert_eq!(settings.theme, Theme::System);
+        assert_eq!(settings.worker_concurrency, None);
+        assert_eq!(settings.default_output_dir.as_deref(), Some("/exports"));
+    }
+
+    #[test]
+    fn read_returns_typed_values() {
+        let conn = preferences(&[("theme", "\"light\""), ("worker_concurrency", "nope")]);
+        assert_eq!(read::<Theme>(&conn, "theme").unwrap(), Some(Theme::Light));
+        assert_eq!(read::<bool>(&conn, "missing").unwrap(), None);
+        assert!(read::<usize>(&conn, "worker_concurrency").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{set_preference, Db};
+use crate::error::Error;
+use crate::imaging::CompressOptions;
+use crate::presets::Preset;
+use crate::safe_path::SafePath;
+use crate::watch::WatchState;
+
+const FORMAT: &str = "squish-settings";
+const VERSION: u32 = 1;
+
+// Preferences that describe this machine or this library rather than the
+// user's configuration, so they never travel with an export
+const MACHINE_KEYS: &[&str] = &["sync_device_id", "db_maintenance_last_run"];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsFile {
+    pub format: String,
+    pub version: u32,
+    // Includes shortcuts, which live in AppSettings
+    pub preferences: Map<String, Value>,
+    pub presets: Vec<Preset>,
+    pub watch_folders: Vec<ExportedWatchFolder>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedWatchFolder {
+    pub path: String,
+    pub destination: String,
+    pub options: CompressOptions,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub preferences: u32,
+    pub presets: u32,
+    pub watch_folders: u32,
+    // Watch folders that don't exist on this machine or that Squish hasn't
+    // been given access to; the UI asks the user to pick them
+    pub skipped: Vec<String>,
+}
+
+#[tauri::command]
+pub fn export_settings(app: AppHandle, db: State<Db>, path: String) -> Result<(), Error> {
+    let path = SafePath::write(&app, &path)?.into_string();
+    let mut preferences = Map::new();
+    {
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM preferences ORDER BY key")
+            .map_err(|e| format!("Failed to read preferences: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| format!("Failed to read preferences: {}", e))?;
+        for row in rows {
+            let (key, value) = row.map_err(|e| format!("Failed to read preference: {}", e))?;
+            if MACHINE_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            if let Ok(value) = serde_json::from_str(&value) {
+                preferences.insert(key, value);
+            }
+        }
+    }
+
+    let file = SettingsFile {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        preferences,
+        presets: crate::presets::list_presets(app.state::<Db>())?,
+        watch_folders: export_watch_folders(&app)?,
+    };
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!("Exported settings to {}", path);
+    Ok(())
+}
+
+// Merges an export into this library: preferences and presets are
+// overwritten by key/id, watch folders are added unless already watched
+#[tauri::command]
+pub fn import_settings(app: AppHandle, path: String) -> Result<ImportSummary, Error> {
+    let path = SafePath::read(&app, &path)?.into_string();
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: SettingsFile = serde_json::from_str(&json)
+        .map_err(|_| format!("{} is not a Squish settings file", path))?;
+    if file.format != FORMAT {
+        return Err(Error::InvalidInput(format!(
+            "{} is not a Squish settings file",
+            path
+        )));
+    }
+    if file.version > VERSION {
+        return Err(Error::InvalidInput(
+            "Settings file was made by a newer version of Squish".to_string(),
+        ));
+    }
+
+    let mut summary = ImportSummary {
+        preferences: 0,
+        presets: 0,
+        watch_folders: 0,
+        skipped: Vec::new(),
+    };
+
+    {
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        for (key, value) in &file.preferences {
+            if MACHINE_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            set_preference(&conn, key, &value.to_string())?;
+            summary.preferences += 1;
+        }
+        crate::settings::reload(&app, &conn)?;
+    }
+
+    for preset in file.presets {
+        crate::presets::save_preset(
+            app.state::<Db>(),
+            Some(preset.id),
+            preset.name,
+            preset.options,
+        )?;
+        summary.presets += 1;
+    }
+
+    let (added, skipped) = import_watch_folders(&app, file.watch_folders)?;
+    summary.watch_folders = added;
+    summary.skipped = skipped;
+
+    tracing::info!("Imported settings from {}", path);
+    Ok(summary)
+}
+
+pub fn export_watch_folders(app: &AppHandle) -> Result<Vec<ExportedWatchFolder>, String> {
+    Ok(crate::watch::list_watch_folders(app.state::<Db>())?
+        .into_iter()
+        .map(|f| ExportedWatchFolder {
+            path: f.path,
+            destination: f.destination,
+            options: f.options,
+            enabled: f.enabled,
+        })
+        .collect())
+}
+
+// Adds folders that aren't watched yet; returns how many were added and the
+// paths skipped because they don't exist here or aren't granted. The file only
+// names the folders, so picking it isn't consent to watch or write into them.
+pub fn import_watch_folders(
+    app: &AppHandle,
+    folders: Vec<ExportedWatchFolder>,
+) -> Result<(u32, Vec<String>), String> {
+    let mut added = 0;
+    let mut skipped = Vec::new();
+    let existing: Vec<String> = crate::watch::list_watch_folders(app.state::<Db>())?
+        .into_iter()
+        .map(|f| f.path)
+        .collect();
+    for folder in folders {
+        if existing.contains(&folder.path) {
+            continue;
+        }
+        let (Ok(path), Ok(destination)) = (
+            SafePath::read(app, &folder.path),
+            SafePath::write(app, &folder.destination),
+        ) else {
+            skipped.push(folder.path);
+            continue;
+        };
+        if !path.as_path().is_dir() {
+            skipped.push(folder.path);
+            continue;
+        }
+        let watched = crate::watch::add_watch_folder(
+            app.clone(),
+            app.state::<Db>(),
+            app.state::<WatchState>(),
+            path.into_string(),
+            destination.into_string(),
+            folder.options,
+        )?;
+        if !folder.enabled {
+            crate::watch::set_watch_folder_enabled(
+                app.clone(),
+                app.state::<Db>(),
+                app.state::<WatchState>(),
+                watched.id,
+                false,
+            )?;
+        }
+        added += 1;
+    }
+
+    Ok((added, skipped))
+}
@@ -0,0 +1,104 @@
+// Library projects in macOS system search. Projects live in the database rather
+// than as files, so they're donated to Core Spotlight as searchable items
+// instead of going through a file importer. Other platforms are a no-op.
+use crate::library::LibraryEntry;
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSArray, NSAutoreleasePool, NSString};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    use crate::library::LibraryEntry;
+
+    const DOMAIN: &str = "com.squish.dev.projects";
+
+    #[link(name = "CoreSpotlight", kind = "framework")]
+    extern "C" {}
+
+    unsafe fn ns_string(s: &str) -> id {
+        NSString::alloc(nil).init_str(s).autorelease()
+    }
+
+    unsafe fn ns_strings(values: &[String]) -> id {
+        let strings: Vec<id> = values.iter().map(|v| ns_string(v)).collect();
+        NSArray::arrayWithObjects(nil, &strings)
+    }
+
+    fn description(entry: &LibraryEntry) -> String {
+        let mut parts: Vec<String> = entry
+            .page_sizes
+            .iter()
+            .map(|[w, h]| format!("{}×{}", w, h))
+            .collect();
+        parts.push(format!("{} assets", entry.asset_count));
+        if !entry.fonts.is_empty() {
+            parts.push(entry.fonts.join(", "));
+        }
+        parts.join(" · ")
+    }
+
+    pub fn index(entry: &LibraryEntry, tags: &[String]) {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+
+            let attrs: id = msg_send![class!(CSSearchableItemAttributeSet), alloc];
+            let attrs: id = msg_send![attrs, initWithItemContentType: ns_string("public.image")];
+            let _: () = msg_send![attrs, setTitle: ns_string(&entry.title)];
+            let _: () = msg_send![attrs, setContentDescription: ns_string(&description(entry))];
+            let _: () = msg_send![attrs, setKeywords: ns_strings(tags)];
+            if let Some([width, height]) = entry.page_sizes.first() {
+                let width: id = msg_send![class!(NSNumber), numberWithUnsignedInt: *width];
+                let height: id = msg_send![class!(NSNumber), numberWithUnsignedInt: *height];
+                let _: () = msg_send![attrs, setPixelWidth: width];
+                let _: () = msg_send![attrs, setPixelHeight: height];
+            }
+            if let Some(thumbnail) = &entry.thumbnail_path {
+                let url: id = msg_send![class!(NSURL), fileURLWithPath: ns_string(thumbnail)];
+                let _: () = msg_send![attrs, setThumbnailURL: url];
+            }
+
+            let item: id = msg_send![class!(CSSearchableItem), alloc];
+            let item: id = msg_send![item,
+                initWithUniqueIdentifier: ns_string(&entry.project_id)
+                domainIdentifier: ns_string(DOMAIN)
+                attributeSet: attrs];
+            let index: id = msg_send![class!(CSSearchableIndex), defaultSearchableIndex];
+            let _: () = msg_send![index,
+                indexSearchableItems: NSArray::arrayWithObject(nil, item)
+                completionHandler: nil];
+
+            let _: () = msg_send![item, release];
+            let _: () = msg_send![attrs, release];
+            pool.drain();
+        }
+    }
+
+    pub fn remove(project_ids: &[String]) {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            let index: id = msg_send![class!(CSSearchableIndex), defaultSearchableIndex];
+            let _: () = msg_send![index,
+                deleteSearchableItemsWithIdentifiers: ns_strings(project_ids)
+                completionHandler: nil];
+            pool.drain();
+        }
+    }
+}
+
+// Best effort: Spotlight indexing happens asynchronously and never fails a save
+pub fn index_project(entry: &LibraryEntry, tags: &[String]) {
+    #[cfg(target_os = "macos")]
+    platform::index(entry, tags);
+    #[cfg(not(target_os = "macos"))]
+    let _ = (entry, tags);
+}
+
+pub fn remove_projects(project_ids: &[String]) {
+    #[cfg(target_os = "macos")]
+    if !project_ids.is_empty() {
+        platform::remove(project_ids);
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = project_ids;
+}
@@ -0,0 +1,173 @@
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+// Lets the frontend run its queries on the Rust connection when it can't open
+// the library itself (an encrypted library). Mirrors tauri-plugin-sql's
+// select/execute so src/lib/db.ts works unchanged on top of it.
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteResult {
+    pub rows_affected: u64,
+    pub last_insert_id: i64,
+}
+
+// Same coercions tauri-plugin-sql applies to bound values
+fn to_sql(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(s) => SqlValue::Text(s),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => Value::from(b.to_vec()),
+    }
+}
+
+fn prepare<'c>(
+    conn: &'c rusqlite::Connection,
+    query: &str,
+    values: Vec<Value>,
+) -> Result<rusqlite::Statement<'c>, String> {
+    let mut stmt = conn
+        .prepare(query)
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    if values.len() != stmt.parameter_count() {
+        return Err(format!(
+            "Query expects {} values but got {}",
+            stmt.parameter_count(),
+            values.len()
+        ));
+    }
+    for (i, value) in values.into_iter().enumerate() {
+        stmt.raw_bind_parameter(i + 1, to_sql(value))
+            .map_err(|e| format!("Failed to bind value {}: {}", i + 1, e))?;
+    }
+    Ok(stmt)
+}
+
+#[tauri::command]
+pub fn sql_execute(
+    db: State<Db>,
+    query: String,
+    values: Option<Vec<Value>>,
+) -> Result<ExecuteResult, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = prepare(&conn, &query, values.unwrap_or_default())?;
+    // PRAGMAs and RETURNING clauses produce rows, which execute would reject
+    let rows_affected = if stmt.column_count() > 0 {
+        let mut rows = stmt.raw_query();
+        while rows
+            .next()
+            .map_err(|e| format!("Failed to execute query: {}", e))?
+            .is_some()
+        {}
+        conn.changes() as usize
+    } else {
+        stmt.raw_execute()
+            .map_err(|e| format!("Failed to execute query: {}", e))?
+    };
+    Ok(ExecuteResult {
+        rows_affected: rows_affected as u64,
+        last_insert_id: conn.last_insert_rowid(),
+    })
+}
+
+#[tauri::command]
+pub fn sql_select(
+    db: State<Db>,
+    query: String,
+    values: Option<Vec<Value>>,
+) -> Result<Vec<Map<String, Value>>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = prepare(&conn, &query, values.unwrap_or_default())?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let mut rows = stmt.raw_query();
+    let mut out = Vec::new();
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| format!("Failed to read row: {}", e))?
+    {
+        let mut map = Map::new();
+        for (i, name) in columns.iter().enumerate() {
+            let value = row
+                .get_ref(i)
+                .map_err(|e| format!("Failed to read column {}: {}", name, e))?;
+            map.insert(name.clone(), to_json(value));
+        }
+        out.push(map);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bound_values_follow_the_plugin_coercions() {
+        assert_eq!(to_sql(json!(null)), SqlValue::Null);
+        assert_eq!(to_sql(json!(true)), SqlValue::Integer(1));
+        assert_eq!(to_sql(json!(false)), SqlValue::Integer(0));
+        assert_eq!(to_sql(json!(-42)), SqlValue::Integer(-42));
+        assert_eq!(to_sql(json!(1.5)), SqlValue::Real(1.5));
+        // Too big for an i64, so it's bound as a float
+        assert_eq!(to_sql(json!(u64::MAX)), SqlValue::Real(u64::MAX as f64));
+        assert_eq!(to_sql(json!("text")), SqlValue::Text("text".to_string()));
+        assert_eq!(to_sql(json!([1, 2])), SqlValue::Text("[1,2]".to_string()));
+        assert_eq!(
+            to_sql(json!({ "a": 1 })),
+            SqlValue::Text(r#"{"a":1}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn columns_come_back_as_json() {
+        assert_eq!(to_json(ValueRef::Null), Value::Null);
+        assert_eq!(to_json(ValueRef::Integer(-7)), json!(-7));
+        assert_eq!(to_json(ValueRef::Real(0.25)), json!(0.25));
+        assert_eq!(to_json(ValueRef::Text("café".as_bytes())), json!("café"));
+        assert_eq!(to_json(ValueRef::Blob(&[1, 2, 255])), json!([1, 2, 255]));
+    }
+
+    #[test]
+    fn values_round_trip_through_sqlite() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut stmt = prepare(
+            &conn,
+            "SELECT ?1, ?2, ?3, ?4",
+            vec![json!(3), json!(2.5), json!("x"), json!(true)],
+        )
+        .unwrap();
+        let mut rows = stmt.raw_query();
+        let row = rows.next().unwrap().unwrap();
+        let values: Vec<Value> = (0..4).map(|i| to_json(row.get_ref(i).unwrap())).collect();
+        assert_eq!(values, [json!(3), json!(2.5), json!("x"), json!(1)]);
+    }
+
+    #[test]
+    fn refuses_the_wrong_number_of_values() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(prepare(&conn, "SELECT ?1, ?2", vec![json!(1)]).is_err());
+        assert!(prepare(&conn, "SELECT 1", vec![json!(1)]).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Gets the window on screen before anything it doesn't need for its first
+// frame. Setup only opens the library, loads settings and manages state; the
+// subsystems below start after the window is created, on a background thread,
+// or earlier on the first command that needs one (which then waits for it).
+// Each starts exactly once. System fonts and the frontend's SQL connection
+// were already loaded on first request.
+use std::sync::Once;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::perf;
+
+// Launch to window created; over this and the launch is logged as slow
+const BUDGET: Duration = Duration::from_millis(500);
+
+pub struct Deferred {
+    scopes: Once,
+    watchers: Once,
+    jobs: Once,
+    snapshot: Once,
+}
+
+impl Default for Deferred {
+    fn default() -> Self {
+        Deferred {
+            scopes: Once::new(),
+            watchers: Once::new(),
+            jobs: Once::new(),
+            snapshot: Once::new(),
+        }
+    }
+}
+
+// fs scope grants: folders from settings, security-scoped bookmarks, dropped
+// files. SafePath waits for these before checking anything.
+pub fn scopes(app: &AppHandle) {
+    app.state::<Deferred>().scopes.call_once(|| {
+        let _phase = perf::phase("scopes");
+        crate::safe_path::start(app);
+        if let Err(e) = crate::bookmarks::start(app) {
+            tracing::warn!("Failed to restore folder bookmarks: {}", e);
+        }
+    });
+}
+
+pub fn watchers(app: &AppHandle) {
+    scopes(app);
+    app.state::<Deferred>().watchers.call_once(|| {
+        let _phase = perf::phase("watchers");
+        if let Err(e) = crate::watch::start_all(app) {
+            tracing::warn!("Failed to start watch folders: {}", e);
+        }
+    });
+}
+
+// Restores the saved queue; anything that reads or changes the queue waits
+// for it so restored jobs aren't missed or run twice
+pub fn jobs(app: &AppHandle) {
+    scopes(app);
+    app.state::<Deferred>().jobs.call_once(|| {
+        let _phase = perf::phase("jobs");
+        if let Err(e) = crate::jobs::start(app) {
+            tracing::warn!("Failed to start the job queue: {}", e);
+        }
+    });
+}
+
+fn snapshot(app: &AppHandle) {
+    app.state::<Deferred>().snapshot.call_once(|| {
+        let _phase = perf::phase("snapshot");
+        if let Err(e) = crate::integrity::snapshot(app) {
+            tracing::warn!("Skipping database snapshot: {}", e);
+        }
+    });
+}
+
+// Call once the window has been created
+pub fn finish(app: &AppHandle) {
+    let elapsed = perf::since_launch();
+    if elapsed > BUDGET {
+        tracing::warn!(
+            "Window took {:?} to open, over the {:?} startup budget",
+            elapsed,
+            BUDGET
+        );
+    } else {
+        tracing::info!("Window opened {:?} after launch", elapsed);
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        scopes(&app);
+        watchers(&app);
+        jobs(&app);
+        snapshot(&app);
+        crate::net::prune_downloads(&app);
+        tracing::info!(
+            "Deferred startup finished {:?} after launch",
+            perf::since_launch()
+        );
+    });
+}
@@ -0,0 +1,282 @@
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+
+// A self-contained copy of one synced item: every row it needs, grouped by
+// table. Keys are sorted, so identical content always hashes the same.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bundle {
+    pub kind: String,
+    pub id: String,
+    pub tables: BTreeMap<String, Vec<Map<String, Value>>>,
+}
+
+// Tables in insertion order, so foreign keys are satisfied when applying
+const PROJECT_TABLES: &[(&str, &str)] = &[
+    (
+        "image_assets",
+        "id IN (SELECT image_asset_id FROM layers WHERE project_id = ?1)
+         OR id IN (SELECT background_image_id FROM canvas_settings WHERE project_id = ?1)
+         OR id IN (SELECT source_image_id FROM sticker_assets
+                   WHERE id IN (SELECT sticker_asset_id FROM layers WHERE project_id = ?1))",
+    ),
+    (
+        "sticker_assets",
+        "id IN (SELECT sticker_asset_id FROM layers WHERE project_id = ?1)",
+    ),
+    ("projects", "id = ?1"),
+    ("canvas_settings", "project_id = ?1"),
+    ("layers", "project_id = ?1"),
+    ("layer_order", "project_id = ?1"),
+];
+
+const PRESET_TABLES: &[(&str, &str)] = &[("presets", "id = ?1")];
+
+// Rows owned by the project that are replaced wholesale rather than merged
+const PROJECT_OWNED: &[&str] = &["layer_order", "layers", "canvas_settings"];
+
+fn tables_for(kind: &str) -> Result<&'static [(&'static str, &'static str)], String> {
+    match kind {
+        "project" => Ok(PROJECT_TABLES),
+        "preset" => Ok(PRESET_TABLES),
+        _ => Err(format!("Unknown sync item kind: {}", kind)),
+    }
+}
+
+// Blobs are tagged so they come back as blobs rather than text
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => serde_json::json!({ "$blob": b }),
+    }
+}
+
+fn to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        Value::Object(o) if o.contains_key("$blob") => {
+            let bytes: Vec<u8> = serde_json::from_value(o["$blob"].clone()).unwrap_or_default();
+            SqlValue::Blob(bytes)
+        }
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+pub fn list_ids(conn: &Connection, kind: &str) -> Result<Vec<String>, String> {
+    let table = match kind {
+        "project" => "projects",
+        "preset" => "presets",
+        _ => return Err(format!("Unknown sync item kind: {}", kind)),
+    };
+    let mut stmt = conn
+        .prepare(&format!("SELECT id FROM {} ORDER BY id", table))
+        .map_err(|e| format!("Failed to list {}: {}", table, e))?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Failed to list {}: {}", table, e))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Failed to list {}: {}", table, e))?;
+    Ok(ids)
+}
+
+pub fn build(conn: &Connection, kind: &str, id: &str) -> Result<Bundle, String> {
+    let mut tables = BTreeMap::new();
+    for (table, filter) in tables_for(kind)? {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT * FROM {} WHERE {} ORDER BY rowid",
+                table, filter
+            ))
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt
+            .query(params![id])
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+
+        let mut out = Vec::new();
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?
+        {
+            let mut map = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                let value = row
+                    .get_ref(i)
+                    .map_err(|e| format!("Failed to read {}.{}: {}", table, column, e))?;
+                map.insert(column.clone(), to_json(value));
+            }
+            out.push(map);
+        }
+        tables.insert(table.to_string(), out);
+    }
+    Ok(Bundle {
+        kind: kind.to_string(),
+        id: id.to_string(),
+        tables,
+    })
+}
+
+pub fn encode(bundle: &Bundle) -> Result<(Vec<u8>, String), String> {
+    let bytes =
+        serde_json::to_vec(bundle).map_err(|e| format!("Failed to serialize bundle: {}", e))?;
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+    Ok((bytes, hash))
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| format!("Failed to read schema of {}: {}", table, e))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Failed to read schema of {}: {}", table, e))?
+        .collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| format!("Failed to read schema of {}: {}", table, e))?;
+    Ok(columns)
+}
+
+// Writes a pulled bundle into the library in one transaction. Column names
+// come from the remote, so only ones that exist in our schema are used.
+pub fn apply(conn: &mut Connection, bundle: &Bundle) -> Result<(), String> {
+    let tables = tables_for(&bundle.kind)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    if bundle.kind == "project" {
+        for table in PROJECT_OWNED {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE project_id = ?1", table),
+                params![bundle.id],
+            )
+            .map_err(|e| format!("Failed to clear {}: {}", table, e))?;
+        }
+    }
+
+    for (table, _) in tables {
+        let Some(rows) = bundle.tables.get(*table) else {
+            continue;
+        };
+        let known = table_columns(&tx, table)?;
+        for row in rows {
+            let (columns, values): (Vec<&String>, Vec<SqlValue>) = row
+                .iter()
+                .filter(|(column, _)| known.contains(*column))
+                .map(|(column, value)| (column, to_sql(value)))
+                .unzip();
+            if columns.is_empty() {
+                continue;
+            }
+            let names = columns.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+            let placeholders = (1..=names.len())
+                .map(|i| format!("?{}", i))
+                .collect::<Vec<_>>();
+            let updates = names
+                .iter()
+                .map(|c| format!("{} = excluded.{}", c, c))
+                .collect::<Vec<_>>();
+            // Upsert rather than REPLACE, which would cascade-delete children
+            let sql = format!(
+                "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT DO UPDATE SET {}",
+                table,
+                names.join(", "),
+                placeholders.join(", "),
+                updates.join(", ")
+            );
+            tx.execute(&sql, rusqlite::params_from_iter(values))
+                .map_err(|e| format!("Failed to write {}: {}", table, e))?;
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit pulled {}: {}", bundle.kind, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn
+    }
+
+    fn project() -> Connection {
+        let conn = library();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster');
+             INSERT INTO image_assets (id, name, mime_type, data) VALUES ('a1', 'a.png', 'image/png', x'89504e47');
+             INSERT INTO canvas_settings (project_id, width, height) VALUES ('p1', 800, 600);
+             INSERT INTO layers (id, project_id, type, image_asset_id, transform)
+                 VALUES ('l1', 'p1', 'image', 'a1', '{}');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn a_bundle_restores_the_same_rows() {
+        let bundle = build(&project(), "project", "p1").unwrap();
+        assert_eq!(bundle.tables["image_assets"].len(), 1);
+        assert_eq!(bundle.tables["layers"].len(), 1);
+
+        let mut copy = library();
+        apply(&mut copy, &bundle).unwrap();
+        let data: Vec<u8> = copy
+            .query_row("SELECT data FROM image_assets WHERE id = 'a1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(data, b"\x89PNG");
+        let rebuilt = build(&copy, "project", "p1").unwrap();
+        assert_eq!(encode(&rebuilt).unwrap().1, encode(&bundle).unwrap().1);
+    }
+
+    #[test]
+    fn applying_replaces_the_project_layers() {
+        let bundle = build(&project(), "project", "p1").unwrap();
+        let mut conn = project();
+        conn.execute(
+            "INSERT INTO layers (id, project_id, type, content, transform, style)
+             VALUES ('stale', 'p1', 'text', 'Old', '{}', '{}')",
+            [],
+        )
+        .unwrap();
+        apply(&mut conn, &bundle).unwrap();
+        let ids = conn
+            .prepare("SELECT id FROM layers")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(ids, vec!["l1"]);
+    }
+
+    #[test]
+    fn unknown_columns_and_kinds_are_ignored_or_refused() {
+        let mut bundle = build(&project(), "project", "p1").unwrap();
+        bundle.tables.get_mut("projects").unwrap()[0]
+            .insert("dropped_in_v9".to_string(), Value::from(1));
+        apply(&mut library(), &bundle).unwrap();
+
+        bundle.kind = "font".to_string();
+        assert!(apply(&mut library(), &bundle).is_err());
+        assert!(build(&project(), "font", "p1").is_err());
+        assert!(list_ids(&project(), "font").is_err());
+        assert_eq!(list_ids(&project(), "project").unwrap(), vec!["p1"]);
+    }
+}
@@ -0,0 +1,617 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::connectivity::ConnectivityState;
+use crate::db::{get_preference, set_preference, Db};
+use crate::error::Error;
+use crate::permissions::{self, Capability};
+use crate::settings::SettingsState;
+
+pub mod bundle;
+mod remote;
+mod version;
+
+use bundle::Bundle;
+use remote::Remote;
+pub use remote::RemoteConfig;
+use version::{Causality, VersionVector};
+
+const CONFIG_KEY: &str = "sync_remote";
+const DEVICE_KEY: &str = "sync_device_id";
+const SECRET_ACCOUNT: &str = "sync-secret";
+const MANIFEST_KEY: &str = "manifest.json";
+// Where one-off uploads (from scripts) go, apart from the synced library
+const UPLOADS_DIR: &str = "uploads";
+const KINDS: &[&str] = &["project", "preset"];
+pub const SYNC_STATUS_EVENT: &str = "sync://status";
+
+// Set while a sync is running so overlapping requests are refused
+pub struct SyncState(pub AtomicBool);
+
+// The remote's view of every item: which edits it has seen and what it holds
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    items: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    version_vector: VersionVector,
+    content_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub kind: String,
+    pub id: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    // "syncing", "idle" or "error"
+    pub state: &'static str,
+    pub message: Option<String>,
+    pub pushed: u32,
+    pub pulled: u32,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl SyncStatus {
+    fn new(state: &'static str) -> SyncStatus {
+        SyncStatus {
+            state,
+            message: None,
+            pushed: 0,
+            pulled: 0,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+struct LocalItem {
+    kind: &'static str,
+    id: String,
+    bytes: Vec<u8>,
+    hash: String,
+    vector: VersionVector,
+    conflict: bool,
+}
+
+struct SyncRecord {
+    vector: VersionVector,
+    hash: String,
+    conflict: bool,
+}
+
+fn item_key(kind: &str, id: &str) -> String {
+    format!("{}/{}", kind, id)
+}
+
+fn remote_path(kind: &str, id: &str) -> String {
+    format!("{}s/{}.json", kind, id)
+}
+
+fn emit(app: &AppHandle, status: &SyncStatus) {
+    if let Err(e) = app.emit(SYNC_STATUS_EVENT, status) {
+        tracing::warn!("Failed to emit sync status: {}", e);
+    }
+}
+
+fn device_id(conn: &Connection) -> Result<String, String> {
+    if let Some(id) =
+        get_preference(conn, DEVICE_KEY)?.and_then(|raw| serde_json::from_str::<String>(&raw).ok())
+    {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    set_preference(conn, DEVICE_KEY, &Value::from(id.clone()).to_string())?;
+    Ok(id)
+}
+
+pub fn load_config(conn: &Connection) -> Result<Option<RemoteConfig>, String> {
+    match get_preference(conn, CONFIG_KEY)? {
+        Some(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| format!("Invalid sync configuration: {}", e)),
+        None => Ok(None),
+    }
+}
+
+fn read_record(conn: &Connection, kind: &str, id: &str) -> Result<Option<SyncRecord>, String> {
+    conn.query_row(
+        "SELECT version_vector, content_hash, conflict FROM sync_state
+         WHERE kind = ?1 AND item_id = ?2",
+        params![kind, id],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        },
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read sync state: {}", e))
+    .map(|row| {
+        row.map(|(vector, hash, conflict)| SyncRecord {
+            vector: serde_json::from_str(&vector).unwrap_or_default(),
+            hash,
+            conflict,
+        })
+    })
+}
+
+fn write_record(
+    conn: &Connection,
+    kind: &str,
+    id: &str,
+    vector: &VersionVector,
+    hash: &str,
+    conflict: bool,
+) -> Result<(), String> {
+    let vector =
+        serde_json::to_string(vector).map_err(|e| format!("Failed to serialize version: {}", e))?;
+    conn.execute(
+        "INSERT INTO sync_state (kind, item_id, version_vector, content_hash, conflict)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(kind, item_id) DO UPDATE SET
+            version_vector = excluded.version_vector,
+            content_hash = excluded.content_hash,
+            conflict = excluded.conflict,
+            synced_at = CURRENT_TIMESTAMP",
+        params![kind, id, vector, hash, conflict],
+    )
+    .map_err(|e| format!("Failed to save sync state: {}", e))?;
+    Ok(())
+}
+
+fn set_conflict(conn: &Connection, kind: &str, id: &str, hash: &str) -> Result<(), String> {
+    // Keep the last agreed version vector so resolving can merge from it
+    let vector = read_record(conn, kind, id)?
+        .map(|r| r.vector)
+        .unwrap_or_default();
+    write_record(conn, kind, id, &vector, hash, true)
+}
+
+// Snapshots one item. Its version is bumped for this device whenever its
+// content changed since the last sync; unchanged items keep their version.
+fn local_item(
+    conn: &Connection,
+    device: &str,
+    kind: &'static str,
+    id: &str,
+) -> Result<LocalItem, String> {
+    let (bytes, hash) = bundle::encode(&bundle::build(conn, kind, id)?)?;
+    let record = read_record(conn, kind, id)?;
+    let (mut vector, conflict, changed) = match record {
+        Some(r) => (r.vector, r.conflict, r.hash != hash),
+        None => (VersionVector::new(), false, true),
+    };
+    if changed {
+        version::bump(&mut vector, device);
+    }
+    Ok(LocalItem {
+        kind,
+        id: id.to_string(),
+        bytes,
+        hash,
+        vector,
+        conflict,
+    })
+}
+
+fn with_conn<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    let db = app.state::<Db>();
+    let mut conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    f(&mut conn)
+}
+
+fn keychain_secret() -> Result<String, String> {
+    Ok(crate::secrets::get(SECRET_ACCOUNT)?.unwrap_or_default())
+}
+
+async fn connect(app: &AppHandle) -> Result<(Remote, String, Manifest), String> {
+    let (config, device) = with_conn(app, |conn| {
+        let config = load_config(conn)?.ok_or("Sync is not configured")?;
+        Ok((config, device_id(conn)?))
+    })?;
+    let timeout = crate::net::timeout(&app.state::<SettingsState>().snapshot());
+    let remote = Remote::connect(
+        &config,
+        &keychain_secret()?,
+        crate::net::client(app),
+        timeout,
+    )?;
+    let manifest = match remote.get(MANIFEST_KEY).await? {
+        Some(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Remote sync manifest is invalid: {}", e))?,
+        None => Manifest::default(),
+    };
+    Ok((remote, device, manifest))
+}
+
+// Puts one file on the sync remote under uploads/ and returns its key. Takes
+// everything as arguments since scripts run it from the CLI too, without an
+// app; callers check connectivity and consent first.
+pub async fn upload(
+    config: &RemoteConfig,
+    client: reqwest::Client,
+    timeout: Duration,
+    path: &Path,
+    name: &str,
+) -> Result<String, String> {
+    let remote = Remote::connect(config, &keychain_secret()?, client, timeout)?;
+    remote.ensure_dirs(&[UPLOADS_DIR]).await?;
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let content_type = image::ImageFormat::from_path(path)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream");
+    let key = format!("{}/{}", UPLOADS_DIR, name.trim_start_matches('/'));
+    remote.put_as(&key, &bytes, content_type).await?;
+    tracing::info!("Uploaded {} to {}", path.display(), key);
+    Ok(key)
+}
+
+async fn push(
+    app: &AppHandle,
+    remote: &Remote,
+    manifest: &mut Manifest,
+    item: &LocalItem,
+    vector: VersionVector,
+) -> Result<(), String> {
+    remote
+        .put(&remote_path(item.kind, &item.id), &item.bytes)
+        .await?;
+    manifest.items.insert(
+        item_key(item.kind, &item.id),
+        ManifestEntry {
+            version_vector: vector.clone(),
+            content_hash: item.hash.clone(),
+        },
+    );
+    with_conn(app, |conn| {
+        write_record(conn, item.kind, &item.id, &vector, &item.hash, false)
+    })
+}
+
+// Downloads and applies the remote copy. The hash recorded afterwards is of
+// the local rebuild, so the next sync doesn't mistake the pull for an edit.
+async fn pull(
+    app: &AppHandle,
+    remote: &Remote,
+    kind: &'static str,
+    id: &str,
+    entry: &ManifestEntry,
+    vector: VersionVector,
+) -> Result<(), String> {
+    let bytes = remote
+        .get(&remote_path(kind, id))
+        .await?
+        .ok_or_else(|| format!("Remote is missing {} {}", kind, id))?;
+    if format!("{:x}", Sha256::digest(&bytes)) != entry.content_hash {
+        return Err(format!("Remote copy of {} {} is corrupted", kind, id));
+    }
+    let bundle: Bundle = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Remote copy of {} {} is invalid: {}", kind, id, e))?;
+    if bundle.kind != kind || bundle.id != id {
+        return Err(format!(
+            "Remote copy of {} {} is for another item",
+            kind, id
+        ));
+    }
+
+    with_conn(app, |conn| {
+        bundle::apply(conn, &bundle)?;
+        if kind == "project" {
+            crate::library::index(conn, id, None)?;
+        }
+        let (_, hash) = bundle::encode(&bundle::build(conn, kind, id)?)?;
+        write_record(conn, kind, id, &vector, &hash, false)
+    })
+}
+
+async fn save_manifest(remote: &Remote, manifest: &Manifest) -> Result<(), String> {
+    let bytes = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize sync manifest: {}", e))?;
+    remote.put(MANIFEST_KEY, &bytes).await
+}
+
+fn kind_from(kind: &str) -> Result<&'static str, String> {
+    KINDS
+        .iter()
+        .find(|k| **k == kind)
+        .copied()
+        .ok_or_else(|| format!("Unknown sync item kind: {}", kind))
+}
+
+async fn run_sync(app: &AppHandle) -> Result<SyncStatus, String> {
+    let (remote, device, mut manifest) = connect(app).await?;
+    remote.ensure_dirs(&["projects", "presets"]).await?;
+
+    let locals = with_conn(app, |conn| {
+        let mut items = Vec::new();
+        for &kind in KINDS {
+            for id in bundle::list_ids(conn, kind)? {
+                items.push(local_item(conn, &device, kind, &id)?);
+            }
+        }
+        Ok(items)
+    })?;
+
+    let mut status = SyncStatus::new("idle");
+    let mut manifest_changed = false;
+    let mut seen = HashSet::new();
+
+    for item in &locals {
+        let key = item_key(item.kind, &item.id);
+        seen.insert(key.clone());
+        let Some(entry) = manifest.items.get(&key).cloned() else {
+            push(app, &remote, &mut manifest, item, item.vector.clone()).await?;
+            status.pushed += 1;
+            manifest_changed = true;
+            continue;
+        };
+
+        // Identical content needs no transfer, just agreement on the version
+        if entry.content_hash == item.hash {
+            let merged = version::merge(&item.vector, &entry.version_vector);
+            if merged != entry.version_vector {
+                manifest.items.insert(
+                    key,
+                    ManifestEntry {
+                        version_vector: merged.clone(),
+                        content_hash: item.hash.clone(),
+                    },
+                );
+                manifest_changed = true;
+            }
+            with_conn(app, |conn| {
+                write_record(conn, item.kind, &item.id, &merged, &item.hash, false)
+            })?;
+            continue;
+        }
+
+        match (
+            item.conflict,
+            version::compare(&item.vector, &entry.version_vector),
+        ) {
+            (false, Causality::Equal) => {}
+            (false, Causality::Ahead) => {
+                push(app, &remote, &mut manifest, item, item.vector.clone()).await?;
+                status.pushed += 1;
+                manifest_changed = true;
+            }
+            (false, Causality::Behind) => {
+                let vector = entry.version_vector.clone();
+                pull(app, &remote, item.kind, &item.id, &entry, vector).await?;
+                status.pulled += 1;
+            }
+            // Edited on both sides since they last agreed; left for the user
+            _ => {
+                with_conn(app, |conn| {
+                    set_conflict(conn, item.kind, &item.id, &item.hash)
+                })?;
+                status.conflicts.push(SyncConflict {
+                    kind: item.kind.to_string(),
+                    id: item.id.clone(),
+                });
+            }
+        }
+    }
+
+    // Items created on other devices
+    let remote_only: Vec<(String, ManifestEntry)> = manifest
+        .items
+        .iter()
+        .filter(|(key, _)| !seen.contains(*key))
+        .map(|(key, entry)| (key.clone(), entry.clone()))
+        .collect();
+    for (key, entry) in remote_only {
+        let Some((kind, id)) = key.split_once('/') else {
+            continue;
+        };
+        let Ok(kind) = kind_from(kind) else {
+            continue;
+        };
+        pull(app, &remote, kind, id, &entry, entry.version_vector.clone()).await?;
+        status.pulled += 1;
+    }
+
+    if manifest_changed {
+        save_manifest(&remote, &manifest).await?;
+    }
+    tracing::info!(
+        "Sync finished: {} pushed, {} pulled, {} conflicts",
+        status.pushed,
+        status.pulled,
+        status.conflicts.len()
+    );
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn get_sync_config(db: State<Db>) -> Result<Option<RemoteConfig>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(load_config(&conn)?)
+}
+
+// The secret goes to the OS keychain, never the database; None keeps the saved one
+#[tauri::command]
+pub fn set_sync_config(
+    db: State<Db>,
+    config: RemoteConfig,
+    secret: Option<String>,
+) -> Result<(), Error> {
+    if let Some(secret) = secret {
+        crate::secrets::set(SECRET_ACCOUNT, &secret)?;
+    }
+    let config =
+        serde_json::to_string(&config).map_err(|e| format!("Failed to save sync config: {}", e))?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    Ok(set_preference(&conn, CONFIG_KEY, &config)?)
+}
+
+// Progress and the final outcome are also streamed on sync://status
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn sync_now(app: AppHandle) -> Result<SyncStatus, Error> {
+    if !app.state::<ConnectivityState>().is_online() {
+        return Err(Error::Offline);
+    }
+    // The library is pushed to whatever server was configured
+    permissions::check(&app, None, Capability::NetworkUpload, "sync")?;
+    let running = app.state::<SyncState>();
+    if running.0.swap(true, Ordering::SeqCst) {
+        return Err(Error::Busy("A sync is already running".to_string()));
+    }
+    emit(&app, &SyncStatus::new("syncing"));
+    let awake = crate::power::keep_awake("Syncing the library");
+    let result = run_sync(&app).await;
+    drop(awake);
+    running.0.store(false, Ordering::SeqCst);
+
+    match &result {
+        Ok(status) => emit(&app, status),
+        Err(e) => {
+            tracing::warn!("Sync failed: {}", e);
+            let mut status = SyncStatus::new("error");
+            status.message = Some(e.clone());
+            emit(&app, &status);
+        }
+    }
+    Ok(result?)
+}
+
+#[tauri::command]
+pub fn list_sync_conflicts(db: State<Db>) -> Result<Vec<SyncConflict>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT kind, item_id FROM sync_state WHERE conflict = 1 ORDER BY kind, item_id")
+        .map_err(|e| format!("Failed to read sync conflicts: {}", e))?;
+    let conflicts = stmt
+        .query_map([], |row| {
+            Ok(SyncConflict {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read sync conflicts: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read sync conflicts: {}", e))?;
+    Ok(conflicts)
+}
+
+// Settles a conflict by keeping one side. Either way the result's version
+// covers both histories, so it replaces the other copy on the next sync.
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn resolve_sync_conflict(
+    app: AppHandle,
+    kind: String,
+    id: String,
+    keep_local: bool,
+) -> Result<(), Error> {
+    let kind = kind_from(&kind)?;
+    let (remote, device, mut manifest) = connect(&app).await?;
+    let item = with_conn(&app, |conn| local_item(conn, &device, kind, &id))?;
+    let entry = manifest.items.get(&item_key(kind, &id)).cloned();
+
+    let mut vector = match &entry {
+        Some(entry) => version::merge(&item.vector, &entry.version_vector),
+        None => item.vector.clone(),
+    };
+    match entry {
+        Some(entry) if !keep_local => {
+            pull(&app, &remote, kind, &id, &entry, vector).await?;
+        }
+        _ => {
+            version::bump(&mut vector, &device);
+            push(&app, &remote, &mut manifest, &item, vector).await?;
+            save_manifest(&remote, &manifest).await?;
+        }
+    }
+    tracing::info!("Resolved sync conflict for {} {}", kind, id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn save(conn: &Connection, item: &LocalItem) {
+        write_record(conn, item.kind, &item.id, &item.vector, &item.hash, false).unwrap();
+    }
+
+    #[test]
+    fn the_device_id_is_kept() {
+        let conn = library();
+        let id = device_id(&conn).unwrap();
+        assert_eq!(device_id(&conn).unwrap(), id);
+        assert!(load_config(&conn).unwrap().is_none());
+    }
+
+    #[test]
+    fn versions_are_bumped_only_for_edits() {
+        let conn = library();
+        let item = local_item(&conn, "mac", "project", "p1").unwrap();
+        assert_eq!(item.vector["mac"], 1);
+        save(&conn, &item);
+
+        let unchanged = local_item(&conn, "mac", "project", "p1").unwrap();
+        assert_eq!(unchanged.vector["mac"], 1);
+        assert_eq!(unchanged.hash, item.hash);
+
+        conn.execute("UPDATE projects SET name = 'Flyer' WHERE id = 'p1'", [])
+            .unwrap();
+        let edited = local_item(&conn, "mac", "project", "p1").unwrap();
+        assert_eq!(edited.vector["mac"], 2);
+        assert_ne!(edited.hash, item.hash);
+    }
+
+    #[test]
+    fn a_conflict_keeps_the_last_agreed_version() {
+        let conn = library();
+        let item = local_item(&conn, "mac", "project", "p1").unwrap();
+        save(&conn, &item);
+        set_conflict(&conn, "project", "p1", "edited").unwrap();
+
+        let record = read_record(&conn, "project", "p1").unwrap().unwrap();
+        assert!(record.conflict);
+        assert_eq!(record.hash, "edited");
+        assert_eq!(record.vector, item.vector);
+        assert!(local_item(&conn, "mac", "project", "p1").unwrap().conflict);
+    }
+
+    #[test]
+    fn only_known_kinds_are_synced() {
+        assert_eq!(kind_from("preset"), Ok("preset"));
+        assert!(kind_from("font").is_err());
+        assert_eq!(item_key("project", "p1"), "project/p1");
+        assert_eq!(remote_path("project", "p1"), "projects/p1.json");
+    }
+}
@@ -0,0 +1,250 @@
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RemoteConfig {
+    #[serde(rename_all = "camelCase")]
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        access_key_id: String,
+        // Key prefix inside the bucket, e.g. "squish/"
+        #[serde(default)]
+        prefix: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    WebDav { url: String, username: String },
+}
+
+// A place bundles can be stored under string keys like "projects/<id>.json"
+pub enum Remote {
+    S3 {
+        bucket: Box<Bucket>,
+        prefix: String,
+    },
+    WebDav {
+        client: reqwest::Client,
+        base_url: String,
+        username: String,
+        password: String,
+    },
+}
+
+impl Remote {
+    // The secret is the S3 secret access key or the WebDAV password. WebDAV
+    // goes through the shared client; rust-s3 brings its own, which only
+    // picks up the timeout and environment proxies.
+    pub fn connect(
+        config: &RemoteConfig,
+        secret: &str,
+        client: reqwest::Client,
+        timeout: Duration,
+    ) -> Result<Remote, String> {
+        match config {
+            RemoteConfig::S3 {
+                endpoint,
+                region,
+                bucket,
+                access_key_id,
+                prefix,
+            } => {
+                let region = Region::Custom {
+                    region: region.clone(),
+                    endpoint: endpoint.clone(),
+                };
+                let credentials =
+                    Credentials::new(Some(access_key_id), Some(secret), None, None, None)
+                        .map_err(|e| format!("Invalid S3 credentials: {}", e))?;
+                // Path-style addressing is what most S3-compatible services expect
+                let mut bucket = Bucket::new(bucket, region, credentials)
+                    .map_err(|e| format!("Invalid S3 bucket: {}", e))?
+                    .with_path_style();
+                bucket.set_request_timeout(Some(timeout));
+                Ok(Remote::S3 {
+                    bucket,
+                    prefix: prefix.trim_matches('/').to_string(),
+                })
+            }
+            RemoteConfig::WebDav { url, username } => Ok(Remote::WebDav {
+                client,
+                base_url: url.trim_end_matches('/').to_string(),
+                username: username.clone(),
+                password: secret.to_string(),
+            }),
+        }
+    }
+
+    fn s3_key(prefix: &str, key: &str) -> String {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", prefix, key)
+        }
+    }
+
+    // None when nothing is stored under the key yet
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Remote::S3 { bucket, prefix } => {
+                let response = bucket
+                    .get_object(Self::s3_key(prefix, key))
+                    .await
+                    .map_err(|e| format!("Failed to download {}: {}", key, e))?;
+                match response.status_code() {
+                    200 => Ok(Some(response.bytes().to_vec())),
+                    404 => Ok(None),
+                    code => Err(format!("Failed to download {}: HTTP {}", key, code)),
+                }
+            }
+            Remote::WebDav {
+                client,
+                base_url,
+                username,
+                password,
+            } => {
+                let response = client
+                    .get(format!("{}/{}", base_url, key))
+                    .basic_auth(username, Some(password))
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to download {}: {}", key, e))?;
+                match response.status().as_u16() {
+                    200 => Ok(Some(
+                        response
+                            .bytes()
+                            .await
+                            .map_err(|e| format!("Failed to download {}: {}", key, e))?
+                            .to_vec(),
+                    )),
+                    404 => Ok(None),
+                    code => Err(format!("Failed to download {}: HTTP {}", key, code)),
+                }
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        self.put_as(key, bytes, "application/json").await
+    }
+
+    pub async fn put_as(&self, key: &str, bytes: &[u8], content_type: &str) -> Result<(), String> {
+        match self {
+            Remote::S3 { bucket, prefix } => {
+                let response = bucket
+                    .put_object_with_content_type(Self::s3_key(prefix, key), bytes, content_type)
+                    .await
+                    .map_err(|e| format!("Failed to upload {}: {}", key, e))?;
+                match response.status_code() {
+                    200..=299 => Ok(()),
+                    code => Err(format!("Failed to upload {}: HTTP {}", key, code)),
+                }
+            }
+            Remote::WebDav {
+                client,
+                base_url,
+                username,
+                password,
+            } => {
+                let response = client
+                    .put(format!("{}/{}", base_url, key))
+                    .basic_auth(username, Some(password))
+                    .header("Content-Type", content_type)
+                    .body(bytes.to_vec())
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to upload {}: {}", key, e))?;
+                match response.status().as_u16() {
+                    200..=299 => Ok(()),
+                    code => Err(format!("Failed to upload {}: HTTP {}", key, code)),
+                }
+            }
+        }
+    }
+
+    // WebDAV needs collections to exist before files can be put in them;
+    // S3 has no directories so this is a no-op there
+    pub async fn ensure_dirs(&self, dirs: &[&str]) -> Result<(), String> {
+        let Remote::WebDav {
+            client,
+            base_url,
+            username,
+            password,
+        } = self
+        else {
+            return Ok(());
+        };
+        let mkcol = reqwest::Method::from_bytes(b"MKCOL").expect("valid method");
+        for dir in dirs {
+            let response = client
+                .request(mkcol.clone(), format!("{}/{}/", base_url, dir))
+                .basic_auth(username, Some(password))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+            // 405 means the collection is already there
+            match response.status().as_u16() {
+                200..=299 | 405 => {}
+                code => return Err(format!("Failed to create {}: HTTP {}", dir, code)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(config: serde_json::Value) -> Remote {
+        let config: RemoteConfig = serde_json::from_value(config).unwrap();
+        Remote::connect(
+            &config,
+            "secret",
+            reqwest::Client::new(),
+            Duration::from_secs(5),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn s3_keys_go_under_the_prefix() {
+        let remote = connect(serde_json::json!({
+            "type": "s3",
+            "endpoint": "https://s3.example.com",
+            "region": "auto",
+            "bucket": "library",
+            "accessKeyId": "key",
+            "prefix": "/squish/",
+        }));
+        let Remote::S3 { prefix, .. } = remote else {
+            panic!("expected an S3 remote");
+        };
+        assert_eq!(prefix, "squish");
+        assert_eq!(
+            Remote::s3_key(&prefix, "manifest.json"),
+            "squish/manifest.json"
+        );
+        assert_eq!(Remote::s3_key("", "manifest.json"), "manifest.json");
+    }
+
+    #[test]
+    fn webdav_urls_lose_their_trailing_slash() {
+        let remote = connect(serde_json::json!({
+            "type": "webDav",
+            "url": "https://dav.example.com/squish/",
+            "username": "me",
+        }));
+        let Remote::WebDav {
+            base_url, password, ..
+        } = remote
+        else {
+            panic!("expected a WebDAV remote");
+        };
+        assert_eq!(base_url, "https://dav.example.com/squish");
+        assert_eq!(password, "secret");
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+// Per-device edit counters. One vector dominates another when it has seen
+// every edit the other has; when neither does, the item was edited on two
+// devices since they last agreed, which is a conflict.
+pub type VersionVector = BTreeMap<String, u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    Equal,
+    Ahead,
+    Behind,
+    Concurrent,
+}
+
+pub fn compare(local: &VersionVector, remote: &VersionVector) -> Causality {
+    let mut ahead = false;
+    let mut behind = false;
+    for device in local.keys().chain(remote.keys()) {
+        let l = local.get(device).copied().unwrap_or(0);
+        let r = remote.get(device).copied().unwrap_or(0);
+        ahead |= l > r;
+        behind |= l < r;
+    }
+    match (ahead, behind) {
+        (false, false) => Causality::Equal,
+        (true, false) => Causality::Ahead,
+        (false, true) => Causality::Behind,
+        (true, true) => Causality::Concurrent,
+    }
+}
+
+pub fn merge(a: &VersionVector, b: &VersionVector) -> VersionVector {
+    let mut merged = a.clone();
+    for (device, counter) in b {
+        let entry = merged.entry(device.clone()).or_insert(0);
+        *entry = (*entry).max(*counter);
+    }
+    merged
+}
+
+pub fn bump(vector: &mut VersionVector, device: &str) {
+    *vector.entry(device.to_string()).or_insert(0) += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(counts: &[(&str, u64)]) -> VersionVector {
+        counts.iter().map(|(d, c)| (d.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn compares_by_what_each_side_has_seen() {
+        let base = vector(&[("mac", 2), ("ipad", 1)]);
+        assert_eq!(compare(&base, &base), Causality::Equal);
+        assert_eq!(
+            compare(&base, &vector(&[("mac", 2), ("ipad", 1), ("pc", 0)])),
+            Causality::Equal
+        );
+        assert_eq!(
+            compare(&vector(&[("mac", 3), ("ipad", 1)]), &base),
+            Causality::Ahead
+        );
+        assert_eq!(
+            compare(&base, &vector(&[("mac", 2), ("ipad", 2)])),
+            Causality::Behind
+        );
+        assert_eq!(
+            compare(
+                &vector(&[("mac", 3), ("ipad", 1)]),
+                &vector(&[("mac", 2), ("ipad", 2)])
+            ),
+            Causality::Concurrent
+        );
+    }
+
+    #[test]
+    fn a_merge_is_ahead_of_both_sides_after_a_bump() {
+        let a = vector(&[("mac", 3), ("ipad", 1)]);
+        let b = vector(&[("mac", 2), ("pc", 4)]);
+        let mut merged = merge(&a, &b);
+        assert_eq!(merged, vector(&[("mac", 3), ("ipad", 1), ("pc", 4)]));
+        bump(&mut merged, "mac");
+        assert_eq!(merged["mac"], 4);
+        assert_eq!(compare(&merged, &a), Causality::Ahead);
+        assert_eq!(compare(&merged, &b), Causality::Ahead);
+    }
+}
@@ -0,0 +1,334 @@
+use rusqlite::{params, params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    pub item_count: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaggedItem {
+    pub kind: String,
+    pub id: String,
+}
+
+fn check_kind(kind: &str) -> Result<(), String> {
+    match kind {
+        "project" | "asset" => Ok(()),
+        _ => Err(format!("Cannot tag items of kind {}", kind)),
+    }
+}
+
+fn clean_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+fn map_write_error(name: &str, e: rusqlite::Error) -> String {
+    match e {
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            format!("A tag named {} already exists", name)
+        }
+        e => format!("Failed to save tag: {}", e),
+    }
+}
+
+pub fn names_for(conn: &Connection, kind: &str, item_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.name FROM tags t JOIN item_tags it ON it.tag_id = t.id
+             WHERE it.kind = ?1 AND it.item_id = ?2 ORDER BY t.name",
+        )
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let names = stmt
+        .query_map(params![kind, item_id], |row| row.get(0))
+        .map_err(|e| format!("Failed to read tags: {}", e))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    Ok(names)
+}
+
+// Keeps the search index's tags column in step with item_tags
+fn refresh_search(conn: &Connection, kind: &str, item_id: &str) -> Result<(), String> {
+    let names = names_for(conn, kind, item_id)?.join(" ");
+    conn.execute(
+        "UPDATE library_fts SET tags = ?1 WHERE kind = ?2 AND ref_id = ?3",
+        params![names, kind, item_id],
+    )
+    .map_err(|e| format!("Failed to update search index: {}", e))?;
+    Ok(())
+}
+
+fn items_with_tag(conn: &Connection, tag_id: i64) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT kind, item_id FROM item_tags WHERE tag_id = ?1")
+        .map_err(|e| format!("Failed to read tagged items: {}", e))?;
+    let items = stmt
+        .query_map(params![tag_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to read tagged items: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read tagged items: {}", e))?;
+    Ok(items)
+}
+
+#[tauri::command]
+pub fn list_tags(db: State<Db>) -> Result<Vec<Tag>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color, COUNT(it.item_id)
+             FROM tags t LEFT JOIN item_tags it ON it.tag_id = t.id
+             GROUP BY t.id ORDER BY t.name",
+        )
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                item_count: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read tags: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    Ok(tags)
+}
+
+#[tauri::command]
+pub fn create_tag(db: State<Db>, name: String, color: Option<String>) -> Result<Tag, Error> {
+    let name = clean_name(&name)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT INTO tags (name, color) VALUES (?1, ?2)",
+        params![name, color],
+    )
+    .map_err(|e| map_write_error(&name, e))?;
+    Ok(Tag {
+        id: conn.last_insert_rowid(),
+        name,
+        color,
+        item_count: 0,
+    })
+}
+
+#[tauri::command]
+pub fn update_tag(
+    db: State<Db>,
+    id: i64,
+    name: String,
+    color: Option<String>,
+) -> Result<(), Error> {
+    let name = clean_name(&name)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let updated = conn
+        .execute(
+            "UPDATE tags SET name = ?1, color = ?2 WHERE id = ?3",
+            params![name, color, id],
+        )
+        .map_err(|e| map_write_error(&name, e))?;
+    if updated == 0 {
+        return Err(Error::NotFound(format!("Tag {} not found", id)));
+    }
+    for (kind, item_id) in items_with_tag(&conn, id)? {
+        refresh_search(&conn, &kind, &item_id)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_tag(db: State<Db>, id: i64) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let items = items_with_tag(&conn, id)?;
+    conn.execute("DELETE FROM tags WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete tag: {}", e))?;
+    for (kind, item_id) in items {
+        refresh_search(&conn, &kind, &item_id)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn tag_item(db: State<Db>, tag_id: i64, kind: String, item_id: String) -> Result<(), Error> {
+    check_kind(&kind)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO item_tags (tag_id, kind, item_id)
+         SELECT id, ?2, ?3 FROM tags WHERE id = ?1",
+        params![tag_id, kind, item_id],
+    )
+    .map_err(|e| format!("Failed to tag item: {}", e))?;
+    Ok(refresh_search(&conn, &kind, &item_id)?)
+}
+
+#[tauri::command]
+pub fn untag_item(db: State<Db>, tag_id: i64, kind: String, item_id: String) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "DELETE FROM item_tags WHERE tag_id = ?1 AND kind = ?2 AND item_id = ?3",
+        params![tag_id, kind, item_id],
+    )
+    .map_err(|e| format!("Failed to untag item: {}", e))?;
+    Ok(refresh_search(&conn, &kind, &item_id)?)
+}
+
+#[tauri::command]
+pub fn get_item_tags(db: State<Db>, kind: String, item_id: String) -> Result<Vec<Tag>, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color,
+                    (SELECT COUNT(*) FROM item_tags c WHERE c.tag_id = t.id)
+             FROM tags t JOIN item_tags it ON it.tag_id = t.id
+             WHERE it.kind = ?1 AND it.item_id = ?2 ORDER BY t.name",
+        )
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let tags = stmt
+        .query_map(params![kind, item_id], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                item_count: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read tags: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read tags: {}", e))?;
+    Ok(tags)
+}
+
+// Items carrying the given tags: all of them when match_all, otherwise any
+#[tauri::command]
+pub fn list_tagged_items(
+    db: State<Db>,
+    tag_ids: Vec<i64>,
+    kind: Option<String>,
+    match_all: bool,
+) -> Result<Vec<TaggedItem>, Error> {
+    if tag_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; tag_ids.len()].join(", ");
+    let required = if match_all { tag_ids.len() } else { 1 };
+    let query = format!(
+        "SELECT kind, item_id FROM item_tags
+         WHERE tag_id IN ({}) AND (?{} IS NULL OR kind = ?{})
+         GROUP BY kind, item_id
+         HAVING COUNT(DISTINCT tag_id) >= {}
+         ORDER BY kind, item_id",
+        placeholders,
+        tag_ids.len() + 1,
+        tag_ids.len() + 1,
+        required
+    );
+
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| format!("Failed to query tagged items: {}", e))?;
+    let mut values: Vec<rusqlite::types::Value> = tag_ids.into_iter().map(Into::into).collect();
+    values.push(kind.into());
+    let items = stmt
+        .query_map(params_from_iter(values), |row| {
+            Ok(TaggedItem {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query tagged items: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to query tagged items: {}", e))?;
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Poster')",
+            [],
+        )
+        .unwrap();
+        crate::search::index_project(&conn, "p1", "Poster", &[]).unwrap();
+        for name in ["travel", "Print"] {
+            conn.execute("INSERT INTO tags (name) VALUES (?1)", params![name])
+                .unwrap();
+            conn.execute(
+                "INSERT INTO item_tags (tag_id, kind, item_id)
+                 VALUES (last_insert_rowid(), 'project', 'p1')",
+                [],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn names_are_trimmed_and_kinds_checked() {
+        assert_eq!(clean_name("  travel ").unwrap(), "travel");
+        assert!(clean_name(" ").is_err());
+        assert!(check_kind("asset").is_ok());
+        assert!(check_kind("collection").is_err());
+    }
+
+    #[test]
+    fn names_differing_in_case_are_the_same_tag() {
+        let conn = library();
+        let error = conn
+            .execute("INSERT INTO tags (name) VALUES ('TRAVEL')", [])
+            .unwrap_err();
+        assert_eq!(
+            map_write_error("TRAVEL", error),
+            "A tag named TRAVEL already exists"
+        );
+    }
+
+    #[test]
+    fn search_sees_the_item_tags() {
+        let conn = library();
+        assert_eq!(
+            names_for(&conn, "project", "p1").unwrap(),
+            ["Print", "travel"]
+        );
+        refresh_search(&conn, "project", "p1").unwrap();
+        let tags: String = conn
+            .query_row(
+                "SELECT tags FROM library_fts WHERE ref_id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tags, "Print travel");
+    }
+
+    #[test]
+    fn deleting_a_tag_or_project_untags() {
+        let conn = library();
+        conn.execute("DELETE FROM tags WHERE name = 'Print'", [])
+            .unwrap();
+        assert_eq!(names_for(&conn, "project", "p1").unwrap(), ["travel"]);
+        conn.execute("DELETE FROM projects WHERE id = 'p1'", [])
+            .unwrap();
+        assert!(names_for(&conn, "project", "p1").unwrap().is_empty());
+    }
+}
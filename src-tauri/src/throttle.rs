@@ -0,0 +1,91 @@
+// Debouncing for commands the UI fires on every keystroke or slider tick.
+// Calls to a listed command wait out its window; if another call to the same
+// command from the same window arrives meanwhile, the earlier one is dropped
+// (rejected as cancelled) and only the latest runs. Everything else goes
+// straight to the handler.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::ipc::Invoke;
+
+use crate::error::Error;
+
+// Command name and how long to wait for a newer call
+const DEBOUNCED: &[(&str, u64)] = &[
+    // Search as you type
+    ("search_library", 150),
+    // Live previews while settings change
+    ("estimate_compression", 250),
+    ("compare_images", 250),
+    ("compare_heatmap", 250),
+    ("extract_palette", 250),
+];
+
+fn window_for(command: &str) -> Option<Duration> {
+    DEBOUNCED
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map(|(_, ms)| Duration::from_millis(*ms))
+}
+
+// Wraps the generated invoke handler
+pub fn wrap<H>(handler: H) -> impl Fn(Invoke) -> bool + Send + Sync + 'static
+where
+    H: Fn(Invoke) -> bool + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    // Latest ticket per window and command
+    let latest: Arc<Mutex<HashMap<String, u64>>> = Default::default();
+    let next = Arc::new(AtomicU64::new(0));
+    move |invoke| {
+        let Some(wait) = window_for(invoke.message.command()) else {
+            return handler(invoke);
+        };
+        let key = format!(
+            "{}:{}",
+            invoke.message.webview_ref().label(),
+            invoke.message.command()
+        );
+        let ticket = next.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut latest) = latest.lock() {
+            latest.insert(key.clone(), ticket);
+        }
+
+        let handler = handler.clone();
+        let latest = latest.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(wait);
+            let superseded = latest
+                .lock()
+                .map(|latest| latest.get(&key) != Some(&ticket))
+                .unwrap_or(false);
+            if superseded {
+                tracing::debug!("Dropped {} superseded by a newer call", key);
+                invoke.resolver.reject(Error::Cancelled);
+            } else {
+                handler(invoke);
+            }
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_commands_are_debounced() {
+        assert_eq!(
+            window_for("search_library"),
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(
+            window_for("compare_images"),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(window_for("compress_images"), None);
+        assert_eq!(window_for("search_library_extra"), None);
+    }
+}
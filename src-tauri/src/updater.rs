@@ -0,0 +1,187 @@
+// Updates are off until there's a signing key. To turn them on, generate one
+// with `npm run tauri signer generate`, then in tauri.release.conf.json set
+// plugins.updater.pubkey to the public key and bundle.createUpdaterArtifacts to
+// true. Release builds then need TAURI_SIGNING_PRIVATE_KEY (and
+// TAURI_SIGNING_PRIVATE_KEY_PASSWORD if the key has one) in the environment
+// to sign the update artifacts.
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Config, Emitter, Manager, Url};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::error::Error;
+use crate::settings::{SettingsState, UpdateChannel};
+
+pub const PROGRESS_EVENT: &str = "updater://progress";
+pub const READY_EVENT: &str = "updater://ready";
+
+// Release manifests on GitHub; beta builds are attached to a rolling "beta" release
+const STABLE_ENDPOINT: &str =
+    "https://github.com/JoshJarabek7/squish/releases/latest/download/latest.json";
+const BETA_ENDPOINT: &str =
+    "https://github.com/JoshJarabek7/squish/releases/download/beta/latest.json";
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+    pub channel: UpdateChannel,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+// The last update found by a check, and once downloaded, the bytes that are
+// installed when the app quits so nobody loses work to a mid-session restart
+#[derive(Default)]
+pub struct UpdateState {
+    available: Mutex<Option<Update>>,
+    staged: Mutex<Option<(Update, Vec<u8>)>>,
+}
+
+// Whether this build was configured with a key to verify updates against
+pub fn enabled(config: &Config) -> bool {
+    config
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .is_some_and(|key| !key.trim().is_empty())
+}
+
+fn endpoint(channel: UpdateChannel) -> Result<Url, String> {
+    let url = match channel {
+        UpdateChannel::Stable => STABLE_ENDPOINT,
+        UpdateChannel::Beta => BETA_ENDPOINT,
+    };
+    Url::parse(url).map_err(|e| format!("Invalid update endpoint: {}", e))
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn check_for_updates(app: AppHandle) -> Result<Option<UpdateInfo>, Error> {
+    if !enabled(app.config()) {
+        return Err(Error::Internal(
+            "Updates aren't available in this build".to_string(),
+        ));
+    }
+    let settings = app.state::<SettingsState>().snapshot();
+    let channel = settings.update_channel;
+    // The plugin owns its HTTP client, so the shared network settings are
+    // passed on here; a custom CA bundle has to be trusted system-wide
+    let mut builder = app
+        .updater_builder()
+        .endpoints(vec![endpoint(channel)?])
+        .map_err(|e| format!("Failed to configure updater: {}", e))?
+        .timeout(crate::net::timeout(&settings));
+    if let Some(proxy) = settings.proxy_url.as_deref().filter(|u| !u.is_empty()) {
+        builder = builder.proxy(Url::parse(proxy).map_err(|e| format!("Invalid proxy: {}", e))?);
+    }
+    let update = builder
+        .build()
+        .map_err(|e| format!("Failed to configure updater: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for updates: {}", e))?;
+
+    let info = update.as_ref().map(|u| UpdateInfo {
+        version: u.version.clone(),
+        current_version: u.current_version.clone(),
+        notes: u.body.clone(),
+        date: u.date.map(|d| d.to_string()),
+        channel,
+    });
+    *app.state::<UpdateState>()
+        .available
+        .lock()
+        .map_err(|_| Error::Lock("update state"))? = update;
+    Ok(info)
+}
+
+// Progress goes out on updater://progress and updater://ready fires once the
+// update is staged for install on quit
+#[tauri::command]
+#[tracing::instrument(target = "perf::command", skip_all)]
+pub async fn download_update(app: AppHandle) -> Result<(), Error> {
+    let update = app
+        .state::<UpdateState>()
+        .available
+        .lock()
+        .map_err(|_| Error::Lock("update state"))?
+        .clone()
+        .ok_or("No update available; check for updates first")?;
+
+    let mut downloaded = 0u64;
+    let progress_app = app.clone();
+    let bytes = update
+        .download(
+            move |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = progress_app.emit(PROGRESS_EVENT, DownloadProgress { downloaded, total });
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| format!("Failed to download update: {}", e))?;
+
+    tracing::info!("Update {} downloaded, installing on quit", update.version);
+    let version = update.version.clone();
+    *app.state::<UpdateState>()
+        .staged
+        .lock()
+        .map_err(|_| Error::Lock("update state"))? = Some((update, bytes));
+    let _ = app.emit(READY_EVENT, version);
+    Ok(())
+}
+
+// Called on exit; on Windows this hands over to the installer
+pub fn install_staged(app: &AppHandle) {
+    let state = app.state::<UpdateState>();
+    let Ok(mut staged) = state.staged.lock() else {
+        return;
+    };
+    if let Some((update, bytes)) = staged.take() {
+        tracing::info!("Installing update {}", update.version);
+        if let Err(e) = update.install(bytes) {
+            tracing::warn!("Failed to install update: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(updater: serde_json::Value) -> Config {
+        let mut config = Config::default();
+        config.plugins.0.insert("updater".to_string(), updater);
+        config
+    }
+
+    #[test]
+    fn updates_need_a_public_key() {
+        assert!(!enabled(&Config::default()));
+        assert!(!enabled(&config(serde_json::json!({ "pubkey": "  " }))));
+        assert!(!enabled(&config(serde_json::json!({ "endpoints": [] }))));
+        assert!(enabled(&config(
+            serde_json::json!({ "pubkey": "dW50cnVzdGVk" })
+        )));
+    }
+
+    #[test]
+    fn each_channel_has_its_own_manifest() {
+        let stable = endpoint(UpdateChannel::Stable).unwrap();
+        let beta = endpoint(UpdateChannel::Beta).unwrap();
+        assert_ne!(stable, beta);
+        assert!(beta.path().contains("/beta/"));
+        assert_eq!(stable.scheme(), "https");
+    }
+}
@@ -0,0 +1,157 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::Error;
+
+const ENABLED_KEY: &str = "usage_stats_enabled";
+const MAX_FEATURE_LEN: usize = 64;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureUsage {
+    pub feature: String,
+    pub total: u64,
+    pub first_used: String,
+    pub last_used: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageStats {
+    pub enabled: bool,
+    pub features: Vec<FeatureUsage>,
+}
+
+// Feature names are short dotted identifiers like "compress.batch", which
+// keeps anything user-identifying from being smuggled in as a name
+fn valid_feature(feature: &str) -> bool {
+    !feature.is_empty()
+        && feature.len() <= MAX_FEATURE_LEN
+        && feature
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '_')
+}
+
+fn enabled(conn: &Connection) -> bool {
+    crate::settings::read::<bool>(conn, ENABLED_KEY)
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
+
+// Counts one use of a feature when the user has opted in; otherwise a no-op
+pub fn track(conn: &Connection, feature: &str) {
+    if !valid_feature(feature) || !enabled(conn) {
+        return;
+    }
+    if let Err(e) = conn.execute(
+        "INSERT INTO usage_counters (feature, day, count) VALUES (?1, date('now'), 1)
+         ON CONFLICT(feature, day) DO UPDATE SET count = count + 1",
+        params![feature],
+    ) {
+        tracing::warn!("Failed to record usage: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn track_usage(db: State<Db>, feature: String) -> Result<(), Error> {
+    if !valid_feature(&feature) {
+        return Err(Error::InvalidInput(format!(
+            "Invalid feature name: {}",
+            feature
+        )));
+    }
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    track(&conn, &feature);
+    Ok(())
+}
+
+// Everything that's stored, so users can see exactly what they would share
+#[tauri::command]
+pub fn get_usage_stats(db: State<Db>) -> Result<UsageStats, Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT feature, SUM(count), MIN(day), MAX(day) FROM usage_counters
+             GROUP BY feature ORDER BY SUM(count) DESC, feature",
+        )
+        .map_err(|e| format!("Failed to read usage stats: {}", e))?;
+    let features = stmt
+        .query_map([], |row| {
+            Ok(FeatureUsage {
+                feature: row.get(0)?,
+                total: row.get::<_, i64>(1)? as u64,
+                first_used: row.get(2)?,
+                last_used: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read usage stats: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read usage stats: {}", e))?;
+    Ok(UsageStats {
+        enabled: enabled(&conn),
+        features,
+    })
+}
+
+#[tauri::command]
+pub fn erase_usage_stats(db: State<Db>) -> Result<(), Error> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM usage_counters", [])
+        .map_err(|e| format!("Failed to erase usage stats: {}", e))?;
+    tracing::info!("Erased local usage stats");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(enabled: bool) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        crate::db::set_preference(&conn, ENABLED_KEY, &enabled.to_string()).unwrap();
+        conn
+    }
+
+    fn total(conn: &Connection, feature: &str) -> i64 {
+        conn.query_row(
+            "SELECT COALESCE(SUM(count), 0) FROM usage_counters WHERE feature = ?1",
+            params![feature],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn feature_names_are_short_identifiers() {
+        assert!(valid_feature("compress.batch"));
+        assert!(valid_feature("export_pdf2"));
+        assert!(!valid_feature(""));
+        assert!(!valid_feature("Compress"));
+        assert!(!valid_feature("open /Users/me/secret.png"));
+        assert!(!valid_feature(&"a".repeat(MAX_FEATURE_LEN + 1)));
+    }
+
+    #[test]
+    fn nothing_is_counted_until_the_user_opts_in() {
+        let conn = library(false);
+        track(&conn, "compress.batch");
+        assert_eq!(total(&conn, "compress.batch"), 0);
+    }
+
+    #[test]
+    fn uses_are_counted_per_day() {
+        let conn = library(true);
+        track(&conn, "compress.batch");
+        track(&conn, "compress.batch");
+        track(&conn, "not valid");
+        assert_eq!(total(&conn, "compress.batch"), 2);
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM usage_counters", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+}
@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_shell::process::{Command, CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::suffixed_path;
+use crate::job_history::{record, JobRecord};
+use crate::notifications::{self, NotificationTarget};
+use crate::safe_path::{self, SafePath};
+
+// Running ffmpeg processes keyed by job id
+pub struct VideoJobs(pub Mutex<HashMap<String, CommandChild>>);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    Av1,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VideoPreset {
+    // Broad compatibility, streams before fully downloaded
+    Web,
+    // Capped at 1080p with a little more quality for re-encoding by the platform
+    Social,
+    // Mostly static content: low frame rate, aggressive CRF
+    ScreenRecording,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoOptions {
+    pub codec: VideoCodec,
+    pub preset: VideoPreset,
+    // Overrides the preset's quality (lower is better)
+    pub crf: Option<u8>,
+    pub dest: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct VideoProgress {
+    job_id: String,
+    // 0-100, None until ffmpeg has reported the input duration
+    percent: Option<f32>,
+    out_time_seconds: f64,
+    speed: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct VideoDone {
+    job_id: String,
+    output_path: String,
+    success: bool,
+    error: Option<String>,
+}
+
+impl VideoCodec {
+    fn extension(&self) -> &'static str {
+        match self {
+            VideoCodec::Vp9 => "webm",
+            _ => "mp4",
+        }
+    }
+
+    fn default_crf(&self, preset: VideoPreset) -> u8 {
+        let base = match self {
+            VideoCodec::H264 => 23,
+            VideoCodec::H265 => 28,
+            VideoCodec::Vp9 => 33,
+            VideoCodec::Av1 => 35,
+        };
+        match preset {
+            VideoPreset::Web => base,
+            VideoPreset::Social => base - 2,
+            VideoPreset::ScreenRecording => base + 5,
+        }
+    }
+
+    fn encoder_args(&self, crf: u8) -> Vec<String> {
+        let crf = crf.to_string();
+        let args: Vec<&str> = match self {
+            VideoCodec::H264 => vec!["-c:v", "libx264", "-preset", "slow", "-crf", &crf],
+            // hvc1 tag so QuickTime and Safari will play it
+            VideoCodec::H265 => vec![
+                "-c:v", "libx265", "-preset", "medium", "-crf", &crf, "-tag:v", "hvc1",
+            ],
+            VideoCodec::Vp9 => vec![
+                "-c:v",
+                "libvpx-vp9",
+                "-crf",
+                &crf,
+                "-b:v",
+                "0",
+                "-row-mt",
+                "1",
+            ],
+            VideoCodec::Av1 => vec!["-c:v", "libsvtav1", "-preset", "6", "-crf", &crf],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+}
+
+fn build_args(input: &str, output: &str, options: &VideoOptions) -> Vec<String> {
+    let crf = options
+        .crf
+        .unwrap_or_else(|| options.codec.default_crf(options.preset));
+
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-y",
+        "-i",
+        input,
+        "-progress",
+        "pipe:1",
+        "-nostats",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+    args.extend(options.codec.encoder_args(crf));
+    args.extend(["-pix_fmt", "yuv420p"].map(String::from));
+
+    match options.preset {
+        VideoPreset::Web => {}
+        VideoPreset::Social => {
+            args.extend(["-vf", "scale='min(1920,iw)':-2"].map(String::from));
+        }
+        VideoPreset::ScreenRecording => {
+            args.extend(["-r", "30"].map(String::from));
+        }
+    }
+
+    let audio: &[&str] = match options.codec {
+        VideoCodec::Vp9 => &["-c:a", "libopus", "-b:a", "96k"],
+        _ => &["-c:a", "aac", "-b:a", "128k"],
+    };
+    args.extend(audio.iter().map(|s| s.to_string()));
+
+    if options.codec.extension() == "mp4" {
+        args.extend(["-movflags", "+faststart"].map(String::from));
+    }
+    args.push(output.to_string());
+    args
+}
+
+// The sidecar bundled with release builds (see tauri.release.conf.json), or
+// ffmpeg from PATH in dev builds, which don't bundle it
+fn ffmpeg(app: &AppHandle) -> Result<Command, String> {
+    let bundled = std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            Some(
+                exe.parent()?
+                    .join(format!("ffmpeg{}", std::env::consts::EXE_SUFFIX)),
+            )
+        })
+        .is_some_and(|path| path.is_file());
+    if bundled {
+        app.shell()
+            .sidecar("ffmpeg")
+            .map_err(|e| format!("ffmpeg sidecar is not available: {}", e))
+    } else {
+        Ok(app.shell().command("ffmpeg"))
+    }
+}
+
+// Starts a transcode on the bundled ffmpeg sidecar and returns its job id.
+// Progress arrives as video://progress events and completion as video://done.
+#[tauri::command]
+pub fn compress_video(
+    app: AppHandle,
+    jobs: State<VideoJobs>,
+    path: String,
+    mut options: VideoOptions,
+) -> Result<String, Error> {
+    let input = SafePath::read(&app, &path)?.into_path_buf();
+    let path = input.to_string_lossy().to_string();
+    options.dest = safe_path::write_opt(&app, options.dest)?;
+    let output = options
+        .dest
+        .clone()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| suffixed_path(&input, None, "squished", options.codec.extension()));
+    safe_path::allow(&app, &output);
+    let output = output.to_string_lossy().to_string();
+
+    let args = build_args(&path, &output, &options);
+    let settings = serde_json::to_value(&options).unwrap_or_default();
+    let started = Instant::now();
+    let (mut rx, child) = ffmpeg(&app)?
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let job_id = uuid::Uuid::new_v4().to_string();
+    jobs.0
+        .lock()
+        .map_err(|_| Error::Lock("video jobs"))?
+        .insert(job_id.clone(), child);
+
+    let id = job_id.clone();
+    let job = crate::crash::job_started(format!("video {}", path));
+    let awake = crate::power::keep_awake("Compressing a video");
+    tauri::async_runtime::spawn(async move {
+        let _job = job;
+        let _awake = awake;
+        let mut duration: Option<f64> = None;
+        let mut speed: Option<String> = None;
+        let mut last_error = String::new();
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let line = String::from_utf8_lossy(&line);
+                    let Some((key, value)) = line.trim().split_once('=') else {
+                        continue;
+                    };
+                    match key {
+                        "speed" => speed = Some(value.to_string()),
+                        "out_time_us" | "out_time_ms" => {
+                            // Both keys are microseconds despite the name
+                            let seconds = value.parse::<f64>().unwrap_or(0.0) / 1_000_000.0;
+                            let progress = VideoProgress {
+                                job_id: id.clone(),
+                                percent: duration
+                                    .map(|d| (seconds / d * 100.0).clamp(0.0, 100.0) as f32),
+                                out_time_seconds: seconds,
+                                speed: speed.clone(),
+                            };
+                            let _ = app.emit("video://progress", progress);
+                        }
+                        _ => {}
+                    }
+                }
+                CommandEvent::Stderr(line) => {
+                    let line = String::from_utf8_lossy(&line).trim().to_string();
+                    if duration.is_none() {
+                        duration = parse_duration(&line);
+                    }
+                    if !line.is_empty() {
+                        last_error = line;
+                    }
+                }
+                CommandEvent::Terminated(payload) => {
+                    let success = payload.code == Some(0);
+                    if !success {
+                        tracing::warn!("ffmpeg job {} failed: {}", id, last_error);
+                    }
+                    let done = VideoDone {
+                        job_id: id.clone(),
+                        output_path: output.clone(),
+                        success,
+                        error: (!success).then(|| last_error.clone()),
+                    };
+                    let size = |p: &str| std::fs::metadata(p).map(|m| m.len()).ok();
+                    let job = JobRecord {
+                        kind: "video",
+                        input_path: &path,
+                        output_path: success.then_some(output.as_str()),
+                        input_bytes: size(&path),
+                        output_bytes: if success { size(&output) } else { None },
+                        started,
+                        settings: settings.clone(),
+                        error: (!success).then_some(last_error.as_str()),
+                    };
+                    record(&app.state::<Db>(), &job);
+                    crate::hooks::after_export(&app, &job);
+                    let title = crate::i18n::tr(
+                        &app,
+                        if success {
+                            "video-converted"
+                        } else {
+                            "video-failed"
+                        },
+                        &[],
+                    );
+                    notifications::job_finished(
+                        &app,
+                        &title,
+                        if success { &output } else { &last_error },
+                        NotificationTarget {
+                            job_kind: "video".to_string(),
+                            output_paths: if success {
+                                vec![output.clone()]
+                            } else {
+                                Vec::new()
+                            },
+                            failed: !success,
+                        },
+                    );
+                    let _ = app.emit("video://done", done);
+                    if let Ok(mut jobs) = app.state::<VideoJobs>().0.lock() {
+                        jobs.remove(&id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn cancel_video(jobs: State<VideoJobs>, job_id: String) -> Result<(), Error> {
+    let child = jobs
+        .0
+        .lock()
+        .map_err(|_| Error::Lock("video jobs"))?
+        .remove(&job_id)
+        .ok_or_else(|| format!("Unknown video job: {}", job_id))?;
+    child
+        .kill()
+        .map_err(|e| Error::Internal(format!("Failed to stop ffmpeg: {}", e)))
+}
+
+// "  Duration: 00:01:23.45, start: ..." from ffmpeg's input summary
+fn parse_duration(line: &str) -> Option<f64> {
+    let rest = line.strip_prefix("Duration: ")?;
+    let timestamp = rest.split(',').next()?;
+    let mut parts = timestamp.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(codec: VideoCodec, preset: VideoPreset, crf: Option<u8>) -> VideoOptions {
+        VideoOptions {
+            codec,
+            preset,
+            crf,
+            dest: None,
+        }
+    }
+
+    fn after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        let i = args.iter().position(|a| a == flag)?;
+        args.get(i + 1).map(String::as_str)
+    }
+
+    #[test]
+    fn mp4_output_starts_fast_and_uses_aac() {
+        let args = build_args(
+            "in.mov",
+            "out.mp4",
+            &options(VideoCodec::H264, VideoPreset::Social, None),
+        );
+        assert_eq!(after(&args, "-i"), Some("in.mov"));
+        assert_eq!(after(&args, "-crf"), Some("21"));
+        assert_eq!(after(&args, "-vf"), Some("scale='min(1920,iw)':-2"));
+        assert_eq!(after(&args, "-c:a"), Some("aac"));
+        assert_eq!(after(&args, "-movflags"), Some("+faststart"));
+        assert_eq!(args.last().map(String::as_str), Some("out.mp4"));
+    }
+
+    #[test]
+    fn webm_output_uses_opus_and_an_explicit_crf() {
+        let args = build_args(
+            "in.mov",
+            "out.webm",
+            &options(VideoCodec::Vp9, VideoPreset::ScreenRecording, Some(40)),
+        );
+        assert_eq!(VideoCodec::Vp9.extension(), "webm");
+        assert_eq!(after(&args, "-c:v"), Some("libvpx-vp9"));
+        assert_eq!(after(&args, "-crf"), Some("40"));
+        assert_eq!(after(&args, "-r"), Some("30"));
+        assert_eq!(after(&args, "-c:a"), Some("libopus"));
+        assert!(!args.iter().any(|a| a == "-movflags"));
+    }
+
+    #[test]
+    fn durations_are_read_from_the_input_summary() {
+        assert_eq!(
+            parse_duration("Duration: 00:01:23.50, start: 0.000000, bitrate: 1205 kb/s"),
+            Some(83.5)
+        );
+        assert_eq!(parse_duration("Duration: 01:00:00.00"), Some(3600.0));
+        assert_eq!(parse_duration("Duration: N/A, bitrate: N/A"), None);
+        assert_eq!(parse_duration("Stream #0:0: Video: h264"), None);
+    }
+}
@@ -0,0 +1,413 @@
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::Db;
+use crate::error::Error;
+use crate::imaging::{output_path_for, CompressOptions, SourceFormat};
+use crate::jobs::{self, JobSpec, Priority};
+use crate::safe_path::{self, SafePath};
+
+// Give apps time to finish writing before a file gets picked up
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+// Active watchers keyed by watch folder id; dropping one stops watching
+pub struct WatchState(pub Mutex<HashMap<i64, Debouncer<RecommendedWatcher>>>);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolder {
+    pub id: i64,
+    pub path: String,
+    pub destination: String,
+    pub options: CompressOptions,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct WatchActivity {
+    folder_id: i64,
+    path: String,
+    // The compression job; its result arrives on job://update
+    job_id: Option<String>,
+    output_path: Option<String>,
+    error: Option<String>,
+}
+
+#[tauri::command]
+pub fn list_watch_folders(db: State<Db>) -> Result<Vec<WatchFolder>, Error> {
+    Ok(load_folders(&db)?)
+}
+
+#[tauri::command]
+pub fn add_watch_folder(
+    app: AppHandle,
+    db: State<Db>,
+    watchers: State<WatchState>,
+    path: String,
+    destination: String,
+    options: CompressOptions,
+) -> Result<WatchFolder, Error> {
+    let path = SafePath::read(&app, &path)?.into_string();
+    let destination = SafePath::write(&app, &destination)?.into_string();
+    if !Path::new(&path).is_dir() {
+        return Err(Error::InvalidInput(format!("Not a folder: {}", path)));
+    }
+    std::fs::create_dir_all(&destination)
+        .map_err(|e| format!("Failed to create {}: {}", destination, e))?;
+    // Watching resumes on every launch, long after the dialog's grant expired
+    crate::bookmarks::remember(&app, &path);
+    crate::bookmarks::remember(&app, &destination);
+
+    let options_json = serde_json::to_string(&options)
+        .map_err(|e| format!("Failed to serialize options: {}", e))?;
+    let id = {
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.execute(
+            "INSERT INTO watch_folders (path, destination, options) VALUES (?1, ?2, ?3)",
+            params![path, destination, options_json],
+        )
+        .map_err(|e| format!("Failed to add watch folder: {}", e))?;
+        conn.last_insert_rowid()
+    };
+
+    let folder = WatchFolder {
+        id,
+        path,
+        destination,
+        options,
+        enabled: true,
+    };
+    start_watcher(&app, &watchers, &folder)?;
+    Ok(folder)
+}
+
+#[tauri::command]
+pub fn remove_watch_folder(
+    db: State<Db>,
+    watchers: State<WatchState>,
+    id: i64,
+) -> Result<(), Error> {
+    stop_watcher(&watchers, id)?;
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute("DELETE FROM watch_ledger WHERE folder_id = ?1", params![id])
+        .map_err(|e| format!("Failed to clear watch ledger: {}", e))?;
+    conn.execute("DELETE FROM watch_folders WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to remove watch folder: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_watch_folder_enabled(
+    app: AppHandle,
+    db: State<Db>,
+    watchers: State<WatchState>,
+    id: i64,
+    enabled: bool,
+) -> Result<(), Error> {
+    {
+        let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+        conn.execute(
+            "UPDATE watch_folders SET enabled = ?1 WHERE id = ?2",
+            params![enabled, id],
+        )
+        .map_err(|e| format!("Failed to update watch folder: {}", e))?;
+    }
+
+    if enabled {
+        let folder = load_folders(&db)?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| Error::NotFound(format!("Unknown watch folder: {}", id)))?;
+        start_watcher(&app, &watchers, &folder)?;
+    } else {
+        stop_watcher(&watchers, id)?;
+    }
+    Ok(())
+}
+
+// Resume every enabled folder at startup
+pub fn start_all(app: &AppHandle) -> Result<(), String> {
+    let db = app.state::<Db>();
+    let watchers = app.state::<WatchState>();
+    let folders = load_folders(&db)?;
+    // Granted when they were added, in an earlier session
+    for folder in &folders {
+        safe_path::allow(app, Path::new(&folder.path));
+        safe_path::allow(app, Path::new(&folder.destination));
+    }
+    for folder in folders.into_iter().filter(|f| f.enabled) {
+        if let Err(e) = start_watcher(app, &watchers, &folder) {
+            tracing::warn!("Failed to watch {}: {}", folder.path, e);
+        }
+    }
+    Ok(())
+}
+
+fn load_folders(db: &Db) -> Result<Vec<WatchFolder>, String> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let mut stmt = conn
+        .prepare("SELECT id, path, destination, options, enabled FROM watch_folders ORDER BY id")
+        .map_err(|e| format!("Failed to query watch folders: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to query watch folders: {}", e))?;
+
+    let mut folders = Vec::new();
+    for row in rows {
+        let (id, path, destination, options, enabled) =
+            row.map_err(|e| format!("Failed to read watch folder: {}", e))?;
+        let options = serde_json::from_str(&options)
+            .map_err(|e| format!("Invalid options for watch folder {}: {}", id, e))?;
+        folders.push(WatchFolder {
+            id,
+            path,
+            destination,
+            options,
+            enabled,
+        });
+    }
+    Ok(folders)
+}
+
+fn start_watcher(
+    app: &AppHandle,
+    watchers: &WatchState,
+    folder: &WatchFolder,
+) -> Result<(), String> {
+    let handle = app.clone();
+    let watched = folder.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+        Ok(events) => {
+            for event in events {
+                process_file(&handle, &watched, &event.path);
+            }
+        }
+        Err(e) => tracing::warn!("Watch error on {}: {}", watched.path, e),
+    })
+    .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+    debouncer
+        .watcher()
+        .watch(Path::new(&folder.path), RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {}: {}", folder.path, e))?;
+
+    tracing::info!("Watching {} for new images", folder.path);
+    watchers
+        .0
+        .lock()
+        .map_err(|_| Error::Lock("watchers"))?
+        .insert(folder.id, debouncer);
+    Ok(())
+}
+
+pub fn stop_all(watchers: &WatchState) -> Result<(), String> {
+    watchers
+        .0
+        .lock()
+        .map_err(|_| Error::Lock("watchers"))?
+        .clear();
+    Ok(())
+}
+
+fn stop_watcher(watchers: &WatchState, id: i64) -> Result<(), String> {
+    watchers
+        .0
+        .lock()
+        .map_err(|_| Error::Lock("watchers"))?
+        .remove(&id);
+    Ok(())
+}
+
+fn process_file(app: &AppHandle, folder: &WatchFolder, path: &Path) {
+    if !path.is_file() || !SourceFormat::is_supported(path) {
+        return;
+    }
+
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let path_str = path.to_string_lossy().to_string();
+
+    let db = app.state::<Db>();
+    // Our own outputs land here too when the destination is inside the watched
+    // folder (or is it); the ledger knows them, and compressing them would loop
+    match is_output(&db, folder.id, &path_str) {
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    }
+    match already_processed(&db, folder.id, &path_str, modified) {
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    }
+
+    let mut options = folder.options.clone();
+    options.output_dir = Some(folder.destination.clone());
+    let output_path = output_path_for(path, Some(&folder.destination), options.format)
+        .to_string_lossy()
+        .to_string();
+    // Queued like any other batch, so it can be cancelled and keeps the computer
+    // awake; validating checks the folders are still permitted
+    let queued = JobSpec::Compress {
+        paths: vec![path_str.clone()],
+        options,
+        project_id: None,
+    }
+    .validate(app)
+    .map_err(String::from)
+    .and_then(|spec| jobs::enqueue(app, spec, Priority::Low));
+
+    let activity = match queued {
+        Ok(job_id) => WatchActivity {
+            folder_id: folder.id,
+            path: path_str.clone(),
+            job_id: Some(job_id),
+            output_path: Some(output_path),
+            error: None,
+        },
+        Err(e) => {
+            tracing::warn!("Failed to queue {}: {}", path_str, e);
+            WatchActivity {
+                folder_id: folder.id,
+                path: path_str.clone(),
+                job_id: None,
+                output_path: None,
+                error: Some(e),
+            }
+        }
+    };
+
+    if let Err(e) = record_processed(&db, folder.id, &path_str, modified, &activity) {
+        tracing::warn!("{}", e);
+    }
+    if let Err(e) = app.emit("watch://activity", activity) {
+        tracing::warn!("Failed to emit watch activity: {}", e);
+    }
+}
+
+fn already_processed(db: &Db, folder_id: i64, path: &str, modified: i64) -> Result<bool, String> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    let previous: Option<i64> = conn
+        .query_row(
+            "SELECT modified FROM watch_ledger WHERE folder_id = ?1 AND path = ?2",
+            params![folder_id, path],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read watch ledger: {}", e))?;
+    Ok(previous == Some(modified))
+}
+
+fn is_output(db: &Db, folder_id: i64, path: &str) -> Result<bool, String> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM watch_ledger WHERE folder_id = ?1 AND output_path = ?2)",
+        params![folder_id, path],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to read watch ledger: {}", e))
+}
+
+fn record_processed(
+    db: &Db,
+    folder_id: i64,
+    path: &str,
+    modified: i64,
+    activity: &WatchActivity,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|_| Error::Lock("database"))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO watch_ledger (folder_id, path, modified, output_path, error)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            folder_id,
+            path,
+            modified,
+            activity.output_path,
+            activity.error
+        ],
+    )
+    .map_err(|e| format!("Failed to write watch ledger: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library() -> Db {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::migrations::run(&conn).unwrap();
+        conn.execute(
+            r#"INSERT INTO watch_folders (id, path, destination, options, enabled)
+               VALUES (1, '/in', '/in/out', '{"format":"webp","quality":80}', 0)"#,
+            [],
+        )
+        .unwrap();
+        Db(Mutex::new(conn))
+    }
+
+    fn activity(output_path: Option<&str>) -> WatchActivity {
+        WatchActivity {
+            folder_id: 1,
+            path: "/in/a.png".to_string(),
+            job_id: None,
+            output_path: output_path.map(str::to_string),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn folders_load_with_their_options() {
+        let folders = load_folders(&library()).unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].destination, "/in/out");
+        assert_eq!(folders[0].options.quality, 80);
+        assert!(!folders[0].enabled);
+    }
+
+    #[test]
+    fn files_are_processed_again_only_when_modified() {
+        let db = library();
+        assert!(!already_processed(&db, 1, "/in/a.png", 100).unwrap());
+        record_processed(&db, 1, "/in/a.png", 100, &activity(None)).unwrap();
+        assert!(already_processed(&db, 1, "/in/a.png", 100).unwrap());
+        assert!(!already_processed(&db, 1, "/in/a.png", 101).unwrap());
+        assert!(!already_processed(&db, 1, "/in/b.png", 100).unwrap());
+    }
+
+    #[test]
+    fn only_recorded_outputs_are_skipped() {
+        let db = library();
+        record_processed(&db, 1, "/in/a.png", 100, &activity(Some("/in/out/a.webp"))).unwrap();
+        assert!(is_output(&db, 1, "/in/out/a.webp").unwrap());
+        // Something else saved into the destination is still picked up
+        assert!(!is_output(&db, 1, "/in/out/b.png").unwrap());
+    }
+}
@@ -0,0 +1,186 @@
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Listener, State};
+
+use crate::error::Error;
+use crate::settings::{self, AppSettings, SettingChange, SETTINGS_CHANGED_EVENT};
+
+const CONCURRENCY_KEY: &str = "worker_concurrency";
+
+// Number of encode jobs allowed to run at once
+pub struct WorkerConfig {
+    configured: AtomicUsize,
+    // Battery saver halves the configured count
+    throttled: AtomicBool,
+}
+
+impl WorkerConfig {
+    pub fn get(&self) -> usize {
+        let n = self.configured.load(Ordering::Relaxed);
+        if self.is_throttled() {
+            (n / 2).max(1)
+        } else {
+            n
+        }
+    }
+
+    pub fn set(&self, n: usize) {
+        self.configured.store(n, Ordering::Relaxed);
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_throttled(&self, throttled: bool) {
+        self.throttled.store(throttled, Ordering::Relaxed);
+    }
+}
+
+// Leave one physical core free so the UI (and the rest of the laptop) stays responsive
+pub fn default_concurrency() -> usize {
+    num_cpus::get_physical().saturating_sub(1).max(1)
+}
+
+pub fn load(settings: &AppSettings) -> WorkerConfig {
+    WorkerConfig {
+        configured: AtomicUsize::new(effective_concurrency(settings.worker_concurrency)),
+        throttled: AtomicBool::new(false),
+    }
+}
+
+pub fn effective_concurrency(setting: Option<usize>) -> usize {
+    match setting {
+        Some(n) if n > 0 => n.min(num_cpus::get()),
+        _ => default_concurrency(),
+    }
+}
+
+// Keeps the live worker count in sync when the setting is changed from anywhere
+pub fn watch_settings(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen(SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) else {
+            return;
+        };
+        if change.key == CONCURRENCY_KEY {
+            let setting = serde_json::from_value(change.value).ok().flatten();
+            let n = effective_concurrency(setting);
+            tauri::Manager::state::<WorkerConfig>(&handle).set(n);
+            tracing::info!("Worker concurrency set to {}", n);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_worker_concurrency(workers: State<WorkerConfig>) -> usize {
+    workers.get()
+}
+
+// Saves and applies a new worker count; 0 resets to the default
+#[tauri::command]
+pub fn set_worker_concurrency(app: AppHandle, n: usize) -> Result<usize, Error> {
+    let setting = (n > 0).then_some(n);
+    settings::write(&app, CONCURRENCY_KEY, &setting)?;
+    Ok(effective_concurrency(setting))
+}
+
+// CPU-bound work (encoding, hashing, preview tiles) shares one rayon pool sized
+// by the concurrency setting instead of every feature spawning threads. It's
+// rebuilt lazily when the count changes; work already running on the old pool
+// finishes there.
+static POOL: Mutex<Option<Arc<ThreadPool>>> = Mutex::new(None);
+
+fn pool(threads: usize) -> Option<Arc<ThreadPool>> {
+    let threads = threads.max(1);
+    let mut current = POOL.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = current.as_ref() {
+        if pool.current_num_threads() == threads {
+            return Some(pool.clone());
+        }
+    }
+    match ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("squish-worker-{}", i))
+        .build()
+    {
+        Ok(pool) => {
+            tracing::debug!("Started a worker pool with {} threads", threads);
+            let pool = Arc::new(pool);
+            *current = Some(pool.clone());
+            Some(pool)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to start worker pool: {}", e);
+            None
+        }
+    }
+}
+
+// Runs f on the shared pool with `workers` threads. Rayon calls inside it
+// (par_iter) stay on the pool, so nested parallelism respects the setting too.
+pub fn install<R, F>(workers: usize, f: F) -> R
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    match pool(workers) {
+        Some(pool) => pool.install(f),
+        // Rayon's global pool still beats running serially
+        None => f(),
+    }
+}
+
+// Runs f over items on up to `workers` threads and returns results in input order
+pub fn run_parallel<T, R, F>(items: Vec<T>, workers: usize, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    install(workers, || items.into_par_iter().map(f).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_the_input_order() {
+        let squares = run_parallel((0..100).collect(), 4, |n: u64| n * n);
+        assert_eq!(squares, (0..100).map(|n| n * n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unset_or_zero_concurrency_uses_the_default() {
+        assert_eq!(effective_concurrency(None), default_concurrency());
+        assert_eq!(effective_concurrency(Some(0)), default_concurrency());
+        assert_eq!(effective_concurrency(Some(1)), 1);
+        assert_eq!(effective_concurrency(Some(10_000)), num_cpus::get());
+    }
+
+    #[test]
+    fn battery_saver_halves_the_count() {
+        let config = load(&AppSettings::default());
+        config.set(6);
+        config.set_throttled(true);
+        assert_eq!(config.get(), 3);
+        config.set(1);
+        assert_eq!(config.get(), 1);
+        config.set_throttled(false);
+        assert_eq!(config.get(), 1);
+    }
+
+    #[test]
+    fn work_runs_on_a_pool_of_the_requested_size() {
+        assert_eq!(install(3, rayon::current_num_threads), 3);
+        let names = run_parallel(vec![(); 8], 2, |_| {
+            std::thread::current().name().map(str::to_string)
+        });
+        assert!(names.iter().all(|name| name
+            .as_deref()
+            .is_some_and(|n| n.starts_with("squish-worker-"))));
+    }
+}
@@ -14,16 +14,27 @@ import {
   type SettingsUpdate,
 } from '@/types/SettingsType';
 import Database from '@tauri-apps/plugin-sql';
-import { appConfigDir, join } from '@tauri-apps/api/path';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { nanoid } from 'nanoid';
 import { z } from 'zod';
 import { convertToTransparentPng } from '@/lib/utils';
+import { RustDatabase } from '@/lib/rust-db';
 
 type LayerCreate = z.infer<typeof LayerCreateSchema>;
 
+// The backend swapped the library underneath us (profile switch, restore or
+// encryption); cached state everywhere is stale, so start over
+for (const event of ['db://reopened', 'db://restored']) {
+  listen(event, () => window.location.reload());
+}
+
+// Either the plugin connection or, for encrypted libraries, the Rust one
+type SqlConnection = Pick<Database, 'select' | 'execute' | 'close'>;
+
 // Create a store for the database connection
-let db: Database | null = null;
-let initializationPromise: Promise<Database | null> | null = null;
+let db: SqlConnection | null = null;
+let initializationPromise: Promise<SqlConnection | null> | null = null;
 
 // Update canvas_settings in TABLES
 const TABLES = {
@@ -138,14 +149,13 @@ const TABLES = {
 } as const;
 
 // Get the absolute path to the database
+// The backend picks the file, since it depends on the active profile
 export async function getDatabasePath(): Promise<string> {
-  const configDir = await appConfigDir();
-  console.log('Config directory:', configDir);
-  return join(configDir, 'squish.db');
+  return invoke<string>('get_database_path');
 }
 
 // Initialize database tables
-export async function initTables(db: Database) {
+export async function initTables(db: SqlConnection) {
   try {
     console.log('Initializing tables...');
     for (const [tableName, createTable] of Object.entries(TABLES)) {
@@ -166,7 +176,7 @@ export async function initTables(db: Database) {
 }
 
 // Add function to initialize triggers
-async function initTriggers(db: Database) {
+async function initTriggers(db: SqlConnection) {
   try {
     // Drop existing triggers first
     await db.execute('DROP TRIGGER IF EXISTS check_layer_exists');
@@ -598,7 +608,7 @@ export async function createLayer(
 
 // Add transaction helper function
 export async function withTransaction<T>(
-  db: Database,
+  db: SqlConnection,
   operation: () => Promise<T>
 ): Promise<T> {
   let inTransaction = false;
@@ -1079,6 +1089,8 @@ export async function updateProjectTimestamp(id: string): Promise<void> {
     'UPDATE projects SET updated_at = CURRENT_TIMESTAMP WHERE id = $1',
     [id]
   );
+  // Keep the library grid's summary row in sync with the saved project
+  await invoke('index_project', { projectId: id });
 }
 
 // Add after other exports
@@ -1183,7 +1195,10 @@ export async function initDatabase() {
       console.log('Initializing database at:', dbPath);
 
       console.log('Loading database...');
-      const newDb = await Database.load(`sqlite:${dbPath}`);
+      const encrypted = await invoke<boolean>('is_library_encrypted');
+      const newDb: SqlConnection = encrypted
+        ? new RustDatabase()
+        : await Database.load(`sqlite:${dbPath}`);
       console.log('Database loaded successfully');
 
       if (newDb) {
@@ -1413,3 +1428,17 @@ export async function applyAction(
     }
   });
 }
+
+// Encrypts the library in place. Our plugin connection must be closed first so
+// the backend can replace the file; afterwards every query goes through Rust.
+export async function enableLibraryEncryption(passphrase: string): Promise<void> {
+  if (db) {
+    await db.close();
+    db = null;
+  }
+  try {
+    await invoke('enable_library_encryption', { passphrase });
+  } finally {
+    await initDatabase();
+  }
+}
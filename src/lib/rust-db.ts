@@ -0,0 +1,22 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { QueryResult } from '@tauri-apps/plugin-sql';
+
+// Runs queries on the Rust-side connection. Used when the library is encrypted,
+// since tauri-plugin-sql has no way to pass a SQLCipher key.
+export class RustDatabase {
+  async select<T>(query: string, bindValues?: unknown[]): Promise<T> {
+    return invoke<T>('sql_select', { query, values: bindValues ?? [] });
+  }
+
+  async execute(query: string, bindValues?: unknown[]): Promise<QueryResult> {
+    return invoke<QueryResult>('sql_execute', {
+      query,
+      values: bindValues ?? [],
+    });
+  }
+
+  // The Rust connection lives as long as the app
+  async close(): Promise<boolean> {
+    return true;
+  }
+}